@@ -0,0 +1,288 @@
+use std::ffi::OsString;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use deko::write::Compression;
+use deko::Format;
+
+use crate::format::parse_format;
+use crate::format::parse_level;
+
+pub const USAGE: &str = "\
+Usage: deko [OPTIONS] [FILE...]
+       deko list [--verify] FILE...
+
+Compress or decompress files. The compression format is detected automatically
+when decompressing. The list command shows the format and the sizes of the files.
+With no FILE, or when FILE is -, read standard input and write standard output.
+
+Options:
+  -d, --decompress         Decompress.
+  -c, --stdout             Write the output to standard output and keep the input files.
+  -o, --output PATH        Write the output to PATH (only one input file is allowed).
+  -S, --suffix SUFFIX      Use SUFFIX instead of the default suffix of the format.
+  -f, --force              Overwrite existing output files.
+  -N, --name               Store (restore) the original file name and modification time
+                           in (from) gzip header.
+  -F, --format FORMAT      Output format: gz, bz2, zlib, xz, zst.
+                           When decompressing, the input format (no detection).
+  -l, --level LEVEL        Compression level: a number, fast, default or best.
+  -1 ... -9                Compression level.
+  -T, --threads N          The number of files that are processed in parallel.
+  -x, --exclude PATTERN    Skip the files which names match the pattern.
+      --config PATH        Read the configuration from PATH.
+      --no-config          Don't read the configuration file.
+      --verify             Decompress the files to find out the exact uncompressed size
+                           (list command).
+  -h, --help               Print help.
+  -V, --version            Print version.
+
+The configuration is read from $XDG_CONFIG_HOME/deko/config.toml
+or $HOME/.config/deko/config.toml. The environment variables override
+the configuration, and the options override both.
+
+Environment:
+  DEKO_FORMAT              Output format.
+  DEKO_LEVEL               Compression level.
+  DEKO_THREADS             The number of files that are processed in parallel.
+  GZIP, BZIP2, XZ_OPT      Compression level options of the corresponding tools (e.g. -9).
+  ZSTD_CLEVEL              Zstd compression level.
+";
+
+/// Command-line arguments.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Args {
+    pub decompress: bool,
+    pub output: Option<PathBuf>,
+    pub stdout: bool,
+    pub suffix: Option<String>,
+    pub force: bool,
+    pub name: bool,
+    pub format: Option<Format>,
+    pub level: Option<Compression>,
+    pub threads: Option<usize>,
+    pub exclude: Vec<String>,
+    pub config: Option<PathBuf>,
+    pub no_config: bool,
+    pub list: bool,
+    pub verify: bool,
+    pub help: bool,
+    pub version: bool,
+    pub files: Vec<PathBuf>,
+}
+
+impl Args {
+    /// Parse the arguments excluding the program name.
+    pub fn parse<I: IntoIterator<Item = OsString>>(args: I) -> Result<Self, Error> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter().peekable();
+        if args.peek().is_some_and(|arg| arg == "list") {
+            parsed.list = true;
+            args.next();
+        }
+        while let Some(arg) = args.next() {
+            let Some(arg_str) = arg.to_str() else {
+                parsed.files.push(arg.into());
+                continue;
+            };
+            if arg_str == "--" {
+                parsed.files.extend(args.by_ref().map(PathBuf::from));
+                break;
+            }
+            if let Some(long) = arg_str.strip_prefix("--") {
+                let (name, inline_value) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                parsed.parse_long(name, inline_value, &mut args)?;
+            } else if arg_str.len() > 1 && arg_str.starts_with('-') {
+                let shorts = arg_str[1..].chars().collect::<Vec<_>>();
+                for (i, short) in shorts.iter().enumerate() {
+                    if parsed.parse_flag(*short) {
+                        continue;
+                    }
+                    let name = long_name(*short)
+                        .ok_or_else(|| invalid_input(format!("unknown option `-{short}`")))?;
+                    // the rest of the argument is the value
+                    let rest = shorts[i + 1..].iter().collect::<String>();
+                    let value = if rest.is_empty() {
+                        next_value(&format!("-{short}"), &mut args)?
+                    } else {
+                        rest
+                    };
+                    parsed.parse_option(name, value)?;
+                    break;
+                }
+            } else {
+                parsed.files.push(arg.into());
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn parse_long<I: Iterator<Item = OsString>>(
+        &mut self,
+        name: &str,
+        inline_value: Option<String>,
+        args: &mut I,
+    ) -> Result<(), Error> {
+        let option = format!("--{name}");
+        let flag = match name {
+            "decompress" => Some('d'),
+            "stdout" => Some('c'),
+            "force" => Some('f'),
+            "name" => Some('N'),
+            "help" => Some('h'),
+            "version" => Some('V'),
+            "no-config" | "verify" => None,
+            "output" | "suffix" | "format" | "level" | "threads" | "exclude" | "config" => {
+                let value = match inline_value {
+                    Some(value) => value,
+                    None => next_value(&option, args)?,
+                };
+                return self.parse_option(name, value);
+            }
+            _ => return Err(invalid_input(format!("unknown option `{option}`"))),
+        };
+        if inline_value.is_some() {
+            return Err(invalid_input(format!("`{option}` doesn't take a value")));
+        }
+        match flag {
+            Some(short) => {
+                self.parse_flag(short);
+            }
+            None if name == "verify" => self.verify = true,
+            None => self.no_config = true,
+        }
+        Ok(())
+    }
+
+    /// Returns `false` if `short` is not a flag.
+    fn parse_flag(&mut self, short: char) -> bool {
+        match short {
+            'd' => self.decompress = true,
+            'c' => self.stdout = true,
+            'f' => self.force = true,
+            'N' => self.name = true,
+            'h' => self.help = true,
+            'V' => self.version = true,
+            '1'..='9' => self.level = Some(Compression::Level(short as u32 - '0' as u32)),
+            _ => return false,
+        }
+        true
+    }
+
+    fn parse_option(&mut self, name: &str, value: String) -> Result<(), Error> {
+        match name {
+            "output" => self.output = Some(value.into()),
+            "suffix" if value.is_empty() => return Err(invalid_input("empty suffix".into())),
+            "suffix" => self.suffix = Some(value),
+            "format" => self.format = Some(parse_format(&value)?),
+            "level" => self.level = Some(parse_level(&value)?),
+            "threads" => {
+                self.threads = match value.parse() {
+                    Ok(n) if n > 0 => Some(n),
+                    _ => return Err(invalid_input(format!("invalid thread count `{value}`"))),
+                }
+            }
+            "exclude" => self.exclude.push(value),
+            _ => self.config = Some(value.into()),
+        }
+        Ok(())
+    }
+}
+
+/// Long name of the option that takes a value.
+fn long_name(short: char) -> Option<&'static str> {
+    match short {
+        'o' => Some("output"),
+        'S' => Some("suffix"),
+        'F' => Some("format"),
+        'l' => Some("level"),
+        'T' => Some("threads"),
+        'x' => Some("exclude"),
+        _ => None,
+    }
+}
+
+fn next_value<I: Iterator<Item = OsString>>(option: &str, args: &mut I) -> Result<String, Error> {
+    let value = args
+        .next()
+        .ok_or_else(|| invalid_input(format!("`{option}` requires a value")))?;
+    value
+        .into_string()
+        .map_err(|_| invalid_input(format!("invalid value of `{option}`")))
+}
+
+fn invalid_input(message: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, Error> {
+        Args::parse(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn options() {
+        let args = parse(&[
+            "-fNcd9",
+            "-o",
+            "out",
+            "--suffix=.z",
+            "--format=zstd",
+            "-T",
+            "4",
+            "-x*.jpg",
+            "--exclude",
+            "*.png",
+            "--config",
+            "deko.toml",
+            "a",
+            "--",
+            "-b",
+        ])
+        .unwrap();
+        assert_eq!(
+            Args {
+                decompress: true,
+                output: Some("out".into()),
+                stdout: true,
+                suffix: Some(".z".into()),
+                force: true,
+                name: true,
+                format: Some(Format::Zstd),
+                level: Some(Compression::Level(9)),
+                threads: Some(4),
+                exclude: vec!["*.jpg".into(), "*.png".into()],
+                config: Some("deko.toml".into()),
+                files: vec!["a".into(), "-b".into()],
+                ..Default::default()
+            },
+            args
+        );
+        let args = parse(&["--level", "best", "--no-config", "-"]).unwrap();
+        assert_eq!(Some(Compression::Best), args.level);
+        assert!(args.no_config);
+        assert_eq!(vec![PathBuf::from("-")], args.files);
+        let args = parse(&["list", "--verify", "list"]).unwrap();
+        assert!(args.list);
+        assert!(args.verify);
+        assert_eq!(vec![PathBuf::from("list")], args.files);
+    }
+
+    #[test]
+    fn invalid_options() {
+        assert!(parse(&["--unknown"]).is_err());
+        assert!(parse(&["-q"]).is_err());
+        assert!(parse(&["--format"]).is_err());
+        assert!(parse(&["--format", "arj"]).is_err());
+        assert!(parse(&["--threads=0"]).is_err());
+        assert!(parse(&["--decompress=1"]).is_err());
+        assert!(parse(&["--suffix="]).is_err());
+    }
+}
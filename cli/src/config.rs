@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+
+use deko::write::Compression;
+use deko::write::FormatConfig;
+use deko::Format;
+
+use crate::format::parse_format;
+use crate::format::parse_level;
+
+/// Settings from the configuration file.
+///
+/// Example:
+///
+/// ```toml
+/// format = "zstd"
+/// level = "default"
+/// threads = 4
+/// exclude = ["*.jpg", "*.png"]
+///
+/// [levels]
+/// gz = 9
+/// zstd = 19
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug, Default, serde::Deserialize)]
+#[serde(try_from = "ConfigFile")]
+pub struct Config {
+    /// Default output format.
+    pub format: Option<Format>,
+    /// Compression levels.
+    pub encoder: deko::write::Config,
+    /// The number of files that are processed in parallel.
+    pub threads: Option<usize>,
+    /// File name patterns of the files that are skipped.
+    pub exclude: Vec<String>,
+}
+
+impl Config {
+    /// Default configuration file path.
+    ///
+    /// The path is `$XDG_CONFIG_HOME/deko/config.toml` or `$HOME/.config/deko/config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(dir.join("deko").join("config.toml"))
+    }
+
+    /// Load the configuration from the default path.
+    ///
+    /// Returns the default configuration if the file doesn't exist.
+    pub fn load_default() -> Result<Self, Error> {
+        let Some(path) = Self::default_path() else {
+            return Ok(Default::default());
+        };
+        match Self::load(&path) {
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Default::default()),
+            other => other,
+        }
+    }
+
+    /// Load the configuration from the file.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let contents = read_to_string(path)?;
+        Self::parse(&contents).map_err(|e| Error::new(e.kind(), format!("{}: {e}", path.display())))
+    }
+
+    /// Parse the configuration file contents.
+    pub fn parse(contents: &str) -> Result<Self, Error> {
+        toml::from_str(contents).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+}
+
+/// The configuration file as it is written.
+#[derive(serde::Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    format: Option<String>,
+    level: Option<Level>,
+    threads: Option<usize>,
+    exclude: Vec<String>,
+    levels: BTreeMap<String, Level>,
+}
+
+impl TryFrom<ConfigFile> for Config {
+    type Error = Error;
+
+    fn try_from(file: ConfigFile) -> Result<Self, Self::Error> {
+        if file.threads == Some(0) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "the number of threads must be positive",
+            ));
+        }
+        let mut encoder = deko::write::Config::default();
+        if let Some(level) = file.level {
+            encoder.compression = level.try_into()?;
+        }
+        for (name, level) in file.levels.into_iter() {
+            encoder.formats.insert(
+                parse_format(&name)?,
+                FormatConfig {
+                    compression: Some(level.try_into()?),
+                    ..Default::default()
+                },
+            );
+        }
+        Ok(Self {
+            format: file.format.as_deref().map(parse_format).transpose()?,
+            encoder,
+            threads: file.threads,
+            exclude: file.exclude,
+        })
+    }
+}
+
+/// Compression level is either a name or a number.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum Level {
+    Name(String),
+    Number(u32),
+}
+
+impl TryFrom<Level> for Compression {
+    type Error = Error;
+
+    fn try_from(level: Level) -> Result<Self, Self::Error> {
+        match level {
+            Level::Name(name) => parse_level(&name),
+            Level::Number(level) => Ok(Compression::Level(level)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let config = Config::parse(
+            r#"
+format = "zstd"
+level = "fast"
+threads = 4
+exclude = ["*.jpg", "*.png"]
+
+[levels]
+gz = 9
+xz = "best"
+"#,
+        )
+        .unwrap();
+        assert_eq!(Some(Format::Zstd), config.format);
+        assert_eq!(Some(4), config.threads);
+        assert_eq!(vec!["*.jpg".to_string(), "*.png".into()], config.exclude);
+        assert_eq!(Compression::Fast, config.encoder.compression(Format::Zstd));
+        assert_eq!(
+            Compression::Level(9),
+            config.encoder.compression(Format::Gz)
+        );
+        assert_eq!(Compression::Best, config.encoder.compression(Format::Xz));
+        assert_eq!(Config::default(), Config::parse("").unwrap());
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!(Config::parse("format = 1").is_err());
+        assert!(Config::parse("format = \"arj\"").is_err());
+        assert!(Config::parse("threads = 0").is_err());
+        assert!(Config::parse("level = -1").is_err());
+        assert!(Config::parse("exclude = [1]").is_err());
+        assert!(Config::parse("[levels]\narj = 1").is_err());
+        assert!(Config::parse("compression = 1").is_err());
+    }
+}
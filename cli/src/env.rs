@@ -0,0 +1,197 @@
+use std::io::Error;
+use std::io::ErrorKind;
+
+use deko::write::Compression;
+use deko::Format;
+
+use crate::config::Config;
+use crate::format::parse_format;
+use crate::format::parse_level;
+
+/// Settings from the environment variables.
+///
+/// `DEKO_FORMAT`, `DEKO_LEVEL` and `DEKO_THREADS` have the same meaning as the corresponding
+/// options. The compression levels are also read from the variables of the other tools:
+/// `GZIP`, `BZIP2`, `BZIP`, `XZ_OPT` (e.g. `-9`, `--best`) and `ZSTD_CLEVEL` (e.g. `19`);
+/// other options and invalid values of these variables are ignored.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Env {
+    /// Default output format.
+    pub format: Option<Format>,
+    /// Compression level of all formats.
+    pub level: Option<Compression>,
+    /// The number of files that are processed in parallel.
+    pub threads: Option<usize>,
+    /// Compression levels of the individual formats.
+    pub levels: Vec<(Format, Compression)>,
+}
+
+impl Env {
+    /// Read the settings from the environment of the current process.
+    pub fn from_env() -> Result<Self, Error> {
+        Self::parse(|name| std::env::var(name).ok())
+    }
+
+    /// Read the settings using `var` to get the values of the variables.
+    ///
+    /// Empty variables are treated as unset.
+    pub fn parse<F: Fn(&str) -> Option<String>>(var: F) -> Result<Self, Error> {
+        let var = |name| var(name).filter(|value| !value.is_empty());
+        let mut env = Self::default();
+        if let Some(value) = var("DEKO_FORMAT") {
+            env.format = Some(parse_format(&value).map_err(|e| invalid_var("DEKO_FORMAT", e))?);
+        }
+        if let Some(value) = var("DEKO_LEVEL") {
+            env.level = Some(parse_level(&value).map_err(|e| invalid_var("DEKO_LEVEL", e))?);
+        }
+        if let Some(value) = var("DEKO_THREADS") {
+            env.threads = match value.parse() {
+                Ok(n) if n > 0 => Some(n),
+                _ => {
+                    return Err(invalid_var(
+                        "DEKO_THREADS",
+                        format!("invalid thread count `{value}`"),
+                    ))
+                }
+            };
+        }
+        // the first variable wins
+        for (name, format) in [
+            ("GZIP", Format::Gz),
+            ("BZIP2", Format::Bz),
+            ("BZIP", Format::Bz),
+            ("XZ_OPT", Format::Xz),
+        ] {
+            let Some(level) = var(name).and_then(|value| tool_level(&value)) else {
+                continue;
+            };
+            if !env.levels.iter().any(|(f, _)| *f == format) {
+                env.levels.push((format, level));
+            }
+        }
+        if let Some(level) = var("ZSTD_CLEVEL").and_then(|value| value.parse().ok()) {
+            env.levels.push((Format::Zstd, Compression::Level(level)));
+        }
+        Ok(env)
+    }
+
+    /// Override the configuration file settings.
+    pub fn apply(self, config: &mut Config) {
+        if let Some(format) = self.format {
+            config.format = Some(format);
+        }
+        if let Some(level) = self.level {
+            config.encoder.compression = level;
+            for format_config in config.encoder.formats.values_mut() {
+                format_config.compression = None;
+            }
+        }
+        if let Some(threads) = self.threads {
+            config.threads = Some(threads);
+        }
+        for (format, level) in self.levels.into_iter() {
+            config
+                .encoder
+                .formats
+                .entry(format)
+                .or_default()
+                .compression = Some(level);
+        }
+    }
+}
+
+/// Get compression level from gzip-like command-line options.
+///
+/// The last level wins.
+fn tool_level(options: &str) -> Option<Compression> {
+    let mut level = None;
+    for option in options.split_whitespace() {
+        match option {
+            "--fast" => level = Some(Compression::Fast),
+            "--best" => level = Some(Compression::Best),
+            _ if option.starts_with("--") => {}
+            // the level might be followed by modifiers, e.g. `-6e`,
+            // other options might take numeric values, e.g. `-T4`
+            _ => {
+                if let Some(digit) = option
+                    .strip_prefix('-')
+                    .and_then(|flags| flags.chars().next())
+                    .and_then(|flag| flag.to_digit(10))
+                {
+                    level = Some(Compression::Level(digit));
+                }
+            }
+        }
+    }
+    level
+}
+
+fn invalid_var(name: &str, error: impl std::fmt::Display) -> Error {
+    Error::new(ErrorKind::InvalidInput, format!("{name}: {error}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn parse(vars: &[(&str, &str)]) -> Result<Env, Error> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Env::parse(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn variables() {
+        let env = parse(&[
+            ("DEKO_FORMAT", "xz"),
+            ("DEKO_LEVEL", "fast"),
+            ("DEKO_THREADS", "8"),
+            ("GZIP", "-n --rsyncable -9"),
+            ("BZIP2", "-s"),
+            ("BZIP", "--best"),
+            ("XZ_OPT", "-6e -T4"),
+            ("ZSTD_CLEVEL", "19"),
+        ])
+        .unwrap();
+        assert_eq!(
+            Env {
+                format: Some(Format::Xz),
+                level: Some(Compression::Fast),
+                threads: Some(8),
+                levels: vec![
+                    (Format::Gz, Compression::Level(9)),
+                    (Format::Bz, Compression::Best),
+                    (Format::Xz, Compression::Level(6)),
+                    (Format::Zstd, Compression::Level(19)),
+                ],
+            },
+            env
+        );
+        assert_eq!(Env::default(), parse(&[("DEKO_FORMAT", "")]).unwrap());
+        let mut config = Config::parse("format = \"gz\"\n[levels]\nzstd = 1\nxz = 1").unwrap();
+        env.apply(&mut config);
+        assert_eq!(Some(Format::Xz), config.format);
+        assert_eq!(Some(8), config.threads);
+        assert_eq!(Compression::Fast, config.encoder.compression(Format::Zlib));
+        assert_eq!(
+            Compression::Level(19),
+            config.encoder.compression(Format::Zstd)
+        );
+        assert_eq!(
+            Compression::Level(6),
+            config.encoder.compression(Format::Xz)
+        );
+    }
+
+    #[test]
+    fn invalid_variables() {
+        assert!(parse(&[("DEKO_FORMAT", "arj")]).is_err());
+        assert!(parse(&[("DEKO_LEVEL", "max")]).is_err());
+        assert!(parse(&[("DEKO_THREADS", "0")]).is_err());
+        assert_eq!(Env::default(), parse(&[("ZSTD_CLEVEL", "-1")]).unwrap());
+    }
+}
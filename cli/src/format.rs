@@ -0,0 +1,96 @@
+use std::io::Error;
+use std::io::ErrorKind;
+
+use deko::write::Compression;
+use deko::Format;
+
+/// Parse format name.
+///
+/// Both the names of the tools and the file name suffixes are accepted, e.g. `gzip` and `gz`.
+pub fn parse_format(name: &str) -> Result<Format, Error> {
+    let format = match name.to_ascii_lowercase().as_str() {
+        "verbatim" | "none" => Format::Verbatim,
+        "gz" | "gzip" => Format::Gz,
+        "bz" | "bz2" | "bzip2" => Format::Bz,
+        "zlib" => Format::Zlib,
+        "xz" => Format::Xz,
+        "zst" | "zstd" => Format::Zstd,
+        "zip" => Format::Zip,
+        "szdd" => Format::Szdd,
+        "kwaj" => Format::Kwaj,
+        "lz4" => Format::Lz4,
+        "br" | "brotli" => Format::Brotli,
+        "lzo" | "lzop" => Format::Lzop,
+        "deflate" => Format::Deflate,
+        "lzfse" => Format::Lzfse,
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("unknown format `{name}`"),
+            ))
+        }
+    };
+    Ok(format)
+}
+
+/// Parse compression level.
+///
+/// The level is either a number or one of `fast`, `default`, `best`.
+pub fn parse_level(level: &str) -> Result<Compression, Error> {
+    match level.to_ascii_lowercase().as_str() {
+        "fast" => Ok(Compression::Fast),
+        "default" => Ok(Compression::Default),
+        "best" => Ok(Compression::Best),
+        other => other.parse().map(Compression::Level).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid compression level `{level}`"),
+            )
+        }),
+    }
+}
+
+/// File name suffix (without the dot) of the `format`.
+pub fn suffix(format: Format) -> Option<&'static str> {
+    match format {
+        Format::Verbatim => None,
+        Format::Gz => Some("gz"),
+        Format::Bz => Some("bz2"),
+        Format::Zlib => Some("zlib"),
+        Format::Xz => Some("xz"),
+        Format::Zstd => Some("zst"),
+        Format::Zip => Some("zip"),
+        Format::Szdd => Some("szdd"),
+        Format::Kwaj => Some("kwaj"),
+        Format::Lz4 => Some("lz4"),
+        Format::Brotli => Some("br"),
+        Format::Lzop => Some("lzo"),
+        Format::Deflate => Some("deflate"),
+        Format::Lzfse => Some("lzfse"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        for format in Format::ALL.iter() {
+            if let Some(suffix) = suffix(*format) {
+                assert_eq!(*format, parse_format(suffix).unwrap());
+            }
+        }
+        assert_eq!(Format::Gz, parse_format("GZIP").unwrap());
+        assert!(parse_format("arj").is_err());
+    }
+
+    #[test]
+    fn levels() {
+        assert_eq!(Compression::Best, parse_level("best").unwrap());
+        assert_eq!(Compression::Level(19), parse_level("19").unwrap());
+        assert!(parse_level("-1").is_err());
+        assert!(parse_level("max").is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use std::path::Path;
+
+/// Returns `true` if either the file name or the whole `path` matches the `pattern`.
+pub fn matches_path(pattern: &str, path: &Path) -> bool {
+    let file_name_matches = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| matches(pattern, name));
+    file_name_matches || path.to_str().is_some_and(|path| matches(pattern, path))
+}
+
+/// Match the `text` against shell-style `pattern`.
+///
+/// `*` matches any sequence of characters, `?` matches any single character.
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let mut p = 0;
+    let mut t = 0;
+    // the position of the last `*` in the pattern and the matching position in the text
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(ch) if *ch == '?' || *ch == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    // let the star consume one more character
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|ch| *ch == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob() {
+        assert!(matches("*.jpg", "a.jpg"));
+        assert!(matches("*", ""));
+        assert!(matches("a?c", "abc"));
+        assert!(matches("*a*b*", "xxaxxbxx"));
+        assert!(!matches("*.jpg", "a.jpeg"));
+        assert!(!matches("a?c", "ac"));
+        assert!(!matches("", "a"));
+        assert!(matches_path("*.jpg", Path::new("dir/a.jpg")));
+        assert!(matches_path("dir/*", Path::new("dir/a.jpg")));
+        assert!(!matches_path("*.png", Path::new("dir/a.jpg")));
+    }
+}
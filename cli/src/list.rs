@@ -0,0 +1,99 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use deko::inspect_file;
+use deko::FileInfo;
+
+/// Print the format and the sizes of the files like `gzip -l` does.
+///
+/// The ratio is the space saving, i.e. one minus compressed size divided by uncompressed size.
+pub fn list(files: &[PathBuf], verify: bool) -> ExitCode {
+    let mut code = ExitCode::SUCCESS;
+    let mut total_compressed = 0;
+    let mut total_uncompressed = Some(0);
+    println!(
+        "{:>15} {:>15} {:>6}  {:<8}  name",
+        "compressed", "uncompressed", "ratio", "format"
+    );
+    for path in files.iter() {
+        match inspect_file(path, verify) {
+            Ok(info) => {
+                println!("{}  {}", row(&info), path.display());
+                total_compressed += info.compressed_size;
+                total_uncompressed = total_uncompressed
+                    .zip(info.uncompressed_size)
+                    .map(|(a, b)| a + b);
+            }
+            Err(e) => {
+                eprintln!("deko: {}: {e}", path.display());
+                code = ExitCode::FAILURE;
+            }
+        }
+    }
+    if files.len() > 1 {
+        println!(
+            "{:>15} {:>15} {:>6}  {:<8}  (totals)",
+            total_compressed,
+            size(total_uncompressed),
+            ratio(total_compressed, total_uncompressed),
+            ""
+        );
+    }
+    code
+}
+
+fn row(info: &FileInfo) -> String {
+    format!(
+        "{:>15} {:>15} {:>6}  {:<8}",
+        info.compressed_size,
+        size(info.uncompressed_size),
+        ratio(info.compressed_size, info.uncompressed_size),
+        format!("{:?}", info.format).to_lowercase()
+    )
+}
+
+fn size(size: Option<u64>) -> String {
+    match size {
+        Some(size) => size.to_string(),
+        None => "-".into(),
+    }
+}
+
+fn ratio(compressed: u64, uncompressed: Option<u64>) -> String {
+    match uncompressed {
+        Some(uncompressed) if uncompressed != 0 => {
+            let saving = 1.0 - compressed as f64 / uncompressed as f64;
+            format!("{:.1}%", saving * 100.0)
+        }
+        _ => "-".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use deko::Format;
+
+    use super::*;
+
+    #[test]
+    fn rows() {
+        let info = FileInfo {
+            format: Format::Gz,
+            compressed_size: 25,
+            uncompressed_size: Some(100),
+        };
+        assert_eq!(
+            "             25             100  75.0%  gz      ",
+            row(&info)
+        );
+        let info = FileInfo {
+            format: Format::Bz,
+            compressed_size: 25,
+            uncompressed_size: None,
+        };
+        assert_eq!(
+            "             25               -      -  bz      ",
+            row(&info)
+        );
+    }
+}
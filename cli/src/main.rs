@@ -0,0 +1,381 @@
+//! Command-line compressor and decompressor that automatically detects compression format.
+
+use std::fs::remove_file;
+use std::fs::File;
+use std::fs::Metadata;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::IsTerminal;
+use std::io::Read;
+use std::io::Seek;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::time::SystemTime;
+
+use deko::bufread::AnyDecoder;
+use deko::write::AnyEncoder;
+use deko::write::CompressionLevel;
+use deko::write::GzEncoder;
+use deko::write::GzHeader;
+use deko::Format;
+
+use crate::args::Args;
+use crate::args::USAGE;
+use crate::config::Config;
+use crate::env::Env;
+use crate::metadata::copy_metadata;
+use crate::metadata::GzMetadata;
+use crate::output::compressed_path;
+use crate::output::decompressed_path;
+use crate::output::original_path;
+
+mod args;
+mod config;
+mod env;
+mod format;
+mod glob;
+mod list;
+mod metadata;
+mod output;
+
+fn main() -> ExitCode {
+    match do_main() {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("deko: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn do_main() -> Result<ExitCode, Error> {
+    let args = Args::parse(std::env::args_os().skip(1))?;
+    if args.help {
+        print!("{USAGE}");
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.version {
+        println!("deko {}", env!("CARGO_PKG_VERSION"));
+        return Ok(ExitCode::SUCCESS);
+    }
+    let mut config = match args.config {
+        _ if args.no_config => Config::default(),
+        Some(ref path) => Config::load(path)?,
+        None => Config::load_default()?,
+    };
+    Env::from_env()?.apply(&mut config);
+    let settings = Settings::new(args, config);
+    if settings.files.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidInput, "no input files"));
+    }
+    if settings.stdout && settings.output.is_some() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "`--stdout` and `--output` are mutually exclusive",
+        ));
+    }
+    if settings.list {
+        return Ok(list::list(&settings.files, settings.verify));
+    }
+    if settings.output.is_some() && settings.files.len() > 1 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "`--output` requires exactly one input file",
+        ));
+    }
+    let failed = AtomicBool::new(false);
+    let next = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        for _ in 0..settings.threads.min(settings.files.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = settings.files.get(i) else {
+                    break;
+                };
+                if let Err(e) = settings.process(path) {
+                    eprintln!("deko: {}: {e}", path.display());
+                    failed.store(true, Ordering::Relaxed);
+                }
+            });
+        }
+    });
+    if failed.load(Ordering::Relaxed) {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Command-line arguments merged with the configuration file.
+#[derive(Debug)]
+struct Settings {
+    decompress: bool,
+    output: Option<PathBuf>,
+    stdout: bool,
+    suffix: Option<String>,
+    force: bool,
+    name: bool,
+    list: bool,
+    verify: bool,
+    format: Format,
+    /// The format that is used for decompression instead of the detected one.
+    forced_format: Option<Format>,
+    encoder: deko::write::Config,
+    threads: usize,
+    files: Vec<PathBuf>,
+}
+
+impl Settings {
+    fn new(args: Args, mut config: Config) -> Self {
+        if let Some(level) = args.level {
+            config.encoder.compression = level;
+            for format_config in config.encoder.formats.values_mut() {
+                format_config.compression = None;
+            }
+        }
+        let exclude = config
+            .exclude
+            .iter()
+            .chain(args.exclude.iter())
+            .collect::<Vec<_>>();
+        let mut files: Vec<PathBuf> = args
+            .files
+            .into_iter()
+            .filter(|path| {
+                !exclude
+                    .iter()
+                    .any(|pattern| glob::matches_path(pattern, path))
+            })
+            .collect();
+        if files.is_empty() && !args.list {
+            // read standard input
+            files.push(STDIO.into());
+        }
+        let stdout = args.stdout || (args.output.is_none() && files == [Path::new(STDIO)]);
+        // the output is written sequentially
+        let threads = match stdout {
+            true => 1,
+            false => args.threads.or(config.threads).unwrap_or(1),
+        };
+        Self {
+            decompress: args.decompress,
+            output: args.output,
+            stdout,
+            suffix: args.suffix,
+            force: args.force,
+            name: args.name,
+            list: args.list,
+            verify: args.verify,
+            format: args.format.or(config.format).unwrap_or(Format::Gz),
+            forced_format: args.format.filter(|_| args.decompress),
+            encoder: config.encoder,
+            threads,
+            files,
+        }
+    }
+
+    fn process(&self, path: &Path) -> Result<(), Error> {
+        if path == Path::new(STDIO) {
+            let input = std::io::stdin().lock();
+            let Some(ref output_path) = self.output else {
+                return self.write_stdout(path, input, None);
+            };
+            let output = output::create(path, output_path, self.force)?;
+            let result = self.transcode(path, input, output, None);
+            if result.is_err() {
+                // don't leave partial output
+                let _ = remove_file(output_path);
+            }
+            return result.map(|_| ());
+        }
+        let input = File::open(path)?;
+        if self.stdout {
+            let header = self.gz_header(path, &input.metadata()?);
+            return self.write_stdout(path, BufReader::new(input), header);
+        }
+        let metadata = input.metadata()?;
+        let gz_metadata = if self.decompress && self.name {
+            let gz_metadata = GzMetadata::read(BufReader::new(&input))?;
+            (&input).rewind()?;
+            gz_metadata
+        } else {
+            None
+        };
+        let suffix = self.suffix.as_deref();
+        let filename = gz_metadata.as_ref().and_then(|gz| gz.filename.as_deref());
+        let output_path = match (self.output.as_ref(), filename) {
+            (Some(output), _) => output.clone(),
+            (None, Some(filename)) if self.decompress => original_path(path, filename)?,
+            (None, None) if self.decompress => decompressed_path(path, suffix)?,
+            (None, _) => compressed_path(path, self.format, suffix, self.force)?,
+        };
+        let output = output::create(path, &output_path, self.force)?;
+        let mtime = gz_metadata.and_then(|gz| gz.mtime);
+        let header = self.gz_header(path, &metadata);
+        let result = self
+            .transcode(path, BufReader::new(input), output, header)
+            .and_then(|output| copy_metadata(&metadata, &output, mtime));
+        if result.is_err() {
+            // don't leave partial output
+            let _ = remove_file(&output_path);
+        }
+        result
+    }
+
+    /// Compress or decompress the `input` to the standard output.
+    fn write_stdout<R: BufRead>(
+        &self,
+        path: &Path,
+        input: R,
+        header: Option<GzHeader>,
+    ) -> Result<(), Error> {
+        let stdout = std::io::stdout().lock();
+        if !self.decompress && !self.force && stdout.is_terminal() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "compressed data not written to a terminal, use -f to force",
+            ));
+        }
+        self.transcode(path, input, stdout, header)?.flush()
+    }
+
+    /// Compress or decompress the `input` depending on the settings.
+    fn transcode<R: BufRead, W: Write>(
+        &self,
+        path: &Path,
+        input: R,
+        output: W,
+        header: Option<GzHeader>,
+    ) -> Result<W, Error> {
+        if self.decompress {
+            self.decompress(path, input, output)
+        } else {
+            self.compress(input, output, header)
+        }
+    }
+
+    /// Gzip header with the original name and modification time of the file.
+    fn gz_header(&self, path: &Path, metadata: &Metadata) -> Option<GzHeader> {
+        if self.decompress || !self.name || self.format != Format::Gz {
+            return None;
+        }
+        Some(GzHeader {
+            filename: path
+                .file_name()
+                .map(|name| name.as_encoded_bytes().to_vec()),
+            mtime: metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+                .map(|t| t.as_secs().min(u32::MAX as u64) as u32)
+                .unwrap_or(0),
+            ..Default::default()
+        })
+    }
+
+    fn compress<R: Read, W: Write>(
+        &self,
+        mut input: R,
+        output: W,
+        header: Option<GzHeader>,
+    ) -> Result<W, Error> {
+        let writer = BufWriter::new(output);
+        let mut writer = match header {
+            // store the original name and modification time in the header
+            Some(header) => {
+                let level = match self.encoder.compression(Format::Gz).to_level(Format::Gz) {
+                    CompressionLevel::Gz(level) => level,
+                    _ => Default::default(),
+                };
+                AnyEncoder::Gz(GzEncoder::with_header(writer, level, header))
+            }
+            None => AnyEncoder::with_config(writer, self.format, &self.encoder)?,
+        };
+        std::io::copy(&mut input, &mut writer)?;
+        let mut writer = writer.finish()?;
+        writer.flush()?;
+        writer.into_inner().map_err(|e| e.into_error())
+    }
+
+    fn decompress<R: BufRead, W: Write>(
+        &self,
+        path: &Path,
+        input: R,
+        output: W,
+    ) -> Result<W, Error> {
+        let mut reader = match self.input_format(path) {
+            Some(format) => AnyDecoder::with_format(input, format),
+            None => AnyDecoder::new(input),
+        };
+        reader.fail_on_unknown_format(true);
+        let mut writer = BufWriter::new(output);
+        std::io::copy(&mut reader, &mut writer)?;
+        writer.into_inner().map_err(|e| e.into_error())
+    }
+
+    /// The format of the input file.
+    ///
+    /// Brotli and raw DEFLATE streams have no magic bytes,
+    /// hence the format is inferred from the file extension.
+    fn input_format(&self, path: &Path) -> Option<Format> {
+        self.forced_format.or_else(|| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .and_then(Format::from_extension)
+                .filter(|format| matches!(format, Format::Brotli | Format::Deflate))
+        })
+    }
+}
+
+/// The file name that means standard input.
+const STDIO: &str = "-";
+
+#[cfg(test)]
+mod tests {
+    use deko::write::Compression;
+
+    use super::*;
+
+    #[test]
+    fn settings() {
+        let args = Args {
+            format: Some(Format::Xz),
+            level: Some(Compression::Best),
+            exclude: vec!["*.jpg".into()],
+            files: vec!["a.txt".into(), "b.jpg".into(), "c.png".into()],
+            ..Default::default()
+        };
+        let config = Config::parse(
+            r#"
+format = "zstd"
+threads = 2
+exclude = ["*.png"]
+[levels]
+xz = 1
+"#,
+        )
+        .unwrap();
+        let settings = Settings::new(args, config.clone());
+        assert_eq!(Format::Xz, settings.format);
+        assert_eq!(2, settings.threads);
+        assert_eq!(Compression::Best, settings.encoder.compression(Format::Xz));
+        assert_eq!(vec![PathBuf::from("a.txt")], settings.files);
+        let settings = Settings::new(Default::default(), config);
+        assert_eq!(Format::Zstd, settings.format);
+        assert_eq!(vec![PathBuf::from(STDIO)], settings.files);
+        assert!(settings.stdout);
+        assert_eq!(1, settings.threads);
+        assert_eq!(
+            Compression::Level(1),
+            settings.encoder.compression(Format::Xz)
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use std::fs::File;
+use std::fs::FileTimes;
+use std::fs::Metadata;
+use std::io::BufRead;
+use std::io::Error;
+use std::io::Read;
+use std::time::Duration;
+use std::time::SystemTime;
+
+/// Copy permissions and access/modification times of the input file to the output file.
+///
+/// The modification time is overridden with `mtime` (e.g. the time stored in gzip header).
+pub fn copy_metadata(
+    input: &Metadata,
+    output: &File,
+    mtime: Option<SystemTime>,
+) -> Result<(), Error> {
+    output.set_permissions(input.permissions())?;
+    let mut times = FileTimes::new();
+    if let Ok(accessed) = input.accessed() {
+        times = times.set_accessed(accessed);
+    }
+    if let Some(modified) = mtime.or_else(|| input.modified().ok()) {
+        times = times.set_modified(modified);
+    }
+    output.set_times(times)
+}
+
+/// Original file name and modification time from the gzip header.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct GzMetadata {
+    pub filename: Option<Vec<u8>>,
+    pub mtime: Option<SystemTime>,
+}
+
+impl GzMetadata {
+    /// Read the header of the first gzip member.
+    ///
+    /// Returns `None` if the input is not gzip.
+    pub fn read<R: BufRead>(mut reader: R) -> Result<Option<Self>, Error> {
+        let mut header = [0_u8; 10];
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        if header[..3] != [0x1f, 0x8b, 8] {
+            return Ok(None);
+        }
+        let flags = header[3];
+        let mtime = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        if flags & FEXTRA != 0 {
+            let mut len = [0_u8; 2];
+            reader.read_exact(&mut len)?;
+            let len = u16::from_le_bytes(len) as u64;
+            std::io::copy(&mut (&mut reader).take(len), &mut std::io::sink())?;
+        }
+        let filename = if flags & FNAME != 0 {
+            let mut filename = Vec::new();
+            reader.read_until(0, &mut filename)?;
+            filename.pop();
+            Some(filename)
+        } else {
+            None
+        };
+        Ok(Some(Self {
+            filename,
+            mtime: (mtime != 0).then(|| SystemTime::UNIX_EPOCH + Duration::from_secs(mtime as u64)),
+        }))
+    }
+}
+
+const FEXTRA: u8 = 1 << 2;
+const FNAME: u8 = 1 << 3;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use deko::write::GzEncoder;
+    use deko::write::GzHeader;
+
+    use super::*;
+
+    #[test]
+    fn gz_metadata() {
+        let header = GzHeader {
+            filename: Some(b"a.txt".to_vec()),
+            extra: Some(vec![1, 2, 3]),
+            mtime: 1_000_000,
+            ..Default::default()
+        };
+        let mut writer = GzEncoder::with_header(Vec::new(), Default::default(), header);
+        writer.write_all(b"hello").unwrap();
+        let compressed = writer.finish().unwrap();
+        let expected = GzMetadata {
+            filename: Some(b"a.txt".to_vec()),
+            mtime: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000)),
+        };
+        assert_eq!(Some(expected), GzMetadata::read(&compressed[..]).unwrap());
+        assert_eq!(None, GzMetadata::read(&b"hello"[..]).unwrap());
+    }
+}
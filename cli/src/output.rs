@@ -0,0 +1,214 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+
+use deko::Format;
+
+use crate::format::suffix;
+
+/// Append the suffix to the compressed file name.
+///
+/// The suffix is either the one supplied by the user or the default suffix of the `format`.
+/// The files that already have the suffix are compressed only if `force` is `true`.
+pub fn compressed_path(
+    path: &Path,
+    format: Format,
+    custom_suffix: Option<&str>,
+    force: bool,
+) -> Result<PathBuf, Error> {
+    let suffix = match custom_suffix {
+        Some(suffix) => suffix.to_string(),
+        None => {
+            let suffix = suffix(format).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    "verbatim format has no file name suffix",
+                )
+            })?;
+            format!(".{suffix}")
+        }
+    };
+    if !force
+        && path
+            .as_os_str()
+            .as_encoded_bytes()
+            .ends_with(suffix.as_bytes())
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("already has `{suffix}` suffix, use -f to compress anyway"),
+        ));
+    }
+    let mut output = path.as_os_str().to_os_string();
+    output.push(suffix);
+    Ok(output.into())
+}
+
+/// Strip the suffix from the compressed file name.
+///
+/// Tar-specific suffixes (`.tgz`, `.txz` etc.) are replaced with `.tar`.
+pub fn decompressed_path(path: &Path, custom_suffix: Option<&str>) -> Result<PathBuf, Error> {
+    let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+        return Err(unknown_suffix());
+    };
+    let suffixes = match custom_suffix {
+        Some(suffix) => &[(suffix, "")][..],
+        None => SUFFIXES,
+    };
+    for (suffix, replacement) in suffixes.iter() {
+        let Some(stem) = strip_suffix_ignore_case(file_name, suffix) else {
+            continue;
+        };
+        if stem.is_empty() {
+            continue;
+        }
+        let mut output = OsString::from(stem);
+        output.push(replacement);
+        return Ok(path.with_file_name(output));
+    }
+    Err(unknown_suffix())
+}
+
+/// Replace the file name with the original name from the compressed file header.
+///
+/// Only the last component of the original name is used.
+pub fn original_path(path: &Path, filename: &[u8]) -> Result<PathBuf, Error> {
+    let filename = String::from_utf8_lossy(filename);
+    let Some(filename) = Path::new(filename.as_ref()).file_name() else {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "invalid original file name",
+        ));
+    };
+    Ok(path.with_file_name(filename))
+}
+
+/// Create the output file.
+///
+/// Existing files are overwritten only if `force` is `true`.
+pub fn create(input: &Path, output: &Path, force: bool) -> Result<File, Error> {
+    if same_file(input, output) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "input and output are the same file",
+        ));
+    }
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    options.open(output).map_err(|e| {
+        if e.kind() == ErrorKind::AlreadyExists {
+            Error::new(
+                e.kind(),
+                format!("{} already exists, use -f to overwrite", output.display()),
+            )
+        } else {
+            e
+        }
+    })
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn strip_suffix_ignore_case<'a>(name: &'a str, suffix: &str) -> Option<&'a str> {
+    let i = name.len().checked_sub(suffix.len())?;
+    if !name.is_char_boundary(i) || !name[i..].eq_ignore_ascii_case(suffix) {
+        return None;
+    }
+    Some(&name[..i])
+}
+
+fn unknown_suffix() -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        "unknown suffix, use -S to specify one",
+    )
+}
+
+/// Known suffixes and their replacements.
+const SUFFIXES: &[(&str, &str)] = &[
+    (".gz", ""),
+    (".tgz", ".tar"),
+    (".taz", ".tar"),
+    (".bz2", ""),
+    (".bz", ""),
+    (".tbz2", ".tar"),
+    (".tbz", ".tar"),
+    (".zlib", ""),
+    (".xz", ""),
+    (".txz", ".tar"),
+    (".zst", ""),
+    (".tzst", ".tar"),
+    (".br", ""),
+    (".lzo", ""),
+    (".tzo", ".tar"),
+    (".deflate", ""),
+    (".lzfse", ""),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compressed_paths() {
+        assert_eq!(
+            PathBuf::from("a.txt.zst"),
+            compressed_path(Path::new("a.txt"), Format::Zstd, None, false).unwrap()
+        );
+        assert_eq!(
+            PathBuf::from("a.txt.z"),
+            compressed_path(Path::new("a.txt"), Format::Gz, Some(".z"), false).unwrap()
+        );
+        assert!(compressed_path(Path::new("a.txt"), Format::Verbatim, None, false).is_err());
+        assert!(compressed_path(Path::new("a.gz"), Format::Gz, None, false).is_err());
+        assert_eq!(
+            PathBuf::from("a.gz.gz"),
+            compressed_path(Path::new("a.gz"), Format::Gz, None, true).unwrap()
+        );
+    }
+
+    #[test]
+    fn decompressed_paths() {
+        for (input, output) in [
+            ("a.txt.bz2", "a.txt"),
+            ("dir/a.tgz", "dir/a.tar"),
+            ("A.TXZ", "A.tar"),
+            ("a.gz.zst", "a.gz"),
+        ] {
+            assert_eq!(
+                PathBuf::from(output),
+                decompressed_path(Path::new(input), None).unwrap()
+            );
+        }
+        assert_eq!(
+            PathBuf::from("a.txt"),
+            decompressed_path(Path::new("a.txt.z"), Some(".z")).unwrap()
+        );
+        assert!(decompressed_path(Path::new("a.txt"), None).is_err());
+        assert!(decompressed_path(Path::new(".gz"), None).is_err());
+        assert!(decompressed_path(Path::new("a.gz"), Some(".z")).is_err());
+    }
+
+    #[test]
+    fn original_paths() {
+        assert_eq!(
+            PathBuf::from("dir/a.txt"),
+            original_path(Path::new("dir/b.gz"), b"/etc/a.txt").unwrap()
+        );
+        assert!(original_path(Path::new("dir/b.gz"), b"..").is_err());
+    }
+}
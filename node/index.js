@@ -0,0 +1,60 @@
+'use strict';
+
+const { Transform } = require('node:stream');
+const native = require('./deko.node');
+
+/** A transform stream that compresses the data using the format and the level. */
+class CompressStream extends Transform {
+  constructor(format, level, options) {
+    super(options);
+    this.inner = new native.Compressor(format, level);
+  }
+
+  _transform(chunk, encoding, callback) {
+    pushOutput(this, () => this.inner.transform(chunk), callback);
+  }
+
+  _flush(callback) {
+    pushOutput(this, () => this.inner.flush(), callback);
+  }
+}
+
+/** A transform stream that decompresses the data detecting the format automatically. */
+class DecompressStream extends Transform {
+  constructor(options) {
+    super(options);
+    this.inner = new native.Decompressor();
+  }
+
+  _transform(chunk, encoding, callback) {
+    pushOutput(this, () => this.inner.transform(chunk), callback);
+  }
+
+  _flush(callback) {
+    pushOutput(this, () => this.inner.flush(), callback);
+  }
+}
+
+function pushOutput(stream, f, callback) {
+  let output;
+  try {
+    output = f();
+  } catch (e) {
+    callback(e);
+    return;
+  }
+  if (output.length !== 0) {
+    stream.push(output);
+  }
+  callback();
+}
+
+module.exports = {
+  detect: native.detect,
+  compress: native.compress,
+  decompress: native.decompress,
+  Compressor: native.Compressor,
+  Decompressor: native.Decompressor,
+  CompressStream,
+  DecompressStream,
+};
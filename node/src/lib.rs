@@ -0,0 +1,389 @@
+//! Node.js bindings for `deko`.
+//!
+//! The functions decode/encode the whole buffer at once,
+//! the classes decode/encode the data chunk by chunk and
+//! back [Transform](https://nodejs.org/api/stream.html#class-streamtransform) streams
+//! from `index.js`.
+
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+use std::sync::mpsc::TryRecvError;
+
+use deko::write::AnyEncoder;
+use deko::write::Compression;
+use deko::Format;
+use napi::bindgen_prelude::Buffer;
+use napi::Either;
+use napi_derive::napi;
+
+/// Detect compression format of the data.
+///
+/// Returns the name of the format, e.g. `gzip`, `zstd` or `verbatim` for uncompressed data;
+/// the same names are accepted by [compress].
+#[napi]
+pub fn detect(data: Buffer) -> napi::Result<String> {
+    let format = detect_format(&data).map_err(to_napi_error)?;
+    Ok(format_name(format).into())
+}
+
+/// Decompress the data detecting the format automatically.
+#[napi]
+pub fn decompress(data: Buffer) -> napi::Result<Buffer> {
+    let output = decompress_all(&data).map_err(to_napi_error)?;
+    Ok(output.into())
+}
+
+/// Compress the data using the `format` and the compression `level`.
+///
+/// The level is either a number or one of `fast`, `default`, `best`.
+#[napi]
+pub fn compress(
+    data: Buffer,
+    format: String,
+    level: Option<Either<u32, String>>,
+) -> napi::Result<Buffer> {
+    let output = compress_all(&data, &format, level).map_err(to_napi_error)?;
+    Ok(output.into())
+}
+
+fn detect_format(data: &[u8]) -> Result<Format, Error> {
+    deko::AnyDecoder::new(data).kind()
+}
+
+fn decompress_all(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut decoder = deko::AnyDecoder::new(data);
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output)?;
+    Ok(output)
+}
+
+fn compress_all(
+    data: &[u8],
+    format: &str,
+    level: Option<Either<u32, String>>,
+) -> Result<Vec<u8>, Error> {
+    let mut encoder = new_encoder(format, level)?;
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+/// Chunk-by-chunk compressor.
+#[napi]
+pub struct Compressor {
+    encoder: Option<AnyEncoder<Vec<u8>>>,
+}
+
+#[napi]
+impl Compressor {
+    /// Create new compressor for the `format` and the compression `level`.
+    #[napi(constructor)]
+    pub fn new(format: String, level: Option<Either<u32, String>>) -> napi::Result<Self> {
+        Ok(Self {
+            encoder: Some(new_encoder(&format, level).map_err(to_napi_error)?),
+        })
+    }
+
+    /// Compress the `chunk` and return the compressed data that is ready so far.
+    #[napi]
+    pub fn transform(&mut self, chunk: Buffer) -> napi::Result<Buffer> {
+        let encoder = self
+            .encoder
+            .as_mut()
+            .ok_or_else(finished)
+            .map_err(to_napi_error)?;
+        encoder.write_all(&chunk).map_err(to_napi_error)?;
+        Ok(std::mem::take(encoder.get_mut()).into())
+    }
+
+    /// Finish compression and return the rest of the compressed data.
+    #[napi]
+    pub fn flush(&mut self) -> napi::Result<Buffer> {
+        let encoder = self
+            .encoder
+            .take()
+            .ok_or_else(finished)
+            .map_err(to_napi_error)?;
+        let output = encoder.finish().map_err(to_napi_error)?;
+        Ok(output.into())
+    }
+}
+
+/// Chunk-by-chunk decompressor.
+///
+/// The format is detected automatically.
+/// The data is decompressed on a background thread,
+/// i.e. [transform](Decompressor::transform) never blocks and returns the data
+/// that has been decompressed so far, and [flush](Decompressor::flush) waits for the rest.
+#[napi]
+pub struct Decompressor {
+    input: Option<Sender<Vec<u8>>>,
+    output: Receiver<Result<Vec<u8>, Error>>,
+}
+
+#[napi]
+impl Decompressor {
+    /// Create new decompressor and start the background thread.
+    #[napi(constructor)]
+    pub fn new() -> Self {
+        let (input, receiver) = channel();
+        let (sender, output) = channel();
+        std::thread::spawn(move || decode(ChannelReader::new(receiver), sender));
+        Self {
+            input: Some(input),
+            output,
+        }
+    }
+
+    /// Pass the `chunk` to the decompressor and return the data that is decompressed so far.
+    #[napi]
+    pub fn transform(&mut self, chunk: Buffer) -> napi::Result<Buffer> {
+        let data = self.send(chunk.to_vec()).map_err(to_napi_error)?;
+        Ok(data.into())
+    }
+
+    /// Signal the end of the input and return the rest of the decompressed data.
+    #[napi]
+    pub fn flush(&mut self) -> napi::Result<Buffer> {
+        let data = self.finish().map_err(to_napi_error)?;
+        Ok(data.into())
+    }
+}
+
+impl Decompressor {
+    fn send(&mut self, chunk: Vec<u8>) -> Result<Vec<u8>, Error> {
+        let input = self.input.as_ref().ok_or_else(finished)?;
+        // the thread exits only on error; the error is received below
+        let _ = input.send(chunk);
+        let mut data = Vec::new();
+        loop {
+            match self.output.try_recv() {
+                Ok(Ok(chunk)) => data.extend(chunk),
+                Ok(Err(e)) => return Err(e),
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => break,
+            }
+        }
+        Ok(data)
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>, Error> {
+        // closing the channel signals end-of-file
+        self.input.take().ok_or_else(finished)?;
+        let mut data = Vec::new();
+        for result in self.output.iter() {
+            data.extend(result?);
+        }
+        Ok(data)
+    }
+}
+
+impl Default for Decompressor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn decode(reader: ChannelReader, sender: Sender<Result<Vec<u8>, Error>>) {
+    let mut decoder = deko::read::AnyDecoder::new(reader);
+    loop {
+        let mut buf = vec![0_u8; BUFFER_SIZE];
+        let result = match decoder.read(&mut buf) {
+            Ok(0) => return,
+            Ok(n) => {
+                buf.truncate(n);
+                Ok(buf)
+            }
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => Err(e),
+        };
+        let stop = result.is_err();
+        if sender.send(result).is_err() || stop {
+            return;
+        }
+    }
+}
+
+/// A reader that blocks until the next chunk arrives via the channel.
+struct ChannelReader {
+    receiver: Receiver<Vec<u8>>,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl ChannelReader {
+    fn new(receiver: Receiver<Vec<u8>>) -> Self {
+        Self {
+            receiver,
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        while self.pos == self.buf.len() {
+            match self.receiver.recv() {
+                Ok(chunk) => {
+                    self.buf = chunk;
+                    self.pos = 0;
+                }
+                // the sender is dropped, i.e. end-of-file
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.buf.len() - self.pos);
+        buf[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+fn new_encoder(
+    format: &str,
+    level: Option<Either<u32, String>>,
+) -> Result<AnyEncoder<Vec<u8>>, Error> {
+    let format = parse_format(format)?;
+    let compression = match level {
+        None => Compression::Default,
+        Some(Either::A(level)) => Compression::Level(level),
+        Some(Either::B(level)) => parse_level(&level)?,
+    };
+    AnyEncoder::new(Vec::new(), format, compression)
+}
+
+/// Parse format name.
+///
+/// Both the names of the tools and the file name suffixes are accepted, e.g. `gzip` and `gz`.
+fn parse_format(name: &str) -> Result<Format, Error> {
+    let format = match name.to_ascii_lowercase().as_str() {
+        "verbatim" | "none" => Format::Verbatim,
+        "gz" | "gzip" => Format::Gz,
+        "bz" | "bz2" | "bzip2" => Format::Bz,
+        "zlib" => Format::Zlib,
+        "xz" => Format::Xz,
+        "zst" | "zstd" => Format::Zstd,
+        "zip" => Format::Zip,
+        "szdd" => Format::Szdd,
+        "kwaj" => Format::Kwaj,
+        "lz4" => Format::Lz4,
+        "br" | "brotli" => Format::Brotli,
+        "lzo" | "lzop" => Format::Lzop,
+        "deflate" => Format::Deflate,
+        "lzfse" => Format::Lzfse,
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("unknown format `{name}`"),
+            ))
+        }
+    };
+    Ok(format)
+}
+
+/// Parse compression level.
+fn parse_level(level: &str) -> Result<Compression, Error> {
+    match level.to_ascii_lowercase().as_str() {
+        "fast" => Ok(Compression::Fast),
+        "default" => Ok(Compression::Default),
+        "best" => Ok(Compression::Best),
+        other => other.parse().map(Compression::Level).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid compression level `{level}`"),
+            )
+        }),
+    }
+}
+
+fn format_name(format: Format) -> &'static str {
+    match format {
+        Format::Verbatim => "verbatim",
+        Format::Gz => "gzip",
+        Format::Bz => "bzip2",
+        Format::Zlib => "zlib",
+        Format::Xz => "xz",
+        Format::Zstd => "zstd",
+        Format::Zip => "zip",
+        Format::Szdd => "szdd",
+        Format::Kwaj => "kwaj",
+        Format::Lz4 => "lz4",
+        Format::Brotli => "brotli",
+        Format::Lzop => "lzop",
+        Format::Deflate => "deflate",
+        Format::Lzfse => "lzfse",
+        Format::Other(name) => name,
+        _ => "unknown",
+    }
+}
+
+fn to_napi_error(e: Error) -> napi::Error {
+    napi::Error::from_reason(e.to_string())
+}
+
+fn finished() -> Error {
+    Error::other("the stream is already finished")
+}
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_names() {
+        for format in Format::ALL.iter().copied() {
+            let name = format_name(format);
+            assert_ne!("unknown", name, "{format:?}");
+            assert_eq!(format, parse_format(name).unwrap());
+        }
+    }
+
+    #[test]
+    fn compress_decompress() {
+        let expected = b"hello world".repeat(100);
+        for format in Format::enabled() {
+            let level = Some(Either::B("best".into()));
+            let compressed = compress_all(&expected, format_name(format), level).unwrap();
+            if !Format::DETECTION_ORDER.contains(&format) {
+                continue;
+            }
+            assert_eq!(format, detect_format(&compressed).unwrap());
+            assert_eq!(expected, decompress_all(&compressed).unwrap(), "{format:?}");
+        }
+    }
+
+    #[test]
+    fn decompressor() {
+        let expected = b"hello world".repeat(1000);
+        let compressed = compress_all(&expected, "zstd", None).unwrap();
+        let mut decompressor = Decompressor::new();
+        let mut actual = Vec::new();
+        for chunk in compressed.chunks(100) {
+            actual.extend(decompressor.send(chunk.to_vec()).unwrap());
+        }
+        actual.extend(decompressor.finish().unwrap());
+        assert_eq!(expected, actual);
+        assert!(decompressor.send(compressed).is_err());
+        assert!(decompressor.finish().is_err());
+    }
+
+    #[test]
+    fn decompressor_error() {
+        let expected = b"hello world".repeat(1000);
+        let mut compressed = compress_all(&expected, "gzip", None).unwrap();
+        // corrupt the CRC
+        let n = compressed.len();
+        compressed[n - 8] ^= 0xff;
+        let mut decompressor = Decompressor::new();
+        let result = decompressor
+            .send(compressed)
+            .and_then(|_| decompressor.finish());
+        assert!(result.is_err());
+    }
+}
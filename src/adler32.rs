@@ -0,0 +1,36 @@
+/// Adler-32 checksum (RFC1950) that is computed incrementally.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Adler32 {
+    pub(crate) fn new() -> Self {
+        Self { a: 1, b: 0 }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        // the largest number of bytes that can be summed up without overflow
+        for chunk in data.chunks(5552) {
+            for byte in chunk {
+                self.a += *byte as u32;
+                self.b += self.a;
+            }
+            self.a %= ADLER32_MODULUS;
+            self.b %= ADLER32_MODULUS;
+        }
+    }
+
+    pub(crate) fn sum(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+pub(crate) fn adler32(data: &[u8]) -> u32 {
+    let mut adler = Adler32::new();
+    adler.update(data);
+    adler.sum()
+}
+
+const ADLER32_MODULUS: u32 = 65521;
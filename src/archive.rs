@@ -0,0 +1,75 @@
+/// Archive format.
+///
+/// Archives are containers of files rather than compressed streams, hence they are not decoded
+/// (ZIP files with a single entry are the exception, see `Format::Zip`).
+/// The decoder reads them verbatim, or fails with a descriptive error if
+/// [fail_on_unknown_format](crate::AnyDecoder::fail_on_unknown_format) is enabled.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[non_exhaustive]
+pub enum Archive {
+    /// ZIP archive.
+    Zip,
+    /// 7-Zip archive.
+    SevenZip,
+    /// RAR archive (version 1.5 and later).
+    Rar,
+    /// POSIX or GNU tar archive.
+    Tar,
+}
+
+impl Archive {
+    /// Detect the archive format using the magic bytes at the start of `bytes`.
+    ///
+    /// Tar archives are detected by the `ustar` magic at offset 257,
+    /// i.e. at least 262 bytes are needed.
+    /// Returns `None` if none of the formats matches.
+    pub fn from_magic(bytes: &[u8]) -> Option<Archive> {
+        match bytes {
+            // https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT
+            [b'P', b'K', 3, 4, ..] => Some(Archive::Zip),
+            // https://py7zr.readthedocs.io/en/latest/archive_format.html
+            [b'7', b'z', 0xbc, 0xaf, 0x27, 0x1c, ..] => Some(Archive::SevenZip),
+            // RAR 1.5 and RAR 5
+            [b'R', b'a', b'r', b'!', 0x1a, 0x07, ..] => Some(Archive::Rar),
+            // https://www.gnu.org/software/tar/manual/html_node/Standard.html
+            _ if bytes.get(257..262) == Some(b"ustar") => Some(Archive::Tar),
+            _ => None,
+        }
+    }
+
+    /// Get the name of the archive format.
+    pub fn name(self) -> &'static str {
+        match self {
+            Archive::Zip => "zip",
+            Archive::SevenZip => "7z",
+            Archive::Rar => "rar",
+            Archive::Tar => "tar",
+        }
+    }
+}
+
+/// The number of bytes that is enough to detect any archive format.
+pub(crate) const ARCHIVE_MAGIC_LEN: usize = 262;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_magic() {
+        assert_eq!(Some(Archive::Zip), Archive::from_magic(b"PK\x03\x04"));
+        assert_eq!(
+            Some(Archive::SevenZip),
+            Archive::from_magic(b"7z\xbc\xaf\x27\x1c\x00\x04")
+        );
+        assert_eq!(
+            Some(Archive::Rar),
+            Archive::from_magic(b"Rar!\x1a\x07\x01\x00")
+        );
+        let mut tar = vec![0_u8; 512];
+        tar[257..265].copy_from_slice(b"ustar\x0000");
+        assert_eq!(Some(Archive::Tar), Archive::from_magic(&tar));
+        assert_eq!(None, Archive::from_magic(&tar[..261]));
+        assert_eq!(None, Archive::from_magic(b"hello world"));
+    }
+}
@@ -0,0 +1,236 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Error;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+
+use crate::recompress_with_buffer;
+use crate::write::Compression;
+use crate::Format;
+use crate::RecompressStats;
+use crate::BUFFER_SIZE;
+
+/// Processes many inputs with the same settings.
+///
+/// Each input is decompressed (the format is detected automatically) and then compressed
+/// using the target format and compression level.
+/// Use [Format::Verbatim] as the target format to only decompress the inputs.
+///
+/// Scratch buffers are reused between the inputs.
+/// With `rayon` feature enabled the inputs can be processed in parallel on rayon's thread pool.
+pub struct Batch {
+    format: Format,
+    compression: Compression,
+    buf: Vec<u8>,
+}
+
+impl Batch {
+    /// Create new batch with the target `format` and `compression` level.
+    pub fn new(format: Format, compression: Compression) -> Self {
+        Self {
+            format,
+            compression,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Process one input.
+    pub fn process<R: Read, W: Write>(
+        &mut self,
+        reader: R,
+        writer: W,
+    ) -> Result<RecompressStats, Error> {
+        if self.buf.is_empty() {
+            self.buf.resize(BUFFER_SIZE, 0);
+        }
+        recompress_with_buffer(reader, writer, self.format, self.compression, &mut self.buf)
+    }
+
+    /// Process one file.
+    ///
+    /// The output file is created or truncated.
+    pub fn process_file<P1: AsRef<Path>, P2: AsRef<Path>>(
+        &mut self,
+        input: P1,
+        output: P2,
+    ) -> Result<RecompressStats, Error> {
+        let reader = File::open(input)?;
+        let mut writer = BufWriter::new(File::create(output)?);
+        let stats = self.process(reader, &mut writer)?;
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        Ok(stats)
+    }
+
+    /// Process all inputs sequentially.
+    ///
+    /// Returns the results in the same order as the inputs.
+    /// An error doesn't stop the processing of the remaining inputs.
+    pub fn process_all<I, R, W>(&mut self, items: I) -> Vec<Result<RecompressStats, Error>>
+    where
+        I: IntoIterator<Item = (R, W)>,
+        R: Read,
+        W: Write,
+    {
+        items
+            .into_iter()
+            .map(|(reader, writer)| self.process(reader, writer))
+            .collect()
+    }
+
+    /// Process all files sequentially.
+    ///
+    /// Returns the results in the same order as the inputs.
+    /// An error doesn't stop the processing of the remaining files.
+    pub fn process_files<I, P1, P2>(&mut self, items: I) -> Vec<Result<RecompressStats, Error>>
+    where
+        I: IntoIterator<Item = (P1, P2)>,
+        P1: AsRef<Path>,
+        P2: AsRef<Path>,
+    {
+        items
+            .into_iter()
+            .map(|(input, output)| self.process_file(input, output))
+            .collect()
+    }
+
+    /// Process all inputs in parallel.
+    ///
+    /// Returns the results in the same order as the inputs.
+    /// An error doesn't stop the processing of the remaining inputs.
+    #[cfg(feature = "rayon")]
+    pub fn par_process_all<R, W>(&self, items: Vec<(R, W)>) -> Vec<Result<RecompressStats, Error>>
+    where
+        R: Read + Send,
+        W: Write + Send,
+    {
+        use rayon::prelude::*;
+        items
+            .into_par_iter()
+            .map_init(
+                || self.clone_settings(),
+                |batch, (reader, writer)| batch.process(reader, writer),
+            )
+            .collect()
+    }
+
+    /// Process all files in parallel.
+    ///
+    /// Returns the results in the same order as the inputs.
+    /// An error doesn't stop the processing of the remaining files.
+    #[cfg(feature = "rayon")]
+    pub fn par_process_files<P1, P2>(
+        &self,
+        items: &[(P1, P2)],
+    ) -> Vec<Result<RecompressStats, Error>>
+    where
+        P1: AsRef<Path> + Sync,
+        P2: AsRef<Path> + Sync,
+    {
+        use rayon::prelude::*;
+        items
+            .par_iter()
+            .map_init(
+                || self.clone_settings(),
+                |batch, (input, output)| batch.process_file(input, output),
+            )
+            .collect()
+    }
+
+    #[cfg(feature = "rayon")]
+    fn clone_settings(&self) -> Self {
+        Self::new(self.format, self.compression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arbitrary::Unstructured;
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::bufread::AnyDecoder;
+    use crate::write::AnyEncoder;
+
+    #[test]
+    fn test_process_all() {
+        arbtest(|u| {
+            let format: Format = u.arbitrary()?;
+            let inputs = arbitrary_inputs(u)?;
+            let compressed = compress_all(&inputs);
+            let mut outputs = vec![Vec::new(); inputs.len()];
+            let mut batch = Batch::new(format, Compression::Fast);
+            let results =
+                batch.process_all(compressed.iter().map(|x| &x[..]).zip(outputs.iter_mut()));
+            check(format, &inputs, &outputs, results);
+            Ok(())
+        });
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_process_all() {
+        arbtest(|u| {
+            let format: Format = u.arbitrary()?;
+            let inputs = arbitrary_inputs(u)?;
+            let compressed = compress_all(&inputs);
+            let mut outputs = vec![Vec::new(); inputs.len()];
+            let batch = Batch::new(format, Compression::Fast);
+            let results = batch.par_process_all(
+                compressed
+                    .iter()
+                    .map(|x| &x[..])
+                    .zip(outputs.iter_mut())
+                    .collect(),
+            );
+            check(format, &inputs, &outputs, results);
+            Ok(())
+        });
+    }
+
+    fn arbitrary_inputs(u: &mut Unstructured<'_>) -> arbitrary::Result<Vec<(Format, Vec<u8>)>> {
+        let len = u.arbitrary_len::<(u8, Vec<u8>)>()?;
+        (0..len)
+            .map(|_| {
+                // verbatim data might be detected as some other format
+                let format = *u.choose(Format::DETECTION_ORDER)?;
+                Ok((format, u.arbitrary()?))
+            })
+            .collect()
+    }
+
+    fn compress_all(inputs: &[(Format, Vec<u8>)]) -> Vec<Vec<u8>> {
+        inputs
+            .iter()
+            .map(|(format, data)| {
+                let mut writer = AnyEncoder::new(Vec::new(), *format, Compression::Fast).unwrap();
+                writer.write_all(data).unwrap();
+                writer.finish().unwrap()
+            })
+            .collect()
+    }
+
+    fn check(
+        format: Format,
+        inputs: &[(Format, Vec<u8>)],
+        outputs: &[Vec<u8>],
+        results: Vec<Result<RecompressStats, Error>>,
+    ) {
+        assert_eq!(inputs.len(), results.len());
+        for (((_, expected), output), result) in inputs.iter().zip(outputs).zip(results) {
+            let stats = result.unwrap();
+            assert_eq!(expected.len() as u64, stats.decoded_bytes);
+            if format == Format::Verbatim {
+                assert_eq!(expected, output);
+                continue;
+            }
+            let mut reader = AnyDecoder::with_format(&output[..], format);
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, &actual);
+        }
+    }
+}
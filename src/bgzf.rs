@@ -0,0 +1,281 @@
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+
+use flate2::Crc;
+use flate2::Decompress;
+use flate2::FlushDecompress;
+use flate2::Status;
+
+/// BGZF (`bgzip`) reader that supports random access via virtual offsets.
+///
+/// BGZF file is a series of gzip members (blocks) of at most 64 KiB each,
+/// and the compressed size of each block is stored in the extra field of its header
+/// ([specification](https://samtools.github.io/hts-specs/SAMv1.pdf)).
+/// The virtual offset is the offset of the block in the file shifted left by 16 bits
+/// plus the offset within the decompressed block;
+/// such offsets are stored in BAI, CSI and tabix indices.
+///
+/// The blocks are decompressed one at a time, and their checksums are verified.
+/// The underlying reader has to be positioned at the start of the file.
+pub struct BgzfReader<R> {
+    reader: R,
+    // decompressed block
+    block: Vec<u8>,
+    pos: usize,
+    // the offset of the current block in the file
+    block_offset: u64,
+    // the offset of the next block in the file
+    next_offset: u64,
+    compressed: Vec<u8>,
+    decompress: Decompress,
+}
+
+impl<R: Read> BgzfReader<R> {
+    /// Create new reader.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            block: Vec::with_capacity(MAX_BLOCK_SIZE),
+            pos: 0,
+            block_offset: 0,
+            next_offset: 0,
+            compressed: Vec::with_capacity(MAX_BLOCK_SIZE),
+            decompress: Decompress::new(false),
+        }
+    }
+
+    /// The virtual offset of the next byte that is read.
+    pub fn virtual_offset(&self) -> u64 {
+        (self.block_offset << 16) | self.pos as u64
+    }
+
+    /// Get immutable reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Get mutable reference to the underlying reader.
+    ///
+    /// Reading from the underlying reader might break block decoding.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Return the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Read and decompress the next block.
+    ///
+    /// Returns `false` at the end of the file.
+    fn read_block(&mut self) -> Result<bool, Error> {
+        self.block.clear();
+        self.pos = 0;
+        let mut header = [0_u8; HEADER_LEN];
+        if !read_exact_or_eof(&mut self.reader, &mut header)? {
+            return Ok(false);
+        }
+        if header[..4] != [0x1f, 0x8b, 0x08, FEXTRA] {
+            return Err(invalid_data("not a BGZF block"));
+        }
+        let extra_len = u16::from_le_bytes([header[10], header[11]]) as usize;
+        self.compressed.resize(extra_len, 0);
+        self.reader.read_exact(&mut self.compressed)?;
+        let block_size = bgzf_block_size(&self.compressed)
+            .ok_or_else(|| invalid_data("BGZF block size is missing"))?;
+        // compressed data, CRC32 and ISIZE
+        let Some(len) = block_size.checked_sub(HEADER_LEN + extra_len + 8) else {
+            return Err(invalid_data("invalid BGZF block size"));
+        };
+        self.compressed.resize(len + 8, 0);
+        self.reader.read_exact(&mut self.compressed)?;
+        let (data, trailer) = self.compressed.split_at(len);
+        let crc32 = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        let isize = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]) as usize;
+        if isize > MAX_BLOCK_SIZE {
+            return Err(invalid_data("invalid BGZF block size"));
+        }
+        self.decompress.reset(false);
+        let status = self
+            .decompress
+            .decompress_vec(data, &mut self.block, FlushDecompress::Finish)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        if status != Status::StreamEnd || self.block.len() != isize {
+            return Err(invalid_data("invalid BGZF block data"));
+        }
+        let mut crc = Crc::new();
+        crc.update(&self.block);
+        if crc.sum() != crc32 {
+            return Err(invalid_data("BGZF block checksum mismatch"));
+        }
+        self.block_offset = self.next_offset;
+        self.next_offset += block_size as u64;
+        Ok(true)
+    }
+}
+
+impl<R: Read + Seek> BgzfReader<R> {
+    /// Move to the supplied virtual offset.
+    ///
+    /// Returns an error of kind [InvalidInput](ErrorKind::InvalidInput)
+    /// if the offset points past the end of the block.
+    pub fn seek_virtual(&mut self, virtual_offset: u64) -> Result<(), Error> {
+        let block_offset = virtual_offset >> 16;
+        let pos = (virtual_offset & 0xffff) as usize;
+        let loaded = block_offset == self.block_offset && self.next_offset != self.block_offset;
+        if !loaded {
+            self.reader.seek(SeekFrom::Start(block_offset))?;
+            self.block_offset = block_offset;
+            self.next_offset = block_offset;
+            self.read_block()?;
+        }
+        if pos > self.block.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "virtual offset points past the end of BGZF block",
+            ));
+        }
+        self.pos = pos;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for BgzfReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        // skip empty blocks including the end-of-file marker
+        while self.pos == self.block.len() {
+            if !self.read_block()? {
+                return Ok(0);
+            }
+        }
+        let n = (self.block.len() - self.pos).min(buf.len());
+        buf[..n].copy_from_slice(&self.block[self.pos..(self.pos + n)]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Get BGZF block size from the gzip header's `extra` field.
+///
+/// Returns `None` if the field doesn't contain BGZF subfield.
+pub(crate) fn bgzf_block_size(mut extra: &[u8]) -> Option<usize> {
+    while let [si1, si2, len0, len1, rest @ ..] = extra {
+        let len = u16::from_le_bytes([*len0, *len1]) as usize;
+        let data = rest.get(..len)?;
+        if let (b'B', b'C', [size0, size1]) = (si1, si2, data) {
+            return Some(u16::from_le_bytes([*size0, *size1]) as usize + 1);
+        }
+        extra = &rest[len..];
+    }
+    None
+}
+
+/// Fill `buf` completely or return `false` if the reader is at the end of the stream.
+fn read_exact_or_eof<R: Read>(mut reader: R, buf: &mut [u8]) -> Result<bool, Error> {
+    let mut n = 0;
+    while n != buf.len() {
+        match reader.read(&mut buf[n..]) {
+            Ok(0) if n == 0 => return Ok(false),
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(m) => n += m,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+fn invalid_data(message: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Gzip header up to and including XLEN field.
+const HEADER_LEN: usize = 12;
+const FEXTRA: u8 = 1 << 2;
+const MAX_BLOCK_SIZE: usize = 64 * 1024;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::io::Write;
+
+    use arbtest::arbtest;
+    use flate2::write::DeflateEncoder;
+    use flate2::Compression;
+
+    use super::*;
+    use crate::AnyDecoder;
+    use crate::GzDecoderExt;
+
+    #[test]
+    fn virtual_offsets() {
+        arbtest(|u| {
+            let mut blocks: Vec<Vec<u8>> = u.arbitrary()?;
+            for block in blocks.iter_mut() {
+                block.truncate(MAX_BLOCK_SIZE / 2);
+            }
+            let mut file = Vec::new();
+            let mut offsets = Vec::new();
+            for block in blocks.iter() {
+                offsets.push(file.len() as u64);
+                file.extend(bgzf_block(block));
+            }
+            // end-of-file marker
+            file.extend(bgzf_block(&[]));
+            let expected = blocks.concat();
+            let mut actual = Vec::new();
+            BgzfReader::new(&file[..]).read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, actual);
+            let mut actual = Vec::new();
+            AnyDecoder::new(&file[..]).read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, actual);
+            let mut reader = BgzfReader::new(Cursor::new(&file));
+            for _ in 0..blocks.len() {
+                let i = u.choose_index(blocks.len())?;
+                let pos = u.int_in_range(0..=blocks[i].len())?;
+                let virtual_offset = (offsets[i] << 16) | pos as u64;
+                reader.seek_virtual(virtual_offset).unwrap();
+                assert_eq!(virtual_offset, reader.virtual_offset());
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                let start = blocks[..i].iter().map(|block| block.len()).sum::<usize>() + pos;
+                assert_eq!(&expected[start..], &actual[..]);
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn detection() {
+        let block = bgzf_block(b"hello");
+        let mut reader = AnyDecoder::new(&block[..]);
+        assert!(reader.is_bgzf().unwrap());
+        let mut writer = flate2::write::GzEncoder::new(Vec::new(), Compression::fast());
+        writer.write_all(b"hello").unwrap();
+        let gz = writer.finish().unwrap();
+        let mut reader = AnyDecoder::new(&gz[..]);
+        assert!(!reader.is_bgzf().unwrap());
+        let mut reader = BgzfReader::new(&gz[..]);
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+    }
+
+    fn bgzf_block(data: &[u8]) -> Vec<u8> {
+        let mut writer = DeflateEncoder::new(Vec::new(), Compression::fast());
+        writer.write_all(data).unwrap();
+        let compressed = writer.finish().unwrap();
+        let mut crc = Crc::new();
+        crc.update(data);
+        let block_size = (HEADER_LEN + 6 + compressed.len() + 8 - 1) as u16;
+        let mut block = vec![0x1f, 0x8b, 0x08, FEXTRA, 0, 0, 0, 0, 0, 0xff, 6, 0];
+        block.extend([b'B', b'C', 2, 0]);
+        block.extend(block_size.to_le_bytes());
+        block.extend(compressed);
+        block.extend(crc.sum().to_le_bytes());
+        block.extend((data.len() as u32).to_le_bytes());
+        block
+    }
+}
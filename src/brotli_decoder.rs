@@ -0,0 +1,117 @@
+use std::io::BufRead;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+
+use brotli::enc::StandardAlloc;
+use brotli::BrotliDecompressStream;
+use brotli::BrotliResult;
+use brotli::BrotliState;
+
+/// Brotli decoder that consumes exactly the bytes of the stream from the underlying reader.
+///
+/// Unlike `brotli::Decompressor`, the decoder doesn't read ahead,
+/// i.e. the data that follows the stream stays in the underlying reader,
+/// and truncated streams are reported as errors.
+pub(crate) struct BrotliDecoder<R: BufRead> {
+    reader: R,
+    state: Box<BrotliState<StandardAlloc, StandardAlloc, StandardAlloc>>,
+    finished: bool,
+}
+
+impl<R: BufRead> BrotliDecoder<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            // large window is not a part of RFC7932
+            state: Box::new(BrotliState::new_strict(
+                StandardAlloc::default(),
+                StandardAlloc::default(),
+                StandardAlloc::default(),
+            )),
+            finished: false,
+        }
+    }
+
+    pub(crate) fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: BufRead> Read for BrotliDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if buf.is_empty() || self.finished {
+            return Ok(0);
+        }
+        loop {
+            let input = self.reader.fill_buf()?;
+            let eof = input.is_empty();
+            let mut available_in = input.len();
+            let mut input_offset = 0;
+            let mut available_out = buf.len();
+            let mut output_offset = 0;
+            let mut total_out = 0;
+            let result = BrotliDecompressStream(
+                &mut available_in,
+                &mut input_offset,
+                input,
+                &mut available_out,
+                &mut output_offset,
+                buf,
+                &mut total_out,
+                &mut self.state,
+            );
+            self.reader.consume(input_offset);
+            match result {
+                BrotliResult::ResultSuccess => {
+                    self.finished = true;
+                    return Ok(output_offset);
+                }
+                BrotliResult::NeedsMoreOutput => return Ok(output_offset),
+                BrotliResult::NeedsMoreInput if output_offset != 0 => return Ok(output_offset),
+                BrotliResult::NeedsMoreInput if eof => {
+                    return Err(Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "decompression not finished but EOF reached",
+                    ))
+                }
+                BrotliResult::NeedsMoreInput => {}
+                BrotliResult::ResultFailure => {
+                    return Err(Error::new(ErrorKind::InvalidData, "corrupt brotli stream"))
+                }
+            }
+        }
+    }
+}
+
+/// Check whether `bytes` look like the start of a brotli stream.
+///
+/// Brotli streams have no magic bytes, hence the bytes are decoded:
+/// the data is accepted if it's decodable, if there is no trailing data after the stream,
+/// and if the stream is not empty.
+/// `complete` means that `bytes` contain the whole stream,
+/// i.e. a truncated stream is not a brotli stream.
+pub(crate) fn brotli_detect(bytes: &[u8], complete: bool) -> bool {
+    let mut decoder = BrotliDecoder::new(bytes);
+    let mut buf = [0_u8; 4096];
+    let mut total = 0;
+    while total < BROTLI_DETECT_OUTPUT_LEN {
+        match decoder.read(&mut buf) {
+            Ok(0) => return total != 0 && decoder.into_inner().is_empty(),
+            Ok(n) => total += n,
+            Err(e) => return !complete && e.kind() == ErrorKind::UnexpectedEof && total != 0,
+        }
+    }
+    true
+}
+
+/// The maximum number of output bytes that are decoded to detect brotli stream.
+const BROTLI_DETECT_OUTPUT_LEN: usize = 64 * 1024;
@@ -1,5 +1,161 @@
 use crate::bufread::MagicReader;
 use std::io::BufRead;
+use std::io::BufReader;
 
 crate::define_decoder!(BufRead);
+crate::define_decoder_builder!(BufRead);
+crate::define_members!(BufRead);
 crate::define_decoder_tests!();
+
+impl<'a, R: Read> AnyDecoder<'a, BufReader<R>> {
+    /// Create new decoder from the supplied `reader` that doesn't implement [BufRead].
+    ///
+    /// The reader is wrapped in [BufReader].
+    /// Unlike [read::AnyDecoder](crate::read::AnyDecoder) the magic bytes are read
+    /// through the same buffer, i.e. the data is never copied between two buffers.
+    ///
+    /// Readers that already implement [BufRead], e.g. [BufReader] or `&[u8]`,
+    /// should be passed to [new](AnyDecoder::new) instead: this method would wrap them
+    /// in one more buffer, i.e. the data would be copied twice.
+    pub fn from_reader(reader: R) -> Self {
+        Self::new(BufReader::new(reader))
+    }
+}
+
+impl<'a, R: BufRead> AnyDecoder<'a, R> {
+    /// The number of bytes that were read from the underlying reader
+    /// but not yet passed to the decoder.
+    pub(crate) fn buffered_len(&self) -> usize {
+        let inner = match self.state {
+            State::Pending(ref r) => return r.buffered().len(),
+            State::Active(ref inner) => inner,
+        };
+        match *inner {
+            InnerDecoder::Reader(ref r, _) => r.buffered().len(),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::Gz(ref r) => r.get_ref().buffered().len(),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::GzMember(ref r) => r.get_ref().buffered().len(),
+            #[cfg(feature = "bzip2")]
+            InnerDecoder::Bz(ref r) => r.get_ref().buffered().len(),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::Zlib(ref r) => r.get_ref().buffered().len(),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::ZlibDict(ref r) => r.get_ref().buffered().len(),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::Deflate(ref r) => r.get_ref().buffered().len(),
+            #[cfg(feature = "xz")]
+            InnerDecoder::Xz(ref r) => r.get_ref().buffered().len(),
+            #[cfg(feature = "zstd")]
+            InnerDecoder::Zstd(ref r) => r.reader().buffered().len(),
+            #[cfg(feature = "zip")]
+            InnerDecoder::Zip(ref r) => r.get_ref().buffered().len(),
+            #[cfg(feature = "mscompress")]
+            InnerDecoder::Szdd(ref r) => r.get_ref().buffered().len(),
+            #[cfg(feature = "mscompress")]
+            InnerDecoder::Kwaj(ref r) => r.get_ref().buffered().len(),
+            #[cfg(feature = "lzop")]
+            InnerDecoder::Lzop(ref r) => r.get_ref().buffered().len(),
+            #[cfg(feature = "lzfse")]
+            InnerDecoder::Lzfse(ref r) => r.get_ref().buffered().len(),
+            #[cfg(feature = "lz4")]
+            InnerDecoder::Lz4(ref r) => r.get_ref().buffered().len() + r.read_ahead().len(),
+            #[cfg(feature = "brotli")]
+            InnerDecoder::Brotli(ref r) => r.get_ref().buffered().len(),
+            InnerDecoder::Custom(ref r) => r.get_ref().buffered().len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod bufread_tests {
+    use std::io::Write;
+
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::test::NBytesReader;
+    use crate::write::AnyEncoder;
+    use crate::write::Compression;
+
+    #[test]
+    fn from_reader() {
+        arbtest(|u| {
+            let format = *u.choose(Format::DETECTION_ORDER)?;
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut writer = AnyEncoder::new(Vec::new(), format, Compression::Fast).unwrap();
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            let n = u.int_in_range(1..=100)?;
+            // the reader doesn't implement `BufRead`
+            let mut reader = AnyDecoder::from_reader(NBytesReader::new(&compressed[..], n));
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, actual);
+            assert_eq!(format, reader.kind().unwrap());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn generic_reader() {
+        fn decode<R: BufRead>(reader: R) -> Vec<u8> {
+            let mut actual = Vec::new();
+            crate::AnyDecoder::new(reader)
+                .read_to_end(&mut actual)
+                .unwrap();
+            actual
+        }
+
+        arbtest(|u| {
+            let format = *u.choose(Format::DETECTION_ORDER)?;
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut writer = AnyEncoder::new(Vec::new(), format, Compression::Fast).unwrap();
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            let mut reader = &compressed[..];
+            assert_eq!(expected, decode(&mut reader));
+            assert_eq!(expected, decode((&compressed[..]).take(u64::MAX)));
+            let (a, b) = compressed.split_at(compressed.len() / 2);
+            assert_eq!(expected, decode(a.chain(b)));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn single_member() {
+        let formats = [
+            #[cfg(feature = "flate2")]
+            Format::Gz,
+            #[cfg(feature = "xz")]
+            Format::Xz,
+            #[cfg(feature = "zstd")]
+            Format::Zstd,
+            #[cfg(feature = "lz4")]
+            Format::Lz4,
+        ];
+        if formats.is_empty() {
+            return;
+        }
+        arbtest(|u| {
+            let format = *u.choose(&formats)?;
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut writer = AnyEncoder::new(Vec::new(), format, Compression::Fast).unwrap();
+            writer.write_all(&expected).unwrap();
+            let mut compressed = writer.finish().unwrap();
+            // the data that follows the member in a container
+            let trailer: Vec<u8> = u.arbitrary()?;
+            compressed.extend(&trailer);
+            let mut reader = AnyDecoder::new(&compressed[..]);
+            reader.single_member(true);
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, actual);
+            // shorter streams are buffered by format detection
+            if compressed.len() >= crate::MAX_MAGIC_BYTES {
+                assert_eq!(&trailer[..], reader.into_inner());
+            }
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,457 @@
+use std::io::BufReader;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+
+use crate::BzDecoder;
+
+/// Bzip2 block summary.
+///
+/// Bzip2 blocks are compressed independently and start with a bit-aligned magic number.
+/// A block is decompressed by wrapping it in a separate stream, see [bz_decode_block].
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct BzBlockInfo {
+    /// The offset of the block magic in bits from the start of the file.
+    pub bit_offset: u64,
+    /// The size of the block in bits including the magic.
+    pub bit_len: u64,
+    /// Block size level (1-9) from the header of the stream that contains the block.
+    pub level: u8,
+    /// CRC of the decompressed block data.
+    pub crc: u32,
+}
+
+/// Bzip2 stream summary.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct BzStreamInfo {
+    /// Combined CRC of the stream from the end-of-stream marker.
+    pub crc: u32,
+    /// The blocks of the stream.
+    pub blocks: Vec<BzBlockInfo>,
+}
+
+impl BzStreamInfo {
+    /// Compute combined CRC of the stream from the CRCs of its blocks.
+    ///
+    /// The value is equal to [crc](Self::crc) unless the file is corrupted.
+    pub fn combined_crc(&self) -> u32 {
+        self.blocks
+            .iter()
+            .fold(0, |combined, block| combined.rotate_left(1) ^ block.crc)
+    }
+}
+
+/// Bzip2 stream integrity report, see [bz_verify].
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct BzStreamReport {
+    /// The stream summary.
+    pub stream: BzStreamInfo,
+    /// Whether the combined CRC of the stream matches the CRCs of the blocks.
+    pub crc_verified: bool,
+    /// Whether each block was decompressed without errors and its CRC matched.
+    ///
+    /// The values are in the same order as [BzStreamInfo::blocks].
+    pub blocks_verified: Vec<bool>,
+}
+
+impl BzStreamReport {
+    /// Check that the stream and all its blocks are verified.
+    pub fn is_verified(&self) -> bool {
+        self.crc_verified && self.blocks_verified.iter().all(|verified| *verified)
+    }
+}
+
+/// Locate the blocks in bzip2 file without decompressing them.
+///
+/// Concatenated streams are supported.
+/// The blocks are returned in the order they appear in the file.
+///
+/// The block magic is only 48 bits long and can occur inside the compressed data by chance.
+/// Such false positives are extremely rare and are caught by the CRC check
+/// when the block is decompressed.
+pub fn bz_blocks<R: Read>(reader: R) -> Result<Vec<BzBlockInfo>, Error> {
+    Ok(bz_streams(reader)?
+        .into_iter()
+        .flat_map(|stream| stream.blocks)
+        .collect())
+}
+
+/// Locate the streams and their blocks in bzip2 file without decompressing them.
+///
+/// See [bz_blocks].
+pub fn bz_streams<R: Read>(reader: R) -> Result<Vec<BzStreamInfo>, Error> {
+    let mut bits = BitReader::new(BufReader::new(reader));
+    let mut streams = Vec::new();
+    // stream header
+    while let Some(first) = bits.read_byte()? {
+        let mut header = [first, 0, 0, 0];
+        for byte in header[1..].iter_mut() {
+            *byte = bits.read_byte()?.ok_or(ErrorKind::UnexpectedEof)?;
+        }
+        if header[..3] != STREAM_MAGIC || !(b'1'..=b'9').contains(&header[3]) {
+            return Err(Error::new(ErrorKind::InvalidData, "invalid bzip2 header"));
+        }
+        let level = header[3] - b'0';
+        let mut blocks = Vec::new();
+        let mut current: Option<BzBlockInfo> = None;
+        let mut register: u64 = 0;
+        let mut count = 0;
+        loop {
+            let bit = bits.read_bit()?.ok_or(ErrorKind::UnexpectedEof)?;
+            register = ((register << 1) | bit as u64) & MAGIC_MASK;
+            count += 1;
+            if count < MAGIC_BITS {
+                continue;
+            }
+            if register != BLOCK_MAGIC && register != END_MAGIC {
+                continue;
+            }
+            let magic_offset = bits.position() - MAGIC_BITS as u64;
+            if let Some(mut block) = current.take() {
+                block.bit_len = magic_offset - block.bit_offset;
+                blocks.push(block);
+            }
+            let crc = bits.read_u32()?;
+            if register == END_MAGIC {
+                bits.align();
+                streams.push(BzStreamInfo { crc, blocks });
+                break;
+            }
+            current = Some(BzBlockInfo {
+                bit_offset: magic_offset,
+                bit_len: 0,
+                level,
+                crc,
+            });
+            register = 0;
+            count = 0;
+        }
+    }
+    Ok(streams)
+}
+
+/// Verify the integrity of bzip2 file.
+///
+/// Each block is decompressed separately and its CRC is checked;
+/// the combined CRC of each stream is checked against the CRCs of its blocks.
+/// Corrupted blocks are reported rather than returned as errors,
+/// i.e. only I/O errors and malformed stream headers result in an error.
+/// The decompressed data is discarded.
+pub fn bz_verify<R: Read + Seek>(mut reader: R) -> Result<Vec<BzStreamReport>, Error> {
+    reader.rewind()?;
+    let streams = bz_streams(&mut reader)?;
+    let mut reports = Vec::with_capacity(streams.len());
+    for stream in streams.into_iter() {
+        let mut blocks_verified = Vec::with_capacity(stream.blocks.len());
+        for block in stream.blocks.iter() {
+            let data = read_block(&mut reader, block)?;
+            blocks_verified.push(decode_block(&data, (block.bit_offset % 8) as u32, block).is_ok());
+        }
+        reports.push(BzStreamReport {
+            crc_verified: stream.combined_crc() == stream.crc,
+            stream,
+            blocks_verified,
+        });
+    }
+    Ok(reports)
+}
+
+/// Decompress one block of bzip2 file.
+///
+/// The `reader` is positioned at the start of the block before reading.
+pub fn bz_decode_block<R: Read + Seek>(
+    mut reader: R,
+    block: &BzBlockInfo,
+) -> Result<Vec<u8>, Error> {
+    let data = read_block(&mut reader, block)?;
+    decode_block(&data, (block.bit_offset % 8) as u32, block)
+}
+
+/// Read the bytes that contain the block.
+fn read_block<R: Read + Seek>(reader: &mut R, block: &BzBlockInfo) -> Result<Vec<u8>, Error> {
+    let first = block.bit_offset / 8;
+    let last = (block.bit_offset + block.bit_len).div_ceil(8);
+    let mut data = vec![0_u8; (last - first) as usize];
+    reader.seek(SeekFrom::Start(first))?;
+    reader.read_exact(&mut data)?;
+    Ok(data)
+}
+
+/// Check that bzip2 file ends with the end-of-stream marker.
+///
+/// The marker is followed by the combined CRC and at most seven zero padding bits.
+/// Only the tail of the file is read.
+pub(crate) fn bz_check_tail<R: Read + Seek>(mut reader: R) -> Result<(), Error> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    if len < (STREAM_MAGIC.len() + TAIL_LEN) as u64 {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "truncated bzip2 stream",
+        ));
+    }
+    let mut tail = [0_u8; 16];
+    reader.seek(SeekFrom::End(-(TAIL_LEN as i64)))?;
+    reader.read_exact(&mut tail[16 - TAIL_LEN..])?;
+    let tail = u128::from_be_bytes(tail);
+    let found = (0..8).any(|padding| {
+        let magic = (tail >> (32 + padding)) as u64 & MAGIC_MASK;
+        magic == END_MAGIC && tail & ((1 << padding) - 1) == 0
+    });
+    if !found {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "bzip2 end-of-stream marker not found",
+        ));
+    }
+    Ok(())
+}
+
+/// Decompress bzip2 `input` using all available cores.
+///
+/// The blocks are located via [bz_blocks] and are decompressed in parallel.
+#[cfg(feature = "rayon")]
+pub fn bz_decompress_parallel(input: &[u8]) -> Result<Vec<u8>, Error> {
+    use rayon::prelude::*;
+    let blocks = bz_blocks(input)?;
+    let outputs = blocks
+        .par_iter()
+        .map(|block| {
+            let first = (block.bit_offset / 8) as usize;
+            let last = (block.bit_offset + block.bit_len).div_ceil(8) as usize;
+            decode_block(&input[first..last], (block.bit_offset % 8) as u32, block)
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(outputs.concat())
+}
+
+/// Wrap the block in a separate stream and decompress it.
+fn decode_block(data: &[u8], skip_bits: u32, block: &BzBlockInfo) -> Result<Vec<u8>, Error> {
+    let mut stream = BitWriter::new();
+    for byte in STREAM_MAGIC.iter() {
+        stream.write(*byte as u64, 8);
+    }
+    stream.write((b'0' + block.level) as u64, 8);
+    let mut bits = BitReader::new(data);
+    for _ in 0..skip_bits {
+        bits.read_bit()?;
+    }
+    for _ in 0..block.bit_len {
+        let bit = bits.read_bit()?.ok_or(ErrorKind::UnexpectedEof)?;
+        stream.write(bit as u64, 1);
+    }
+    stream.write(END_MAGIC, MAGIC_BITS);
+    // the stream CRC of a single-block stream equals the block CRC
+    stream.write(block.crc as u64, 32);
+    let stream = stream.finish();
+    let mut decoder = BzDecoder::new(&stream[..], false);
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output)?;
+    Ok(output)
+}
+
+/// Reads the input stream bit by bit, most-significant bit first.
+struct BitReader<R> {
+    reader: R,
+    byte: u8,
+    count: u32,
+    position: u64,
+}
+
+impl<R: Read> BitReader<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            byte: 0,
+            count: 0,
+            position: 0,
+        }
+    }
+
+    fn position(&self) -> u64 {
+        self.position
+    }
+
+    fn read_bit(&mut self) -> Result<Option<u8>, Error> {
+        if self.count == 0 {
+            let mut byte = [0_u8; 1];
+            if self.reader.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            self.byte = byte[0];
+            self.count = 8;
+        }
+        self.count -= 1;
+        self.position += 1;
+        Ok(Some((self.byte >> self.count) & 1))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        let mut value = 0_u32;
+        for _ in 0..32 {
+            let bit = self.read_bit()?.ok_or(ErrorKind::UnexpectedEof)?;
+            value = (value << 1) | bit as u32;
+        }
+        Ok(value)
+    }
+
+    /// Read byte-aligned byte.
+    fn read_byte(&mut self) -> Result<Option<u8>, Error> {
+        let mut byte = [0_u8; 1];
+        if self.reader.read(&mut byte)? == 0 {
+            return Ok(None);
+        }
+        self.position += 8;
+        Ok(Some(byte[0]))
+    }
+
+    fn align(&mut self) {
+        self.position += self.count as u64;
+        self.count = 0;
+    }
+}
+
+/// Writes the output stream bit by bit, most-significant bit first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    byte: u8,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            byte: 0,
+            count: 0,
+        }
+    }
+
+    fn write(&mut self, value: u64, bits: u32) {
+        for i in (0..bits).rev() {
+            self.byte = (self.byte << 1) | ((value >> i) & 1) as u8;
+            self.count += 1;
+            if self.count == 8 {
+                self.bytes.push(self.byte);
+                self.byte = 0;
+                self.count = 0;
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count != 0 {
+            self.bytes.push(self.byte << (8 - self.count));
+        }
+        self.bytes
+    }
+}
+
+const STREAM_MAGIC: [u8; 3] = *b"BZh";
+const MAGIC_BITS: u32 = 48;
+const MAGIC_MASK: u64 = (1 << MAGIC_BITS) - 1;
+// BCD-encoded pi
+const BLOCK_MAGIC: u64 = 0x3141_5926_5359;
+// BCD-encoded square root of pi
+const END_MAGIC: u64 = 0x1772_4538_5090;
+// end-of-stream marker, combined CRC and padding
+const TAIL_LEN: usize = 11;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::io::Write;
+
+    use arbtest::arbtest;
+    use bzip2::write::BzEncoder;
+    use bzip2::Compression;
+
+    use super::*;
+
+    #[test]
+    fn blocks() {
+        arbtest(|u| {
+            let streams: Vec<Vec<u8>> = u.arbitrary()?;
+            let mut compressed = Vec::new();
+            for stream in streams.iter() {
+                let mut writer = BzEncoder::new(Vec::new(), Compression::new(1));
+                writer.write_all(stream).unwrap();
+                compressed.extend(writer.finish().unwrap());
+            }
+            let blocks = bz_blocks(&compressed[..]).unwrap();
+            let non_empty = streams.iter().filter(|s| !s.is_empty()).count();
+            assert_eq!(non_empty, blocks.len());
+            let mut reader = Cursor::new(&compressed);
+            let mut actual = Vec::new();
+            for block in blocks.iter() {
+                actual.extend(bz_decode_block(&mut reader, block).unwrap());
+            }
+            assert_eq!(streams.concat(), actual);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn many_blocks() {
+        // incompressible data to get multiple blocks with the smallest block size
+        let mut state = 1_u32;
+        let expected = (0..300_000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect::<Vec<_>>();
+        let mut writer = BzEncoder::new(Vec::new(), Compression::new(1));
+        writer.write_all(&expected).unwrap();
+        let compressed = writer.finish().unwrap();
+        let blocks = bz_blocks(&compressed[..]).unwrap();
+        assert!(blocks.len() > 1);
+        assert!(blocks.iter().any(|b| b.bit_offset % 8 != 0));
+        let mut reader = Cursor::new(&compressed);
+        let last = blocks.last().unwrap();
+        let actual = bz_decode_block(&mut reader, last).unwrap();
+        assert!(expected.ends_with(&actual));
+        #[cfg(feature = "rayon")]
+        assert_eq!(expected, bz_decompress_parallel(&compressed).unwrap());
+    }
+
+    #[test]
+    fn verify() {
+        arbtest(|u| {
+            let streams: Vec<Vec<u8>> = u.arbitrary()?;
+            let mut compressed = Vec::new();
+            for stream in streams.iter() {
+                let mut writer = BzEncoder::new(Vec::new(), Compression::new(1));
+                writer.write_all(stream).unwrap();
+                compressed.extend(writer.finish().unwrap());
+            }
+            let reports = bz_verify(Cursor::new(&compressed)).unwrap();
+            assert_eq!(streams.len(), reports.len());
+            for report in reports.iter() {
+                assert!(report.is_verified());
+            }
+            // corrupt the CRC of the first block
+            let Some(block) = reports.iter().find_map(|r| r.stream.blocks.first()) else {
+                return Ok(());
+            };
+            let crc_offset = block.bit_offset + MAGIC_BITS as u64;
+            let byte = (crc_offset / 8) as usize;
+            compressed[byte + 1] ^= 1;
+            let reports = bz_verify(Cursor::new(&compressed)).unwrap();
+            let report = reports
+                .iter()
+                .find(|r| !r.stream.blocks.is_empty())
+                .unwrap();
+            assert!(!report.crc_verified);
+            assert!(!report.blocks_verified[0]);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn invalid_header() {
+        assert!(bz_blocks(&b"BZx1"[..]).is_err());
+        assert!(bz_blocks(&b"BZh1"[..]).is_err());
+    }
+}
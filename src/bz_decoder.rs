@@ -0,0 +1,98 @@
+use std::io::BufRead;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+
+use bzip2::Decompress;
+use bzip2::Status;
+
+/// Bzip2 decoder that supports _small_ decompression mode.
+///
+/// `bzip2::bufread::BzDecoder` always uses the default mode.
+/// Concatenated streams are decoded as one stream.
+pub(crate) struct BzDecoder<R> {
+    reader: R,
+    decompress: Decompress,
+    small: bool,
+    done: bool,
+}
+
+impl<R: BufRead> BzDecoder<R> {
+    pub(crate) fn new(reader: R, small: bool) -> Self {
+        Self {
+            reader,
+            decompress: Decompress::new(small),
+            small,
+            done: false,
+        }
+    }
+
+    /// Check whether another stream follows the current one.
+    fn next_stream(&mut self) -> Result<bool, Error> {
+        let input = self.reader.fill_buf()?;
+        let n = input.len().min(STREAM_MAGIC.len());
+        if n == 0 || input[..n] != STREAM_MAGIC[..n] {
+            return Ok(false);
+        }
+        self.decompress = Decompress::new(self.small);
+        Ok(true)
+    }
+}
+
+impl<R> BzDecoder<R> {
+    pub(crate) fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: BufRead> Read for BzDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        loop {
+            if self.done {
+                return Ok(0);
+            }
+            let input = self.reader.fill_buf()?;
+            let before_out = self.decompress.total_out();
+            let before_in = self.decompress.total_in();
+            let ret = self.decompress.decompress(input, buf);
+            let read = (self.decompress.total_out() - before_out) as usize;
+            let consumed = (self.decompress.total_in() - before_in) as usize;
+            let remaining = input.len() - consumed;
+            self.reader.consume(consumed);
+            let status = ret.map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+            if status == Status::StreamEnd {
+                self.done = !self.next_stream()?;
+            } else if consumed == 0 && remaining == 0 && read == 0 {
+                return Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "decompression not finished but EOF reached",
+                ));
+            }
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+        }
+    }
+}
+
+const STREAM_MAGIC: [u8; 3] = *b"BZh";
+
+/// Memory in bytes that is needed to decompress bzip2 stream with the specified block size.
+///
+/// The formulas are taken from `bzip2` manual page.
+pub(crate) const fn bz_memory_usage(block_size: u8, small: bool) -> u64 {
+    let block_size = block_size as u64 * 100_000;
+    if small {
+        100_000 + block_size * 5 / 2
+    } else {
+        100_000 + block_size * 4
+    }
+}
@@ -0,0 +1,177 @@
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+
+/// An iterator over the fixed-size chunks of the decompressed data.
+///
+/// Every chunk except the last one has exactly the requested size;
+/// the last one is shorter unless the data size is a multiple of the chunk size.
+/// Empty chunks are never returned.
+/// The iteration stops after the first error.
+///
+/// Created by [AnyDecoder::chunks](crate::AnyDecoder::chunks).
+pub struct Chunks<R> {
+    reader: R,
+    size: usize,
+    done: bool,
+}
+
+impl<R: Read> Chunks<R> {
+    /// Create new iterator.
+    ///
+    /// Zero `size` is treated as one.
+    pub fn new(reader: R, size: usize) -> Self {
+        Self {
+            reader,
+            size: size.max(1),
+            done: false,
+        }
+    }
+
+    /// Return the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Iterator for Chunks<R> {
+    type Item = Result<Vec<u8>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut chunk = vec![0_u8; self.size];
+        match read_chunk(&mut self.reader, &mut chunk) {
+            Ok(n) => {
+                self.done = n != chunk.len();
+                chunk.truncate(n);
+                (n != 0).then_some(Ok(chunk))
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// An iterator over the fixed-size chunks of the decompressed data as [Bytes](bytes::Bytes).
+///
+/// Behaves the same as [Chunks], but reuses the allocation of the underlying buffer
+/// when the previous chunks are dropped.
+///
+/// Created by [AnyDecoder::bytes_chunks](crate::AnyDecoder::bytes_chunks).
+#[cfg(feature = "bytes")]
+pub struct BytesChunks<R> {
+    reader: R,
+    size: usize,
+    buf: bytes::BytesMut,
+    done: bool,
+}
+
+#[cfg(feature = "bytes")]
+impl<R: Read> BytesChunks<R> {
+    /// Create new iterator.
+    ///
+    /// Zero `size` is treated as one.
+    pub fn new(reader: R, size: usize) -> Self {
+        Self {
+            reader,
+            size: size.max(1),
+            buf: bytes::BytesMut::new(),
+            done: false,
+        }
+    }
+
+    /// Return the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl<R: Read> Iterator for BytesChunks<R> {
+    type Item = Result<bytes::Bytes, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        self.buf.resize(self.size, 0);
+        match read_chunk(&mut self.reader, &mut self.buf[..]) {
+            Ok(n) => {
+                self.done = n != self.size;
+                let chunk = self.buf.split_to(n).freeze();
+                (n != 0).then_some(Ok(chunk))
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Fill the `chunk` with the data from the `reader`.
+///
+/// Returns the number of bytes read; the chunk is not filled completely only at the end of file.
+pub(crate) fn read_chunk<R: Read>(reader: &mut R, chunk: &mut [u8]) -> Result<usize, Error> {
+    let mut offset = 0;
+    while offset != chunk.len() {
+        match reader.read(&mut chunk[offset..]) {
+            Ok(0) => break,
+            Ok(n) => offset += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use arbtest::arbtest;
+
+    use crate::write::AnyEncoder;
+    use crate::write::Compression;
+    use crate::AnyDecoder;
+    use crate::Format;
+
+    #[test]
+    fn chunks() {
+        arbtest(|u| {
+            let format = *u.choose(Format::DETECTION_ORDER)?;
+            let expected: Vec<u8> = u.arbitrary()?;
+            let size = u.int_in_range(1..=100)?;
+            let mut writer = AnyEncoder::new(Vec::new(), format, Compression::Fast).unwrap();
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            let chunks = AnyDecoder::new(&compressed[..])
+                .chunks(size)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(expected.chunks(size).collect::<Vec<_>>(), chunks);
+            #[cfg(feature = "bytes")]
+            {
+                let chunks = AnyDecoder::new(&compressed[..])
+                    .bytes_chunks(size)
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap();
+                assert_eq!(expected.chunks(size).collect::<Vec<_>>(), chunks);
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn error() {
+        let mut decoder = AnyDecoder::new(&b"\x1f\x8b\x08 invalid gzip"[..]);
+        decoder.fail_on_unknown_format(true);
+        let mut chunks = decoder.chunks(10);
+        assert!(matches!(chunks.next(), Some(Err(..))));
+        assert!(chunks.next().is_none());
+    }
+}
@@ -0,0 +1,56 @@
+use std::io::Error;
+use std::io::Read;
+use std::io::Write;
+
+use crate::Format;
+
+/// A decoder that decompresses the data read from the underlying reader.
+///
+/// The trait is implemented for [AnyDecoder](crate::AnyDecoder),
+/// [bufread::AnyDecoder](crate::bufread::AnyDecoder),
+/// [read::AnyDecoder](crate::read::AnyDecoder) and [typed::Decoder](crate::typed::Decoder),
+/// i.e. generic code can accept any of them.
+pub trait Decode: Read {
+    /// The underlying reader.
+    type Reader;
+
+    /// Get the input stream format.
+    ///
+    /// If the format hasn't been detected yet, a small amount of data is read from the stream.
+    fn kind(&mut self) -> Result<Format, Error>;
+
+    /// Get immutable reference to the underlying reader.
+    fn get_ref(&self) -> &Self::Reader;
+
+    /// Get mutable reference to the underlying reader.
+    fn get_mut(&mut self) -> &mut Self::Reader;
+
+    /// Return the underlying reader.
+    fn into_inner(self) -> Self::Reader
+    where
+        Self: Sized;
+}
+
+/// An encoder that compresses the data written to the underlying writer.
+///
+/// The trait is implemented for [write::AnyEncoder](crate::write::AnyEncoder) and
+/// [typed::Encoder](crate::typed::Encoder),
+/// i.e. generic code can accept any of them.
+pub trait Encode: Write {
+    /// The underlying writer.
+    type Writer;
+
+    /// Get encoding format.
+    fn format(&self) -> Format;
+
+    /// Get immutable reference to the underlying writer.
+    fn get_ref(&self) -> &Self::Writer;
+
+    /// Get mutable reference to the underlying writer.
+    fn get_mut(&mut self) -> &mut Self::Writer;
+
+    /// Finish encoding and return the underlying writer.
+    fn finish(self) -> Result<Self::Writer, Error>
+    where
+        Self: Sized;
+}
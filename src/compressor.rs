@@ -0,0 +1,268 @@
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Write;
+
+use crate::write::AnyEncoder;
+use crate::write::Compression;
+use crate::write::EncoderOptions;
+use crate::Format;
+use crate::Status;
+
+/// A compressor that doesn't do any I/O: the caller supplies input and output buffers.
+///
+/// The data is compressed by [write::AnyEncoder](crate::write::AnyEncoder),
+/// i.e. all the formats and the options are supported.
+///
+/// ```rust
+/// use deko::Compressor;
+/// use deko::Format;
+/// use deko::Status;
+/// use deko::write::Compression;
+///
+/// let mut compressor = Compressor::new(Format::Verbatim, Compression::Default).unwrap();
+/// let mut output = [0_u8; 64];
+/// assert_eq!(Status::NeedInput, compressor.compress(b"Hello world", &mut output).unwrap());
+/// let n = compressor.total_out() as usize;
+/// assert_eq!(Status::StreamEnd, compressor.finish(&mut output[n..]).unwrap());
+/// assert_eq!(b"Hello world", &output[..compressor.total_out() as usize]);
+/// ```
+pub struct Compressor {
+    // `None` when the stream is finished
+    encoder: Option<AnyEncoder<Vec<u8>>>,
+    format: Format,
+    // the output that didn't fit into the caller's buffer
+    pending: Vec<u8>,
+    pos: usize,
+    // whether the encoder has been flushed but the output hasn't been drained yet
+    flushing: bool,
+    total_in: u64,
+    total_out: u64,
+}
+
+impl Compressor {
+    /// Create new compressor for the supplied `format` and `compression` ratio.
+    pub fn new(format: Format, compression: Compression) -> Result<Self, Error> {
+        Ok(Self::from_encoder(AnyEncoder::new(
+            Vec::new(),
+            format,
+            compression,
+        )?))
+    }
+
+    /// Create new compressor using the supplied `options`.
+    pub fn with_options(options: &EncoderOptions) -> Result<Self, Error> {
+        Ok(Self::from_encoder(AnyEncoder::with_options(
+            Vec::new(),
+            options,
+        )?))
+    }
+
+    fn from_encoder(encoder: AnyEncoder<Vec<u8>>) -> Self {
+        Self {
+            format: encoder.format(),
+            encoder: Some(encoder),
+            pending: Vec::new(),
+            pos: 0,
+            flushing: false,
+            total_in: 0,
+            total_out: 0,
+        }
+    }
+
+    /// Get encoding format.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Returns `true` if [flush](Compressor::flush) is supported by the encoder.
+    ///
+    /// See [write::AnyEncoder::supports_flush](crate::write::AnyEncoder::supports_flush).
+    pub fn supports_flush(&self) -> bool {
+        self.encoder
+            .as_ref()
+            .is_some_and(|encoder| encoder.supports_flush())
+    }
+
+    /// The total number of input bytes consumed so far.
+    pub fn total_in(&self) -> u64 {
+        self.total_in
+    }
+
+    /// The total number of output bytes produced so far.
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    /// Compress `input` into `output`.
+    ///
+    /// The number of consumed and produced bytes is the difference of
+    /// [total_in](Compressor::total_in) and [total_out](Compressor::total_out)
+    /// before and after the call.
+    /// The input that wasn't consumed should be supplied again in the next call.
+    /// Returns [Status::NeedInput] when all the input has been consumed.
+    pub fn compress(&mut self, input: &[u8], output: &mut [u8]) -> Result<Status, Error> {
+        let mut consumed = 0;
+        let mut produced = 0;
+        let result = loop {
+            if !self.drain(output, &mut produced) {
+                break Ok(Status::NeedOutput);
+            }
+            let Some(encoder) = self.encoder.as_mut() else {
+                break Err(finished());
+            };
+            if consumed == input.len() {
+                break Ok(Status::NeedInput);
+            }
+            let end = input.len().min(consumed + INPUT_CHUNK_LEN);
+            if let Err(e) = encoder.write_all(&input[consumed..end]) {
+                break Err(e);
+            }
+            consumed = end;
+            // reuse the buffer
+            std::mem::swap(&mut self.pending, encoder.get_mut());
+        };
+        self.total_in += consumed as u64;
+        self.total_out += produced as u64;
+        result
+    }
+
+    /// Make all the input consumed so far decodable from the output produced so far.
+    ///
+    /// Call the method until it returns [Status::NeedInput].
+    /// Returns an error of kind [Unsupported](ErrorKind::Unsupported) if the format
+    /// [doesn't support](Compressor::supports_flush) flushing.
+    pub fn flush(&mut self, output: &mut [u8]) -> Result<Status, Error> {
+        let mut produced = 0;
+        let result = loop {
+            if !self.drain(output, &mut produced) {
+                break Ok(Status::NeedOutput);
+            }
+            if self.flushing {
+                self.flushing = false;
+                break Ok(Status::NeedInput);
+            }
+            let Some(encoder) = self.encoder.as_mut() else {
+                break Err(finished());
+            };
+            if let Err(e) = encoder.flush() {
+                break Err(e);
+            }
+            std::mem::swap(&mut self.pending, encoder.get_mut());
+            self.flushing = true;
+        };
+        self.total_out += produced as u64;
+        result
+    }
+
+    /// Finish the stream.
+    ///
+    /// Call the method until it returns [Status::StreamEnd].
+    /// The compressor can't be used afterwards.
+    pub fn finish(&mut self, output: &mut [u8]) -> Result<Status, Error> {
+        let mut produced = 0;
+        let result = loop {
+            if !self.drain(output, &mut produced) {
+                break Ok(Status::NeedOutput);
+            }
+            let Some(encoder) = self.encoder.take() else {
+                break Ok(Status::StreamEnd);
+            };
+            match encoder.finish() {
+                Ok(pending) => self.pending = pending,
+                Err(e) => break Err(e),
+            }
+        };
+        self.total_out += produced as u64;
+        result
+    }
+
+    /// Copy the pending output to `output`.
+    ///
+    /// Returns `true` if all the pending output has been copied.
+    fn drain(&mut self, output: &mut [u8], produced: &mut usize) -> bool {
+        let n = (output.len() - *produced).min(self.pending.len() - self.pos);
+        output[*produced..*produced + n].copy_from_slice(&self.pending[self.pos..self.pos + n]);
+        *produced += n;
+        self.pos += n;
+        if self.pos != self.pending.len() {
+            return false;
+        }
+        self.pending.clear();
+        self.pos = 0;
+        true
+    }
+}
+
+fn finished() -> Error {
+    Error::new(ErrorKind::InvalidInput, "the stream is finished")
+}
+
+/// The maximum number of input bytes that are compressed at once.
+///
+/// The output of this chunk is buffered until it is copied into the caller's buffers.
+const INPUT_CHUNK_LEN: usize = 16 * 1024;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use arbtest::arbtest;
+
+    use super::*;
+
+    #[test]
+    fn compress() {
+        arbtest(|u| {
+            let format = *u.choose(Format::ALL)?;
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut compressor = Compressor::new(format, Compression::Fast).unwrap();
+            let mut compressed = Vec::new();
+            let mut input = &expected[..];
+            let mut flushed = None;
+            while !input.is_empty() {
+                let len = u.int_in_range(1..=input.len())?;
+                let mut output = vec![0_u8; u.int_in_range(1..=4096)?];
+                let total_in = compressor.total_in();
+                let total_out = compressor.total_out();
+                compressor.compress(&input[..len], &mut output).unwrap();
+                input = &input[(compressor.total_in() - total_in) as usize..];
+                compressed
+                    .extend_from_slice(&output[..(compressor.total_out() - total_out) as usize]);
+                if flushed.is_none() && compressor.supports_flush() && u.ratio(1, 10)? {
+                    loop {
+                        let total_out = compressor.total_out();
+                        let status = compressor.flush(&mut output).unwrap();
+                        compressed.extend_from_slice(
+                            &output[..(compressor.total_out() - total_out) as usize],
+                        );
+                        if status == Status::NeedInput {
+                            break;
+                        }
+                    }
+                    flushed = Some((compressor.total_in() as usize, compressed.clone()));
+                }
+            }
+            loop {
+                let mut output = vec![0_u8; u.int_in_range(1..=4096)?];
+                let total_out = compressor.total_out();
+                let status = compressor.finish(&mut output).unwrap();
+                compressed
+                    .extend_from_slice(&output[..(compressor.total_out() - total_out) as usize]);
+                if status == Status::StreamEnd {
+                    break;
+                }
+            }
+            let mut reader = crate::bufread::AnyDecoder::with_format(&compressed[..], format);
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, actual);
+            if let Some((len, prefix)) = flushed {
+                let mut reader = crate::bufread::AnyDecoder::with_format(&prefix[..], format);
+                let mut actual = vec![0_u8; len];
+                reader.read_exact(&mut actual).unwrap();
+                assert_eq!(&expected[..len], &actual[..]);
+            }
+            Ok(())
+        });
+    }
+}
@@ -1 +1,46 @@
-pub(crate) const MAX_MAGIC_BYTES: usize = 6;
+/// The number of bytes that is enough to detect any of the enabled formats.
+pub(crate) const MAX_MAGIC_BYTES: usize = max_magic_bytes();
+
+#[allow(unused_mut)]
+const fn max_magic_bytes() -> usize {
+    let mut n = crate::MAX_SIGNATURE_LEN;
+    #[cfg(feature = "flate2")]
+    {
+        // CMF and FLG
+        n = max(n, 2);
+        // ID1, ID2, CM, FLG, MTIME, XFL and OS
+        n = max(n, 10);
+    }
+    // "BZh" and block size
+    #[cfg(feature = "bzip2")]
+    {
+        n = max(n, 4);
+    }
+    #[cfg(feature = "xz")]
+    {
+        n = max(n, 6);
+    }
+    // magic and the largest frame header
+    #[cfg(feature = "zstd")]
+    {
+        n = max(n, 18);
+    }
+    #[cfg(feature = "mscompress")]
+    {
+        n = max(n, 8);
+    }
+    #[cfg(feature = "lzop")]
+    {
+        n = max(n, 9);
+    }
+    n
+}
+
+#[allow(unused)]
+const fn max(a: usize, b: usize) -> usize {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
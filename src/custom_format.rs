@@ -0,0 +1,163 @@
+use std::fmt;
+use std::io::BufRead;
+use std::io::Error;
+use std::io::Read;
+use std::sync::Arc;
+
+/// A decoder of the [custom format](CustomFormat).
+///
+/// Unlike the built-in decoders the custom decoder doesn't own the underlying reader:
+/// the compressed data is read from `input` on each call.
+pub trait CustomDecoder: Send {
+    /// Decompress some bytes from `input` into `buf`.
+    ///
+    /// Has the same semantics as [Read::read], i.e. returns zero at the end of the stream.
+    /// The decoder should consume only the bytes of its own stream from `input`,
+    /// then the following members of the concatenated stream can be decoded too.
+    fn read(&mut self, input: &mut dyn BufRead, buf: &mut [u8]) -> Result<usize, Error>;
+}
+
+type Matcher = dyn Fn(&[u8]) -> bool + Send + Sync;
+type Factory = dyn Fn() -> Box<dyn CustomDecoder> + Send + Sync;
+
+/// A user-defined compression format that takes part in format detection.
+///
+/// The format consists of the name, the matcher of the magic bytes and
+/// the factory of the [decoders](CustomDecoder).
+/// The decoders report the format as [Other](crate::Format::Other) with this name.
+/// Register the format via [custom_format](crate::bufread::AnyDecoder::custom_format).
+///
+/// ```rust
+/// use std::io::{BufRead, Error, Read};
+///
+/// use deko::{CustomDecoder, CustomFormat, Format};
+///
+/// // the stream is the magic bytes followed by the uncompressed data
+/// struct Stored {
+///     header: bool,
+/// }
+///
+/// impl CustomDecoder for Stored {
+///     fn read(&mut self, input: &mut dyn BufRead, buf: &mut [u8]) -> Result<usize, Error> {
+///         if !self.header {
+///             input.read_exact(&mut [0_u8; 4])?;
+///             self.header = true;
+///         }
+///         input.read(buf)
+///     }
+/// }
+///
+/// let format = CustomFormat::new("stored", b"STO\0", || Box::new(Stored { header: false }));
+/// let mut reader = deko::bufread::AnyDecoder::builder(&b"STO\0hello"[..])
+///     .custom_format(format)
+///     .build();
+/// let mut string = String::new();
+/// reader.read_to_string(&mut string).unwrap();
+/// assert_eq!("hello", string);
+/// assert_eq!(Format::Other("stored"), reader.kind().unwrap());
+/// ```
+#[derive(Clone)]
+pub struct CustomFormat {
+    name: &'static str,
+    magic_len: usize,
+    matcher: Arc<Matcher>,
+    factory: Arc<Factory>,
+}
+
+impl CustomFormat {
+    /// Create the format that is detected by the `magic` bytes.
+    pub fn new<F>(name: &'static str, magic: &[u8], factory: F) -> Self
+    where
+        F: Fn() -> Box<dyn CustomDecoder> + Send + Sync + 'static,
+    {
+        let magic = magic.to_vec();
+        Self {
+            name,
+            magic_len: magic.len(),
+            matcher: Arc::new(move |bytes: &[u8]| bytes.starts_with(&magic)),
+            factory: Arc::new(factory),
+        }
+    }
+
+    /// Create the format that is detected by the `matcher`.
+    ///
+    /// The matcher receives the first bytes of the stream,
+    /// at least 18 bytes unless the stream is shorter.
+    pub fn with_matcher<M, F>(name: &'static str, matcher: M, factory: F) -> Self
+    where
+        M: Fn(&[u8]) -> bool + Send + Sync + 'static,
+        F: Fn() -> Box<dyn CustomDecoder> + Send + Sync + 'static,
+    {
+        Self {
+            name,
+            magic_len: 0,
+            matcher: Arc::new(matcher),
+            factory: Arc::new(factory),
+        }
+    }
+
+    /// Get the name of the format.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The number of bytes the matcher needs.
+    pub(crate) fn magic_len(&self) -> usize {
+        self.magic_len
+    }
+
+    pub(crate) fn matches(&self, magic: &[u8]) -> bool {
+        (self.matcher)(magic)
+    }
+
+    pub(crate) fn new_decoder(&self) -> Box<dyn CustomDecoder> {
+        (self.factory)()
+    }
+}
+
+impl fmt::Debug for CustomFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomFormat")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Reader that decodes the custom format.
+pub(crate) struct CustomReader<R> {
+    reader: R,
+    decoder: Box<dyn CustomDecoder>,
+    name: &'static str,
+}
+
+impl<R: BufRead> CustomReader<R> {
+    pub(crate) fn new(reader: R, name: &'static str, decoder: Box<dyn CustomDecoder>) -> Self {
+        Self {
+            reader,
+            decoder,
+            name,
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub(crate) fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: BufRead> Read for CustomReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.decoder.read(&mut self.reader, buf)
+    }
+}
@@ -2,15 +2,16 @@ macro_rules! define_decoder {
     ($trait: ident) => {
         #[cfg(feature = "nightly")]
         use std::io::BorrowedCursor;
-        use std::io::Empty;
         use std::io::Error;
-        use std::io::ErrorKind;
         use std::io::IoSliceMut;
         use std::io::Read;
 
         #[cfg(feature = "nightly")]
         use crate::dispatch;
         use crate::dispatch_mut;
+        use crate::map_memory_limit_error;
+        use crate::replace_with;
+        use crate::DecoderLimits;
         use crate::Format;
 
         /// A decoder that decompresses the supplied input stream using any of the supported formats.
@@ -18,44 +19,437 @@ macro_rules! define_decoder {
         /// The format is detected using the _magic bytes_ at the start of the stream.
         /// By default, if the format is not supported, the data is read verbatim.
         /// Use [fail_on_unknown_format](AnyDecoder::fail_on_unknown_format) to change this behaviour.
-        pub struct AnyDecoder<R: $trait> {
-            reader: Option<MagicReader<R>>,
-            inner: InnerDecoder<MagicReader<R>>,
-            fail_on_unknown_format: bool,
+        ///
+        /// The lifetime `'a` is the lifetime of the borrowed zstd dictionary
+        /// (see [zstd_dictionary](AnyDecoder::zstd_dictionary)); it is `'static` otherwise.
+        pub struct AnyDecoder<'a, R: $trait> {
+            state: State<'a, R>,
+            options: DecoderOptions,
+            #[cfg(feature = "zstd")]
+            zstd_dictionary: Option<&'a zstd::dict::DecoderDictionary<'a>>,
+            gz_fields: Option<[u8; 2]>,
+            /// The details of the format detection of the current member.
+            detection: Option<crate::Detection>,
+            /// Whether none of the formats matched the non-empty stream.
+            unknown_format: bool,
+            /// Whether the current zstd frame includes content checksum.
+            #[cfg(feature = "zstd")]
+            zstd_checksum: bool,
+            /// The ID of the dictionary that is needed to decompress the current zstd frame.
+            #[cfg(feature = "zstd")]
+            zstd_dict_id: Option<u32>,
+            /// The decompressed size of the current zstd frame.
+            #[cfg(feature = "zstd")]
+            zstd_content_size: Option<u64>,
+            #[cfg(feature = "metrics")]
+            metrics: Option<crate::DecoderMetrics>,
+            #[cfg(feature = "tracing")]
+            decoded_bytes: u64,
+        }
+
+        impl<'a, 'b> AnyDecoder<'a, &'b [u8]> {
+            /// Create new decoder from the in-memory `input`.
+            ///
+            /// The decoder knows that the whole input is available at once,
+            /// hence the magic bytes of short inputs are checked in place rather than buffered.
+            /// Nothing is allocated for uncompressed input.
+            pub fn from_slice(input: &'b [u8]) -> Self {
+                Self::with_magic_reader(MagicReader::in_memory(input))
+            }
         }
 
-        impl<R: $trait> AnyDecoder<R> {
+        impl<'a, R: $trait> AnyDecoder<'a, R> {
             /// Create new decoder from the supplied `reader`.
             pub fn new(reader: R) -> Self {
+                Self::with_magic_reader(MagicReader::new(reader))
+            }
+
+            fn with_magic_reader(reader: MagicReader<R>) -> Self {
                 Self {
-                    reader: Some(MagicReader::new(reader)),
-                    inner: InnerDecoder::Empty(std::io::empty()),
-                    fail_on_unknown_format: false,
+                    state: State::Pending(reader),
+                    options: Default::default(),
+                    #[cfg(feature = "zstd")]
+                    zstd_dictionary: None,
+                    gz_fields: None,
+                    detection: None,
+                    unknown_format: false,
+                    #[cfg(feature = "zstd")]
+                    zstd_checksum: false,
+                    #[cfg(feature = "zstd")]
+                    zstd_dict_id: None,
+                    #[cfg(feature = "zstd")]
+                    zstd_content_size: None,
+                    #[cfg(feature = "metrics")]
+                    metrics: None,
+                    #[cfg(feature = "tracing")]
+                    decoded_bytes: 0,
                 }
             }
 
+            /// Create new decoder that decodes the supplied `reader` using the `format`.
+            ///
+            /// The format is not detected, and the magic bytes are not checked.
+            /// Use this constructor when the format is known in advance,
+            /// e.g. from a container's table of contents.
+            pub fn with_format(reader: R, format: Format) -> Self {
+                let mut decoder = Self::new(reader);
+                decoder.options.format = Some(format);
+                decoder
+            }
+
             /// Throw an error when the decoder fails to detect compression format.
             ///
+            /// If the input is an [archive](crate::Archive) rather than a compressed stream,
+            /// the error message names the archive format;
+            /// encrypted data (OpenPGP, age, `openssl enc`) is reported as such.
+            ///
             /// By default no error is thrown, and the data is read verbatim.
             pub fn fail_on_unknown_format(&mut self, value: bool) {
-                self.fail_on_unknown_format = value;
+                self.options.fail_on_unknown_format = value;
+            }
+
+            /// Read at most `size` bytes of compressed data from the underlying reader.
+            ///
+            /// Use this method when the compressed size is known in advance,
+            /// e.g. from a container's table of contents,
+            /// so that the decoder doesn't read past the end of the entry.
+            /// The decoder returns end-of-file when the limit is reached, and
+            /// the data that follows remains in the underlying reader.
+            /// A limit that is less than the size of the compressed stream results in an error.
+            /// The entries that follow the first entry of a ZIP file and its central directory
+            /// are skipped up to the limit.
+            ///
+            /// The limit applies to the data that is read after this call,
+            /// i.e. the method is supposed to be called before reading from the decoder.
+            /// By default the size is not limited.
+            pub fn compressed_size(&mut self, size: u64) {
+                if let State::Pending(ref mut reader)
+                | State::Active(InnerDecoder::Reader(ref mut reader, _)) = self.state
+                {
+                    reader.set_limit(size);
+                }
+            }
+
+            /// Limit the amount of memory that the decoder is allowed to allocate.
+            ///
+            /// The limit is mapped to xz memory limit, zstd maximum window size and
+            /// bzip2 _small_ decompression mode.
+            /// If the stream can't be decoded within the limit, the decoder returns
+            /// an error of kind [OutOfMemory](std::io::ErrorKind::OutOfMemory).
+            ///
+            /// By default the memory usage is not limited.
+            pub fn memory_limit(&mut self, bytes: u64) {
+                self.options.memory_limit = Some(bytes);
+            }
+
+            /// Bound every internal allocation by the supplied `limits`.
+            ///
+            /// This mode is intended for decoding untrusted input.
+            /// Besides the [memory limit](AnyDecoder::memory_limit) of the codec state,
+            /// it bounds the [scan window](AnyDecoder::scan_for_magic) buffer and
+            /// the size of the zstd dictionary.
+            /// Gzip header fields are bounded by flate2 (64 KiB).
+            /// Exceeding any of the limits results in an error of kind
+            /// [OutOfMemory](std::io::ErrorKind::OutOfMemory).
+            ///
+            /// By default only the [memory limit](AnyDecoder::memory_limit) is applied.
+            pub fn hardened(&mut self, limits: DecoderLimits) {
+                self.options.limits = Some(limits);
+            }
+
+            /// Use bzip2 _small_ decompression mode.
+            ///
+            /// This mode is roughly two times slower but uses ~2.5 MB less memory.
+            /// It is enabled automatically when the default mode doesn't fit
+            /// into the [memory limit](AnyDecoder::memory_limit).
+            ///
+            /// By default the small mode is disabled.
+            #[cfg(feature = "bzip2")]
+            pub fn bzip2_small_mode(&mut self, value: bool) {
+                self.options.bzip2_small_mode = value;
+            }
+
+            /// Stop at the end of each gzip member, xz stream, zstd and LZ4 frame.
+            ///
+            /// In this mode the decoder returns end-of-file at the end of the current member.
+            /// Use [next_member](AnyDecoder::next_member) to continue with the following one.
+            /// The padding between xz streams is skipped in both modes.
+            ///
+            /// By default all members are decoded as one continuous stream.
+            pub fn per_member(&mut self, value: bool) {
+                self.options.per_member = value;
+            }
+
+            /// Stop at the end of the first gzip member, xz stream, zstd or LZ4 frame.
+            ///
+            /// Use this mode to decode the compressed data that is embedded in a container
+            /// and is followed by unrelated data.
+            /// The decoder returns end-of-file at the end of the member, and
+            /// the bytes that follow it are left unread in the underlying reader,
+            /// i.e. they are accessible via [into_inner](AnyDecoder::into_inner).
+            /// This is not the case for [read::AnyDecoder](crate::read::AnyDecoder)
+            /// that reads ahead, when the format is detected using
+            /// [trial decoding](AnyDecoder::trial_decode) or [magic scanning](AnyDecoder::scan_for_magic),
+            /// and when the underlying reader returns too few bytes to detect the format at once
+            /// (e.g. the member and the data that follows it are only a few bytes long):
+            /// the decoder then buffers the magic bytes and the bytes that follow them.
+            ///
+            /// By default all members are decoded as one continuous stream.
+            pub fn single_member(&mut self, value: bool) {
+                self.options.single_member = value;
+            }
+
+            /// Detect zlib streams.
+            ///
+            /// Zlib has no magic bytes: the stream is detected by the two-byte header
+            /// whose value is a multiple of 31, and such headers often occur in arbitrary binary data.
+            /// Disabling this heuristic prevents such data from being decoded as zlib
+            /// while the formats with exact magic bytes are still detected.
+            /// Zlib streams can still be decoded via [with_format](AnyDecoder::with_format).
+            ///
+            /// By default zlib detection is enabled.
+            #[cfg(feature = "flate2")]
+            pub fn zlib_heuristic(&mut self, value: bool) {
+                self.options.zlib_heuristic = value;
+            }
+
+            /// Throw an error when gzip header doesn't include CRC16 (FHCRC).
+            ///
+            /// The header CRC is always verified when present.
+            /// This option makes its absence an error as well.
+            /// Only the first member is checked unless the members are decoded
+            /// [one by one](AnyDecoder::per_member):
+            /// the following members are decoded by [flate2] that doesn't report
+            /// whether their headers include CRC.
+            ///
+            /// By default the header CRC is optional.
+            #[cfg(feature = "flate2")]
+            pub fn require_gz_header_crc(&mut self, value: bool) {
+                self.options.require_gz_header_crc = value;
+            }
+
+            /// Throw an error when zstd frame doesn't include content checksum.
+            ///
+            /// The checksum is always verified when present.
+            /// This option makes its absence an error as well.
+            /// Only the first frame is checked unless the frames are decoded
+            /// [one by one](AnyDecoder::per_member).
+            ///
+            /// By default the checksum is optional.
+            #[cfg(feature = "zstd")]
+            pub fn require_zstd_checksum(&mut self, value: bool) {
+                self.options.require_zstd_checksum = value;
+            }
+
+            /// Stop at the end of the first zstd frame.
+            ///
+            /// Use this mode for container formats that embed exactly one frame
+            /// followed by unrelated data.
+            ///
+            /// By default the concatenated frames are decoded as one continuous stream.
+            #[cfg(feature = "zstd")]
+            pub fn zstd_single_frame(&mut self, value: bool) {
+                self.options.zstd_single_frame = value;
+            }
+
+            /// Look for the magic bytes in the first `window` bytes of the stream.
+            ///
+            /// If no format is detected at the start of the stream,
+            /// the decoder searches for the magic bytes of a supported format at the offsets
+            /// from 1 to `window`, skips the bytes that precede them and decodes the rest.
+            /// This is useful for firmware images and files with a small proprietary prefix.
+            /// Zlib streams are only detected at the start, because their header is too short
+            /// to be found reliably in arbitrary data.
+            /// If nothing is found, the stream is handled as if no scanning was done.
+            ///
+            /// By default the magic bytes are only checked at the start of the stream (zero window).
+            pub fn scan_for_magic(&mut self, window: usize) {
+                self.options.magic_scan_window = window;
+            }
+
+            /// Confirm the format detected by the magic bytes by decoding the first bytes of the stream.
+            ///
+            /// Up to 4 KiB of the input are decoded, and the output is limited to 64 KiB.
+            /// If decoding fails, the stream is handled as if its format is unknown.
+            /// When [scanning for the magic bytes](AnyDecoder::scan_for_magic),
+            /// zlib streams are detected at any offset, and the first offset at which
+            /// the decoding succeeds is used (at most 16 offsets are tried).
+            /// This mode is useful for the formats with weak signatures like zlib,
+            /// whose two-byte header often occurs in uncompressed data.
+            ///
+            /// By default the format is detected using the magic bytes only.
+            pub fn trial_decode(&mut self, value: bool) {
+                self.options.trial_decode = value;
+            }
+
+            /// Test the formats in the specified order during detection.
+            ///
+            /// The first format whose magic bytes match is used;
+            /// with [trial decoding](AnyDecoder::trial_decode) enabled the matching formats are tried
+            /// in this order until the decoding succeeds.
+            /// The formats that are not in the list are never detected,
+            /// e.g. putting zlib last or leaving it out altogether
+            /// reduces false positives on arbitrary binary data.
+            /// [Verbatim](Format::Verbatim) is ignored.
+            ///
+            /// Brotli has no magic bytes and is detected only if it's in the list:
+            /// when no other format matches, the first bytes are decoded as a brotli stream,
+            /// and the stream is accepted if the decoding succeeds.
+            /// This heuristic is prone to false positives on short inputs.
+            ///
+            /// By default [Format::DETECTION_ORDER] is used.
+            pub fn detection_order(&mut self, formats: &[Format]) {
+                self.options.detection_order = Some(formats.to_vec());
+            }
+
+            /// Detect only the specified formats.
+            ///
+            /// The streams of the other formats are treated as unknown,
+            /// i.e. they are passed through verbatim or rejected if
+            /// [fail_on_unknown_format](AnyDecoder::fail_on_unknown_format) is enabled.
+            /// This rejects unexpected formats even if they are enabled via Cargo features,
+            /// e.g. xz streams in a service that accepts only gzip and zstd.
+            /// Unlike [detection_order](AnyDecoder::detection_order) the order of the formats
+            /// doesn't matter. The format specified via [with_format](AnyDecoder::with_format)
+            /// is not restricted.
+            ///
+            /// By default all formats are allowed.
+            pub fn allowed_formats(&mut self, formats: &[Format]) {
+                self.options.allowed_formats = Some(formats.to_vec());
+            }
+
+            /// Register user-defined format.
+            ///
+            /// Custom formats are detected at zero offset before the built-in formats
+            /// in the order they were registered, regardless of
+            /// [scanning](AnyDecoder::scan_for_magic) and
+            /// [trial decoding](AnyDecoder::trial_decode).
+            /// Use [Format::Other] with [with_format](AnyDecoder::with_format)
+            /// to decode the stream using the registered format without detection.
+            /// See [CustomFormat](crate::CustomFormat).
+            pub fn custom_format(&mut self, format: crate::CustomFormat) {
+                self.options.custom_formats.push(format);
+            }
+
+            /// Decompress zstd frames using the supplied prepared dictionary.
+            ///
+            /// The dictionary is borrowed rather than copied,
+            /// i.e. the same dictionary can be shared by many decoders.
+            /// The dictionary is prepared from the bytes via
+            /// `zstd::dict::DecoderDictionary::copy(dictionary.as_bytes())`,
+            /// where `dictionary` is [ZstdDictionary](crate::ZstdDictionary).
+            ///
+            /// By default no dictionary is used.
+            #[cfg(feature = "zstd")]
+            pub fn zstd_dictionary(&mut self, dictionary: &'a zstd::dict::DecoderDictionary<'a>) {
+                self.zstd_dictionary = Some(dictionary);
+            }
+
+            /// Decompress zlib streams using the supplied preset dictionary.
+            ///
+            /// Such streams have FDICT flag set in the header that is followed by
+            /// Adler-32 of the dictionary (see [zlib_dict_id](crate::ZlibDecoderExt::zlib_dict_id)).
+            /// The dictionary can be supplied after the format is detected
+            /// but before the decompressed data is read.
+            /// Reading such a stream without the dictionary or with the wrong one
+            /// results in an error of kind [InvalidInput](std::io::ErrorKind::InvalidInput).
+            /// The dictionary is ignored for the streams without FDICT flag.
+            ///
+            /// By default no dictionary is used.
+            #[cfg(feature = "flate2")]
+            pub fn zlib_dictionary(&mut self, dictionary: &[u8]) {
+                self.options.zlib_dictionary = Some(dictionary.to_vec());
+                if let State::Active(InnerDecoder::ZlibDict(ref mut r)) = self.state {
+                    r.set_dictionary(dictionary);
+                }
+            }
+
+            /// Skip the rest of the current member and start decoding the next one.
+            ///
+            /// The format of the next member is detected anew.
+            /// Returns `false` if there are no more members in the input stream.
+            pub fn next_member(&mut self) -> Result<bool, Error> {
+                self.detect()?;
+                std::io::copy(self, &mut std::io::sink())?;
+                #[cfg(feature = "xz")]
+                let xz = self.get_kind() == Format::Xz;
+                replace_with(&mut self.state, |state| match state {
+                    State::Active(inner) => State::Pending(inner.into_reader()),
+                    state => state,
+                });
+                if let State::Pending(ref mut reader) = self.state {
+                    #[cfg(feature = "xz")]
+                    if xz {
+                        reader.skip_xz_padding()?;
+                    }
+                    if reader.read_magic()?.is_empty() {
+                        // read the rest verbatim, i.e. return end-of-file
+                        self.detection = None;
+                        self.activate(DecoderParams::Verbatim(PhantomData));
+                        return Ok(false);
+                    }
+                }
+                #[cfg(feature = "tracing")]
+                {
+                    self.decoded_bytes = 0;
+                }
+                self.detect()?;
+                Ok(true)
+            }
+
+            /// Iterate over the fixed-size chunks of the decompressed data.
+            ///
+            /// Zero `size` is treated as one.
+            pub fn chunks(self, size: usize) -> crate::Chunks<Self> {
+                crate::Chunks::new(self, size)
+            }
+
+            /// Iterate over the fixed-size chunks of the decompressed data as [Bytes](bytes::Bytes).
+            ///
+            /// Zero `size` is treated as one.
+            #[cfg(feature = "bytes")]
+            pub fn bytes_chunks(self, size: usize) -> crate::BytesChunks<Self> {
+                crate::BytesChunks::new(self, size)
             }
 
             #[inline]
             fn get_kind(&self) -> Format {
-                match self.inner {
+                let State::Active(ref inner) = self.state else {
+                    return Format::Verbatim;
+                };
+                match *inner {
                     InnerDecoder::Reader(..) => Format::Verbatim,
                     #[cfg(feature = "flate2")]
                     InnerDecoder::Gz(..) => Format::Gz,
+                    #[cfg(feature = "flate2")]
+                    InnerDecoder::GzMember(..) => Format::Gz,
                     #[cfg(feature = "bzip2")]
                     InnerDecoder::Bz(..) => Format::Bz,
                     #[cfg(feature = "flate2")]
                     InnerDecoder::Zlib(..) => Format::Zlib,
+                    #[cfg(feature = "flate2")]
+                    InnerDecoder::ZlibDict(..) => Format::Zlib,
+                    #[cfg(feature = "flate2")]
+                    InnerDecoder::Deflate(..) => Format::Deflate,
                     #[cfg(feature = "xz")]
                     InnerDecoder::Xz(..) => Format::Xz,
                     #[cfg(feature = "zstd")]
                     InnerDecoder::Zstd(..) => Format::Zstd,
-                    InnerDecoder::Empty(..) => unreachable!(),
+                    #[cfg(feature = "zip")]
+                    InnerDecoder::Zip(..) => Format::Zip,
+                    #[cfg(feature = "mscompress")]
+                    InnerDecoder::Szdd(..) => Format::Szdd,
+                    #[cfg(feature = "mscompress")]
+                    InnerDecoder::Kwaj(..) => Format::Kwaj,
+                    #[cfg(feature = "lzop")]
+                    InnerDecoder::Lzop(..) => Format::Lzop,
+                    #[cfg(feature = "lzfse")]
+                    InnerDecoder::Lzfse(..) => Format::Lzfse,
+                    #[cfg(feature = "lz4")]
+                    InnerDecoder::Lz4(..) => Format::Lz4,
+                    #[cfg(feature = "brotli")]
+                    InnerDecoder::Brotli(..) => Format::Brotli,
+                    InnerDecoder::Custom(ref r) => Format::Other(r.name()),
                 }
             }
 
@@ -70,119 +464,534 @@ macro_rules! define_decoder {
                 Ok(self.get_kind())
             }
 
+            /// Check whether none of the formats matched the stream.
+            ///
+            /// Unlike [kind](AnyDecoder::kind) that returns [Verbatim](Format::Verbatim) in any case,
+            /// distinguishes undetected data from the empty stream and from the verbatim format
+            /// specified via [with_format](AnyDecoder::with_format).
+            /// Use it to log or count undetected inputs without
+            /// [failing](AnyDecoder::fail_on_unknown_format) on them.
+            pub fn is_unknown_format(&mut self) -> Result<bool, Error> {
+                self.detect()?;
+                Ok(self.unknown_format)
+            }
+
+            /// Get the details of the input stream format detection.
+            ///
+            /// Returns `None` if the format was not detected, i.e. the stream is not compressed.
+            /// Always returns `None` if the format was specified via
+            /// [with_format](AnyDecoder::with_format), because no detection takes place.
+            /// The magic bytes are those of the current member.
+            /// See also [is_unknown_format](AnyDecoder::is_unknown_format).
+            pub fn detection(&mut self) -> Result<Option<crate::Detection>, Error> {
+                self.detect()?;
+                Ok(self.detection)
+            }
+
+            /// Get gzip XFL (extra flags) byte of the current member.
+            ///
+            /// The value is a compression-effort hint: 2 means the slowest compression,
+            /// 4 means the fastest compression.
+            /// Returns `None` if the input stream is not gzip-compressed.
+            #[cfg(feature = "flate2")]
+            pub fn gz_extra_flags(&mut self) -> Result<Option<u8>, Error> {
+                self.detect()?;
+                Ok(self.gz_fields().map(|[xfl, _]| xfl))
+            }
+
+            /// Get gzip OS byte of the current member.
+            ///
+            /// The value identifies the operating system on which the compression took place
+            /// (255 means unknown).
+            /// Returns `None` if the input stream is not gzip-compressed.
+            #[cfg(feature = "flate2")]
+            pub fn gz_operating_system(&mut self) -> Result<Option<u8>, Error> {
+                self.detect()?;
+                Ok(self.gz_fields().map(|[_, os]| os))
+            }
+
+            #[cfg(feature = "flate2")]
+            fn gz_fields(&self) -> Option<[u8; 2]> {
+                match self.state {
+                    State::Active(InnerDecoder::Gz(..) | InnerDecoder::GzMember(..)) => {
+                        self.gz_fields
+                    }
+                    _ => None,
+                }
+            }
+
+            /// Check whether the current zstd frame includes content checksum.
+            ///
+            /// The checksum is verified at the end of the frame, and a mismatch results in an error,
+            /// i.e. the frame that has been read to the end without errors is verified.
+            /// Only the first frame is checked unless the frames are decoded
+            /// [one by one](AnyDecoder::per_member).
+            /// Returns `None` if the input stream is not zstd-compressed.
+            #[cfg(feature = "zstd")]
+            pub fn zstd_checksum(&mut self) -> Result<Option<bool>, Error> {
+                self.detect()?;
+                match self.state {
+                    State::Active(InnerDecoder::Zstd(..)) => Ok(Some(self.zstd_checksum)),
+                    _ => Ok(None),
+                }
+            }
+
             /// Get immutable reference to the underlying reader.
             pub fn get_ref(&self) -> &R {
-                if let Some(r) = self.reader.as_ref() {
-                    return r.get_ref();
-                }
-                match self.inner {
-                    InnerDecoder::Reader(ref r) => r.get_ref(),
+                let inner = match self.state {
+                    State::Pending(ref r) => return r.get_ref(),
+                    State::Active(ref inner) => inner,
+                };
+                match *inner {
+                    InnerDecoder::Reader(ref r, _) => r.get_ref(),
+                    #[cfg(feature = "flate2")]
+                    InnerDecoder::Gz(ref r) => {
+                        crate::buffered_get_ref!($trait, r.get_ref()).get_ref()
+                    }
                     #[cfg(feature = "flate2")]
-                    InnerDecoder::Gz(ref r) => r.get_ref().get_ref(),
+                    InnerDecoder::GzMember(ref r) => {
+                        crate::buffered_get_ref!($trait, r.get_ref()).get_ref()
+                    }
                     #[cfg(feature = "bzip2")]
-                    InnerDecoder::Bz(ref r) => r.get_ref().get_ref(),
+                    InnerDecoder::Bz(ref r) => {
+                        crate::buffered_get_ref!($trait, r.get_ref()).get_ref()
+                    }
                     #[cfg(feature = "flate2")]
-                    InnerDecoder::Zlib(ref r) => r.get_ref().get_ref(),
+                    InnerDecoder::Zlib(ref r) => {
+                        crate::buffered_get_ref!($trait, r.get_ref()).get_ref()
+                    }
+                    #[cfg(feature = "flate2")]
+                    InnerDecoder::ZlibDict(ref r) => {
+                        crate::buffered_get_ref!($trait, r.get_ref()).get_ref()
+                    }
+                    #[cfg(feature = "flate2")]
+                    InnerDecoder::Deflate(ref r) => {
+                        crate::buffered_get_ref!($trait, r.get_ref()).get_ref()
+                    }
                     #[cfg(feature = "xz")]
-                    InnerDecoder::Xz(ref r) => r.get_ref().get_ref(),
+                    InnerDecoder::Xz(ref r) => {
+                        crate::buffered_get_ref!($trait, r.get_ref()).get_ref()
+                    }
                     #[cfg(feature = "zstd")]
-                    InnerDecoder::Zstd(ref r) => crate::zstd_get_ref!($trait, r),
-                    InnerDecoder::Empty(..) => unreachable!(),
+                    InnerDecoder::Zstd(ref r) => {
+                        crate::buffered_get_ref!($trait, r.reader()).get_ref()
+                    }
+                    #[cfg(feature = "zip")]
+                    InnerDecoder::Zip(ref r) => {
+                        crate::buffered_get_ref!($trait, r.get_ref()).get_ref()
+                    }
+                    #[cfg(feature = "mscompress")]
+                    InnerDecoder::Szdd(ref r) => {
+                        crate::buffered_get_ref!($trait, r.get_ref()).get_ref()
+                    }
+                    #[cfg(feature = "mscompress")]
+                    InnerDecoder::Kwaj(ref r) => {
+                        crate::buffered_get_ref!($trait, r.get_ref()).get_ref()
+                    }
+                    #[cfg(feature = "lzop")]
+                    InnerDecoder::Lzop(ref r) => {
+                        crate::buffered_get_ref!($trait, r.get_ref()).get_ref()
+                    }
+                    #[cfg(feature = "lzfse")]
+                    InnerDecoder::Lzfse(ref r) => {
+                        crate::buffered_get_ref!($trait, r.get_ref()).get_ref()
+                    }
+                    #[cfg(feature = "lz4")]
+                    InnerDecoder::Lz4(ref r) => {
+                        crate::buffered_get_ref!($trait, r.get_ref()).get_ref()
+                    }
+                    #[cfg(feature = "brotli")]
+                    InnerDecoder::Brotli(ref r) => {
+                        crate::buffered_get_ref!($trait, r.get_ref()).get_ref()
+                    }
+                    InnerDecoder::Custom(ref r) => {
+                        crate::buffered_get_ref!($trait, r.get_ref()).get_ref()
+                    }
                 }
             }
 
             /// Get mutable reference to the underlying reader.
             pub fn get_mut(&mut self) -> &mut R {
-                if let Some(r) = self.reader.as_mut() {
-                    return r.get_mut();
-                }
-                match self.inner {
-                    InnerDecoder::Reader(ref mut r) => r.get_mut(),
+                let inner = match self.state {
+                    State::Pending(ref mut r) => return r.get_mut(),
+                    State::Active(ref mut inner) => inner,
+                };
+                match *inner {
+                    InnerDecoder::Reader(ref mut r, _) => r.get_mut(),
                     #[cfg(feature = "flate2")]
-                    InnerDecoder::Gz(ref mut r) => r.get_mut().get_mut(),
+                    InnerDecoder::Gz(ref mut r) => {
+                        crate::buffered_get_mut!($trait, r.get_mut()).get_mut()
+                    }
+                    #[cfg(feature = "flate2")]
+                    InnerDecoder::GzMember(ref mut r) => {
+                        crate::buffered_get_mut!($trait, r.get_mut()).get_mut()
+                    }
                     #[cfg(feature = "bzip2")]
-                    InnerDecoder::Bz(ref mut r) => r.get_mut().get_mut(),
+                    InnerDecoder::Bz(ref mut r) => {
+                        crate::buffered_get_mut!($trait, r.get_mut()).get_mut()
+                    }
+                    #[cfg(feature = "flate2")]
+                    InnerDecoder::Zlib(ref mut r) => {
+                        crate::buffered_get_mut!($trait, r.get_mut()).get_mut()
+                    }
                     #[cfg(feature = "flate2")]
-                    InnerDecoder::Zlib(ref mut r) => r.get_mut().get_mut(),
+                    InnerDecoder::ZlibDict(ref mut r) => {
+                        crate::buffered_get_mut!($trait, r.get_mut()).get_mut()
+                    }
+                    #[cfg(feature = "flate2")]
+                    InnerDecoder::Deflate(ref mut r) => {
+                        crate::buffered_get_mut!($trait, r.get_mut()).get_mut()
+                    }
                     #[cfg(feature = "xz")]
-                    InnerDecoder::Xz(ref mut r) => r.get_mut().get_mut(),
+                    InnerDecoder::Xz(ref mut r) => {
+                        crate::buffered_get_mut!($trait, r.get_mut()).get_mut()
+                    }
                     #[cfg(feature = "zstd")]
-                    InnerDecoder::Zstd(ref mut r) => crate::zstd_get_mut!($trait, r),
-                    InnerDecoder::Empty(..) => unreachable!(),
+                    InnerDecoder::Zstd(ref mut r) => {
+                        crate::buffered_get_mut!($trait, r.reader_mut()).get_mut()
+                    }
+                    #[cfg(feature = "zip")]
+                    InnerDecoder::Zip(ref mut r) => {
+                        crate::buffered_get_mut!($trait, r.get_mut()).get_mut()
+                    }
+                    #[cfg(feature = "mscompress")]
+                    InnerDecoder::Szdd(ref mut r) => {
+                        crate::buffered_get_mut!($trait, r.get_mut()).get_mut()
+                    }
+                    #[cfg(feature = "mscompress")]
+                    InnerDecoder::Kwaj(ref mut r) => {
+                        crate::buffered_get_mut!($trait, r.get_mut()).get_mut()
+                    }
+                    #[cfg(feature = "lzop")]
+                    InnerDecoder::Lzop(ref mut r) => {
+                        crate::buffered_get_mut!($trait, r.get_mut()).get_mut()
+                    }
+                    #[cfg(feature = "lzfse")]
+                    InnerDecoder::Lzfse(ref mut r) => {
+                        crate::buffered_get_mut!($trait, r.get_mut()).get_mut()
+                    }
+                    #[cfg(feature = "lz4")]
+                    InnerDecoder::Lz4(ref mut r) => {
+                        crate::buffered_get_mut!($trait, r.get_mut()).get_mut()
+                    }
+                    #[cfg(feature = "brotli")]
+                    InnerDecoder::Brotli(ref mut r) => {
+                        crate::buffered_get_mut!($trait, r.get_mut()).get_mut()
+                    }
+                    InnerDecoder::Custom(ref mut r) => {
+                        crate::buffered_get_mut!($trait, r.get_mut()).get_mut()
+                    }
                 }
             }
 
             /// Return the underlying reader.
-            pub fn into_inner(mut self) -> R {
-                if let Some(r) = self.reader.take() {
-                    return r.into_inner();
-                }
-                match self.inner {
-                    InnerDecoder::Reader(r) => r.into_inner(),
+            pub fn into_inner(self) -> R {
+                let inner = match self.state {
+                    State::Pending(r) => return r.into_inner(),
+                    State::Active(inner) => inner,
+                };
+                match inner {
+                    InnerDecoder::Reader(r, _) => r.into_inner(),
                     #[cfg(feature = "flate2")]
-                    InnerDecoder::Gz(r) => r.into_inner().into_inner(),
+                    InnerDecoder::Gz(r) => {
+                        crate::buffered_into_inner!($trait, r.into_inner()).into_inner()
+                    }
+                    #[cfg(feature = "flate2")]
+                    InnerDecoder::GzMember(r) => {
+                        crate::buffered_into_inner!($trait, r.into_inner()).into_inner()
+                    }
                     #[cfg(feature = "bzip2")]
-                    InnerDecoder::Bz(r) => r.into_inner().into_inner(),
+                    InnerDecoder::Bz(r) => {
+                        crate::buffered_into_inner!($trait, r.into_inner()).into_inner()
+                    }
+                    #[cfg(feature = "flate2")]
+                    InnerDecoder::Zlib(r) => {
+                        crate::buffered_into_inner!($trait, r.into_inner()).into_inner()
+                    }
+                    #[cfg(feature = "flate2")]
+                    InnerDecoder::ZlibDict(r) => {
+                        crate::buffered_into_inner!($trait, r.into_inner()).into_inner()
+                    }
                     #[cfg(feature = "flate2")]
-                    InnerDecoder::Zlib(r) => r.into_inner().into_inner(),
+                    InnerDecoder::Deflate(r) => {
+                        crate::buffered_into_inner!($trait, r.into_inner()).into_inner()
+                    }
                     #[cfg(feature = "xz")]
-                    InnerDecoder::Xz(r) => r.into_inner().into_inner(),
+                    InnerDecoder::Xz(r) => {
+                        crate::buffered_into_inner!($trait, r.into_inner()).into_inner()
+                    }
                     #[cfg(feature = "zstd")]
-                    InnerDecoder::Zstd(r) => crate::zstd_into_inner!($trait, r),
-                    InnerDecoder::Empty(..) => unreachable!(),
+                    InnerDecoder::Zstd(r) => {
+                        crate::buffered_into_inner!($trait, r.into_inner()).into_inner()
+                    }
+                    #[cfg(feature = "zip")]
+                    InnerDecoder::Zip(r) => {
+                        crate::buffered_into_inner!($trait, r.into_inner()).into_inner()
+                    }
+                    #[cfg(feature = "mscompress")]
+                    InnerDecoder::Szdd(r) => {
+                        crate::buffered_into_inner!($trait, r.into_inner()).into_inner()
+                    }
+                    #[cfg(feature = "mscompress")]
+                    InnerDecoder::Kwaj(r) => {
+                        crate::buffered_into_inner!($trait, r.into_inner()).into_inner()
+                    }
+                    #[cfg(feature = "lzop")]
+                    InnerDecoder::Lzop(r) => {
+                        crate::buffered_into_inner!($trait, r.into_inner()).into_inner()
+                    }
+                    #[cfg(feature = "lzfse")]
+                    InnerDecoder::Lzfse(r) => {
+                        crate::buffered_into_inner!($trait, r.into_inner()).into_inner()
+                    }
+                    #[cfg(feature = "lz4")]
+                    InnerDecoder::Lz4(r) => {
+                        crate::buffered_into_inner!($trait, r.into_inner()).into_inner()
+                    }
+                    #[cfg(feature = "brotli")]
+                    InnerDecoder::Brotli(r) => {
+                        crate::buffered_into_inner!($trait, r.into_inner()).into_inner()
+                    }
+                    InnerDecoder::Custom(r) => {
+                        crate::buffered_into_inner!($trait, r.into_inner()).into_inner()
+                    }
                 }
             }
 
+            /// Detect the format and create the decoder, unless it's already done.
             #[inline]
-            fn detect(&mut self) -> Result<(), Error> {
-                if let Some(r) = self.reader.take() {
-                    self.inner = InnerDecoder::new(r, self.fail_on_unknown_format)?;
+            fn detect(&mut self) -> Result<&mut InnerDecoder<'a, MagicReader<R>>, Error> {
+                if let State::Pending(ref mut reader) = self.state {
+                    #[cfg(feature = "tracing")]
+                    let _span = tracing::debug_span!("deko::detect").entered();
+                    #[cfg(feature = "metrics")]
+                    let start = std::time::Instant::now();
+                    self.detection = None;
+                    let result = InnerDecoder::detect(
+                        reader,
+                        &self.options,
+                        #[cfg(feature = "zstd")]
+                        self.zstd_dictionary,
+                        &mut self.gz_fields,
+                        &mut self.detection,
+                    )
+                    .map_err(map_memory_limit_error);
+                    #[cfg(feature = "metrics")]
+                    if result.is_err() {
+                        crate::record_detection_error();
+                    }
+                    #[cfg(feature = "tracing")]
+                    if let Err(ref e) = result {
+                        tracing::debug!(error = %e, "failed to detect format");
+                    }
+                    let detection = result?;
+                    // the buffer is allocated when the reader is moved into the decoder,
+                    // i.e. when the allocation can't fail gracefully
+                    if isize::try_from(self.options.buffer_size).is_err() {
+                        return Err(Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            "the buffer size is too large",
+                        ));
+                    }
+                    self.unknown_format = matches!(detection, DecoderParams::Verbatim(..))
+                        && self.options.format.is_none()
+                        && !reader.read_magic()?.is_empty();
+                    #[cfg(feature = "zstd")]
+                    if let DecoderParams::Zstd {
+                        checksum,
+                        dict_id,
+                        content_size,
+                        ..
+                    } = detection
+                    {
+                        self.zstd_checksum = checksum;
+                        self.zstd_dict_id = dict_id;
+                        self.zstd_content_size = content_size;
+                    }
+                    self.activate(detection);
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(format = ?self.get_kind(), "detected format");
+                    #[cfg(feature = "metrics")]
+                    {
+                        self.metrics = Some(crate::DecoderMetrics::new(self.get_kind(), start));
+                    }
                 }
-                Ok(())
+                match self.state {
+                    State::Active(ref mut inner) => Ok(inner),
+                    // the reader is moved into the decoder above
+                    State::Pending(..) => self.detect(),
+                }
+            }
+
+            /// Move the reader into the decoder for the detected format.
+            fn activate(&mut self, detection: DecoderParams<'a>) {
+                let buffer_size = self.options.buffer_size;
+                replace_with(&mut self.state, |state| match state {
+                    State::Pending(reader) => {
+                        State::Active(InnerDecoder::new(reader, detection, buffer_size))
+                    }
+                    state => state,
+                });
+            }
+
+            #[inline]
+            fn do_read<F>(&mut self, f: F) -> Result<usize, Error>
+            where
+                F: FnOnce(&mut InnerDecoder<'a, MagicReader<R>>) -> Result<usize, Error>,
+            {
+                let inner = self.detect()?;
+                #[cfg(feature = "metrics")]
+                let start = std::time::Instant::now();
+                let result = f(inner).map_err(map_memory_limit_error);
+                #[cfg(feature = "metrics")]
+                if let Some(metrics) = self.metrics.as_ref() {
+                    metrics.record(start, &result);
+                }
+                #[cfg(feature = "tracing")]
+                match result {
+                    Ok(0) => tracing::debug!(
+                        format = ?self.get_kind(),
+                        decoded_bytes = self.decoded_bytes,
+                        "finished decoding"
+                    ),
+                    Ok(n) => self.decoded_bytes += n as u64,
+                    Err(ref e) => tracing::debug!(
+                        format = ?self.get_kind(),
+                        decoded_bytes = self.decoded_bytes,
+                        error = %e,
+                        "failed to decode"
+                    ),
+                }
+                result
+            }
+        }
+
+        impl<R: $trait + Send + 'static> AnyDecoder<'static, R> {
+            /// Create new decoder from the supplied `reader` and erase its type.
+            ///
+            /// Boxed decoders for different sources (files, sockets, in-memory buffers)
+            /// have the same type and can be stored in one collection.
+            /// Alternatively, box the source itself (see [BoxedAnyDecoder])
+            /// to keep access to the decoder options.
+            pub fn boxed(reader: R) -> Box<dyn Read + Send> {
+                Box::new(Self::new(reader))
             }
         }
 
-        impl<R: $trait> Read for AnyDecoder<R> {
+        /// A decoder of boxed sources.
+        ///
+        /// The decoder has no generic parameters, is [Send] and `'static`,
+        /// i.e. it can be stored in heterogeneous collections and passed across threads.
+        pub type BoxedAnyDecoder = AnyDecoder<'static, Box<dyn $trait + Send>>;
+
+        impl BoxedAnyDecoder {
+            /// Create new decoder from the supplied boxed `reader`.
+            pub fn new_boxed(reader: Box<dyn $trait + Send>) -> Self {
+                Self::new(reader)
+            }
+        }
+
+        impl<'a, R: $trait> Read for AnyDecoder<'a, R> {
             fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
-                self.detect()?;
-                dispatch_mut!(self.inner, Read::read, buf)
+                self.do_read(|inner| dispatch_mut!(*inner, Read::read, buf))
             }
 
             fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> Result<usize, Error> {
-                self.detect()?;
-                dispatch_mut!(self.inner, Read::read_vectored, bufs)
+                self.do_read(|inner| dispatch_mut!(*inner, Read::read_vectored, bufs))
             }
 
             #[cfg(feature = "nightly")]
             fn is_read_vectored(&self) -> bool {
-                dispatch!(self.inner, Read::is_read_vectored)
+                match self.state {
+                    State::Pending(ref r) => r.is_read_vectored(),
+                    State::Active(ref inner) => dispatch!(*inner, Read::is_read_vectored),
+                }
             }
 
             fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize, Error> {
-                self.detect()?;
-                dispatch_mut!(self.inner, Read::read_to_end, buf)
+                self.do_read(|inner| dispatch_mut!(*inner, Read::read_to_end, buf))
             }
 
             fn read_to_string(&mut self, buf: &mut String) -> Result<usize, Error> {
-                self.detect()?;
-                dispatch_mut!(self.inner, Read::read_to_string, buf)
+                self.do_read(|inner| dispatch_mut!(*inner, Read::read_to_string, buf))
             }
 
             fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
-                self.detect()?;
-                dispatch_mut!(self.inner, Read::read_exact, buf)
+                let n = buf.len();
+                self.do_read(|inner| dispatch_mut!(*inner, Read::read_exact, buf).map(|_| n))?;
+                Ok(())
             }
 
             #[cfg(feature = "nightly")]
             fn read_buf(&mut self, buf: BorrowedCursor<'_>) -> Result<(), Error> {
-                self.detect()?;
-                dispatch_mut!(self.inner, Read::read_buf, buf)
+                let inner = self.detect()?;
+                dispatch_mut!(*inner, Read::read_buf, buf).map_err(map_memory_limit_error)
             }
 
             #[cfg(feature = "nightly")]
             fn read_buf_exact(&mut self, buf: BorrowedCursor<'_>) -> Result<(), Error> {
+                let inner = self.detect()?;
+                dispatch_mut!(*inner, Read::read_buf_exact, buf).map_err(map_memory_limit_error)
+            }
+        }
+
+        impl<R: $trait> crate::Decode for AnyDecoder<'_, R> {
+            type Reader = R;
+
+            fn kind(&mut self) -> Result<Format, Error> {
+                AnyDecoder::kind(self)
+            }
+
+            fn get_ref(&self) -> &R {
+                AnyDecoder::get_ref(self)
+            }
+
+            fn get_mut(&mut self) -> &mut R {
+                AnyDecoder::get_mut(self)
+            }
+
+            fn into_inner(self) -> R {
+                AnyDecoder::into_inner(self)
+            }
+        }
+
+        #[cfg(feature = "flate2")]
+        impl<R: $trait> crate::GzDecoderExt for AnyDecoder<'_, R> {
+            fn gz_header(&mut self) -> Result<Option<&flate2::GzHeader>, Error> {
+                match self.detect()? {
+                    InnerDecoder::Gz(ref r) => Ok(r.header()),
+                    InnerDecoder::GzMember(ref r) => Ok(r.header()),
+                    _ => Ok(None),
+                }
+            }
+        }
+
+        #[cfg(feature = "flate2")]
+        impl<R: $trait> crate::ZlibDecoderExt for AnyDecoder<'_, R> {
+            fn zlib_dict_id(&mut self) -> Result<Option<u32>, Error> {
+                match self.detect()? {
+                    InnerDecoder::ZlibDict(ref r) => Ok(Some(r.dict_id())),
+                    _ => Ok(None),
+                }
+            }
+        }
+
+        #[cfg(feature = "zstd")]
+        impl<R: $trait> crate::ZstdDecoderExt for AnyDecoder<'_, R> {
+            fn dict_id(&mut self) -> Result<Option<u32>, Error> {
                 self.detect()?;
-                dispatch_mut!(self.inner, Read::read_buf_exact, buf)
+                match self.state {
+                    State::Active(InnerDecoder::Zstd(..)) => Ok(self.zstd_dict_id),
+                    _ => Ok(None),
+                }
+            }
+
+            fn content_size(&mut self) -> Result<Option<u64>, Error> {
+                self.detect()?;
+                match self.state {
+                    State::Active(InnerDecoder::Zstd(..)) => Ok(self.zstd_content_size),
+                    _ => Ok(None),
+                }
             }
         }
 
@@ -195,18 +1004,38 @@ pub(crate) use define_decoder;
 macro_rules! dispatch_mut {
     ($inner:expr, $method:expr $(,$args:ident)*) => {
         match $inner {
-            InnerDecoder::Reader(ref mut r) => $method(r, $($args),*),
+            InnerDecoder::Reader(ref mut r, _) => $method(r, $($args),*),
             #[cfg(feature = "flate2")]
             InnerDecoder::Gz(ref mut r) => $method(r, $($args),*),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::GzMember(ref mut r) => $method(r, $($args),*),
             #[cfg(feature = "bzip2")]
             InnerDecoder::Bz(ref mut r) => $method(r, $($args),*),
             #[cfg(feature = "flate2")]
             InnerDecoder::Zlib(ref mut r) => $method(r, $($args),*),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::ZlibDict(ref mut r) => $method(r, $($args),*),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::Deflate(ref mut r) => $method(r, $($args),*),
             #[cfg(feature = "xz")]
             InnerDecoder::Xz(ref mut r) => $method(r, $($args),*),
             #[cfg(feature = "zstd")]
             InnerDecoder::Zstd(ref mut r) => $method(r, $($args),*),
-            InnerDecoder::Empty(ref mut r) => $method(r, $($args),*),
+            #[cfg(feature = "zip")]
+            InnerDecoder::Zip(ref mut r) => $method(r, $($args),*),
+            #[cfg(feature = "mscompress")]
+            InnerDecoder::Szdd(ref mut r) => $method(r, $($args),*),
+            #[cfg(feature = "mscompress")]
+            InnerDecoder::Kwaj(ref mut r) => $method(r, $($args),*),
+            #[cfg(feature = "lzop")]
+            InnerDecoder::Lzop(ref mut r) => $method(r, $($args),*),
+            #[cfg(feature = "lzfse")]
+            InnerDecoder::Lzfse(ref mut r) => $method(r, $($args),*),
+            #[cfg(feature = "lz4")]
+            InnerDecoder::Lz4(ref mut r) => $method(r, $($args),*),
+            #[cfg(feature = "brotli")]
+            InnerDecoder::Brotli(ref mut r) => $method(r, $($args),*),
+            InnerDecoder::Custom(ref mut r) => $method(r, $($args),*),
         }
     }
 }
@@ -217,18 +1046,38 @@ pub(crate) use dispatch_mut;
 macro_rules! dispatch {
     ($inner:expr, $method:expr $(,$args:ident)*) => {
         match $inner {
-            InnerDecoder::Reader(ref r) => $method(r, $($args),*),
+            InnerDecoder::Reader(ref r, _) => $method(r, $($args),*),
             #[cfg(feature = "flate2")]
             InnerDecoder::Gz(ref r) => $method(r, $($args),*),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::GzMember(ref r) => $method(r, $($args),*),
             #[cfg(feature = "bzip2")]
             InnerDecoder::Bz(ref r) => $method(r, $($args),*),
             #[cfg(feature = "flate2")]
             InnerDecoder::Zlib(ref r) => $method(r, $($args),*),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::ZlibDict(ref r) => $method(r, $($args),*),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::Deflate(ref r) => $method(r, $($args),*),
             #[cfg(feature = "xz")]
             InnerDecoder::Xz(ref r) => $method(r, $($args),*),
             #[cfg(feature = "zstd")]
             InnerDecoder::Zstd(ref r) => $method(r, $($args),*),
-            InnerDecoder::Empty(ref r) => $method(r, $($args),*),
+            #[cfg(feature = "zip")]
+            InnerDecoder::Zip(ref r) => $method(r, $($args),*),
+            #[cfg(feature = "mscompress")]
+            InnerDecoder::Szdd(ref r) => $method(r, $($args),*),
+            #[cfg(feature = "mscompress")]
+            InnerDecoder::Kwaj(ref r) => $method(r, $($args),*),
+            #[cfg(feature = "lzop")]
+            InnerDecoder::Lzop(ref r) => $method(r, $($args),*),
+            #[cfg(feature = "lzfse")]
+            InnerDecoder::Lzfse(ref r) => $method(r, $($args),*),
+            #[cfg(feature = "lz4")]
+            InnerDecoder::Lz4(ref r) => $method(r, $($args),*),
+            #[cfg(feature = "brotli")]
+            InnerDecoder::Brotli(ref r) => $method(r, $($args),*),
+            InnerDecoder::Custom(ref r) => $method(r, $($args),*),
         }
     }
 }
@@ -0,0 +1,166 @@
+macro_rules! define_decoder_builder {
+    ($trait: ident) => {
+        /// A builder that configures [AnyDecoder] via chained calls.
+        ///
+        /// Each method has the same meaning as the corresponding [AnyDecoder] setter.
+        ///
+        /// ```rust
+        /// # #[cfg(feature = "flate2")] {
+        /// use deko::bufread::AnyDecoder;
+        /// use deko::Format;
+        ///
+        /// let reader = AnyDecoder::builder(&b"hello"[..])
+        ///     .fail_on_unknown_format(true)
+        ///     .memory_limit(64 * 1024 * 1024)
+        ///     .detection_order(&[Format::Gz, Format::Zlib])
+        ///     .build();
+        /// # }
+        /// ```
+        pub struct DecoderBuilder<'a, R: $trait> {
+            decoder: AnyDecoder<'a, R>,
+        }
+
+        impl<'a, R: $trait> AnyDecoder<'a, R> {
+            /// Start configuring new decoder for the supplied `reader`.
+            pub fn builder(reader: R) -> DecoderBuilder<'a, R> {
+                DecoderBuilder {
+                    decoder: Self::new(reader),
+                }
+            }
+        }
+
+        impl<'a, R: $trait> DecoderBuilder<'a, R> {
+            /// Create the configured decoder.
+            pub fn build(self) -> AnyDecoder<'a, R> {
+                self.decoder
+            }
+
+            /// Decode the stream using the `format` instead of detecting it,
+            /// see [with_format](AnyDecoder::with_format).
+            pub fn format(mut self, format: Format) -> Self {
+                self.decoder.options.format = Some(format);
+                self
+            }
+
+            /// See [fail_on_unknown_format](AnyDecoder::fail_on_unknown_format).
+            pub fn fail_on_unknown_format(mut self, value: bool) -> Self {
+                self.decoder.fail_on_unknown_format(value);
+                self
+            }
+
+            /// See [compressed_size](AnyDecoder::compressed_size).
+            pub fn compressed_size(mut self, size: u64) -> Self {
+                self.decoder.compressed_size(size);
+                self
+            }
+
+            /// See [memory_limit](AnyDecoder::memory_limit).
+            pub fn memory_limit(mut self, bytes: u64) -> Self {
+                self.decoder.memory_limit(bytes);
+                self
+            }
+
+            /// See [hardened](AnyDecoder::hardened).
+            pub fn hardened(mut self, limits: crate::DecoderLimits) -> Self {
+                self.decoder.hardened(limits);
+                self
+            }
+
+            /// See [bzip2_small_mode](AnyDecoder::bzip2_small_mode).
+            #[cfg(feature = "bzip2")]
+            pub fn bzip2_small_mode(mut self, value: bool) -> Self {
+                self.decoder.bzip2_small_mode(value);
+                self
+            }
+
+            /// See [per_member](AnyDecoder::per_member).
+            pub fn per_member(mut self, value: bool) -> Self {
+                self.decoder.per_member(value);
+                self
+            }
+
+            /// See [single_member](AnyDecoder::single_member).
+            pub fn single_member(mut self, value: bool) -> Self {
+                self.decoder.single_member(value);
+                self
+            }
+
+            /// See [zlib_heuristic](AnyDecoder::zlib_heuristic).
+            #[cfg(feature = "flate2")]
+            pub fn zlib_heuristic(mut self, value: bool) -> Self {
+                self.decoder.zlib_heuristic(value);
+                self
+            }
+
+            /// See [require_gz_header_crc](AnyDecoder::require_gz_header_crc).
+            #[cfg(feature = "flate2")]
+            pub fn require_gz_header_crc(mut self, value: bool) -> Self {
+                self.decoder.require_gz_header_crc(value);
+                self
+            }
+
+            /// See [require_zstd_checksum](AnyDecoder::require_zstd_checksum).
+            #[cfg(feature = "zstd")]
+            pub fn require_zstd_checksum(mut self, value: bool) -> Self {
+                self.decoder.require_zstd_checksum(value);
+                self
+            }
+
+            /// See [zstd_single_frame](AnyDecoder::zstd_single_frame).
+            #[cfg(feature = "zstd")]
+            pub fn zstd_single_frame(mut self, value: bool) -> Self {
+                self.decoder.zstd_single_frame(value);
+                self
+            }
+
+            /// See [scan_for_magic](AnyDecoder::scan_for_magic).
+            pub fn scan_for_magic(mut self, window: usize) -> Self {
+                self.decoder.scan_for_magic(window);
+                self
+            }
+
+            /// See [trial_decode](AnyDecoder::trial_decode).
+            pub fn trial_decode(mut self, value: bool) -> Self {
+                self.decoder.trial_decode(value);
+                self
+            }
+
+            /// See [detection_order](AnyDecoder::detection_order).
+            pub fn detection_order(mut self, formats: &[Format]) -> Self {
+                self.decoder.detection_order(formats);
+                self
+            }
+
+            /// See [allowed_formats](AnyDecoder::allowed_formats).
+            pub fn allowed_formats(mut self, formats: &[Format]) -> Self {
+                self.decoder.allowed_formats(formats);
+                self
+            }
+
+            /// See [custom_format](AnyDecoder::custom_format).
+            pub fn custom_format(mut self, format: crate::CustomFormat) -> Self {
+                self.decoder.custom_format(format);
+                self
+            }
+
+            /// See [zstd_dictionary](AnyDecoder::zstd_dictionary).
+            #[cfg(feature = "zstd")]
+            pub fn zstd_dictionary(
+                mut self,
+                dictionary: &'a zstd::dict::DecoderDictionary<'a>,
+            ) -> Self {
+                self.decoder.zstd_dictionary(dictionary);
+                self
+            }
+
+            /// See [zlib_dictionary](AnyDecoder::zlib_dictionary).
+            #[cfg(feature = "flate2")]
+            pub fn zlib_dictionary(mut self, dictionary: &[u8]) -> Self {
+                self.decoder.zlib_dictionary(dictionary);
+                self
+            }
+        }
+    };
+}
+
+pub(crate) use define_decoder_builder;
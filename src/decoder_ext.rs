@@ -0,0 +1,68 @@
+use std::io::Error;
+
+/// Gzip-specific information about the decoded stream.
+///
+/// The trait is implemented for [AnyDecoder](crate::AnyDecoder),
+/// [bufread::AnyDecoder](crate::bufread::AnyDecoder) and
+/// [read::AnyDecoder](crate::read::AnyDecoder).
+#[cfg(feature = "flate2")]
+pub trait GzDecoderExt {
+    /// Get gzip header of the current member.
+    ///
+    /// The header includes the file name, the comment, the modification time and the extra field.
+    /// Returns `None` if the input stream is not gzip-compressed or the header is invalid.
+    fn gz_header(&mut self) -> Result<Option<&flate2::GzHeader>, Error>;
+
+    /// Check whether the current member is a BGZF (`bgzip`) block.
+    ///
+    /// BGZF blocks are ordinary gzip members that store the block size in the extra field;
+    /// use [BgzfReader](crate::BgzfReader) to access such files via virtual offsets.
+    fn is_bgzf(&mut self) -> Result<bool, Error> {
+        Ok(self
+            .gz_header()?
+            .and_then(|header| header.extra())
+            .is_some_and(|extra| crate::bgzf_block_size(extra).is_some()))
+    }
+}
+
+/// Zlib-specific information about the decoded stream.
+///
+/// The trait is implemented for [AnyDecoder](crate::AnyDecoder),
+/// [bufread::AnyDecoder](crate::bufread::AnyDecoder) and
+/// [read::AnyDecoder](crate::read::AnyDecoder).
+#[cfg(feature = "flate2")]
+pub trait ZlibDecoderExt {
+    /// Get the ID of the preset dictionary that is needed to decompress the zlib stream.
+    ///
+    /// The ID is Adler-32 of the dictionary;
+    /// supply the dictionary via [zlib_dictionary](crate::bufread::AnyDecoder::zlib_dictionary).
+    /// Returns `None` if the input stream is not zlib-compressed or
+    /// if it was compressed without a preset dictionary.
+    fn zlib_dict_id(&mut self) -> Result<Option<u32>, Error>;
+}
+
+/// Zstd-specific information about the decoded stream.
+///
+/// The trait is implemented for [AnyDecoder](crate::AnyDecoder),
+/// [bufread::AnyDecoder](crate::bufread::AnyDecoder) and
+/// [read::AnyDecoder](crate::read::AnyDecoder).
+#[cfg(feature = "zstd")]
+pub trait ZstdDecoderExt {
+    /// Get the ID of the dictionary that is needed to decompress the current zstd frame.
+    ///
+    /// Only the first frame is checked unless the frames are decoded
+    /// [one by one](crate::bufread::AnyDecoder::per_member).
+    /// Returns `None` if the input stream is not zstd-compressed or
+    /// if the frame header doesn't store the ID.
+    fn dict_id(&mut self) -> Result<Option<u32>, Error>;
+
+    /// Get the size of the decompressed data of the current zstd frame.
+    ///
+    /// The size is stored in the frame header by most encoders
+    /// when the input size is known in advance.
+    /// Only the first frame is checked unless the frames are decoded
+    /// [one by one](crate::bufread::AnyDecoder::per_member).
+    /// Returns `None` if the input stream is not zstd-compressed or
+    /// if the frame header doesn't store the size.
+    fn content_size(&mut self) -> Result<Option<u64>, Error>;
+}
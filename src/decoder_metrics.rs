@@ -0,0 +1,83 @@
+use std::io::Error;
+use std::time::Instant;
+
+use metrics::counter;
+use metrics::histogram;
+use metrics::Counter;
+use metrics::Histogram;
+
+use crate::Format;
+
+/// Metric handles of a single decoder.
+///
+/// The handles are registered once when the format is detected.
+pub(crate) struct DecoderMetrics {
+    decoded_bytes: Counter,
+    errors: Counter,
+    read_duration: Histogram,
+}
+
+impl DecoderMetrics {
+    /// Record detection outcome and register per-format metrics.
+    pub(crate) fn new(format: Format, start: Instant) -> Self {
+        let label = format_label(format);
+        counter!("deko_detected_total", "format" => label).increment(1);
+        histogram!("deko_detection_duration_seconds", "format" => label).record(start.elapsed());
+        Self {
+            decoded_bytes: counter!("deko_decoded_bytes_total", "format" => label),
+            errors: counter!("deko_decode_errors_total", "format" => label),
+            read_duration: histogram!("deko_read_duration_seconds", "format" => label),
+        }
+    }
+
+    pub(crate) fn record(&self, start: Instant, result: &Result<usize, Error>) {
+        self.read_duration.record(start.elapsed());
+        match result {
+            Ok(n) => self.decoded_bytes.increment(*n as u64),
+            Err(..) => self.errors.increment(1),
+        }
+    }
+}
+
+/// Record failed detection.
+pub(crate) fn record_detection_error() {
+    counter!("deko_detection_errors_total").increment(1);
+}
+
+/// Returns the counter of the bytes read from the underlying reader.
+pub(crate) fn compressed_bytes_counter() -> Counter {
+    counter!("deko_compressed_bytes_total")
+}
+
+fn format_label(format: Format) -> &'static str {
+    match format {
+        Format::Verbatim => "verbatim",
+        #[cfg(feature = "flate2")]
+        Format::Gz => "gz",
+        #[cfg(feature = "bzip2")]
+        Format::Bz => "bz",
+        #[cfg(feature = "flate2")]
+        Format::Zlib => "zlib",
+        #[cfg(feature = "flate2")]
+        Format::Deflate => "deflate",
+        #[cfg(feature = "xz")]
+        Format::Xz => "xz",
+        #[cfg(feature = "zstd")]
+        Format::Zstd => "zstd",
+        #[cfg(feature = "zip")]
+        Format::Zip => "zip",
+        #[cfg(feature = "mscompress")]
+        Format::Szdd => "szdd",
+        #[cfg(feature = "mscompress")]
+        Format::Kwaj => "kwaj",
+        #[cfg(feature = "lzop")]
+        Format::Lzop => "lzop",
+        #[cfg(feature = "lzfse")]
+        Format::Lzfse => "lzfse",
+        #[cfg(feature = "lz4")]
+        Format::Lz4 => "lz4",
+        #[cfg(feature = "brotli")]
+        Format::Brotli => "brotli",
+        Format::Other(name) => name,
+    }
+}
@@ -0,0 +1,54 @@
+use std::borrow::Cow;
+use std::io::Error;
+use std::io::Read;
+
+use crate::bufread::AnyDecoder;
+use crate::Format;
+
+/// Decompress the supplied `input` in one go.
+///
+/// Returns the detected format and the decompressed data.
+/// If the input is not compressed, the data is borrowed from `input` without copying.
+pub fn decompress_cow(input: &[u8]) -> Result<(Format, Cow<'_, [u8]>), Error> {
+    let mut decoder = AnyDecoder::from_slice(input);
+    let format = decoder.kind()?;
+    if format == Format::Verbatim {
+        return Ok((format, Cow::Borrowed(input)));
+    }
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output)?;
+    Ok((format, Cow::Owned(output)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::write::AnyEncoder;
+    use crate::write::Compression;
+
+    #[test]
+    fn test_decompress_cow() {
+        arbtest(|u| {
+            // verbatim data might be detected as some other format
+            let format = *u.choose(Format::DETECTION_ORDER)?;
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut writer = AnyEncoder::new(Vec::new(), format, Compression::Fast).unwrap();
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            let (actual_format, actual) = decompress_cow(&compressed).unwrap();
+            assert_eq!(expected, actual.as_ref());
+            match actual {
+                Cow::Borrowed(..) => assert_eq!(Format::Verbatim, actual_format),
+                Cow::Owned(..) => assert_eq!(format, actual_format),
+            }
+            Ok(())
+        });
+        let (format, actual) = decompress_cow(b"hello world").unwrap();
+        assert_eq!(Format::Verbatim, format);
+        assert!(matches!(actual, Cow::Borrowed(b"hello world")));
+    }
+}
@@ -0,0 +1,225 @@
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Write;
+
+use crate::Format;
+
+/// A decompressor that doesn't do any I/O: the caller supplies input and output buffers.
+///
+/// The format is detected from the magic bytes of the input the same way as
+/// [write::AnyDecoder](crate::write::AnyDecoder) does, and the same formats are supported.
+/// Until the magic bytes are complete the input is consumed but nothing is produced,
+/// and [format](Decompressor::format) returns `None`.
+///
+/// ```rust
+/// use deko::Decompressor;
+/// use deko::Status;
+///
+/// let mut decompressor = Decompressor::new();
+/// let mut output = [0_u8; 64];
+/// let status = decompressor.decompress(b"Hello world", &mut output, true).unwrap();
+/// assert_eq!(Status::StreamEnd, status);
+/// assert_eq!(b"Hello world", &output[..decompressor.total_out() as usize]);
+/// ```
+pub struct Decompressor {
+    // `None` when the stream is finished
+    decoder: Option<crate::write::AnyDecoder<Vec<u8>>>,
+    format: Option<Format>,
+    // the output that didn't fit into the caller's buffer
+    pending: Vec<u8>,
+    pos: usize,
+    total_in: u64,
+    total_out: u64,
+}
+
+impl Decompressor {
+    /// Create new decompressor.
+    pub fn new() -> Self {
+        Self {
+            decoder: Some(crate::write::AnyDecoder::new(Vec::new())),
+            format: None,
+            pending: Vec::new(),
+            pos: 0,
+            total_in: 0,
+            total_out: 0,
+        }
+    }
+
+    /// Throw an error when the decompressor fails to detect compression format.
+    ///
+    /// By default no error is thrown, and the data is passed through verbatim.
+    pub fn fail_on_unknown_format(&mut self, value: bool) {
+        if let Some(decoder) = self.decoder.as_mut() {
+            decoder.fail_on_unknown_format(value);
+        }
+    }
+
+    /// Get the input stream format.
+    ///
+    /// Returns `None` until the format is detected.
+    pub fn format(&self) -> Option<Format> {
+        self.format
+    }
+
+    /// The total number of input bytes consumed so far.
+    pub fn total_in(&self) -> u64 {
+        self.total_in
+    }
+
+    /// The total number of output bytes produced so far.
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    /// Decompress `input` into `output`.
+    ///
+    /// The number of consumed and produced bytes is the difference of
+    /// [total_in](Decompressor::total_in) and [total_out](Decompressor::total_out)
+    /// before and after the call.
+    /// The input that wasn't consumed should be supplied again in the next call.
+    /// Set `finish` when `input` is the last chunk of the stream;
+    /// then call the method until it returns [Status::StreamEnd].
+    /// Incomplete compressed stream is reported as an error.
+    /// Any error is final, i.e. the decompressor can't be used afterwards.
+    pub fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        finish: bool,
+    ) -> Result<Status, Error> {
+        let mut consumed = 0;
+        let mut produced = 0;
+        let result = loop {
+            let n = (output.len() - produced).min(self.pending.len() - self.pos);
+            output[produced..produced + n].copy_from_slice(&self.pending[self.pos..self.pos + n]);
+            produced += n;
+            self.pos += n;
+            if self.pos != self.pending.len() {
+                break Ok(Status::NeedOutput);
+            }
+            self.pending.clear();
+            self.pos = 0;
+            let Some(decoder) = self.decoder.as_mut() else {
+                break Ok(Status::StreamEnd);
+            };
+            if consumed != input.len() {
+                let end = input.len().min(consumed + INPUT_CHUNK_LEN);
+                match decoder.write(&input[consumed..end]) {
+                    Ok(0) => {
+                        break Err(Error::new(
+                            ErrorKind::InvalidData,
+                            "trailing data after the end of the compressed stream",
+                        ))
+                    }
+                    Ok(n) => consumed += n,
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => break Err(e),
+                }
+                self.format = decoder.format();
+                // reuse the buffer
+                std::mem::swap(&mut self.pending, decoder.get_mut());
+                continue;
+            }
+            if !finish {
+                break Ok(Status::NeedInput);
+            }
+            if let Some(mut decoder) = self.decoder.take() {
+                // short streams are detected at the end
+                if let Err(e) = decoder.detect() {
+                    break Err(e);
+                }
+                self.format = decoder.format();
+                match decoder.finish() {
+                    Ok(pending) => self.pending = pending,
+                    Err(e) => break Err(e),
+                }
+            }
+        };
+        self.total_in += consumed as u64;
+        self.total_out += produced as u64;
+        result
+    }
+}
+
+impl Default for Decompressor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The state of [Decompressor] or [Compressor](crate::Compressor) after a call.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Status {
+    /// All the input has been consumed; supply more input.
+    NeedInput,
+    /// The output buffer is full; supply more output space.
+    NeedOutput,
+    /// The stream is finished, and all the output has been produced.
+    StreamEnd,
+}
+
+/// The maximum number of input bytes that are decompressed at once.
+///
+/// The output of this chunk is buffered until it is copied into the caller's buffers.
+const INPUT_CHUNK_LEN: usize = 4096;
+
+#[cfg(test)]
+mod tests {
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::write::AnyEncoder;
+    use crate::write::Compression;
+
+    #[test]
+    fn decompress() {
+        arbtest(|u| {
+            let format = *u.choose(&[
+                Format::Verbatim,
+                #[cfg(feature = "flate2")]
+                Format::Gz,
+                #[cfg(feature = "bzip2")]
+                Format::Bz,
+                #[cfg(feature = "flate2")]
+                Format::Zlib,
+                #[cfg(feature = "xz")]
+                Format::Xz,
+                #[cfg(feature = "zstd")]
+                Format::Zstd,
+            ])?;
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut writer = AnyEncoder::new(Vec::new(), format, Compression::Fast).unwrap();
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            // verbatim data might be detected as some other format
+            if format == Format::Verbatim
+                && crate::detect_format_in(&compressed, Format::ALL).is_some()
+            {
+                return Ok(());
+            }
+            let mut decompressor = Decompressor::new();
+            let mut actual = Vec::new();
+            let mut input = &compressed[..];
+            loop {
+                let len = u.int_in_range(input.len().min(1)..=input.len())?;
+                let mut output = vec![0_u8; u.int_in_range(1..=4096)?];
+                let finish = len == input.len();
+                let total_in = decompressor.total_in();
+                let total_out = decompressor.total_out();
+                let status = decompressor
+                    .decompress(&input[..len], &mut output, finish)
+                    .unwrap();
+                input = &input[(decompressor.total_in() - total_in) as usize..];
+                actual
+                    .extend_from_slice(&output[..(decompressor.total_out() - total_out) as usize]);
+                if status == Status::StreamEnd {
+                    break;
+                }
+            }
+            assert!(input.is_empty());
+            assert_eq!(expected, actual);
+            assert_eq!(Some(format), decompressor.format());
+            Ok(())
+        });
+    }
+}
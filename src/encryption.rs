@@ -0,0 +1,91 @@
+/// Check whether `bytes` look like the start of encrypted data.
+///
+/// Returns the name of the encryption format.
+/// Binary OpenPGP messages are detected heuristically by the first packet.
+pub(crate) fn detect_encryption(bytes: &[u8]) -> Option<&'static str> {
+    // https://age-encryption.org/v1
+    if bytes.starts_with(b"age-encryption.org/")
+        || bytes.starts_with(b"-----BEGIN AGE ENCRYPTED FILE-----")
+    {
+        return Some("age");
+    }
+    // `openssl enc` with salt
+    if bytes.starts_with(b"Salted__") {
+        return Some("openssl");
+    }
+    if bytes.starts_with(b"-----BEGIN PGP MESSAGE-----") || is_openpgp_session_key(bytes) {
+        return Some("openpgp");
+    }
+    None
+}
+
+/// Check whether the first OpenPGP packet is a public-key or symmetric-key encrypted session key.
+///
+/// Encrypted messages start with such a packet (RFC 9580, section 10.3).
+fn is_openpgp_session_key(bytes: &[u8]) -> bool {
+    // RFC 9580, section 4.2
+    let (tag, header_len) = match bytes {
+        // OpenPGP format
+        [first, second, ..] if first & 0xc0 == 0xc0 => {
+            let len_len = match second {
+                192..=223 => 2,
+                255 => 5,
+                _ => 1,
+            };
+            (first & 0x3f, 1 + len_len)
+        }
+        // legacy format
+        [first, ..] if first & 0x80 != 0 => {
+            let len_len = match first & 3 {
+                0 => 1,
+                1 => 2,
+                2 => 4,
+                _ => 0,
+            };
+            ((first >> 2) & 0x0f, 1 + len_len)
+        }
+        _ => return false,
+    };
+    matches!(
+        (tag, bytes.get(header_len)),
+        (OPENPGP_PKESK, Some(3 | 6)) | (OPENPGP_SKESK, Some(4..=6))
+    )
+}
+
+const OPENPGP_PKESK: u8 = 1;
+const OPENPGP_SKESK: u8 = 3;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encryption() {
+        assert_eq!(
+            Some("age"),
+            detect_encryption(b"age-encryption.org/v1\n-> X25519 ")
+        );
+        assert_eq!(Some("openssl"), detect_encryption(b"Salted__12345678"));
+        assert_eq!(
+            Some("openpgp"),
+            detect_encryption(b"-----BEGIN PGP MESSAGE-----\n")
+        );
+        // `gpg --symmetric`
+        assert_eq!(
+            Some("openpgp"),
+            detect_encryption(&[0x8c, 0x0d, 0x04, 0x07, 0x03, 0x02])
+        );
+        assert_eq!(
+            Some("openpgp"),
+            detect_encryption(&[0xc3, 0x0d, 0x04, 0x07, 0x03, 0x02])
+        );
+        // `gpg --encrypt`
+        assert_eq!(
+            Some("openpgp"),
+            detect_encryption(&[0x85, 0x01, 0x0c, 0x03, 0x12, 0x34])
+        );
+        assert_eq!(None, detect_encryption(&[0x8c, 0x0d, 0x01]));
+        assert_eq!(None, detect_encryption(b"hello world"));
+        assert_eq!(None, detect_encryption(b""));
+    }
+}
@@ -0,0 +1,112 @@
+use crate::bufread::AnyDecoder;
+use crate::Format;
+
+/// Check whether the data is already compressed and is not worth compressing again.
+///
+/// The check combines magic bytes detection (supported compression formats as well as common
+/// archive, image, audio and video formats) with byte entropy estimation.
+/// Only the first 64 KiB of the data are examined.
+pub fn is_probably_compressed(data: &[u8]) -> bool {
+    has_compressed_magic(data) || high_entropy(&data[..data.len().min(MAX_SAMPLE_LEN)])
+}
+
+fn has_compressed_magic(data: &[u8]) -> bool {
+    let format = AnyDecoder::new(data).kind().unwrap_or(Format::Verbatim);
+    match format {
+        Format::Verbatim => {}
+        // zlib signature is only two bytes long and is too weak
+        #[cfg(feature = "flate2")]
+        Format::Zlib => {}
+        #[allow(unreachable_patterns)]
+        _ => return true,
+    }
+    MEDIA_MAGIC.iter().any(|(offset, magic)| {
+        data.get(*offset..(offset + magic.len()))
+            .is_some_and(|bytes| bytes == *magic)
+    })
+}
+
+fn high_entropy(data: &[u8]) -> bool {
+    if data.len() < MIN_SAMPLE_LEN {
+        return false;
+    }
+    let mut counts = [0_u32; 256];
+    for byte in data {
+        counts[*byte as usize] += 1;
+    }
+    let len = data.len() as f64;
+    let entropy: f64 = counts
+        .iter()
+        .filter(|count| **count != 0)
+        .map(|count| {
+            let p = *count as f64 / len;
+            -p * p.log2()
+        })
+        .sum();
+    entropy >= ENTROPY_THRESHOLD
+}
+
+/// Offsets and magic bytes of already compressed formats.
+const MEDIA_MAGIC: [(usize, &[u8]); 12] = [
+    // zip, jar, docx etc.
+    (0, b"PK\x03\x04"),
+    (0, b"7z\xbc\xaf\x27\x1c"),
+    (0, b"Rar!\x1a\x07"),
+    (0, b"\x89PNG\r\n\x1a\n"),
+    (0, b"\xff\xd8\xff"),
+    (0, b"GIF8"),
+    (8, b"WEBP"),
+    // mp4, mov, heic etc.
+    (4, b"ftyp"),
+    (0, b"fLaC"),
+    (0, b"OggS"),
+    (0, b"ID3"),
+    // matroska, webm
+    (0, b"\x1a\x45\xdf\xa3"),
+];
+
+const MIN_SAMPLE_LEN: usize = 256;
+const MAX_SAMPLE_LEN: usize = 64 * 1024;
+// bits per byte
+const ENTROPY_THRESHOLD: f64 = 7.5;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::write::AnyEncoder;
+    use crate::write::Compression;
+
+    #[test]
+    fn test_is_probably_compressed() {
+        let text = b"The quick brown fox jumps over the lazy dog. ".repeat(100);
+        assert!(!is_probably_compressed(&text));
+        assert!(!is_probably_compressed(b""));
+        assert!(is_probably_compressed(&random_bytes(4096)));
+        assert!(is_probably_compressed(b"\x89PNG\r\n\x1a\n"));
+        for format in Format::DETECTION_ORDER.iter() {
+            let mut writer = AnyEncoder::new(Vec::new(), *format, Compression::Best).unwrap();
+            writer.write_all(&text).unwrap();
+            let compressed = writer.finish().unwrap();
+            #[cfg(feature = "flate2")]
+            if *format == Format::Zlib {
+                continue;
+            }
+            assert!(is_probably_compressed(&compressed), "{:?}", format);
+        }
+    }
+
+    fn random_bytes(n: usize) -> Vec<u8> {
+        // xorshift
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        (0..n)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+}
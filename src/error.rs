@@ -0,0 +1,29 @@
+use std::io::Error;
+use std::io::ErrorKind;
+
+pub(crate) fn memory_limit_exceeded() -> Error {
+    Error::new(ErrorKind::OutOfMemory, "memory limit exceeded")
+}
+
+/// Convert codec-specific "memory limit" errors into [`memory_limit_exceeded`].
+pub(crate) fn map_memory_limit_error(e: Error) -> Error {
+    if is_memory_limit_error(&e) {
+        memory_limit_exceeded()
+    } else {
+        e
+    }
+}
+
+fn is_memory_limit_error(e: &Error) -> bool {
+    if e.kind() == ErrorKind::OutOfMemory {
+        return false;
+    }
+    #[cfg(feature = "xz")]
+    if let Some(xz::stream::Error::MemLimit) = e
+        .get_ref()
+        .and_then(|e| e.downcast_ref::<xz::stream::Error>())
+    {
+        return true;
+    }
+    false
+}
@@ -0,0 +1,69 @@
+use std::io::Error;
+use std::io::Write;
+use std::time::Instant;
+
+use crate::write::AnyEncoder;
+use crate::write::Compression;
+use crate::Format;
+
+/// Predicted compression ratio and throughput of a format.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Estimate {
+    /// Compression format.
+    pub format: Format,
+    /// Uncompressed size divided by compressed size.
+    ///
+    /// Values greater than one mean that the data shrinks.
+    pub ratio: f32,
+    /// Compression speed in uncompressed bytes per second.
+    pub throughput: f32,
+}
+
+/// Compress the `sample` with each enabled format at the fastest level to predict
+/// achievable compression ratios.
+///
+/// The results are in the same order as in [Format::ALL]; [Format::Verbatim] is not included.
+/// The larger the sample, the more accurate the estimates are;
+/// a few hundred kilobytes are usually enough.
+pub fn estimate(sample: &[u8]) -> Vec<Estimate> {
+    Format::ALL
+        .iter()
+        .filter(|format| **format != Format::Verbatim)
+        .filter_map(|format| estimate_format(sample, *format).ok())
+        .collect()
+}
+
+fn estimate_format(sample: &[u8], format: Format) -> Result<Estimate, Error> {
+    let start = Instant::now();
+    let mut writer = AnyEncoder::new(Vec::new(), format, Compression::Fast)?;
+    writer.write_all(sample)?;
+    let compressed = writer.finish()?;
+    let elapsed = start.elapsed().as_secs_f32().max(f32::MIN_POSITIVE);
+    Ok(Estimate {
+        format,
+        ratio: sample.len() as f32 / compressed.len().max(1) as f32,
+        throughput: sample.len() as f32 / elapsed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate() {
+        let sample = b"hello world ".repeat(1000);
+        let estimates = estimate(&sample);
+        assert_eq!(Format::ALL.len() - 1, estimates.len());
+        for estimate in estimates.iter() {
+            assert!(estimate.throughput > 0.0, "{:?}", estimate);
+            // LZSS encodes at most 18 bytes as 2 bytes and 1 bit
+            #[cfg(feature = "mscompress")]
+            if matches!(estimate.format, Format::Szdd | Format::Kwaj) {
+                assert!(estimate.ratio > 5.0, "{:?}", estimate);
+                continue;
+            }
+            assert!(estimate.ratio > 10.0, "{:?}", estimate);
+        }
+    }
+}
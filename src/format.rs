@@ -1,6 +1,8 @@
 /// Compression format.
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 #[cfg_attr(test, derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum Format {
     /// No encoding.
     Verbatim,
@@ -19,4 +21,363 @@ pub enum Format {
     /// Zstd encoding.
     #[cfg(feature = "zstd")]
     Zstd,
+    /// ZIP file with a single entry.
+    #[cfg(feature = "zip")]
+    Zip,
+    /// Microsoft `COMPRESS.EXE` SZDD encoding.
+    #[cfg(feature = "mscompress")]
+    Szdd,
+    /// Microsoft `COMPRESS.EXE` KWAJ encoding.
+    #[cfg(feature = "mscompress")]
+    Kwaj,
+    /// LZ4 frame encoding (including legacy frames).
+    #[cfg(feature = "lz4")]
+    Lz4,
+    /// Brotli encoding.
+    ///
+    /// The format has no magic bytes, hence it's not detected by default;
+    /// use [AnyDecoder::with_format](crate::AnyDecoder::with_format),
+    /// [from_extension](Format::from_extension) or include the format in the
+    /// [detection order](crate::AnyDecoder::detection_order) explicitly.
+    #[cfg(feature = "brotli")]
+    Brotli,
+    /// Lzop encoding (LZO1X compression).
+    #[cfg(feature = "lzop")]
+    Lzop,
+    /// Raw DEFLATE stream without any header (e.g. HTTP `Content-Encoding: deflate` in practice
+    /// and ZIP entries).
+    ///
+    /// The format has no magic bytes and is never detected;
+    /// use [AnyDecoder::with_format](crate::AnyDecoder::with_format)
+    /// or [from_extension](Format::from_extension) to decode such streams.
+    #[cfg(feature = "flate2")]
+    Deflate,
+    /// LZFSE encoding (Apple Compression library), including LZVN blocks.
+    #[cfg(feature = "lzfse")]
+    Lzfse,
+    /// Externally registered format, see [CustomFormat](crate::CustomFormat).
+    ///
+    /// The value is the name of the format.
+    /// Such formats can only be decoded by the decoders they were registered with
+    /// and are never serialized.
+    #[cfg_attr(test, arbitrary(skip))]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Other(#[cfg_attr(feature = "serde", serde(skip))] &'static str),
+}
+
+impl Format {
+    /// All enabled formats.
+    ///
+    /// The formats are enabled via Cargo features of this crate,
+    /// and each of them can be both encoded and decoded.
+    pub const ALL: &'static [Format] = &[
+        Format::Verbatim,
+        #[cfg(feature = "flate2")]
+        Format::Gz,
+        #[cfg(feature = "bzip2")]
+        Format::Bz,
+        #[cfg(feature = "flate2")]
+        Format::Zlib,
+        #[cfg(feature = "xz")]
+        Format::Xz,
+        #[cfg(feature = "zstd")]
+        Format::Zstd,
+        #[cfg(feature = "zip")]
+        Format::Zip,
+        #[cfg(feature = "mscompress")]
+        Format::Szdd,
+        #[cfg(feature = "mscompress")]
+        Format::Kwaj,
+        #[cfg(feature = "lz4")]
+        Format::Lz4,
+        #[cfg(feature = "brotli")]
+        Format::Brotli,
+        #[cfg(feature = "lzop")]
+        Format::Lzop,
+        #[cfg(feature = "flate2")]
+        Format::Deflate,
+        #[cfg(feature = "lzfse")]
+        Format::Lzfse,
+    ];
+
+    /// The order in which the formats are tested during detection by default.
+    ///
+    /// Zlib comes after the formats with longer magic bytes, because its two-byte header is the
+    /// weakest signature. Brotli and raw DEFLATE have no signature at all and are not included.
+    pub const DETECTION_ORDER: &'static [Format] = &[
+        #[cfg(feature = "xz")]
+        Format::Xz,
+        #[cfg(feature = "zstd")]
+        Format::Zstd,
+        #[cfg(feature = "lz4")]
+        Format::Lz4,
+        #[cfg(feature = "flate2")]
+        Format::Gz,
+        #[cfg(feature = "bzip2")]
+        Format::Bz,
+        #[cfg(feature = "flate2")]
+        Format::Zlib,
+        #[cfg(feature = "zip")]
+        Format::Zip,
+        #[cfg(feature = "mscompress")]
+        Format::Szdd,
+        #[cfg(feature = "mscompress")]
+        Format::Kwaj,
+        #[cfg(feature = "lzop")]
+        Format::Lzop,
+        #[cfg(feature = "lzfse")]
+        Format::Lzfse,
+    ];
+
+    /// The length of the longest [magic bytes](Self::magic_bytes) of the enabled formats.
+    pub const MAX_MAGIC_LEN: usize = crate::MAX_MAGIC_BYTES_LEN;
+
+    /// Iterate over the formats that are enabled in this build, see [ALL](Self::ALL).
+    pub fn enabled() -> impl Iterator<Item = Format> {
+        Self::ALL.iter().copied()
+    }
+
+    /// Get the magic bytes that the stream of this format starts with.
+    ///
+    /// The stream matches the format if it starts with any of the returned byte strings.
+    /// Zstd streams may also start with skippable frames (`50..5f 2a 4d 18`).
+    /// Zlib, brotli and raw DEFLATE have no fixed magic bytes, hence the returned list is empty;
+    /// zlib is detected by the header checksum instead.
+    pub fn magic_bytes(self) -> &'static [&'static [u8]] {
+        crate::SIGNATURES
+            .iter()
+            .find(|signature| {
+                signature.format == self && signature.offset == 0 && signature.mask.is_empty()
+            })
+            .map_or(&[], |signature| signature.patterns)
+    }
+
+    /// Detect the format using the magic bytes at the start of `bytes`.
+    ///
+    /// The formats are tested in [DETECTION_ORDER](Self::DETECTION_ORDER),
+    /// i.e. the same way as the decoder with the default options does.
+    /// Slices shorter than [MAX_MAGIC_LEN](Self::MAX_MAGIC_LEN) match only the formats
+    /// with shorter magic bytes.
+    /// Returns `None` if none of the formats matches.
+    pub fn from_magic(bytes: &[u8]) -> Option<Format> {
+        crate::detect_format_in(bytes, Self::DETECTION_ORDER)
+    }
+
+    /// Get the format from the file name `extension` (without the dot), e.g. `gz` or `br`.
+    ///
+    /// The case is ignored. Returns `None` if the extension is unknown.
+    pub fn from_extension(extension: &str) -> Option<Format> {
+        match extension.to_ascii_lowercase().as_str() {
+            #[cfg(feature = "flate2")]
+            "gz" => Some(Format::Gz),
+            #[cfg(feature = "bzip2")]
+            "bz2" => Some(Format::Bz),
+            #[cfg(feature = "flate2")]
+            "zlib" => Some(Format::Zlib),
+            #[cfg(feature = "xz")]
+            "xz" => Some(Format::Xz),
+            #[cfg(feature = "zstd")]
+            "zst" => Some(Format::Zstd),
+            #[cfg(feature = "zip")]
+            "zip" => Some(Format::Zip),
+            #[cfg(feature = "lz4")]
+            "lz4" => Some(Format::Lz4),
+            #[cfg(feature = "brotli")]
+            "br" => Some(Format::Brotli),
+            #[cfg(feature = "lzop")]
+            "lzo" => Some(Format::Lzop),
+            #[cfg(feature = "flate2")]
+            "deflate" => Some(Format::Deflate),
+            #[cfg(feature = "lzfse")]
+            "lzfse" => Some(Format::Lzfse),
+            _ => None,
+        }
+    }
+
+    /// Get the format from HTTP `Content-Encoding` header value, e.g. `gzip` or `br`.
+    ///
+    /// The case and the surrounding whitespace are ignored.
+    /// `deflate` is mapped to [Zlib](Format::Zlib) as RFC 9110 specifies;
+    /// some servers send raw [DEFLATE](Format::Deflate) instead.
+    /// Returns `None` if the encoding is unknown.
+    pub fn from_content_encoding(encoding: &str) -> Option<Format> {
+        match encoding.trim().to_ascii_lowercase().as_str() {
+            "identity" => Some(Format::Verbatim),
+            #[cfg(feature = "flate2")]
+            "gzip" | "x-gzip" => Some(Format::Gz),
+            #[cfg(feature = "flate2")]
+            "deflate" => Some(Format::Zlib),
+            #[cfg(feature = "brotli")]
+            "br" => Some(Format::Brotli),
+            #[cfg(feature = "zstd")]
+            "zstd" => Some(Format::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Get HTTP `Content-Encoding` header value of the format.
+    ///
+    /// Returns `None` if the format has no registered content coding.
+    pub fn content_encoding(self) -> Option<&'static str> {
+        match self {
+            Format::Verbatim => Some("identity"),
+            #[cfg(feature = "flate2")]
+            Format::Gz => Some("gzip"),
+            #[cfg(feature = "flate2")]
+            Format::Zlib => Some("deflate"),
+            #[cfg(feature = "brotli")]
+            Format::Brotli => Some("br"),
+            #[cfg(feature = "zstd")]
+            Format::Zstd => Some("zstd"),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    /// Get the media type (MIME type) of the files compressed with this format,
+    /// e.g. `application/gzip`.
+    ///
+    /// Unregistered types are returned with `x-` prefix as most tools use them.
+    /// Returns `None` for uncompressed data and for the formats without a common media type.
+    pub fn media_type(self) -> Option<&'static str> {
+        match self {
+            #[cfg(feature = "flate2")]
+            Format::Gz => Some("application/gzip"),
+            #[cfg(feature = "bzip2")]
+            Format::Bz => Some("application/x-bzip2"),
+            #[cfg(feature = "flate2")]
+            Format::Zlib => Some("application/zlib"),
+            #[cfg(feature = "xz")]
+            Format::Xz => Some("application/x-xz"),
+            #[cfg(feature = "zstd")]
+            Format::Zstd => Some("application/zstd"),
+            #[cfg(feature = "zip")]
+            Format::Zip => Some("application/zip"),
+            #[cfg(feature = "lz4")]
+            Format::Lz4 => Some("application/x-lz4"),
+            #[cfg(feature = "lzop")]
+            Format::Lzop => Some("application/x-lzop"),
+            _ => None,
+        }
+    }
+}
+
+/// The result of format detection.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Detection {
+    /// Detected format.
+    pub format: Format,
+    /// The number of magic bytes that matched.
+    ///
+    /// Zero for the formats without magic bytes that were detected via trial decoding.
+    /// For the [custom formats](crate::CustomFormat) this is the length of their magic bytes.
+    pub magic_len: usize,
+    /// Whether the match is heuristic rather than exact.
+    ///
+    /// Zlib header is only two bytes long and occurs in arbitrary data too often,
+    /// the formats without magic bytes are always heuristic matches.
+    pub heuristic: bool,
+}
+
+impl Detection {
+    /// Create the detection result for `magic_len` bytes that matched the built-in `format`.
+    pub(crate) fn new(format: Format, magic_len: usize) -> Self {
+        #[cfg(feature = "flate2")]
+        let zlib = matches!(format, Format::Zlib);
+        #[cfg(not(feature = "flate2"))]
+        let zlib = false;
+        Self {
+            format,
+            magic_len,
+            heuristic: zlib || magic_len == 0,
+        }
+    }
+}
+
+/// Detect the format of the in-memory data using the magic bytes.
+///
+/// Unlike [Format::from_magic] also reports how many bytes matched
+/// and whether the match is [heuristic](Detection::heuristic).
+pub fn detect_format(bytes: &[u8]) -> Option<Detection> {
+    crate::detect_in(bytes, Format::DETECTION_ORDER)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::write::AnyEncoder;
+    use crate::write::Compression;
+
+    #[test]
+    fn from_magic() {
+        arbtest(|u| {
+            let format = *u.choose(Format::DETECTION_ORDER)?;
+            let data: Vec<u8> = u.arbitrary()?;
+            let mut writer = AnyEncoder::new(Vec::new(), format, Compression::Fast).unwrap();
+            writer.write_all(&data).unwrap();
+            let compressed = writer.finish().unwrap();
+            assert_eq!(Some(format), Format::from_magic(&compressed));
+            let detection = detect_format(&compressed).unwrap();
+            assert_eq!(format, detection.format);
+            assert!(detection.magic_len <= compressed.len());
+            #[cfg(feature = "flate2")]
+            assert_eq!(format == Format::Zlib, detection.heuristic);
+            Ok(())
+        });
+        assert_eq!(None, detect_format(b""));
+        assert_eq!(None, detect_format(b"hello world"));
+    }
+    #[test]
+    fn magic_bytes() {
+        for format in Format::enabled() {
+            let mut writer = AnyEncoder::new(Vec::new(), format, Compression::Fast).unwrap();
+            writer.write_all(b"hello").unwrap();
+            let compressed = writer.finish().unwrap();
+            let magic = format.magic_bytes();
+            assert!(magic
+                .iter()
+                .all(|bytes| bytes.len() <= Format::MAX_MAGIC_LEN));
+            if !magic.is_empty() {
+                assert!(
+                    magic.iter().any(|bytes| compressed.starts_with(bytes)),
+                    "{format:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn enabled() {
+        use std::io::Read;
+        for format in Format::enabled() {
+            let mut writer = AnyEncoder::new(Vec::new(), format, Compression::Fast).unwrap();
+            writer.write_all(b"hello").unwrap();
+            let compressed = writer.finish().unwrap();
+            let mut string = String::new();
+            crate::AnyDecoder::with_format(&compressed[..], format)
+                .read_to_string(&mut string)
+                .unwrap();
+            assert_eq!("hello", string, "format = {format:?}");
+        }
+    }
+
+    #[test]
+    fn content_encoding() {
+        for format in Format::enabled() {
+            if let Some(encoding) = format.content_encoding() {
+                assert_eq!(Some(format), Format::from_content_encoding(encoding));
+            }
+        }
+        #[cfg(feature = "flate2")]
+        {
+            assert_eq!(Some(Format::Gz), Format::from_content_encoding(" X-GZIP"));
+            assert_eq!(Some("application/gzip"), Format::Gz.media_type());
+        }
+        assert_eq!(None, Format::from_content_encoding("compress"));
+        assert_eq!(None, Format::Verbatim.media_type());
+    }
 }
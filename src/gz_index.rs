@@ -0,0 +1,451 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+
+use crate::GzInflater;
+
+/// Gzip random-access index.
+///
+/// The index is a list of checkpoints that are recorded at deflate block boundaries
+/// while the file is decompressed once.
+/// Each checkpoint stores the last 32 KiB of the decompressed data (the window) that is needed
+/// to resume decompression from that point.
+/// Multi-member files are supported; a checkpoint is recorded at the start of every member.
+///
+/// Use [GzIndexedReader] to read the file at arbitrary offsets.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct GzIndex {
+    checkpoints: Vec<GzCheckpoint>,
+    compressed_size: u64,
+    uncompressed_size: u64,
+}
+
+/// The point at which gzip decompression can be resumed.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct GzCheckpoint {
+    /// The offset of the byte that contains the first bit of the deflate block.
+    pub compressed_offset: u64,
+    /// The number of bits in the byte at `compressed_offset` that belong to the previous block.
+    pub bits: u8,
+    /// The offset of the decompressed data from the start of the decompressed file.
+    pub uncompressed_offset: u64,
+    /// The last 32 KiB (or less) of the decompressed data of the current member.
+    pub window: Vec<u8>,
+}
+
+impl GzIndex {
+    /// Build the index by decompressing the whole file.
+    ///
+    /// Checkpoints are recorded approximately every `spacing` bytes of decompressed data.
+    /// Smaller spacing means faster seeks and larger index
+    /// (each checkpoint takes up to 32 KiB).
+    pub fn build<R: Read>(reader: R, spacing: u64) -> Result<Self, Error> {
+        let mut inflater = GzInflater::new(reader, 0);
+        let mut checkpoints: Vec<GzCheckpoint> = Vec::new();
+        let mut uncompressed_offset = 0;
+        let mut buf = Vec::new();
+        loop {
+            if inflater.at_block_boundary() {
+                let since_last = checkpoints
+                    .last()
+                    .map(|c| uncompressed_offset - c.uncompressed_offset);
+                let add = match since_last {
+                    None => true,
+                    Some(n) => n >= spacing || (inflater.at_member_start() && n != 0),
+                };
+                if add {
+                    let position = inflater.bit_position();
+                    checkpoints.push(GzCheckpoint {
+                        compressed_offset: position / 8,
+                        bits: (position % 8) as u8,
+                        uncompressed_offset,
+                        window: inflater.window(),
+                    });
+                }
+            }
+            buf.clear();
+            if !inflater.step(&mut buf)? {
+                break;
+            }
+            uncompressed_offset += buf.len() as u64;
+        }
+        Ok(Self {
+            checkpoints,
+            compressed_size: inflater.bit_position() / 8,
+            uncompressed_size: uncompressed_offset,
+        })
+    }
+
+    pub(crate) fn from_parts(
+        checkpoints: Vec<GzCheckpoint>,
+        compressed_size: u64,
+        uncompressed_size: u64,
+    ) -> Self {
+        Self {
+            checkpoints,
+            compressed_size,
+            uncompressed_size,
+        }
+    }
+
+    /// Build the index for the file.
+    pub fn build_file<P: AsRef<Path>>(path: P, spacing: u64) -> Result<Self, Error> {
+        Self::build(File::open(path)?, spacing)
+    }
+
+    /// Get the checkpoints ordered by the offset.
+    pub fn checkpoints(&self) -> &[GzCheckpoint] {
+        &self.checkpoints
+    }
+
+    /// The size of the gzip file.
+    pub fn compressed_size(&self) -> u64 {
+        self.compressed_size
+    }
+
+    /// The size of the decompressed data.
+    pub fn uncompressed_size(&self) -> u64 {
+        self.uncompressed_size
+    }
+
+    /// Find the last checkpoint at or before the supplied offset in the decompressed data.
+    pub fn checkpoint(&self, uncompressed_offset: u64) -> Option<&GzCheckpoint> {
+        let i = self
+            .checkpoints
+            .partition_point(|c| c.uncompressed_offset <= uncompressed_offset);
+        i.checked_sub(1).map(|i| &self.checkpoints[i])
+    }
+
+    /// Write the index to the supplied `writer`.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_all(&INDEX_MAGIC)?;
+        writer.write_all(&[INDEX_VERSION])?;
+        writer.write_all(&self.compressed_size.to_le_bytes())?;
+        writer.write_all(&self.uncompressed_size.to_le_bytes())?;
+        writer.write_all(&(self.checkpoints.len() as u64).to_le_bytes())?;
+        for checkpoint in self.checkpoints.iter() {
+            writer.write_all(&checkpoint.compressed_offset.to_le_bytes())?;
+            writer.write_all(&[checkpoint.bits])?;
+            writer.write_all(&checkpoint.uncompressed_offset.to_le_bytes())?;
+            writer.write_all(&(checkpoint.window.len() as u32).to_le_bytes())?;
+            writer.write_all(&checkpoint.window)?;
+        }
+        Ok(())
+    }
+
+    /// Read the index from the supplied `reader`.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut magic = [0_u8; INDEX_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != INDEX_MAGIC {
+            return Err(invalid_data("invalid gzip index magic"));
+        }
+        if read_u8(&mut reader)? != INDEX_VERSION {
+            return Err(invalid_data("unsupported gzip index version"));
+        }
+        let compressed_size = read_u64(&mut reader)?;
+        let uncompressed_size = read_u64(&mut reader)?;
+        let len = read_u64(&mut reader)?;
+        let mut checkpoints: Vec<GzCheckpoint> = Vec::new();
+        for _ in 0..len {
+            let compressed_offset = read_u64(&mut reader)?;
+            let bits = read_u8(&mut reader)?;
+            let uncompressed_offset = read_u64(&mut reader)?;
+            let window_len = read_u32(&mut reader)? as usize;
+            if bits >= 8 || window_len > MAX_WINDOW_SIZE {
+                return Err(invalid_data("invalid gzip index checkpoint"));
+            }
+            if let Some(last) = checkpoints.last() {
+                if last.uncompressed_offset > uncompressed_offset {
+                    return Err(invalid_data("unordered gzip index checkpoints"));
+                }
+            }
+            let mut window = vec![0_u8; window_len];
+            reader.read_exact(&mut window)?;
+            checkpoints.push(GzCheckpoint {
+                compressed_offset,
+                bits,
+                uncompressed_offset,
+                window,
+            });
+        }
+        Ok(Self {
+            checkpoints,
+            compressed_size,
+            uncompressed_size,
+        })
+    }
+
+    /// Load the index from the file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Save the index to the file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()
+    }
+}
+
+/// Gzip reader that supports seeking via [GzIndex].
+///
+/// Seeking resumes decompression from the nearest checkpoint before the target offset and
+/// discards the data up to the target offset.
+/// The checksums are not verified because decompression rarely starts at the member boundary.
+pub struct GzIndexedReader<R> {
+    index: GzIndex,
+    inflater: GzInflater<R>,
+    buf: Vec<u8>,
+    pos: usize,
+    // the offset of `buf[0]` in the decompressed data
+    offset: u64,
+    // `false` until the inflater is resumed from the first checkpoint
+    resumed: bool,
+    eof: bool,
+}
+
+impl<R: Read + Seek> GzIndexedReader<R> {
+    /// Create new reader from the gzip file and its index.
+    ///
+    /// The reader is positioned at the start of the decompressed data.
+    pub fn new(reader: R, index: GzIndex) -> Self {
+        Self {
+            index,
+            inflater: GzInflater::new(reader, 0),
+            buf: Vec::new(),
+            pos: 0,
+            offset: 0,
+            resumed: false,
+            eof: false,
+        }
+    }
+
+    /// Get the index.
+    pub fn index(&self) -> &GzIndex {
+        &self.index
+    }
+
+    /// Read the data at the supplied offset in the decompressed data.
+    ///
+    /// This is a shorthand for seek followed by read.
+    pub fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<usize, Error> {
+        self.seek(SeekFrom::Start(offset))?;
+        self.read(buf)
+    }
+
+    /// Return the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inflater.into_inner()
+    }
+
+    fn position(&self) -> u64 {
+        self.offset + self.pos as u64
+    }
+
+    fn resume(&mut self, checkpoint: &GzCheckpoint) -> Result<(), Error> {
+        self.inflater
+            .get_mut()
+            .seek(SeekFrom::Start(checkpoint.compressed_offset))?;
+        self.inflater.reset(
+            checkpoint.compressed_offset,
+            checkpoint.bits,
+            &checkpoint.window,
+        )?;
+        self.buf.clear();
+        self.pos = 0;
+        self.offset = checkpoint.uncompressed_offset;
+        self.resumed = true;
+        self.eof = false;
+        Ok(())
+    }
+
+    fn fill(&mut self) -> Result<bool, Error> {
+        if !self.resumed && !self.eof {
+            let checkpoint = self.index.checkpoint(self.position()).cloned();
+            match checkpoint {
+                Some(checkpoint) => self.resume(&checkpoint)?,
+                None => self.eof = true,
+            }
+        }
+        while self.pos == self.buf.len() && !self.eof {
+            self.offset += self.buf.len() as u64;
+            self.buf.clear();
+            self.pos = 0;
+            if !self.inflater.step(&mut self.buf)? {
+                self.eof = true;
+            }
+        }
+        Ok(self.pos != self.buf.len())
+    }
+}
+
+impl<R: Read + Seek> Read for GzIndexedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if !self.fill()? {
+            return Ok(0);
+        }
+        let n = (self.buf.len() - self.pos).min(buf.len());
+        buf[..n].copy_from_slice(&self.buf[self.pos..(self.pos + n)]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for GzIndexedReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Error> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.index.uncompressed_size.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position().checked_add_signed(delta),
+        };
+        let Some(target) = target else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            ));
+        };
+        let Some(checkpoint) = self.index.checkpoint(target).cloned() else {
+            // empty file
+            self.offset = target;
+            self.buf.clear();
+            self.pos = 0;
+            self.eof = true;
+            return Ok(target);
+        };
+        if self.resumed && self.offset <= target && target <= self.offset + self.buf.len() as u64 {
+            // the data is already decompressed
+            self.pos = (target - self.offset) as usize;
+            return Ok(target);
+        }
+        // resume from the checkpoint unless it is faster to decompress the data in between
+        if !self.resumed || target < self.offset || checkpoint.uncompressed_offset > self.position()
+        {
+            self.resume(&checkpoint)?;
+        }
+        while self.position() < target {
+            if !self.fill()? {
+                // past the end of the data
+                self.offset = target;
+                self.buf.clear();
+                self.pos = 0;
+                break;
+            }
+            let n = (target - self.position()).min((self.buf.len() - self.pos) as u64);
+            self.pos += n as usize;
+        }
+        Ok(target)
+    }
+}
+
+fn read_u8<R: Read>(mut reader: R) -> Result<u8, Error> {
+    let mut bytes = [0_u8; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u32<R: Read>(mut reader: R) -> Result<u32, Error> {
+    let mut bytes = [0_u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(mut reader: R) -> Result<u64, Error> {
+    let mut bytes = [0_u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn invalid_data(message: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+const INDEX_MAGIC: [u8; 8] = *b"DEKOGZIX";
+const INDEX_VERSION: u8 = 1;
+pub(crate) const MAX_WINDOW_SIZE: usize = 32 * 1024;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use arbtest::arbtest;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    use super::*;
+
+    #[test]
+    fn random_access() {
+        arbtest(|u| {
+            let members: Vec<Vec<u8>> = u.arbitrary()?;
+            let mut compressed = Vec::new();
+            for member in members.iter() {
+                let level = Compression::new(u.int_in_range(0..=9)?);
+                let mut writer = GzEncoder::new(Vec::new(), level);
+                writer.write_all(member).unwrap();
+                compressed.extend(writer.finish().unwrap());
+            }
+            if compressed.is_empty() {
+                return Ok(());
+            }
+            let expected = members.concat();
+            let spacing = u.int_in_range(1..=1024)?;
+            let index = GzIndex::build(&compressed[..], spacing).unwrap();
+            assert_eq!(compressed.len() as u64, index.compressed_size());
+            assert_eq!(expected.len() as u64, index.uncompressed_size());
+            let mut file = Vec::new();
+            index.write_to(&mut file).unwrap();
+            let index = GzIndex::read_from(&file[..]).unwrap();
+            let mut reader = GzIndexedReader::new(Cursor::new(&compressed), index);
+            for _ in 0..u.int_in_range(0..=10)? {
+                let offset = u.int_in_range(0..=expected.len())?;
+                let len = u.int_in_range(0..=expected.len() - offset)?;
+                let mut actual = vec![0_u8; len];
+                reader.seek(SeekFrom::Start(offset as u64)).unwrap();
+                reader.read_exact(&mut actual).unwrap();
+                assert_eq!(&expected[offset..(offset + len)], &actual);
+            }
+            reader.seek(SeekFrom::Start(0)).unwrap();
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, actual);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn many_checkpoints() {
+        let expected = (0..100_000)
+            .map(|i| format!("line {} {}\n", i, i * 7919 % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let mut writer = GzEncoder::new(Vec::new(), Compression::default());
+        writer.write_all(&expected).unwrap();
+        let compressed = writer.finish().unwrap();
+        let index = GzIndex::build(&compressed[..], 64 * 1024).unwrap();
+        assert!(index.checkpoints().len() > 3);
+        assert!(index.checkpoints()[1..]
+            .iter()
+            .all(|c| c.window.len() == MAX_WINDOW_SIZE));
+        let mut reader = GzIndexedReader::new(Cursor::new(&compressed), index);
+        for offset in (0..expected.len()).rev().step_by(99_991) {
+            let mut actual = vec![0_u8; 1000.min(expected.len() - offset)];
+            reader.read_at(&mut actual, offset as u64).unwrap();
+            assert_eq!(&expected[offset..(offset + actual.len())], &actual);
+        }
+    }
+
+    #[test]
+    fn invalid_index() {
+        assert!(GzIndex::read_from(&b"DEKOGZIX\x02"[..]).is_err());
+        assert!(GzIndex::read_from(&b"garbage"[..]).is_err());
+    }
+}
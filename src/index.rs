@@ -0,0 +1,818 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+
+use crate::AnyDecoder;
+#[cfg(feature = "bzip2")]
+use crate::BzBlockInfo;
+use crate::Format;
+#[cfg(feature = "flate2")]
+use crate::GzCheckpoint;
+#[cfg(feature = "flate2")]
+use crate::GzIndex;
+#[cfg(feature = "flate2")]
+use crate::GzIndexedReader;
+#[cfg(feature = "xz")]
+use crate::XzBlockInfo;
+#[cfg(feature = "xz")]
+use crate::XzCheck;
+#[cfg(feature = "zstd")]
+use crate::ZstdFrameInfo;
+
+/// Random-access index for compressed file (_deko-index_).
+///
+/// The index stores the offsets of independently decodable parts of the file:
+/// gzip checkpoints (see [GzIndex]), bzip2 and xz blocks, and zstd frames.
+/// The index is usually saved next to the compressed file (the _sidecar_ file)
+/// and is used by [IndexedReader] to read the data at arbitrary offsets.
+///
+/// Zlib files are not supported.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Index {
+    format: Format,
+    compressed_size: u64,
+    uncompressed_size: u64,
+    entries: Vec<IndexEntry>,
+}
+
+/// Independently decodable part of compressed file.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum IndexEntry {
+    /// Gzip checkpoint.
+    #[cfg(feature = "flate2")]
+    Gz(GzCheckpoint),
+    /// Bzip2 block.
+    #[cfg(feature = "bzip2")]
+    Bz {
+        /// Block location.
+        block: BzBlockInfo,
+        /// The offset of the decompressed data from the start of the decompressed file.
+        uncompressed_offset: u64,
+        /// The size of the decompressed data.
+        uncompressed_size: u64,
+    },
+    /// Xz block.
+    #[cfg(feature = "xz")]
+    Xz(XzBlockInfo),
+    /// Zstd frame.
+    #[cfg(feature = "zstd")]
+    Zstd(ZstdFrameInfo),
+}
+
+impl IndexEntry {
+    /// The offset of the first byte of the compressed data.
+    pub fn compressed_offset(&self) -> u64 {
+        match self {
+            #[cfg(feature = "flate2")]
+            Self::Gz(checkpoint) => checkpoint.compressed_offset,
+            #[cfg(feature = "bzip2")]
+            Self::Bz { block, .. } => block.bit_offset / 8,
+            #[cfg(feature = "xz")]
+            Self::Xz(block) => block.compressed_offset,
+            #[cfg(feature = "zstd")]
+            Self::Zstd(frame) => frame.compressed_offset,
+        }
+    }
+
+    /// The offset of the decompressed data from the start of the decompressed file.
+    pub fn uncompressed_offset(&self) -> u64 {
+        match self {
+            #[cfg(feature = "flate2")]
+            Self::Gz(checkpoint) => checkpoint.uncompressed_offset,
+            #[cfg(feature = "bzip2")]
+            Self::Bz {
+                uncompressed_offset,
+                ..
+            } => *uncompressed_offset,
+            #[cfg(feature = "xz")]
+            Self::Xz(block) => block.uncompressed_offset,
+            #[cfg(feature = "zstd")]
+            Self::Zstd(frame) => frame.uncompressed_offset,
+        }
+    }
+
+    fn format(&self) -> Format {
+        match self {
+            #[cfg(feature = "flate2")]
+            Self::Gz(..) => Format::Gz,
+            #[cfg(feature = "bzip2")]
+            Self::Bz { .. } => Format::Bz,
+            #[cfg(feature = "xz")]
+            Self::Xz(..) => Format::Xz,
+            #[cfg(feature = "zstd")]
+            Self::Zstd(..) => Format::Zstd,
+        }
+    }
+}
+
+impl Index {
+    /// Build the index for the file.
+    ///
+    /// The format is detected automatically.
+    /// Gzip and bzip2 files are decompressed once to find out the offsets;
+    /// for gzip files the checkpoints are recorded every `spacing` bytes of decompressed data.
+    /// Xz and zstd files are decompressed only if the sizes are not stored in the file.
+    pub fn build<R: Read + Seek>(mut reader: R, spacing: u64) -> Result<Self, Error> {
+        let compressed_size = reader.seek(SeekFrom::End(0))?;
+        reader.rewind()?;
+        let format = AnyDecoder::new(BufReader::new(&mut reader)).kind()?;
+        reader.rewind()?;
+        let (entries, uncompressed_size) = match format {
+            Format::Verbatim => (Vec::new(), compressed_size),
+            #[cfg(feature = "flate2")]
+            Format::Gz => {
+                let index = GzIndex::build(BufReader::new(reader), spacing)?;
+                let entries = index
+                    .checkpoints()
+                    .iter()
+                    .cloned()
+                    .map(IndexEntry::Gz)
+                    .collect();
+                (entries, index.uncompressed_size())
+            }
+            #[cfg(feature = "bzip2")]
+            Format::Bz => {
+                let blocks = crate::bz_blocks(BufReader::new(&mut reader))?;
+                let mut entries = Vec::with_capacity(blocks.len());
+                let mut uncompressed_offset = 0;
+                for block in blocks.into_iter() {
+                    let uncompressed_size = crate::bz_decode_block(&mut reader, &block)?.len();
+                    entries.push(IndexEntry::Bz {
+                        block,
+                        uncompressed_offset,
+                        uncompressed_size: uncompressed_size as u64,
+                    });
+                    uncompressed_offset += uncompressed_size as u64;
+                }
+                (entries, uncompressed_offset)
+            }
+            #[cfg(feature = "flate2")]
+            Format::Zlib => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    "zlib files don't support random access",
+                ))
+            }
+            #[cfg(feature = "zip")]
+            Format::Zip => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    "zip files don't support random access",
+                ))
+            }
+            #[cfg(feature = "mscompress")]
+            Format::Szdd | Format::Kwaj => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    "szdd and kwaj files don't support random access",
+                ))
+            }
+            #[cfg(feature = "lzop")]
+            Format::Lzop => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    "lzop files don't support random access",
+                ))
+            }
+            #[cfg(feature = "lzfse")]
+            Format::Lzfse => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    "lzfse files don't support random access",
+                ))
+            }
+            #[cfg(feature = "lz4")]
+            Format::Lz4 => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    "lz4 files don't support random access",
+                ))
+            }
+            #[cfg(feature = "brotli")]
+            Format::Brotli => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    "brotli files don't support random access",
+                ))
+            }
+            #[cfg(feature = "flate2")]
+            Format::Deflate => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    "deflate streams don't support random access",
+                ))
+            }
+            Format::Other(..) => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    "custom formats don't support random access",
+                ))
+            }
+            #[cfg(feature = "xz")]
+            Format::Xz => {
+                let entries = crate::xz_streams(reader)?
+                    .into_iter()
+                    .flat_map(|s| s.blocks.into_iter())
+                    .map(IndexEntry::Xz)
+                    .collect::<Vec<_>>();
+                let uncompressed_size = match entries.last() {
+                    Some(IndexEntry::Xz(block)) => {
+                        block.uncompressed_offset + block.uncompressed_size
+                    }
+                    _ => 0,
+                };
+                (entries, uncompressed_size)
+            }
+            #[cfg(feature = "zstd")]
+            Format::Zstd => {
+                // prefer the seek table of zstd seekable files over walking the frames
+                let frames = match crate::zstd_seek_table(&mut reader)? {
+                    Some(frames) => frames,
+                    None => {
+                        reader.rewind()?;
+                        crate::zstd_frames(reader)?
+                    }
+                };
+                let entries = frames.into_iter().map(IndexEntry::Zstd).collect::<Vec<_>>();
+                let uncompressed_size = match entries.last() {
+                    Some(IndexEntry::Zstd(frame)) => {
+                        frame.uncompressed_offset + frame.uncompressed_size
+                    }
+                    _ => 0,
+                };
+                (entries, uncompressed_size)
+            }
+        };
+        // `spacing` is only used for gzip files
+        let _ = spacing;
+        Ok(Self {
+            format,
+            compressed_size,
+            uncompressed_size,
+            entries,
+        })
+    }
+
+    #[cfg_attr(not(feature = "zstd"), allow(unused))]
+    pub(crate) fn from_parts(
+        format: Format,
+        entries: Vec<IndexEntry>,
+        compressed_size: u64,
+        uncompressed_size: u64,
+    ) -> Self {
+        Self {
+            format,
+            compressed_size,
+            uncompressed_size,
+            entries,
+        }
+    }
+
+    /// Build the index for the file.
+    pub fn build_file<P: AsRef<Path>>(path: P, spacing: u64) -> Result<Self, Error> {
+        Self::build(File::open(path)?, spacing)
+    }
+
+    /// The format of the compressed file.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// The size of the compressed file.
+    pub fn compressed_size(&self) -> u64 {
+        self.compressed_size
+    }
+
+    /// The size of the decompressed data.
+    pub fn uncompressed_size(&self) -> u64 {
+        self.uncompressed_size
+    }
+
+    /// Get the entries ordered by the offset.
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// Find the last entry at or before the supplied offset in the decompressed data.
+    pub fn entry(&self, uncompressed_offset: u64) -> Option<&IndexEntry> {
+        let i = self
+            .entries
+            .partition_point(|e| e.uncompressed_offset() <= uncompressed_offset);
+        i.checked_sub(1).map(|i| &self.entries[i])
+    }
+
+    /// Write the index to the supplied `writer`.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_all(&INDEX_MAGIC)?;
+        writer.write_all(&[INDEX_VERSION, format_to_id(self.format)])?;
+        writer.write_all(&self.compressed_size.to_le_bytes())?;
+        writer.write_all(&self.uncompressed_size.to_le_bytes())?;
+        writer.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        for entry in self.entries.iter() {
+            match entry {
+                #[cfg(feature = "flate2")]
+                IndexEntry::Gz(checkpoint) => {
+                    writer.write_all(&checkpoint.compressed_offset.to_le_bytes())?;
+                    writer.write_all(&[checkpoint.bits])?;
+                    writer.write_all(&checkpoint.uncompressed_offset.to_le_bytes())?;
+                    writer.write_all(&(checkpoint.window.len() as u32).to_le_bytes())?;
+                    writer.write_all(&checkpoint.window)?;
+                }
+                #[cfg(feature = "bzip2")]
+                IndexEntry::Bz {
+                    block,
+                    uncompressed_offset,
+                    uncompressed_size,
+                } => {
+                    writer.write_all(&block.bit_offset.to_le_bytes())?;
+                    writer.write_all(&block.bit_len.to_le_bytes())?;
+                    writer.write_all(&[block.level])?;
+                    writer.write_all(&block.crc.to_le_bytes())?;
+                    writer.write_all(&uncompressed_offset.to_le_bytes())?;
+                    writer.write_all(&uncompressed_size.to_le_bytes())?;
+                }
+                #[cfg(feature = "xz")]
+                IndexEntry::Xz(block) => {
+                    writer.write_all(&block.compressed_offset.to_le_bytes())?;
+                    writer.write_all(&block.unpadded_size.to_le_bytes())?;
+                    writer.write_all(&block.uncompressed_offset.to_le_bytes())?;
+                    writer.write_all(&block.uncompressed_size.to_le_bytes())?;
+                    writer.write_all(&[block.check.id()])?;
+                }
+                #[cfg(feature = "zstd")]
+                IndexEntry::Zstd(frame) => {
+                    writer.write_all(&frame.compressed_offset.to_le_bytes())?;
+                    writer.write_all(&frame.compressed_size.to_le_bytes())?;
+                    writer.write_all(&frame.uncompressed_offset.to_le_bytes())?;
+                    writer.write_all(&frame.uncompressed_size.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the index from the supplied `reader`.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut magic = [0_u8; INDEX_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != INDEX_MAGIC {
+            return Err(invalid_data("invalid deko index magic"));
+        }
+        if read_u8(&mut reader)? != INDEX_VERSION {
+            return Err(invalid_data("unsupported deko index version"));
+        }
+        let format = format_from_id(read_u8(&mut reader)?)?;
+        let compressed_size = read_u64(&mut reader)?;
+        let uncompressed_size = read_u64(&mut reader)?;
+        let len = read_u64(&mut reader)?;
+        let mut entries: Vec<IndexEntry> = Vec::new();
+        for _ in 0..len {
+            let entry = read_entry(&mut reader, format)?;
+            if let Some(last) = entries.last() {
+                if last.uncompressed_offset() > entry.uncompressed_offset() {
+                    return Err(invalid_data("unordered deko index entries"));
+                }
+            }
+            entries.push(entry);
+        }
+        Ok(Self {
+            format,
+            compressed_size,
+            uncompressed_size,
+            entries,
+        })
+    }
+
+    /// Load the index from the file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Save the index to the file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()
+    }
+}
+
+fn read_entry<R: Read>(mut reader: R, format: Format) -> Result<IndexEntry, Error> {
+    match format {
+        #[cfg(feature = "flate2")]
+        Format::Gz => {
+            let compressed_offset = read_u64(&mut reader)?;
+            let bits = read_u8(&mut reader)?;
+            let uncompressed_offset = read_u64(&mut reader)?;
+            let window_len = read_u32(&mut reader)? as usize;
+            if bits >= 8 || window_len > crate::MAX_WINDOW_SIZE {
+                return Err(invalid_data("invalid deko index entry"));
+            }
+            let mut window = vec![0_u8; window_len];
+            reader.read_exact(&mut window)?;
+            Ok(IndexEntry::Gz(GzCheckpoint {
+                compressed_offset,
+                bits,
+                uncompressed_offset,
+                window,
+            }))
+        }
+        #[cfg(feature = "bzip2")]
+        Format::Bz => {
+            let bit_offset = read_u64(&mut reader)?;
+            let bit_len = read_u64(&mut reader)?;
+            let level = read_u8(&mut reader)?;
+            let crc = read_u32(&mut reader)?;
+            let uncompressed_offset = read_u64(&mut reader)?;
+            let uncompressed_size = read_u64(&mut reader)?;
+            if !(1..=9).contains(&level) {
+                return Err(invalid_data("invalid deko index entry"));
+            }
+            Ok(IndexEntry::Bz {
+                block: BzBlockInfo {
+                    bit_offset,
+                    bit_len,
+                    level,
+                    crc,
+                },
+                uncompressed_offset,
+                uncompressed_size,
+            })
+        }
+        #[cfg(feature = "xz")]
+        Format::Xz => Ok(IndexEntry::Xz(XzBlockInfo {
+            compressed_offset: read_u64(&mut reader)?,
+            unpadded_size: read_u64(&mut reader)?,
+            uncompressed_offset: read_u64(&mut reader)?,
+            uncompressed_size: read_u64(&mut reader)?,
+            check: XzCheck::from_id(read_u8(&mut reader)?),
+        })),
+        #[cfg(feature = "zstd")]
+        Format::Zstd => Ok(IndexEntry::Zstd(ZstdFrameInfo {
+            compressed_offset: read_u64(&mut reader)?,
+            compressed_size: read_u64(&mut reader)?,
+            uncompressed_offset: read_u64(&mut reader)?,
+            uncompressed_size: read_u64(&mut reader)?,
+        })),
+        _ => Err(invalid_data("unexpected deko index entry")),
+    }
+}
+
+/// Compressed file reader that supports seeking via [Index].
+///
+/// Gzip files are decompressed from the nearest checkpoint (see [GzIndexedReader]).
+/// Bzip2 and xz blocks and zstd frames are decompressed into memory one at a time,
+/// i.e. the memory usage is proportional to the size of the largest block or frame.
+pub struct IndexedReader<R> {
+    inner: IndexedReaderInner<R>,
+}
+
+enum IndexedReaderInner<R> {
+    Verbatim(R),
+    #[cfg(feature = "flate2")]
+    Gz(GzIndexedReader<R>),
+    Entries(EntryReader<R>),
+}
+
+impl<R: Read + Seek> IndexedReader<R> {
+    /// Create new reader from the compressed file and its index.
+    ///
+    /// The reader is positioned at the start of the decompressed data.
+    pub fn new(mut reader: R, index: Index) -> Result<Self, Error> {
+        if let Some(entry) = index.entries.iter().find(|e| e.format() != index.format) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{:?} index contains {:?} entry",
+                    index.format,
+                    entry.format()
+                ),
+            ));
+        }
+        let inner = match index.format {
+            Format::Verbatim => {
+                reader.rewind()?;
+                IndexedReaderInner::Verbatim(reader)
+            }
+            #[cfg(feature = "flate2")]
+            Format::Gz => {
+                let checkpoints = index
+                    .entries
+                    .into_iter()
+                    .filter_map(|e| match e {
+                        IndexEntry::Gz(checkpoint) => Some(checkpoint),
+                        #[allow(unreachable_patterns)]
+                        _ => None,
+                    })
+                    .collect();
+                let index = GzIndex::from_parts(
+                    checkpoints,
+                    index.compressed_size,
+                    index.uncompressed_size,
+                );
+                IndexedReaderInner::Gz(GzIndexedReader::new(reader, index))
+            }
+            _ => IndexedReaderInner::Entries(EntryReader {
+                reader,
+                index,
+                current: None,
+                buf: Vec::new(),
+                position: 0,
+            }),
+        };
+        Ok(Self { inner })
+    }
+
+    /// Read the data at the supplied offset in the decompressed data.
+    ///
+    /// This is a shorthand for seek followed by read.
+    pub fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<usize, Error> {
+        self.seek(SeekFrom::Start(offset))?;
+        self.read(buf)
+    }
+
+    /// Return the underlying reader.
+    pub fn into_inner(self) -> R {
+        match self.inner {
+            IndexedReaderInner::Verbatim(reader) => reader,
+            #[cfg(feature = "flate2")]
+            IndexedReaderInner::Gz(reader) => reader.into_inner(),
+            IndexedReaderInner::Entries(reader) => reader.reader,
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for IndexedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        match self.inner {
+            IndexedReaderInner::Verbatim(ref mut reader) => reader.read(buf),
+            #[cfg(feature = "flate2")]
+            IndexedReaderInner::Gz(ref mut reader) => reader.read(buf),
+            IndexedReaderInner::Entries(ref mut reader) => reader.read(buf),
+        }
+    }
+}
+
+impl<R: Read + Seek> Seek for IndexedReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Error> {
+        match self.inner {
+            IndexedReaderInner::Verbatim(ref mut reader) => reader.seek(pos),
+            #[cfg(feature = "flate2")]
+            IndexedReaderInner::Gz(ref mut reader) => reader.seek(pos),
+            IndexedReaderInner::Entries(ref mut reader) => reader.seek(pos),
+        }
+    }
+}
+
+/// Decompresses the entries into memory.
+struct EntryReader<R> {
+    reader: R,
+    index: Index,
+    // the index of the decompressed entry
+    current: Option<usize>,
+    buf: Vec<u8>,
+    position: u64,
+}
+
+impl<R: Read + Seek> EntryReader<R> {
+    fn decode(&mut self, i: usize) -> Result<(), Error> {
+        if self.current == Some(i) {
+            return Ok(());
+        }
+        self.current = None;
+        self.buf = match &self.index.entries[i] {
+            #[cfg(feature = "bzip2")]
+            IndexEntry::Bz { block, .. } => crate::bz_decode_block(&mut self.reader, block),
+            #[cfg(feature = "xz")]
+            IndexEntry::Xz(block) => crate::xz_decode_block(&mut self.reader, block),
+            #[cfg(feature = "zstd")]
+            IndexEntry::Zstd(frame) => crate::zstd_decode_frame(&mut self.reader, frame),
+            #[allow(unreachable_patterns)]
+            _ => Err(invalid_data("unexpected deko index entry")),
+        }?;
+        self.current = Some(i);
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for EntryReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let i = self
+            .index
+            .entries
+            .partition_point(|e| e.uncompressed_offset() <= self.position);
+        let Some(i) = i.checked_sub(1) else {
+            return Ok(0);
+        };
+        self.decode(i)?;
+        let start = self.position - self.index.entries[i].uncompressed_offset();
+        let Ok(start) = usize::try_from(start) else {
+            return Ok(0);
+        };
+        if start >= self.buf.len() {
+            // past the end of the data
+            return Ok(0);
+        }
+        let n = (self.buf.len() - start).min(buf.len());
+        buf[..n].copy_from_slice(&self.buf[start..(start + n)]);
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for EntryReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Error> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.index.uncompressed_size.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        };
+        let Some(position) = position else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            ));
+        };
+        self.position = position;
+        Ok(position)
+    }
+}
+
+fn format_to_id(format: Format) -> u8 {
+    match format {
+        Format::Verbatim => 0,
+        #[cfg(feature = "flate2")]
+        Format::Gz => 1,
+        #[cfg(feature = "bzip2")]
+        Format::Bz => 2,
+        #[cfg(feature = "flate2")]
+        Format::Zlib => 3,
+        #[cfg(feature = "xz")]
+        Format::Xz => 4,
+        #[cfg(feature = "zstd")]
+        Format::Zstd => 5,
+        #[cfg(feature = "zip")]
+        Format::Zip => 6,
+        #[cfg(feature = "mscompress")]
+        Format::Szdd => 7,
+        #[cfg(feature = "mscompress")]
+        Format::Kwaj => 8,
+        #[cfg(feature = "lz4")]
+        Format::Lz4 => 9,
+        #[cfg(feature = "brotli")]
+        Format::Brotli => 10,
+        #[cfg(feature = "lzop")]
+        Format::Lzop => 11,
+        #[cfg(feature = "lzfse")]
+        Format::Lzfse => 13,
+        #[cfg(feature = "flate2")]
+        Format::Deflate => 12,
+        // never stored in the index
+        Format::Other(..) => u8::MAX,
+    }
+}
+
+fn format_from_id(id: u8) -> Result<Format, Error> {
+    Format::ALL
+        .iter()
+        .copied()
+        .find(|format| format_to_id(*format) == id)
+        .ok_or_else(|| Error::new(ErrorKind::Unsupported, "unsupported format in deko index"))
+}
+
+fn read_u8<R: Read>(mut reader: R) -> Result<u8, Error> {
+    let mut bytes = [0_u8; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+#[cfg(any(feature = "bzip2", feature = "flate2"))]
+fn read_u32<R: Read>(mut reader: R) -> Result<u32, Error> {
+    let mut bytes = [0_u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(mut reader: R) -> Result<u64, Error> {
+    let mut bytes = [0_u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn invalid_data(message: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+const INDEX_MAGIC: [u8; 8] = *b"DEKOINDX";
+const INDEX_VERSION: u8 = 1;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::write::AnyEncoder;
+    use crate::write::Compression;
+
+    #[test]
+    fn random_access() {
+        arbtest(|u| {
+            // verbatim data might be detected as some other format
+            let format = *u.choose(&Format::ALL[1..])?;
+            if !supports_random_access(format) {
+                return Ok(());
+            }
+            let members: Vec<Vec<u8>> = u.arbitrary()?;
+            let mut compressed = Vec::new();
+            for member in members.iter() {
+                let mut writer = AnyEncoder::new(Vec::new(), format, Compression::Fast).unwrap();
+                writer.write_all(member).unwrap();
+                compressed.extend(writer.finish().unwrap());
+            }
+            if compressed.is_empty() {
+                return Ok(());
+            }
+            let expected = members.concat();
+            let index = Index::build(Cursor::new(&compressed), 1024).unwrap();
+            assert_eq!(format, index.format());
+            assert_eq!(compressed.len() as u64, index.compressed_size());
+            assert_eq!(expected.len() as u64, index.uncompressed_size());
+            let mut file = Vec::new();
+            index.write_to(&mut file).unwrap();
+            let actual_index = Index::read_from(&file[..]).unwrap();
+            assert_eq!(index, actual_index);
+            let mut reader = IndexedReader::new(Cursor::new(&compressed), index).unwrap();
+            for _ in 0..u.int_in_range(0..=10)? {
+                let offset = u.int_in_range(0..=expected.len())?;
+                let len = u.int_in_range(0..=expected.len() - offset)?;
+                let mut actual = vec![0_u8; len];
+                reader.seek(SeekFrom::Start(offset as u64)).unwrap();
+                reader.read_exact(&mut actual).unwrap();
+                assert_eq!(&expected[offset..(offset + len)], &actual);
+            }
+            reader.rewind().unwrap();
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, actual);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn verbatim() {
+        let expected = b"hello world";
+        let index = Index::build(Cursor::new(expected), 1024).unwrap();
+        assert_eq!(Format::Verbatim, index.format());
+        let mut reader = IndexedReader::new(Cursor::new(expected), index).unwrap();
+        let mut actual = [0_u8; 5];
+        reader.read_at(&mut actual, 6).unwrap();
+        assert_eq!(b"world", &actual);
+    }
+
+    #[test]
+    fn invalid_index() {
+        assert!(Index::read_from(&b"DEKOINDX\x01\xff"[..]).is_err());
+        assert!(Index::read_from(&b"garbage"[..]).is_err());
+    }
+
+    fn supports_random_access(format: Format) -> bool {
+        #[cfg(feature = "flate2")]
+        if matches!(format, Format::Zlib | Format::Deflate) {
+            return false;
+        }
+        #[cfg(feature = "zip")]
+        if format == Format::Zip {
+            return false;
+        }
+        #[cfg(feature = "mscompress")]
+        if matches!(format, Format::Szdd | Format::Kwaj) {
+            return false;
+        }
+        #[cfg(feature = "lz4")]
+        if format == Format::Lz4 {
+            return false;
+        }
+        #[cfg(feature = "brotli")]
+        if format == Format::Brotli {
+            return false;
+        }
+        #[cfg(feature = "lzop")]
+        if format == Format::Lzop {
+            return false;
+        }
+        #[cfg(feature = "lzfse")]
+        if format == Format::Lzfse {
+            return false;
+        }
+        let _ = format;
+        true
+    }
+}
@@ -0,0 +1,583 @@
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+
+use flate2::Crc;
+
+/// Streaming gzip decoder that exposes deflate block boundaries.
+///
+/// The decoder can be suspended at any block boundary and later resumed from the bit position
+/// and the last 32 KiB of the output (the window).
+/// This is what random access into gzip files is built upon.
+/// The decoder is much slower than `flate2` and is only used where the block boundaries matter.
+pub(crate) struct GzInflater<R> {
+    bits: BitReader<R>,
+    state: State,
+    last_block: bool,
+    window: Window,
+    // `None` when decoding started in the middle of a member
+    crc: Option<Crc>,
+}
+
+impl<R: Read> GzInflater<R> {
+    /// Start decoding at the gzip header located at `offset`.
+    pub(crate) fn new(reader: R, offset: u64) -> Self {
+        Self {
+            bits: BitReader::new(reader, offset),
+            state: State::MemberHeader,
+            last_block: false,
+            window: Window::new(),
+            crc: Some(Crc::new()),
+        }
+    }
+
+    /// Resume decoding at the deflate block boundary.
+    ///
+    /// The reader should be positioned at `offset`.
+    /// The first `skip_bits` bits at this offset belong to the previous block.
+    pub(crate) fn reset(&mut self, offset: u64, skip_bits: u8, window: &[u8]) -> Result<(), Error> {
+        if skip_bits >= 8 {
+            return Err(invalid_data("invalid bit offset"));
+        }
+        self.bits.reset(offset);
+        self.bits.bits(skip_bits as u32)?;
+        self.window = Window::new();
+        self.window.extend(window);
+        self.state = State::BlockHeader;
+        self.last_block = false;
+        self.crc = None;
+        Ok(())
+    }
+
+    /// Returns `true` if the decoder is at the deflate block boundary.
+    pub(crate) fn at_block_boundary(&self) -> bool {
+        matches!(self.state, State::BlockHeader)
+    }
+
+    /// Returns `true` if the decoder is at the start of the first block of a member.
+    pub(crate) fn at_member_start(&self) -> bool {
+        self.at_block_boundary() && self.window.len == 0
+    }
+
+    /// The position of the next unread bit in the input stream.
+    pub(crate) fn bit_position(&self) -> u64 {
+        self.bits.bit_position()
+    }
+
+    /// The last 32 KiB of the output of the current member.
+    pub(crate) fn window(&self) -> Vec<u8> {
+        self.window.to_vec()
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        &mut self.bits.reader
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.bits.reader
+    }
+
+    /// Decode the next portion of the data and append it to `out`.
+    ///
+    /// Returns `false` when the end of the stream is reached.
+    pub(crate) fn step(&mut self, out: &mut Vec<u8>) -> Result<bool, Error> {
+        let start = out.len();
+        match std::mem::replace(&mut self.state, State::Done) {
+            State::MemberHeader => {
+                self.read_member_header()?;
+                self.state = State::BlockHeader;
+            }
+            State::BlockHeader => {
+                self.last_block = self.bits.bits(1)? == 1;
+                self.state = match self.bits.bits(2)? {
+                    0 => {
+                        self.bits.align();
+                        let len = self.bits.aligned_u16()?;
+                        let nlen = self.bits.aligned_u16()?;
+                        if len != !nlen {
+                            return Err(invalid_data("invalid stored block length"));
+                        }
+                        State::Stored(len)
+                    }
+                    1 => State::Huffman(Box::new(Tables::fixed()?)),
+                    2 => State::Huffman(Box::new(Tables::dynamic(&mut self.bits)?)),
+                    _ => return Err(invalid_data("invalid deflate block type")),
+                };
+            }
+            State::Stored(remaining) => {
+                for _ in 0..remaining {
+                    let byte = self.bits.aligned_byte()?;
+                    self.window.push(byte);
+                    out.push(byte);
+                }
+                self.state = self.end_of_block();
+            }
+            State::Huffman(tables) => {
+                if self.decode_huffman(&tables, out)? {
+                    self.state = self.end_of_block();
+                } else {
+                    self.state = State::Huffman(tables);
+                }
+            }
+            State::MemberTrailer => {
+                self.read_member_trailer()?;
+                self.state = if self.bits.at_eof()? {
+                    State::Done
+                } else {
+                    self.window = Window::new();
+                    self.crc = Some(Crc::new());
+                    State::MemberHeader
+                };
+            }
+            State::Done => return Ok(false),
+        }
+        if let Some(crc) = self.crc.as_mut() {
+            crc.update(&out[start..]);
+        }
+        Ok(true)
+    }
+
+    fn end_of_block(&self) -> State {
+        if self.last_block {
+            State::MemberTrailer
+        } else {
+            State::BlockHeader
+        }
+    }
+
+    /// Returns `true` when the end of block is reached.
+    fn decode_huffman(&mut self, tables: &Tables, out: &mut Vec<u8>) -> Result<bool, Error> {
+        let limit = out.len() + MAX_STEP_OUTPUT;
+        while out.len() < limit {
+            let symbol = tables.literal.decode(&mut self.bits)?;
+            match symbol {
+                0..=255 => {
+                    self.window.push(symbol as u8);
+                    out.push(symbol as u8);
+                }
+                END_OF_BLOCK => return Ok(true),
+                _ => {
+                    let i = (symbol - 257) as usize;
+                    if i >= LENGTH_BASE.len() {
+                        return Err(invalid_data("invalid deflate length code"));
+                    }
+                    let len =
+                        LENGTH_BASE[i] as usize + self.bits.bits(LENGTH_EXTRA[i] as u32)? as usize;
+                    let i = tables.distance.decode(&mut self.bits)? as usize;
+                    if i >= DISTANCE_BASE.len() {
+                        return Err(invalid_data("invalid deflate distance code"));
+                    }
+                    let distance = DISTANCE_BASE[i] as usize
+                        + self.bits.bits(DISTANCE_EXTRA[i] as u32)? as usize;
+                    if distance > self.window.len {
+                        return Err(invalid_data("deflate distance is too far back"));
+                    }
+                    for _ in 0..len {
+                        let byte = self.window.get(distance);
+                        self.window.push(byte);
+                        out.push(byte);
+                    }
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    fn read_member_header(&mut self) -> Result<(), Error> {
+        let mut header = [0_u8; 10];
+        for byte in header.iter_mut() {
+            *byte = self.bits.aligned_byte()?;
+        }
+        if header[0..3] != [0x1f, 0x8b, 0x08] {
+            return Err(invalid_data("invalid gzip header"));
+        }
+        let flags = header[3];
+        // the checksum of the header bytes that precede the header CRC
+        let mut header_crc = Crc::new();
+        header_crc.update(&header);
+        if flags & FEXTRA != 0 {
+            let len = self.bits.aligned_u16()?;
+            header_crc.update(&len.to_le_bytes());
+            for _ in 0..len {
+                self.header_byte(&mut header_crc)?;
+            }
+        }
+        if flags & FNAME != 0 {
+            while self.header_byte(&mut header_crc)? != 0 {}
+        }
+        if flags & FCOMMENT != 0 {
+            while self.header_byte(&mut header_crc)? != 0 {}
+        }
+        if flags & FHCRC != 0 && self.bits.aligned_u16()? != header_crc.sum() as u16 {
+            return Err(invalid_data("gzip header checksum mismatch"));
+        }
+        Ok(())
+    }
+
+    fn header_byte(&mut self, crc: &mut Crc) -> Result<u8, Error> {
+        let byte = self.bits.aligned_byte()?;
+        crc.update(&[byte]);
+        Ok(byte)
+    }
+
+    fn read_member_trailer(&mut self) -> Result<(), Error> {
+        self.bits.align();
+        let crc = self.bits.aligned_u32()?;
+        let size = self.bits.aligned_u32()?;
+        if let Some(actual) = self.crc.as_ref() {
+            if crc != actual.sum() || size != actual.amount() {
+                return Err(invalid_data("gzip checksum mismatch"));
+            }
+        }
+        Ok(())
+    }
+}
+
+enum State {
+    MemberHeader,
+    BlockHeader,
+    Stored(u16),
+    Huffman(Box<Tables>),
+    MemberTrailer,
+    Done,
+}
+
+struct Tables {
+    literal: Huffman,
+    distance: Huffman,
+}
+
+impl Tables {
+    fn fixed() -> Result<Self, Error> {
+        let mut lengths = [0_u8; 288];
+        lengths[0..144].fill(8);
+        lengths[144..256].fill(9);
+        lengths[256..280].fill(7);
+        lengths[280..288].fill(8);
+        Ok(Self {
+            literal: Huffman::new(&lengths)?,
+            distance: Huffman::new(&[5; 30])?,
+        })
+    }
+
+    fn dynamic<R: Read>(bits: &mut BitReader<R>) -> Result<Self, Error> {
+        let num_literals = bits.bits(5)? as usize + 257;
+        let num_distances = bits.bits(5)? as usize + 1;
+        let num_code_lengths = bits.bits(4)? as usize + 4;
+        if num_literals > 286 || num_distances > 30 {
+            return Err(invalid_data("invalid deflate code lengths"));
+        }
+        let mut code_lengths = [0_u8; 19];
+        for i in CODE_LENGTH_ORDER.iter().take(num_code_lengths) {
+            code_lengths[*i] = bits.bits(3)? as u8;
+        }
+        let code = Huffman::new(&code_lengths)?;
+        let mut lengths = vec![0_u8; num_literals + num_distances];
+        let mut i = 0;
+        while i < lengths.len() {
+            let symbol = code.decode(bits)?;
+            let (value, repeat) = match symbol {
+                0..=15 => (symbol as u8, 1),
+                16 => {
+                    let Some(previous) = i.checked_sub(1).map(|j| lengths[j]) else {
+                        return Err(invalid_data("invalid deflate code lengths"));
+                    };
+                    (previous, 3 + bits.bits(2)? as usize)
+                }
+                17 => (0, 3 + bits.bits(3)? as usize),
+                _ => (0, 11 + bits.bits(7)? as usize),
+            };
+            if i + repeat > lengths.len() {
+                return Err(invalid_data("invalid deflate code lengths"));
+            }
+            lengths[i..(i + repeat)].fill(value);
+            i += repeat;
+        }
+        if lengths[END_OF_BLOCK as usize] == 0 {
+            return Err(invalid_data("missing deflate end-of-block code"));
+        }
+        Ok(Self {
+            literal: Huffman::new(&lengths[..num_literals])?,
+            distance: Huffman::new(&lengths[num_literals..])?,
+        })
+    }
+}
+
+/// Canonical Huffman code.
+struct Huffman {
+    // the number of symbols of each length
+    count: [u16; MAX_BITS + 1],
+    // symbols ordered by their codes
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, Error> {
+        let mut count = [0_u16; MAX_BITS + 1];
+        for len in lengths.iter() {
+            count[*len as usize] += 1;
+        }
+        let mut left: i32 = 1;
+        for n in count.iter().skip(1) {
+            left <<= 1;
+            left -= *n as i32;
+            if left < 0 {
+                return Err(invalid_data("over-subscribed huffman code"));
+            }
+        }
+        let mut offsets = [0_u16; MAX_BITS + 1];
+        for len in 1..MAX_BITS {
+            offsets[len + 1] = offsets[len] + count[len];
+        }
+        let mut symbols = vec![0_u16; lengths.len()];
+        for (symbol, len) in lengths.iter().enumerate() {
+            if *len != 0 {
+                symbols[offsets[*len as usize] as usize] = symbol as u16;
+                offsets[*len as usize] += 1;
+            }
+        }
+        count[0] = 0;
+        Ok(Self { count, symbols })
+    }
+
+    fn decode<R: Read>(&self, bits: &mut BitReader<R>) -> Result<u16, Error> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for len in 1..=MAX_BITS {
+            code |= bits.bits(1)? as i32;
+            let count = self.count[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+        Err(invalid_data("invalid huffman code"))
+    }
+}
+
+/// The last 32 KiB of the output.
+struct Window {
+    buf: Vec<u8>,
+    pos: usize,
+    len: usize,
+}
+
+impl Window {
+    fn new() -> Self {
+        Self {
+            buf: vec![0; WINDOW_SIZE],
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    #[inline]
+    fn push(&mut self, byte: u8) {
+        self.buf[self.pos] = byte;
+        self.pos = (self.pos + 1) % WINDOW_SIZE;
+        if self.len != WINDOW_SIZE {
+            self.len += 1;
+        }
+    }
+
+    fn extend(&mut self, bytes: &[u8]) {
+        for byte in bytes.iter() {
+            self.push(*byte);
+        }
+    }
+
+    /// Get the byte `distance` bytes back.
+    #[inline]
+    fn get(&self, distance: usize) -> u8 {
+        self.buf[(self.pos + WINDOW_SIZE - distance) % WINDOW_SIZE]
+    }
+
+    fn to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.len);
+        let start = (self.pos + WINDOW_SIZE - self.len) % WINDOW_SIZE;
+        if start + self.len <= WINDOW_SIZE {
+            bytes.extend_from_slice(&self.buf[start..(start + self.len)]);
+        } else {
+            bytes.extend_from_slice(&self.buf[start..]);
+            bytes.extend_from_slice(&self.buf[..self.pos]);
+        }
+        bytes
+    }
+}
+
+/// Reads the input stream bit by bit, least-significant bit first.
+struct BitReader<R> {
+    reader: R,
+    buf: Vec<u8>,
+    first: usize,
+    last: usize,
+    bit_buf: u64,
+    bit_count: u32,
+    // the offset of the next byte that is moved to the bit buffer
+    offset: u64,
+}
+
+impl<R: Read> BitReader<R> {
+    fn new(reader: R, offset: u64) -> Self {
+        Self {
+            reader,
+            buf: vec![0; BUFFER_SIZE],
+            first: 0,
+            last: 0,
+            bit_buf: 0,
+            bit_count: 0,
+            offset,
+        }
+    }
+
+    fn reset(&mut self, offset: u64) {
+        self.first = 0;
+        self.last = 0;
+        self.bit_buf = 0;
+        self.bit_count = 0;
+        self.offset = offset;
+    }
+
+    fn bit_position(&self) -> u64 {
+        self.offset * 8 - self.bit_count as u64
+    }
+
+    fn fill(&mut self) -> Result<bool, Error> {
+        if self.first == self.last {
+            let n = loop {
+                match self.reader.read(&mut self.buf) {
+                    Ok(n) => break n,
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                }
+            };
+            self.first = 0;
+            self.last = n;
+        }
+        Ok(self.first != self.last)
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        if !self.fill()? {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        let byte = self.buf[self.first];
+        self.first += 1;
+        self.offset += 1;
+        Ok(byte)
+    }
+
+    #[inline]
+    fn bits(&mut self, n: u32) -> Result<u32, Error> {
+        while self.bit_count < n {
+            let byte = self.byte()?;
+            self.bit_buf |= (byte as u64) << self.bit_count;
+            self.bit_count += 8;
+        }
+        let value = (self.bit_buf & ((1_u64 << n) - 1)) as u32;
+        self.bit_buf >>= n;
+        self.bit_count -= n;
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        let n = self.bit_count % 8;
+        self.bit_buf >>= n;
+        self.bit_count -= n;
+    }
+
+    fn aligned_byte(&mut self) -> Result<u8, Error> {
+        Ok(self.bits(8)? as u8)
+    }
+
+    fn aligned_u16(&mut self) -> Result<u16, Error> {
+        Ok(self.bits(16)? as u16)
+    }
+
+    fn aligned_u32(&mut self) -> Result<u32, Error> {
+        let low = self.bits(16)?;
+        let high = self.bits(16)?;
+        Ok(low | (high << 16))
+    }
+
+    fn at_eof(&mut self) -> Result<bool, Error> {
+        Ok(self.bit_count == 0 && !self.fill()?)
+    }
+}
+
+fn invalid_data(message: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+const MAX_BITS: usize = 15;
+const WINDOW_SIZE: usize = 32 * 1024;
+const BUFFER_SIZE: usize = 64 * 1024;
+const MAX_STEP_OUTPUT: usize = 64 * 1024;
+const END_OF_BLOCK: u16 = 256;
+
+const FHCRC: u8 = 1 << 1;
+const FEXTRA: u8 = 1 << 2;
+const FNAME: u8 = 1 << 3;
+const FCOMMENT: u8 = 1 << 4;
+
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use arbtest::arbtest;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    use super::*;
+
+    #[test]
+    fn inflate() {
+        arbtest(|u| {
+            let members: Vec<Vec<u8>> = u.arbitrary()?;
+            let members = if members.is_empty() {
+                vec![Vec::new()]
+            } else {
+                members
+            };
+            let mut compressed = Vec::new();
+            for member in members.iter() {
+                let level = Compression::new(u.int_in_range(0..=9)?);
+                let mut writer = GzEncoder::new(Vec::new(), level);
+                writer.write_all(member).unwrap();
+                compressed.extend(writer.finish().unwrap());
+            }
+            let mut inflater = GzInflater::new(&compressed[..], 0);
+            let mut actual = Vec::new();
+            while inflater.step(&mut actual).unwrap() {}
+            assert_eq!(members.concat(), actual);
+            Ok(())
+        });
+    }
+}
@@ -1,20 +1,41 @@
-macro_rules! import_decoders {
-    (Read) => {
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+
+use crate::DecoderOptions;
+use crate::Detection;
+use crate::Format;
+
+macro_rules! define_inner_decoder {
+    ($trait: ident) => {
+        use std::marker::PhantomData;
+
+        use crate::find_magic;
+        use crate::DecoderOptions;
+        use crate::DecoderParams;
+        use crate::MAX_MAGIC_BYTES;
+        use crate::TRIAL_INPUT_LEN;
+
+        #[cfg(feature = "brotli")]
+        use crate::BrotliDecoder;
         #[cfg(feature = "bzip2")]
-        use bzip2::read::BzDecoder;
+        use crate::BzDecoder;
+        #[cfg(feature = "lz4")]
+        use crate::Lz4Decoder;
+        #[cfg(feature = "lzfse")]
+        use crate::LzfseDecoder;
+        #[cfg(feature = "lzop")]
+        use crate::LzopDecoder;
+        #[cfg(feature = "mscompress")]
+        use crate::MsCompressDecoder;
+        #[cfg(feature = "zip")]
+        use crate::ZipDecoder;
         #[cfg(feature = "flate2")]
-        use flate2::read::MultiGzDecoder;
+        use crate::ZlibDictDecoder;
         #[cfg(feature = "flate2")]
-        use flate2::read::ZlibDecoder;
-        #[cfg(feature = "xz")]
-        use xz::read::XzDecoder;
-        // TODO ???
-        #[cfg(feature = "zstd")]
-        use zstd::stream::read::Decoder as ZstdDecoder;
-    };
-    (BufRead) => {
-        #[cfg(feature = "bzip2")]
-        use bzip2::bufread::BzDecoder;
+        use flate2::bufread::DeflateDecoder;
+        #[cfg(feature = "flate2")]
+        use flate2::bufread::GzDecoder;
         #[cfg(feature = "flate2")]
         use flate2::bufread::MultiGzDecoder;
         #[cfg(feature = "flate2")]
@@ -22,145 +43,875 @@ macro_rules! import_decoders {
         #[cfg(feature = "xz")]
         use xz::bufread::XzDecoder;
         #[cfg(feature = "zstd")]
-        use zstd::stream::read::Decoder as ZstdDecoder;
-    };
-}
-
-pub(crate) use import_decoders;
-
-macro_rules! define_inner_decoder {
-    ($trait: ident) => {
-        use crate::MAX_MAGIC_BYTES;
-
-        crate::import_decoders!($trait);
+        type ZstdDecoder<'a, R> = zstd::stream::zio::Reader<R, crate::ZstdWindowLimit<'a>>;
 
-        enum InnerDecoder<R: $trait> {
-            Empty(Empty),
-            Reader(R),
+        enum InnerDecoder<'a, R: $trait> {
+            Reader(R, PhantomData<&'a ()>),
             #[cfg(feature = "flate2")]
-            Gz(MultiGzDecoder<R>),
+            Gz(MultiGzDecoder<crate::buffered!($trait, R)>),
+            #[cfg(feature = "flate2")]
+            GzMember(GzDecoder<crate::buffered!($trait, R)>),
             #[cfg(feature = "bzip2")]
-            Bz(BzDecoder<R>),
+            Bz(BzDecoder<crate::buffered!($trait, R)>),
+            #[cfg(feature = "flate2")]
+            Zlib(ZlibDecoder<crate::buffered!($trait, R)>),
             #[cfg(feature = "flate2")]
-            Zlib(ZlibDecoder<R>),
+            ZlibDict(ZlibDictDecoder<crate::buffered!($trait, R)>),
             #[cfg(feature = "xz")]
-            Xz(XzDecoder<R>),
+            Xz(XzDecoder<crate::buffered!($trait, R)>),
             #[cfg(feature = "zstd")]
-            Zstd(crate::zstd_decoder!($trait, R)),
+            Zstd(ZstdDecoder<'a, crate::buffered!($trait, R)>),
+            #[cfg(feature = "zip")]
+            Zip(ZipDecoder<crate::buffered!($trait, R)>),
+            #[cfg(feature = "mscompress")]
+            Szdd(MsCompressDecoder<crate::buffered!($trait, R)>),
+            #[cfg(feature = "mscompress")]
+            Kwaj(MsCompressDecoder<crate::buffered!($trait, R)>),
+            #[cfg(feature = "lzop")]
+            Lzop(LzopDecoder<crate::buffered!($trait, R)>),
+            #[cfg(feature = "lzfse")]
+            Lzfse(LzfseDecoder<crate::buffered!($trait, R)>),
+            #[cfg(feature = "lz4")]
+            Lz4(Box<Lz4Decoder<crate::buffered!($trait, R)>>),
+            #[cfg(feature = "brotli")]
+            Brotli(BrotliDecoder<crate::buffered!($trait, R)>),
+            #[cfg(feature = "flate2")]
+            Deflate(DeflateDecoder<crate::buffered!($trait, R)>),
+            Custom(crate::CustomReader<crate::buffered!($trait, R)>),
         }
 
-        impl<R: $trait> InnerDecoder<MagicReader<R>> {
-            fn new(
-                mut reader: MagicReader<R>,
-                fail_on_unknown_format: bool,
-            ) -> Result<Self, Error> {
+        /// The state of [AnyDecoder].
+        enum State<'a, R: $trait> {
+            /// The format hasn't been detected yet.
+            Pending(MagicReader<R>),
+            /// The format has been detected.
+            Active(InnerDecoder<'a, MagicReader<R>>),
+        }
+
+        impl<'a, R: $trait> InnerDecoder<'a, MagicReader<R>> {
+            const fn reader(reader: MagicReader<R>) -> Self {
+                InnerDecoder::Reader(reader, PhantomData)
+            }
+
+            /// Detect the format without consuming the reader.
+            ///
+            /// Gzip XFL and OS bytes are stored in `gz_fields`,
+            /// the details of the detection are stored in `detected`.
+            fn detect(
+                reader: &mut MagicReader<R>,
+                options: &DecoderOptions,
+                #[cfg(feature = "zstd")] zstd_dictionary: Option<
+                    &'a zstd::dict::DecoderDictionary<'a>,
+                >,
+                gz_fields: &mut Option<[u8; 2]>,
+                detected: &mut Option<crate::Detection>,
+            ) -> Result<DecoderParams<'a>, Error> {
+                // the frame header is stored in the first non-skippable frame
+                #[cfg(feature = "zstd")]
+                if matches!(options.format, None | Some(crate::Format::Zstd)) {
+                    reader.skip_zstd_skippable_frames()?;
+                }
+                let in_memory = reader.is_in_memory();
                 let magic = reader.read_magic()?;
-                let magic = if magic.len() >= MAX_MAGIC_BYTES {
+                // in-memory magic is complete even if it's short
+                let mut magic = if magic.len() >= MAX_MAGIC_BYTES || in_memory {
                     magic
                 } else {
                     reader.read_magic_slow()?
                 };
-                match magic {
-                    // https://tukaani.org/xz/xz-file-format-1.0.4.txt
-                    #[cfg(feature = "xz")]
-                    [0xfd, b'7', b'z', b'X', b'Z', 0, ..] => {
-                        Ok(InnerDecoder::Xz(XzDecoder::new_multi_decoder(reader)))
+                if options.format.is_none() && !options.custom_formats.is_empty() {
+                    let len = options
+                        .custom_formats
+                        .iter()
+                        .map(crate::CustomFormat::magic_len)
+                        .max()
+                        .unwrap_or(0);
+                    if len > magic.len() && !in_memory {
+                        if let Some(limits) = options.limits {
+                            if len > limits.detection_buffer {
+                                return Err(crate::memory_limit_exceeded());
+                            }
+                        }
+                        magic = reader.read_magic_window(len)?;
+                    }
+                    if let Some(format) = options
+                        .custom_formats
+                        .iter()
+                        .find(|format| format.matches(magic))
+                    {
+                        *detected = Some(crate::Detection {
+                            format: crate::Format::Other(format.name()),
+                            magic_len: format.magic_len(),
+                            heuristic: false,
+                        });
+                        return Ok(DecoderParams::Custom(format.name(), format.new_decoder()));
+                    }
+                }
+                let window = options.magic_scan_window;
+                if options.trial_decode && options.format.is_none() {
+                    let len = window.saturating_add(TRIAL_INPUT_LEN);
+                    if let Some(limits) = options.limits {
+                        if len > limits.detection_buffer {
+                            return Err(crate::memory_limit_exceeded());
+                        }
+                    }
+                    let bytes = reader.read_magic_window(len)?;
+                    // the whole stream is buffered
+                    let complete = bytes.len() < len;
+                    match crate::trial_detect(
+                        bytes,
+                        window,
+                        complete,
+                        options,
+                        #[cfg(feature = "zstd")]
+                        zstd_dictionary,
+                    )? {
+                        Some((offset, detection)) => {
+                            reader.skip_buffered(offset);
+                            // the format might not be the first one that matches the magic bytes
+                            let options = DecoderOptions {
+                                format: Some(detection.format),
+                                ..options.clone()
+                            };
+                            *detected = Some(detection);
+                            return DecoderParams::new(
+                                reader.buffered(),
+                                &options,
+                                #[cfg(feature = "zstd")]
+                                zstd_dictionary,
+                                gz_fields,
+                                &mut None,
+                            );
+                        }
+                        // handle as unknown format
+                        None => magic = &[],
+                    }
+                } else if window != 0
+                    && options.format.is_none()
+                    && find_magic(magic, 0, &options.detection_order()).is_none()
+                {
+                    if let Some(limits) = options.limits {
+                        if window.saturating_add(MAX_MAGIC_BYTES) > limits.detection_buffer {
+                            return Err(crate::memory_limit_exceeded());
+                        }
+                    }
+                    let bytes = reader.read_magic_window(window + MAX_MAGIC_BYTES)?;
+                    if let Some(offset) = find_magic(bytes, window, &options.detection_order()) {
+                        reader.skip_buffered(offset);
+                    }
+                    magic = reader.buffered();
+                }
+                #[cfg(feature = "brotli")]
+                if options.format.is_none()
+                    && options.detection_order().contains(&crate::Format::Brotli)
+                    && crate::detect_format_in(magic, &options.detection_order()).is_none()
+                {
+                    if let Some(limits) = options.limits {
+                        if TRIAL_INPUT_LEN > limits.detection_buffer {
+                            return Err(crate::memory_limit_exceeded());
+                        }
                     }
-                    // RFC8878
+                    let bytes = reader.read_magic_window(TRIAL_INPUT_LEN)?;
+                    // the whole stream is buffered
+                    let complete = bytes.len() < TRIAL_INPUT_LEN;
+                    let options = DecoderOptions {
+                        format: crate::brotli_detect(bytes, complete)
+                            .then_some(crate::Format::Brotli),
+                        ..options.clone()
+                    };
+                    *detected = options
+                        .format
+                        .map(|format| crate::Detection::new(format, 0));
+                    // no other format matches, i.e. the magic bytes are irrelevant
+                    return DecoderParams::new(
+                        &[],
+                        &options,
+                        #[cfg(feature = "zstd")]
+                        zstd_dictionary,
+                        gz_fields,
+                        &mut None,
+                    );
+                }
+                if options.fail_on_unknown_format
+                    && options.format.is_none()
+                    && crate::detect_format_in(magic, &options.detection_order()).is_none()
+                {
+                    if let Some(limits) = options.limits {
+                        if crate::ARCHIVE_MAGIC_LEN > limits.detection_buffer {
+                            return Err(crate::memory_limit_exceeded());
+                        }
+                    }
+                    // enough for the encryption headers as well
+                    let bytes = reader.read_magic_window(crate::ARCHIVE_MAGIC_LEN)?;
+                    if let Some(archive) = crate::Archive::from_magic(bytes) {
+                        return Err(Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            format!(
+                                "input is a {} archive, not a compression stream",
+                                archive.name()
+                            ),
+                        ));
+                    }
+                    if let Some(name) = crate::detect_encryption(bytes) {
+                        return Err(Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            format!("input appears to be encrypted ({name})"),
+                        ));
+                    }
+                    magic = reader.buffered();
+                }
+                DecoderParams::new(
+                    magic,
+                    options,
                     #[cfg(feature = "zstd")]
-                    [0x28, 0xb5, 0x2f, 0xfd, ..] => Ok(InnerDecoder::Zstd(
-                        crate::zstd_decoder_new!($trait, reader)?,
+                    zstd_dictionary,
+                    gz_fields,
+                    detected,
+                )
+            }
+
+            /// Create the decoder for the detected format.
+            ///
+            /// Unlike detection, this step can't fail, i.e. the reader is never lost.
+            /// Non-buffered readers are wrapped in a buffer of `buffer_size` bytes.
+            #[allow(unused_variables)]
+            fn new(
+                reader: MagicReader<R>,
+                detection: DecoderParams<'a>,
+                buffer_size: usize,
+            ) -> Self {
+                match detection {
+                    DecoderParams::Verbatim(..) => InnerDecoder::reader(reader),
+                    #[cfg(feature = "xz")]
+                    DecoderParams::Xz(Some(stream)) => InnerDecoder::Xz(XzDecoder::new_stream(
+                        crate::buffered_new!($trait, reader, buffer_size),
+                        stream,
+                    )),
+                    #[cfg(feature = "xz")]
+                    DecoderParams::Xz(None) => InnerDecoder::Xz(XzDecoder::new_multi_decoder(
+                        crate::buffered_new!($trait, reader, buffer_size),
                     )),
-                    // RFC1952
+                    #[cfg(feature = "zstd")]
+                    DecoderParams::Zstd {
+                        decoder,
+                        single_frame,
+                        ..
+                    } => {
+                        let mut decoder = ZstdDecoder::new(
+                            crate::buffered_new!($trait, reader, buffer_size),
+                            decoder,
+                        );
+                        if single_frame {
+                            decoder.set_single_frame();
+                        }
+                        InnerDecoder::Zstd(decoder)
+                    }
                     #[cfg(feature = "flate2")]
-                    [0x1f, 0x8b, 0x08, ..] => Ok(InnerDecoder::Gz(MultiGzDecoder::new(reader))),
-                    // https://en.wikipedia.org/wiki/Bzip2
+                    DecoderParams::Gz { per_member: true } => InnerDecoder::GzMember(
+                        GzDecoder::new(crate::buffered_new!($trait, reader, buffer_size)),
+                    ),
+                    #[cfg(feature = "flate2")]
+                    DecoderParams::Gz { per_member: false } => InnerDecoder::Gz(
+                        MultiGzDecoder::new(crate::buffered_new!($trait, reader, buffer_size)),
+                    ),
                     #[cfg(feature = "bzip2")]
-                    [b'B', b'Z', b'h', ..] => Ok(InnerDecoder::Bz(BzDecoder::new(reader))),
-                    // https://www.rfc-editor.org/rfc/rfc1950
+                    DecoderParams::Bz { small } => InnerDecoder::Bz(BzDecoder::new(
+                        crate::buffered_new!($trait, reader, buffer_size),
+                        small,
+                    )),
                     #[cfg(feature = "flate2")]
-                    [cmf, flg, ..]
-                        if zlib_cm(*cmf) == 8
-                            && zlib_cinfo(*cmf) <= 7
-                            && ((*cmf as u16) * 256 + (*flg as u16)) % 31 == 0 =>
-                    {
-                        Ok(InnerDecoder::Zlib(ZlibDecoder::new(reader)))
+                    DecoderParams::Zlib => InnerDecoder::Zlib(ZlibDecoder::new(
+                        crate::buffered_new!($trait, reader, buffer_size),
+                    )),
+                    #[cfg(feature = "flate2")]
+                    DecoderParams::ZlibDict {
+                        dict_id,
+                        dictionary,
+                    } => InnerDecoder::ZlibDict(ZlibDictDecoder::new(
+                        crate::buffered_new!($trait, reader, buffer_size),
+                        dict_id,
+                        dictionary,
+                    )),
+                    #[cfg(feature = "flate2")]
+                    DecoderParams::Deflate => InnerDecoder::Deflate(DeflateDecoder::new(
+                        crate::buffered_new!($trait, reader, buffer_size),
+                    )),
+                    #[cfg(feature = "zip")]
+                    DecoderParams::Zip => {
+                        // the limited reader ends where the ZIP file ends
+                        let skip_rest = reader.is_limited();
+                        InnerDecoder::Zip(
+                            ZipDecoder::new(crate::buffered_new!($trait, reader, buffer_size))
+                                .skip_rest(skip_rest),
+                        )
+                    }
+                    #[cfg(feature = "mscompress")]
+                    DecoderParams::Szdd => InnerDecoder::Szdd(MsCompressDecoder::szdd(
+                        crate::buffered_new!($trait, reader, buffer_size),
+                    )),
+                    #[cfg(feature = "mscompress")]
+                    DecoderParams::Kwaj => InnerDecoder::Kwaj(MsCompressDecoder::kwaj(
+                        crate::buffered_new!($trait, reader, buffer_size),
+                    )),
+                    #[cfg(feature = "lzop")]
+                    DecoderParams::Lzop => InnerDecoder::Lzop(LzopDecoder::new(
+                        crate::buffered_new!($trait, reader, buffer_size),
+                    )),
+                    #[cfg(feature = "lzfse")]
+                    DecoderParams::Lzfse => InnerDecoder::Lzfse(LzfseDecoder::new(
+                        crate::buffered_new!($trait, reader, buffer_size),
+                    )),
+                    #[cfg(feature = "lz4")]
+                    // the frame state is large, hence boxed
+                    DecoderParams::Lz4 { single_frame } => {
+                        InnerDecoder::Lz4(Box::new(Lz4Decoder::new(
+                            crate::buffered_new!($trait, reader, buffer_size),
+                            single_frame,
+                        )))
+                    }
+                    #[cfg(feature = "brotli")]
+                    DecoderParams::Brotli => InnerDecoder::Brotli(BrotliDecoder::new(
+                        crate::buffered_new!($trait, reader, buffer_size),
+                    )),
+                    DecoderParams::Custom(name, decoder) => {
+                        InnerDecoder::Custom(crate::CustomReader::new(
+                            crate::buffered_new!($trait, reader, buffer_size),
+                            name,
+                            decoder,
+                        ))
+                    }
+                }
+            }
+
+            /// Return the magic reader positioned right after the current member.
+            fn into_reader(self) -> MagicReader<R> {
+                match self {
+                    InnerDecoder::Reader(r, _) => r,
+                    #[cfg(feature = "flate2")]
+                    InnerDecoder::Gz(r) => crate::buffered_into_reader!($trait, r.into_inner()),
+                    #[cfg(feature = "flate2")]
+                    InnerDecoder::GzMember(r) => {
+                        crate::buffered_into_reader!($trait, r.into_inner())
+                    }
+                    #[cfg(feature = "bzip2")]
+                    InnerDecoder::Bz(r) => crate::buffered_into_reader!($trait, r.into_inner()),
+                    #[cfg(feature = "flate2")]
+                    InnerDecoder::Zlib(r) => crate::buffered_into_reader!($trait, r.into_inner()),
+                    #[cfg(feature = "flate2")]
+                    InnerDecoder::ZlibDict(r) => {
+                        crate::buffered_into_reader!($trait, r.into_inner())
+                    }
+                    #[cfg(feature = "flate2")]
+                    InnerDecoder::Deflate(r) => {
+                        crate::buffered_into_reader!($trait, r.into_inner())
+                    }
+                    #[cfg(feature = "xz")]
+                    InnerDecoder::Xz(r) => crate::buffered_into_reader!($trait, r.into_inner()),
+                    #[cfg(feature = "zstd")]
+                    InnerDecoder::Zstd(r) => crate::buffered_into_reader!($trait, r.into_inner()),
+                    #[cfg(feature = "zip")]
+                    InnerDecoder::Zip(r) => crate::buffered_into_reader!($trait, r.into_inner()),
+                    #[cfg(feature = "mscompress")]
+                    InnerDecoder::Szdd(r) => crate::buffered_into_reader!($trait, r.into_inner()),
+                    #[cfg(feature = "mscompress")]
+                    InnerDecoder::Kwaj(r) => crate::buffered_into_reader!($trait, r.into_inner()),
+                    #[cfg(feature = "lzop")]
+                    InnerDecoder::Lzop(r) => crate::buffered_into_reader!($trait, r.into_inner()),
+                    #[cfg(feature = "lzfse")]
+                    InnerDecoder::Lzfse(r) => crate::buffered_into_reader!($trait, r.into_inner()),
+                    #[cfg(feature = "lz4")]
+                    InnerDecoder::Lz4(r) => {
+                        let (r, read_ahead) = r.into_parts();
+                        let mut r = crate::buffered_into_reader!($trait, r);
+                        r.unread(&read_ahead);
+                        r
+                    }
+                    #[cfg(feature = "brotli")]
+                    InnerDecoder::Brotli(r) => {
+                        crate::buffered_into_reader!($trait, r.into_inner())
                     }
-                    // TODO pbzx
-                    _ if fail_on_unknown_format => Err(Error::new(
+                    InnerDecoder::Custom(r) => {
+                        crate::buffered_into_reader!($trait, r.into_inner())
+                    }
+                }
+            }
+        }
+    };
+}
+
+pub(crate) use define_inner_decoder;
+
+/// Everything that is needed to create the decoder for the detected format.
+///
+/// The fallible part of decoder creation happens here, before the reader is moved into the decoder.
+pub(crate) enum DecoderParams<'a> {
+    Verbatim(std::marker::PhantomData<&'a ()>),
+    #[cfg(feature = "xz")]
+    Xz(Option<xz::stream::Stream>),
+    #[cfg(feature = "zstd")]
+    Zstd {
+        decoder: crate::ZstdWindowLimit<'a>,
+        single_frame: bool,
+        /// Whether the frame includes content checksum.
+        checksum: bool,
+        /// The ID of the dictionary that is needed to decompress the frame.
+        dict_id: Option<u32>,
+        /// The size of the decompressed data as stored in the frame header.
+        content_size: Option<u64>,
+    },
+    #[cfg(feature = "flate2")]
+    Gz {
+        per_member: bool,
+    },
+    #[cfg(feature = "bzip2")]
+    Bz {
+        small: bool,
+    },
+    #[cfg(feature = "flate2")]
+    Zlib,
+    /// Zlib stream that was compressed with a preset dictionary.
+    #[cfg(feature = "flate2")]
+    ZlibDict {
+        /// Adler-32 of the dictionary.
+        dict_id: u32,
+        dictionary: Option<Vec<u8>>,
+    },
+    #[cfg(feature = "zip")]
+    Zip,
+    #[cfg(feature = "mscompress")]
+    Szdd,
+    #[cfg(feature = "mscompress")]
+    Kwaj,
+    #[cfg(feature = "lzop")]
+    Lzop,
+    #[cfg(feature = "lzfse")]
+    Lzfse,
+    #[cfg(feature = "lz4")]
+    Lz4 {
+        single_frame: bool,
+    },
+    #[cfg(feature = "brotli")]
+    Brotli,
+    #[cfg(feature = "flate2")]
+    Deflate,
+    Custom(&'static str, Box<dyn crate::CustomDecoder>),
+}
+
+impl<'a> DecoderParams<'a> {
+    /// Detect the format using the `magic` bytes unless the format is set in the `options`.
+    ///
+    /// Gzip XFL and OS bytes are stored in `gz_fields`,
+    /// the details of the detection are stored in `detected`.
+    #[allow(unused_variables)]
+    pub(crate) fn new(
+        magic: &[u8],
+        options: &DecoderOptions,
+        #[cfg(feature = "zstd")] zstd_dictionary: Option<&'a zstd::dict::DecoderDictionary<'a>>,
+        gz_fields: &mut Option<[u8; 2]>,
+        detected: &mut Option<Detection>,
+    ) -> Result<Self, Error> {
+        let format = match options.format {
+            Some(format) => format,
+            None => match detect_in(magic, &options.detection_order()) {
+                Some(detection) => {
+                    *detected = Some(detection);
+                    detection.format
+                }
+                None if options.fail_on_unknown_format => {
+                    return Err(Error::new(
                         ErrorKind::InvalidData,
                         "unknown compression format",
-                    )),
-                    _ => Ok(InnerDecoder::Reader(reader)),
+                    ))
+                }
+                None => Format::Verbatim,
+            },
+        };
+        match format {
+            Format::Verbatim => Ok(Self::Verbatim(std::marker::PhantomData)),
+            #[cfg(feature = "xz")]
+            Format::Xz => match (options.max_memory(), options.stop_at_member_end()) {
+                (None, false) => Ok(Self::Xz(None)),
+                (limit, per_member) => {
+                    // decode the following streams and the padding between them
+                    let flags = if per_member {
+                        0
+                    } else {
+                        xz::stream::CONCATENATED
+                    };
+                    Ok(Self::Xz(Some(xz::stream::Stream::new_stream_decoder(
+                        limit.unwrap_or(u64::MAX),
+                        flags,
+                    )?)))
                 }
+            },
+            #[cfg(feature = "zstd")]
+            Format::Zstd => {
+                use zstd::stream::raw::DParameter;
+                let checksum = matches!(magic, [0x28, 0xb5, 0x2f, 0xfd, descriptor, ..] if descriptor & ZSTD_CHECKSUM != 0);
+                if options.require_zstd_checksum && !checksum {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "zstd content checksum is missing",
+                    ));
+                }
+                let max_window_size = options
+                    .max_memory()
+                    .map(|limit| 1_u64 << zstd_window_log_max(limit));
+                let mut decoder = match zstd_dictionary {
+                    Some(dictionary)
+                        if options.limits.is_some_and(|limits| {
+                            dictionary.as_ddict().sizeof() > limits.zstd_dictionary
+                        }) =>
+                    {
+                        return Err(crate::memory_limit_exceeded());
+                    }
+                    _ if max_window_size.is_some_and(|max_window_size| {
+                        crate::zstd_window_size(magic).is_some_and(|size| size > max_window_size)
+                    }) =>
+                    {
+                        return Err(crate::memory_limit_exceeded());
+                    }
+                    Some(dictionary) => {
+                        zstd::stream::raw::Decoder::with_prepared_dictionary(dictionary)?
+                    }
+                    None => zstd::stream::raw::Decoder::new()?,
+                };
+                if let Some(limit) = options.max_memory() {
+                    decoder.set_parameter(DParameter::WindowLogMax(zstd_window_log_max(limit)))?;
+                }
+                Ok(Self::Zstd {
+                    decoder: crate::ZstdWindowLimit::new(decoder, max_window_size),
+                    single_frame: options.stop_at_member_end() || options.zstd_single_frame,
+                    checksum,
+                    dict_id: crate::zstd_frame_dictionary_id(magic),
+                    content_size: zstd::zstd_safe::get_frame_content_size(magic)
+                        .ok()
+                        .flatten(),
+                })
             }
+            #[cfg(feature = "flate2")]
+            Format::Gz => {
+                if options.require_gz_header_crc
+                    && !matches!(magic, [_, _, _, flags, ..] if flags & GZ_FHCRC != 0)
+                {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "gzip header checksum is missing",
+                    ));
+                }
+                *gz_fields = match magic {
+                    [0x1f, 0x8b, 0x08, _, _, _, _, _, xfl, os, ..] => Some([*xfl, *os]),
+                    _ => None,
+                };
+                Ok(Self::Gz {
+                    per_member: options.stop_at_member_end(),
+                })
+            }
+            #[cfg(feature = "bzip2")]
+            Format::Bz => {
+                let block_size = match magic {
+                    [b'B', b'Z', b'h', block_size, ..] => Some(*block_size),
+                    _ => None,
+                };
+                let small = options.bzip2_small_mode || bz_small(block_size, options.max_memory())?;
+                Ok(Self::Bz { small })
+            }
+            #[cfg(feature = "flate2")]
+            Format::Zlib => match magic {
+                [_, flg, a, b, c, d, ..] if flg & ZLIB_FDICT != 0 => Ok(Self::ZlibDict {
+                    dict_id: u32::from_be_bytes([*a, *b, *c, *d]),
+                    dictionary: options.zlib_dictionary.clone(),
+                }),
+                _ => Ok(Self::Zlib),
+            },
+            #[cfg(feature = "flate2")]
+            Format::Deflate => Ok(Self::Deflate),
+            #[cfg(feature = "zip")]
+            Format::Zip => Ok(Self::Zip),
+            #[cfg(feature = "mscompress")]
+            Format::Szdd => Ok(Self::Szdd),
+            #[cfg(feature = "mscompress")]
+            Format::Kwaj => Ok(Self::Kwaj),
+            #[cfg(feature = "lzop")]
+            Format::Lzop => Ok(Self::Lzop),
+            #[cfg(feature = "lzfse")]
+            Format::Lzfse => Ok(Self::Lzfse),
+            #[cfg(feature = "lz4")]
+            Format::Lz4 => Ok(Self::Lz4 {
+                single_frame: options.stop_at_member_end(),
+            }),
+            #[cfg(feature = "brotli")]
+            Format::Brotli => Ok(Self::Brotli),
+            Format::Other(name) => match options
+                .custom_formats
+                .iter()
+                .find(|format| format.name() == name)
+            {
+                Some(format) => Ok(Self::Custom(name, format.new_decoder())),
+                None => Err(Error::new(
+                    ErrorKind::Unsupported,
+                    "unregistered custom format",
+                )),
+            },
         }
+    }
+}
+
+/// Detect the format using the magic bytes.
+///
+/// The formats are tested in the specified `order`.
+pub(crate) fn detect_format_in(magic: &[u8], order: &[Format]) -> Option<Format> {
+    detect_in(magic, order).map(|detection| detection.format)
+}
+
+/// Detect the format using the magic bytes and report how many bytes matched.
+///
+/// The formats are tested in the specified `order`.
+pub(crate) fn detect_in(magic: &[u8], order: &[Format]) -> Option<Detection> {
+    order.iter().find_map(|format| {
+        magic_match_len(*format, magic).map(|magic_len| Detection::new(*format, magic_len))
+    })
+}
 
+/// Check whether `magic` bytes are the magic bytes of the `format`.
+///
+/// Returns the number of bytes that matched.
+fn magic_match_len(format: Format, magic: &[u8]) -> Option<usize> {
+    match format {
+        // https://www.rfc-editor.org/rfc/rfc1950
         #[cfg(feature = "flate2")]
-        const fn zlib_cm(x: u8) -> u8 {
-            x & 0b1111
-        }
+        Format::Zlib => match magic {
+            [cmf, flg, ..]
+                if zlib_cm(*cmf) == 8
+                    && zlib_cinfo(*cmf) <= 7
+                    && ((*cmf as u16) * 256 + (*flg as u16)).is_multiple_of(31) =>
+            {
+                Some(2)
+            }
+            _ => None,
+        },
+        // brotli has no magic bytes and is detected by `brotli_detect`
+        // TODO pbzx
+        _ => crate::SIGNATURES
+            .iter()
+            .filter(|signature| signature.format == format)
+            .find_map(|signature| signature.match_len(magic)),
+    }
+}
 
+/// Find the first offset not larger than `window`
+/// at which the magic bytes of a supported format start.
+///
+/// Zlib is only considered at zero offset, because its two-byte header occurs in arbitrary data
+/// too often.
+pub(crate) fn find_magic(bytes: &[u8], window: usize, order: &[Format]) -> Option<usize> {
+    (0..=window.min(bytes.len())).find(|offset| match detect_format_in(&bytes[*offset..], order) {
         #[cfg(feature = "flate2")]
-        const fn zlib_cinfo(x: u8) -> u8 {
-            (x >> 4) & 0b1111
+        Some(Format::Zlib) => *offset == 0,
+        format => format.is_some(),
+    })
+}
+
+/// Find the first offset not larger than `window` at which a supported format starts
+/// and the first bytes of the stream can be decoded.
+///
+/// Unlike [find_magic], zlib is considered at any offset.
+/// If the magic bytes of several formats match, the formats are tried in the detection order.
+/// `complete` means that `bytes` contain the whole stream,
+/// i.e. a truncated stream is not decodable.
+/// Returns the offset and the detection result.
+#[allow(unused_variables)]
+pub(crate) fn trial_detect(
+    bytes: &[u8],
+    window: usize,
+    complete: bool,
+    options: &DecoderOptions,
+    #[cfg(feature = "zstd")] zstd_dictionary: Option<&zstd::dict::DecoderDictionary<'_>>,
+) -> Result<Option<(usize, Detection)>, Error> {
+    let mut trials = 0;
+    for offset in 0..=window.min(bytes.len()) {
+        for format in options.detection_order().iter() {
+            let Some(magic_len) = magic_match_len(*format, &bytes[offset..]) else {
+                continue;
+            };
+            let mut decoder = crate::bufread::AnyDecoder::with_format(
+                &bytes[offset..bytes.len().min(offset + TRIAL_INPUT_LEN)],
+                *format,
+            );
+            if let Some(limit) = options.max_memory() {
+                decoder.memory_limit(limit);
+            }
+            #[cfg(feature = "bzip2")]
+            decoder.bzip2_small_mode(options.bzip2_small_mode);
+            #[cfg(feature = "zstd")]
+            if let Some(dictionary) = zstd_dictionary {
+                decoder.zstd_dictionary(dictionary);
+            }
+            #[cfg(feature = "flate2")]
+            if let Some(dictionary) = options.zlib_dictionary.as_deref() {
+                decoder.zlib_dictionary(dictionary);
+            }
+            let complete = complete && offset + TRIAL_INPUT_LEN >= bytes.len();
+            if trial_decode(decoder, complete)? {
+                return Ok(Some((offset, Detection::new(*format, magic_len))));
+            }
+            trials += 1;
+            if trials == MAX_TRIALS {
+                return Ok(None);
+            }
         }
+    }
+    Ok(None)
+}
+
+/// Decode up to [TRIAL_OUTPUT_LEN] bytes.
+///
+/// Returns `false` if the data is corrupted, or if it's truncated while being `complete`.
+fn trial_decode<R: Read>(mut decoder: R, complete: bool) -> Result<bool, Error> {
+    let mut buf = [0_u8; 4096];
+    let mut total = 0;
+    while total < TRIAL_OUTPUT_LEN {
+        match decoder.read(&mut buf) {
+            Ok(0) => return Ok(true),
+            Ok(n) => total += n,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+            // the stream can't be decoded at all
+            Err(e) if e.kind() == ErrorKind::OutOfMemory => return Err(e),
+            Err(e) => return Ok(!complete && e.kind() == ErrorKind::UnexpectedEof),
+        }
+    }
+    Ok(true)
+}
+
+/// The maximum number of input bytes that are decoded to confirm the format.
+pub(crate) const TRIAL_INPUT_LEN: usize = 4096;
+/// The maximum number of output bytes that are decoded to confirm the format.
+const TRIAL_OUTPUT_LEN: usize = 64 * 1024;
+/// The maximum number of offsets that are tried.
+const MAX_TRIALS: usize = 16;
+
+#[cfg(feature = "flate2")]
+const GZ_FHCRC: u8 = 1 << 1;
+
+/// Preset dictionary flag of zlib header.
+#[cfg(feature = "flate2")]
+const ZLIB_FDICT: u8 = 1 << 5;
+
+/// Content checksum flag of zstd frame header descriptor.
+#[cfg(feature = "zstd")]
+const ZSTD_CHECKSUM: u8 = 1 << 2;
+
+#[cfg(feature = "flate2")]
+const fn zlib_cm(x: u8) -> u8 {
+    x & 0b1111
+}
+
+#[cfg(feature = "flate2")]
+const fn zlib_cinfo(x: u8) -> u8 {
+    (x >> 4) & 0b1111
+}
+
+/// Wraps the reader in [BufReader](std::io::BufReader) if the reader doesn't implement
+/// [BufRead](std::io::BufRead).
+macro_rules! buffered {
+    (BufRead, $r: ty) => {
+        $r
+    };
+    (Read, $r: ty) => {
+        std::io::BufReader<$r>
     };
 }
 
-pub(crate) use define_inner_decoder;
+pub(crate) use buffered;
 
-macro_rules! zstd_decoder_new {
-    (BufRead, $reader: ident) => {
-        ZstdDecoder::with_buffer($reader)
+macro_rules! buffered_new {
+    (BufRead, $reader: expr, $capacity: expr) => {
+        $reader
     };
-    (Read, $reader: ident) => {
-        ZstdDecoder::new($reader)
+    (Read, $reader: expr, $capacity: expr) => {
+        std::io::BufReader::with_capacity($capacity, $reader)
     };
 }
 
-pub(crate) use zstd_decoder_new;
+pub(crate) use buffered_new;
 
-macro_rules! zstd_decoder {
-    (BufRead, $r: ident) => {
-        ZstdDecoder<'static, $r>
+macro_rules! buffered_get_ref {
+    (BufRead, $r: expr) => {
+        $r
     };
-    (Read, $r: ident) => {
-        ZstdDecoder<'static, std::io::BufReader<$r>>
+    (Read, $r: expr) => {
+        $r.get_ref()
     };
 }
 
-pub(crate) use zstd_decoder;
+pub(crate) use buffered_get_ref;
 
-macro_rules! zstd_get_ref {
-    (BufRead, $r: ident) => {
-        $r.get_ref().get_ref()
+macro_rules! buffered_get_mut {
+    (BufRead, $r: expr) => {
+        $r
     };
-    (Read, $r: ident) => {
-        $r.get_ref().get_ref().get_ref()
+    (Read, $r: expr) => {
+        $r.get_mut()
     };
 }
 
-pub(crate) use zstd_get_ref;
+pub(crate) use buffered_get_mut;
 
-macro_rules! zstd_get_mut {
-    (BufRead, $r: ident) => {
-        $r.get_mut().get_mut()
+macro_rules! buffered_into_inner {
+    (BufRead, $r: expr) => {
+        $r
     };
-    (Read, $r: ident) => {
-        $r.get_mut().get_mut().get_mut()
+    (Read, $r: expr) => {
+        $r.into_inner()
     };
 }
 
-pub(crate) use zstd_get_mut;
+pub(crate) use buffered_into_inner;
 
-macro_rules! zstd_into_inner {
-    (BufRead, $r: ident) => {
-        $r.finish().into_inner()
+/// Unwraps the reader from [BufReader](std::io::BufReader) and puts the buffered bytes back
+/// into the magic reader.
+macro_rules! buffered_into_reader {
+    (BufRead, $r: expr) => {
+        $r
     };
-    (Read, $r: ident) => {
-        $r.finish().into_inner().into_inner()
+    (Read, $r: expr) => {{
+        let r = $r;
+        let buf = r.buffer().to_vec();
+        let mut r = r.into_inner();
+        r.unread(&buf);
+        r
+    }};
+}
+
+pub(crate) use buffered_into_reader;
+
+/// Choose bzip2 decompression mode that fits into the memory limit.
+///
+/// Block size is the digit that follows "BZh" magic.
+#[cfg(feature = "bzip2")]
+pub(crate) fn bz_small(block_size: Option<u8>, memory_limit: Option<u64>) -> Result<bool, Error> {
+    let Some(limit) = memory_limit else {
+        return Ok(false);
     };
+    let block_size = match block_size {
+        Some(digit @ b'1'..=b'9') => digit - b'0',
+        // let the decoder report the error
+        _ => return Ok(false),
+    };
+    if crate::bz_memory_usage(block_size, false) <= limit {
+        Ok(false)
+    } else if crate::bz_memory_usage(block_size, true) <= limit {
+        Ok(true)
+    } else {
+        Err(crate::memory_limit_exceeded())
+    }
 }
 
-pub(crate) use zstd_into_inner;
+/// Maximum window size (log2) that fits into the memory limit.
+#[cfg(feature = "zstd")]
+pub(crate) fn zstd_window_log_max(memory_limit: u64) -> u32 {
+    const MIN_WINDOW_LOG: u32 = 10;
+    const MAX_WINDOW_LOG: u32 = 31;
+    let log = u64::BITS - 1 - memory_limit.max(1).leading_zeros();
+    log.clamp(MIN_WINDOW_LOG, MAX_WINDOW_LOG)
+}
@@ -0,0 +1,163 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::io::Error;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::path::Path;
+
+use crate::AnyDecoder;
+use crate::Format;
+
+/// Compressed file summary.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct FileInfo {
+    /// Detected compression format.
+    pub format: Format,
+    /// The size of the file.
+    pub compressed_size: u64,
+    /// The size of the decompressed data.
+    ///
+    /// The size is read from the file when the format stores it:
+    /// - gzip: ISIZE field of the last member (i.e. the size modulo 2<sup>32</sup>,
+    ///   only the last member is taken into account, the same as `gzip -l` does);
+    /// - xz: stream indices;
+    /// - zstd: frame headers (the frames that don't store the size are decompressed);
+    /// - SZDD: file header.
+    ///
+    /// For other formats the value is `None` unless the file is decompressed (see [inspect]).
+    pub uncompressed_size: Option<u64>,
+}
+
+impl FileInfo {
+    /// Compression ratio, i.e. uncompressed size divided by compressed size.
+    pub fn ratio(&self) -> Option<f64> {
+        let uncompressed_size = self.uncompressed_size?;
+        if self.compressed_size == 0 {
+            return None;
+        }
+        Some(uncompressed_size as f64 / self.compressed_size as f64)
+    }
+}
+
+/// Detect the format of the file and find out its uncompressed size.
+///
+/// If `verify` is `true` the file is fully decompressed to find out the exact uncompressed size
+/// and to check its integrity.
+/// Otherwise the size is read from the file if possible (see [FileInfo::uncompressed_size]).
+pub fn inspect<R: Read + Seek>(mut reader: R, verify: bool) -> Result<FileInfo, Error> {
+    let compressed_size = reader.seek(SeekFrom::End(0))?;
+    reader.rewind()?;
+    let mut decoder = AnyDecoder::new(BufReader::new(&mut reader));
+    let format = decoder.kind()?;
+    if verify {
+        let uncompressed_size = std::io::copy(&mut decoder, &mut std::io::sink())?;
+        return Ok(FileInfo {
+            format,
+            compressed_size,
+            uncompressed_size: Some(uncompressed_size),
+        });
+    }
+    drop(decoder);
+    reader.rewind()?;
+    let uncompressed_size = match format {
+        Format::Verbatim => Some(compressed_size),
+        #[cfg(feature = "flate2")]
+        Format::Gz => {
+            reader.seek(SeekFrom::End(-4))?;
+            let mut isize = [0_u8; 4];
+            reader.read_exact(&mut isize)?;
+            Some(u32::from_le_bytes(isize) as u64)
+        }
+        #[cfg(feature = "xz")]
+        Format::Xz => Some(
+            crate::xz_streams(reader)?
+                .iter()
+                .map(|stream| stream.uncompressed_size)
+                .sum(),
+        ),
+        #[cfg(feature = "zstd")]
+        Format::Zstd => Some(
+            crate::zstd_frames(reader)?
+                .iter()
+                .map(|frame| frame.uncompressed_size)
+                .sum(),
+        ),
+        #[cfg(feature = "mscompress")]
+        Format::Szdd => {
+            reader.seek(SeekFrom::Start(crate::SZDD_HEADER_LEN as u64 - 4))?;
+            let mut size = [0_u8; 4];
+            reader.read_exact(&mut size)?;
+            Some(u32::from_le_bytes(size) as u64)
+        }
+        #[allow(unreachable_patterns)]
+        _ => None,
+    };
+    Ok(FileInfo {
+        format,
+        compressed_size,
+        uncompressed_size,
+    })
+}
+
+/// Detect the format of the file under `path` and find out its uncompressed size.
+///
+/// See [inspect].
+pub fn inspect_file<P: AsRef<Path>>(path: P, verify: bool) -> Result<FileInfo, Error> {
+    inspect(File::open(path)?, verify)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::io::Write;
+
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::write::AnyEncoder;
+    use crate::write::Compression;
+
+    #[test]
+    fn sizes() {
+        arbtest(|u| {
+            let format = *u.choose(Format::DETECTION_ORDER)?;
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut writer = AnyEncoder::new(Vec::new(), format, Compression::Fast).unwrap();
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            let info = inspect(Cursor::new(&compressed), true).unwrap();
+            assert_eq!(format, info.format);
+            assert_eq!(compressed.len() as u64, info.compressed_size);
+            assert_eq!(Some(expected.len() as u64), info.uncompressed_size);
+            let info = inspect(Cursor::new(&compressed), false).unwrap();
+            assert_eq!(format, info.format);
+            match format {
+                #[cfg(feature = "bzip2")]
+                Format::Bz => assert_eq!(None, info.uncompressed_size),
+                #[cfg(feature = "flate2")]
+                Format::Zlib => assert_eq!(None, info.uncompressed_size),
+                #[cfg(feature = "zip")]
+                Format::Zip => assert_eq!(None, info.uncompressed_size),
+                #[cfg(feature = "mscompress")]
+                Format::Kwaj => assert_eq!(None, info.uncompressed_size),
+                #[cfg(feature = "lz4")]
+                Format::Lz4 => assert_eq!(None, info.uncompressed_size),
+                #[cfg(feature = "lzop")]
+                Format::Lzop => assert_eq!(None, info.uncompressed_size),
+                #[cfg(feature = "lzfse")]
+                Format::Lzfse => assert_eq!(None, info.uncompressed_size),
+                _ => assert_eq!(Some(expected.len() as u64), info.uncompressed_size),
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn verbatim() {
+        let info = inspect(Cursor::new(b"hello world"), false).unwrap();
+        assert_eq!(Format::Verbatim, info.format);
+        assert_eq!(Some(11), info.uncompressed_size);
+        assert_eq!(Some(1.0), info.ratio());
+    }
+}
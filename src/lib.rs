@@ -8,30 +8,183 @@
     html_favicon_url = "https://raw.githubusercontent.com/igankevich/rust-docs-assets/master/deko/deko.png"
 )]
 
+#[cfg(any(feature = "flate2", feature = "lzop"))]
+mod adler32;
+mod archive;
+mod batch;
+#[cfg(feature = "flate2")]
+mod bgzf;
+#[cfg(feature = "brotli")]
+mod brotli_decoder;
 pub mod bufread;
+#[cfg(feature = "bzip2")]
+mod bz_blocks;
+#[cfg(feature = "bzip2")]
+mod bz_decoder;
+mod chunks;
+mod codec;
+mod compressor;
 mod constants;
+mod custom_format;
 mod decoder;
+mod decoder_builder;
+#[cfg(any(feature = "flate2", feature = "zstd"))]
+mod decoder_ext;
+#[cfg(feature = "metrics")]
+mod decoder_metrics;
+mod decompress;
+mod decompressor;
+mod encryption;
+mod entropy;
+mod error;
+mod estimate;
 mod format;
+#[cfg(feature = "flate2")]
+mod gz_index;
+#[cfg(any(
+    feature = "bzip2",
+    feature = "flate2",
+    feature = "xz",
+    feature = "zstd"
+))]
+mod index;
+#[cfg(feature = "flate2")]
+mod inflate;
 mod inner_decoder;
+mod inspect;
+mod limits;
+#[cfg(feature = "lz4")]
+mod lz4_decoder;
+#[cfg(feature = "lzfse")]
+mod lzfse_decoder;
+#[cfg(feature = "lzop")]
+mod lzop_decoder;
 mod magic_reader;
+mod members;
+#[cfg(feature = "mscompress")]
+mod mscompress_decoder;
+mod options;
+#[cfg(feature = "rayon")]
+mod parallel;
+pub mod pipe;
+pub mod prelude;
 pub mod read;
-#[cfg(test)]
-pub mod test;
+#[cfg(feature = "readahead")]
+mod readahead;
+mod recompress;
+mod replace_with;
+mod signature;
+mod split;
+#[cfg(any(test, feature = "testkit"))]
+mod test;
+#[cfg(feature = "testkit")]
+pub mod testkit;
 mod tests;
+pub mod typed;
 pub mod write;
+#[cfg(feature = "flate2")]
+mod xar_entries;
+#[cfg(feature = "xz")]
+mod xz_info;
+#[cfg(feature = "zip")]
+mod zip_decoder;
+#[cfg(feature = "flate2")]
+mod zlib_dict_decoder;
+#[cfg(feature = "zstd")]
+mod zstd_dict;
+#[cfg(feature = "zstd")]
+mod zstd_frames;
+#[cfg(feature = "zstd")]
+mod zstd_seekable;
+#[cfg(feature = "zstd")]
+mod zstd_skippable;
+#[cfg(feature = "zstd")]
+mod zstd_window;
 
+#[cfg(any(feature = "flate2", feature = "lzop"))]
+pub(crate) use self::adler32::*;
+pub use self::archive::*;
+pub use self::batch::*;
+#[cfg(feature = "flate2")]
+pub use self::bgzf::*;
+#[cfg(feature = "brotli")]
+pub(crate) use self::brotli_decoder::*;
 pub use self::bufread::AnyDecoder;
+#[cfg(feature = "bzip2")]
+pub use self::bz_blocks::*;
+#[cfg(feature = "bzip2")]
+pub(crate) use self::bz_decoder::*;
+pub use self::chunks::*;
+pub use self::codec::*;
+pub use self::compressor::*;
 pub(crate) use self::constants::*;
+pub use self::custom_format::*;
 pub(crate) use self::decoder::*;
+pub(crate) use self::decoder_builder::*;
+#[cfg(any(feature = "flate2", feature = "zstd"))]
+pub use self::decoder_ext::*;
+#[cfg(feature = "metrics")]
+pub(crate) use self::decoder_metrics::*;
+pub use self::decompress::*;
+pub use self::decompressor::*;
+pub(crate) use self::encryption::*;
+pub use self::entropy::*;
+pub(crate) use self::error::*;
+pub use self::estimate::*;
 pub use self::format::*;
+#[cfg(feature = "flate2")]
+pub use self::gz_index::*;
+#[cfg(any(
+    feature = "bzip2",
+    feature = "flate2",
+    feature = "xz",
+    feature = "zstd"
+))]
+pub use self::index::*;
+#[cfg(feature = "flate2")]
+pub(crate) use self::inflate::*;
 pub(crate) use self::inner_decoder::*;
+pub use self::inspect::*;
+pub use self::limits::*;
+#[cfg(feature = "lz4")]
+pub(crate) use self::lz4_decoder::*;
+#[cfg(feature = "lzfse")]
+pub(crate) use self::lzfse_decoder::*;
+#[cfg(feature = "lzop")]
+pub(crate) use self::lzop_decoder::*;
 pub(crate) use self::magic_reader::*;
+pub(crate) use self::members::*;
+#[cfg(feature = "mscompress")]
+pub(crate) use self::mscompress_decoder::*;
+pub(crate) use self::options::*;
+#[cfg(feature = "rayon")]
+pub use self::parallel::*;
+#[cfg(feature = "readahead")]
+pub use self::readahead::*;
+pub use self::recompress::*;
+pub(crate) use self::replace_with::*;
+pub(crate) use self::signature::*;
+pub use self::split::*;
 pub(crate) use self::tests::*;
 pub use self::write::AnyEncoder;
+#[cfg(feature = "flate2")]
+pub use self::xar_entries::*;
+#[cfg(feature = "xz")]
+pub use self::xz_info::*;
+#[cfg(feature = "zip")]
+pub(crate) use self::zip_decoder::*;
+#[cfg(feature = "flate2")]
+pub(crate) use self::zlib_dict_decoder::*;
+#[cfg(feature = "zstd")]
+pub use self::zstd_dict::*;
+#[cfg(feature = "zstd")]
+pub use self::zstd_frames::*;
+#[cfg(feature = "zstd")]
+pub use self::zstd_seekable::*;
+#[cfg(feature = "zstd")]
+pub use self::zstd_skippable::*;
+#[cfg(feature = "zstd")]
+pub(crate) use self::zstd_window::*;
 
-// TODO impl write::AnyDecoder
-// TODO impl read::AnyEncoder
 // TODO impl bufread::AnyEncoder
-// TODO add deko-cli crate
 // TODO impl AsyncRead, AsyncBufRead
-// TODO add AnyDecoder constructor that takes Format as an argument. Use case: xar
@@ -0,0 +1,32 @@
+/// Hard limits on the memory that the decoder allocates.
+///
+/// See [hardened](crate::bufread::AnyDecoder::hardened).
+/// The default limits are suitable for the streams compressed with the default settings
+/// of the common tools.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct DecoderLimits {
+    /// The maximum size of the codec state in bytes.
+    ///
+    /// The state includes xz dictionary, zstd window and bzip2 tables.
+    /// The smallest of this value and [memory limit](crate::bufread::AnyDecoder::memory_limit) is used.
+    pub memory: u64,
+    /// The maximum number of bytes that are buffered to detect the format.
+    ///
+    /// This is the upper bound of the [scan window](crate::bufread::AnyDecoder::scan_for_magic).
+    pub detection_buffer: usize,
+    /// The maximum size of the prepared zstd dictionary in bytes.
+    #[cfg(feature = "zstd")]
+    pub zstd_dictionary: usize,
+}
+
+impl Default for DecoderLimits {
+    fn default() -> Self {
+        Self {
+            // xz -9 needs 65 MiB, zstd -19 needs 8 MiB
+            memory: 80 * 1024 * 1024,
+            detection_buffer: 64 * 1024,
+            #[cfg(feature = "zstd")]
+            zstd_dictionary: 16 * 1024 * 1024,
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::io::BufRead;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+
+use lz4_flex::frame::FrameDecoder;
+
+/// LZ4 decoder of modern and legacy frames.
+///
+/// `lz4_flex::frame::FrameDecoder` returns end-of-file at the end of each frame,
+/// treats missing end mark as the end of the frame,
+/// and decodes a legacy frame until the end of the input.
+/// Hence legacy frames are decoded here, and the frames are read one by one.
+/// Concatenated frames are decoded as one stream unless `single_frame` is set.
+/// Truncated frames are reported as errors.
+pub(crate) struct Lz4Decoder<R: BufRead> {
+    decoder: FrameDecoder<Lz4Reader<R>>,
+    state: State,
+    single_frame: bool,
+    // the first frame is decoded even if the magic is wrong to report the error
+    first_frame: bool,
+    // compressed legacy block
+    input: Vec<u8>,
+    // decompressed legacy block
+    output: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: BufRead> Lz4Decoder<R> {
+    pub(crate) fn new(reader: R, single_frame: bool) -> Self {
+        Self {
+            decoder: FrameDecoder::new(Lz4Reader {
+                reader,
+                read_ahead: Vec::new(),
+                eof: false,
+            }),
+            state: State::Start,
+            single_frame,
+            first_frame: true,
+            input: Vec::new(),
+            output: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Choose the decoder for the next frame by its magic number.
+    fn start_frame(&mut self) -> Result<(), Error> {
+        let reader = self.decoder.get_mut();
+        let mut magic = [0_u8; 4];
+        let n = reader.read_full(&mut magic)?;
+        let magic = &magic[..n];
+        self.state = if n == 0 {
+            State::Done
+        } else if magic == LZ4_LEGACY_MAGIC {
+            State::Legacy
+        } else if self.first_frame
+            || LZ4_FRAME_MAGIC.starts_with(magic)
+            || LZ4_LEGACY_MAGIC.starts_with(magic)
+        {
+            // partial magic is decoded as a truncated frame
+            reader.read_ahead.extend_from_slice(magic);
+            State::Frame
+        } else {
+            // trailing data is returned to the underlying reader
+            reader.read_ahead.extend_from_slice(magic);
+            State::Done
+        };
+        self.first_frame = false;
+        Ok(())
+    }
+
+    /// Finish the current frame.
+    fn end_frame(&mut self) {
+        self.state = if self.single_frame {
+            State::Done
+        } else {
+            State::Start
+        };
+    }
+
+    fn read_frame(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let n = self.decoder.read(buf)?;
+        if n == 0 {
+            // the end mark is the last thing that is read from the complete frame
+            if self.decoder.get_ref().eof {
+                return Err(unexpected_eof());
+            }
+            self.end_frame();
+        }
+        Ok(n)
+    }
+
+    /// Legacy frame is a sequence of independent blocks, each one is prefixed with its
+    /// compressed size.
+    ///
+    /// The frame ends at the end of the input or when the size is larger than any block can be,
+    /// i.e. it's the magic number of the next frame.
+    fn read_legacy_frame(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if self.pos == self.output.len() {
+            let reader = self.decoder.get_mut();
+            let mut size = [0_u8; 4];
+            match reader.read_full(&mut size)? {
+                0 => {
+                    self.end_frame();
+                    return Ok(0);
+                }
+                4 => {}
+                _ => return Err(unexpected_eof()),
+            }
+            let compressed_size = u32::from_le_bytes(size) as usize;
+            if compressed_size > LEGACY_MAX_COMPRESSED_BLOCK_SIZE {
+                reader.read_ahead.extend_from_slice(&size);
+                self.end_frame();
+                return Ok(0);
+            }
+            self.input.resize(compressed_size, 0);
+            reader.read_exact(&mut self.input)?;
+            self.output.resize(LEGACY_BLOCK_SIZE, 0);
+            let n = lz4_flex::block::decompress_into(&self.input, &mut self.output)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+            self.output.truncate(n);
+            self.pos = 0;
+        }
+        let n = buf.len().min(self.output.len() - self.pos);
+        buf[..n].copy_from_slice(&self.output[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+
+    /// The bytes that were read from the underlying reader but were not decoded.
+    pub(crate) fn read_ahead(&self) -> &[u8] {
+        &self.decoder.get_ref().read_ahead
+    }
+
+    pub(crate) fn get_ref(&self) -> &R {
+        &self.decoder.get_ref().reader
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        &mut self.decoder.get_mut().reader
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.decoder.into_inner().reader
+    }
+
+    /// Return the underlying reader and the bytes that were read ahead.
+    pub(crate) fn into_parts(self) -> (R, Vec<u8>) {
+        let reader = self.decoder.into_inner();
+        (reader.reader, reader.read_ahead)
+    }
+}
+
+impl<R: BufRead> Read for Lz4Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let n = match self.state {
+                State::Start => {
+                    self.start_frame()?;
+                    continue;
+                }
+                State::Frame => self.read_frame(buf)?,
+                State::Legacy => self.read_legacy_frame(buf)?,
+                State::Done => return Ok(0),
+            };
+            if n != 0 {
+                return Ok(n);
+            }
+        }
+    }
+}
+
+fn unexpected_eof() -> Error {
+    Error::new(
+        ErrorKind::UnexpectedEof,
+        "decompression not finished but EOF reached",
+    )
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Start,
+    Frame,
+    Legacy,
+    Done,
+}
+
+/// A reader that returns the bytes that were read ahead first
+/// and remembers whether the end of the underlying reader has been reached.
+struct Lz4Reader<R> {
+    reader: R,
+    read_ahead: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: Read> Lz4Reader<R> {
+    /// Read until `buf` is full or the end of the input is reached.
+    fn read_full(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let mut n = 0;
+        while n != buf.len() {
+            match self.read(&mut buf[n..]) {
+                Ok(0) => break,
+                Ok(m) => n += m,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl<R: Read> Read for Lz4Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if !self.read_ahead.is_empty() {
+            let n = buf.len().min(self.read_ahead.len());
+            buf[..n].copy_from_slice(&self.read_ahead[..n]);
+            self.read_ahead.drain(..n);
+            return Ok(n);
+        }
+        let n = self.reader.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            self.eof = true;
+        }
+        Ok(n)
+    }
+}
+
+/// LZ4 frame magic number 0x184D2204 in little-endian byte order.
+pub(crate) const LZ4_FRAME_MAGIC: [u8; 4] = [0x04, 0x22, 0x4d, 0x18];
+
+/// LZ4 legacy frame magic number 0x184C2102 in little-endian byte order.
+pub(crate) const LZ4_LEGACY_MAGIC: [u8; 4] = [0x02, 0x21, 0x4c, 0x18];
+
+/// Uncompressed size of legacy blocks (except the last one).
+const LEGACY_BLOCK_SIZE: usize = 8 * 1024 * 1024;
+
+/// `LZ4_COMPRESSBOUND` of the legacy block size.
+const LEGACY_MAX_COMPRESSED_BLOCK_SIZE: usize = LEGACY_BLOCK_SIZE + LEGACY_BLOCK_SIZE / 255 + 16;
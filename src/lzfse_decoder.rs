@@ -0,0 +1,672 @@
+use std::io::BufRead;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+
+/// Decoder of LZFSE streams (Apple Compression library, AEA, `xar` payloads).
+///
+/// The stream is a sequence of blocks that are either stored, compressed with LZFSE
+/// (LZ77 with FSE entropy coding) or compressed with LZVN (LZ77 with byte-aligned opcodes),
+/// and is terminated by the end-of-stream block
+/// ([reference implementation](https://github.com/lzfse/lzfse)).
+/// The matches might reference the data of the previous blocks.
+pub(crate) struct LzfseDecoder<R> {
+    reader: R,
+    done: bool,
+    // compressed block
+    input: Vec<u8>,
+    // the tail of the previous blocks followed by the current decompressed block
+    buf: Vec<u8>,
+    pos: usize,
+    literals: Vec<u8>,
+}
+
+impl<R: BufRead> LzfseDecoder<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            done: false,
+            input: Vec::new(),
+            buf: Vec::new(),
+            pos: 0,
+            literals: Vec::new(),
+        }
+    }
+}
+
+impl<R> LzfseDecoder<R> {
+    pub(crate) fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: BufRead> LzfseDecoder<R> {
+    /// Decode the next block into `buf`.
+    ///
+    /// Returns `false` at the end of the stream.
+    fn read_block(&mut self) -> Result<bool, Error> {
+        if self.done {
+            return Ok(false);
+        }
+        // keep the history that the matches of the next block might reference
+        if self.buf.len() > MAX_DISTANCE {
+            self.buf.drain(..self.buf.len() - MAX_DISTANCE);
+        }
+        self.pos = self.buf.len();
+        let magic = read_u32(&mut self.reader)?;
+        if magic == LZFSE_ENDOFSTREAM_BLOCK_MAGIC {
+            self.done = true;
+            return Ok(false);
+        }
+        if let Err(e) = self.decode_block(magic) {
+            // discard partially decoded block
+            self.buf.truncate(self.pos);
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    fn decode_block(&mut self, magic: u32) -> Result<(), Error> {
+        match magic {
+            LZFSE_UNCOMPRESSED_BLOCK_MAGIC => {
+                let n_raw_bytes = read_u32(&mut self.reader)? as u64;
+                let n = (&mut self.reader)
+                    .take(n_raw_bytes)
+                    .read_to_end(&mut self.buf)?;
+                if n as u64 != n_raw_bytes {
+                    return Err(ErrorKind::UnexpectedEof.into());
+                }
+                Ok(())
+            }
+            LZFSE_COMPRESSEDV1_BLOCK_MAGIC => {
+                let header = BlockHeader::read_v1(&mut self.reader)?;
+                self.decode_lzfse_block(&header)
+            }
+            LZFSE_COMPRESSEDV2_BLOCK_MAGIC => {
+                let header = BlockHeader::read_v2(&mut self.reader)?;
+                self.decode_lzfse_block(&header)
+            }
+            LZFSE_COMPRESSEDLZVN_BLOCK_MAGIC => {
+                let n_raw_bytes = read_u32(&mut self.reader)?;
+                let n_payload_bytes = read_u32(&mut self.reader)?;
+                self.input.resize(n_payload_bytes as usize, 0);
+                self.reader.read_exact(&mut self.input)?;
+                lzvn_decompress(&self.input, &mut self.buf, n_raw_bytes as usize)
+            }
+            _ => Err(invalid_data("invalid lzfse block magic")),
+        }
+    }
+
+    fn decode_lzfse_block(&mut self, header: &BlockHeader) -> Result<(), Error> {
+        header.check()?;
+        let (l_freq, rest) = header.freq.split_at(L_SYMBOLS);
+        let (m_freq, rest) = rest.split_at(M_SYMBOLS);
+        let (d_freq, literal_freq) = rest.split_at(D_SYMBOLS);
+        let literal_payload_len = header.n_literal_payload_bytes as usize;
+        self.input
+            .resize(literal_payload_len + header.n_lmd_payload_bytes as usize, 0);
+        self.reader.read_exact(&mut self.input)?;
+        let (literal_payload, lmd_payload) = self.input.split_at(literal_payload_len);
+        // literals are encoded as four interleaved streams
+        let literal_table = fse_table(literal_freq, LITERAL_STATES, |symbol, k, delta| {
+            LiteralEntry {
+                k: k as u8,
+                symbol: symbol as u8,
+                delta: delta as u16,
+            }
+        });
+        let mut bits = BitReader::new(literal_payload, header.literal_bits)?;
+        let mut states = header.literal_state;
+        self.literals.clear();
+        for _ in (0..header.n_literals).step_by(4) {
+            bits.refill()?;
+            for state in states.iter_mut() {
+                let entry = literal_table[*state as usize];
+                *state = entry.delta + bits.pull(entry.k as u32) as u16;
+                self.literals.push(entry.symbol);
+            }
+        }
+        // literal lengths, match lengths and match distances
+        let l_table = value_table(l_freq, L_STATES, &L_EXTRA_BITS, &L_BASE_VALUE);
+        let m_table = value_table(m_freq, M_STATES, &M_EXTRA_BITS, &M_BASE_VALUE);
+        let d_table = value_table(d_freq, D_STATES, &D_EXTRA_BITS, &D_BASE_VALUE);
+        let mut bits = BitReader::new(lmd_payload, header.lmd_bits)?;
+        let mut l_state = header.l_state;
+        let mut m_state = header.m_state;
+        let mut d_state = header.d_state;
+        let max_len = self.buf.len() + header.n_raw_bytes as usize;
+        let mut literal = 0;
+        // the distance is invalid until the first match sets it
+        let mut distance = 0;
+        for _ in 0..header.n_matches {
+            bits.refill()?;
+            let l = value_decode(&l_table, &mut l_state, &mut bits);
+            let m = value_decode(&m_table, &mut m_state, &mut bits);
+            let d = value_decode(&d_table, &mut d_state, &mut bits);
+            // zero distance means the previous distance
+            if d != 0 {
+                distance = d;
+            }
+            let literals = self
+                .literals
+                .get(literal..literal + l)
+                .ok_or_else(|| invalid_data("invalid lzfse literal length"))?;
+            if self.buf.len() + l + m > max_len {
+                return Err(invalid_data("lzfse output is too large"));
+            }
+            self.buf.extend_from_slice(literals);
+            literal += l;
+            if m != 0 {
+                copy_match(&mut self.buf, distance, m)?;
+            }
+        }
+        if self.buf.len() != max_len {
+            return Err(invalid_data("lzfse block size mismatch"));
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for LzfseDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.pos == self.buf.len() {
+            if !self.read_block()? {
+                return Ok(0);
+            }
+        }
+        let n = buf.len().min(self.buf.len() - self.pos);
+        buf[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// LZFSE compressed block header in the uncompressed (v1) layout.
+struct BlockHeader {
+    n_raw_bytes: u32,
+    n_literals: u32,
+    n_matches: u32,
+    n_literal_payload_bytes: u32,
+    n_lmd_payload_bytes: u32,
+    literal_bits: i32,
+    literal_state: [u16; 4],
+    lmd_bits: i32,
+    l_state: u16,
+    m_state: u16,
+    d_state: u16,
+    // L, M, D and literal frequencies
+    freq: [u16; FREQ_LEN],
+}
+
+impl BlockHeader {
+    /// Read the header with uncompressed frequency tables (without the magic).
+    fn read_v1<R: Read>(reader: &mut R) -> Result<Self, Error> {
+        let mut bytes = [0_u8; V1_HEADER_LEN - 4];
+        reader.read_exact(&mut bytes)?;
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        let u32_at =
+            |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        // the offsets are relative to the end of the magic;
+        // the total payload size at offset 4 is redundant
+        let mut freq = [0_u16; FREQ_LEN];
+        for (i, freq) in freq.iter_mut().enumerate() {
+            *freq = u16_at(46 + 2 * i);
+        }
+        Ok(Self {
+            n_raw_bytes: u32_at(0),
+            n_literals: u32_at(8),
+            n_matches: u32_at(12),
+            n_literal_payload_bytes: u32_at(16),
+            n_lmd_payload_bytes: u32_at(20),
+            literal_bits: u32_at(24) as i32,
+            literal_state: [u16_at(28), u16_at(30), u16_at(32), u16_at(34)],
+            lmd_bits: u32_at(36) as i32,
+            l_state: u16_at(40),
+            m_state: u16_at(42),
+            d_state: u16_at(44),
+            freq,
+        })
+    }
+
+    /// Read the header with compressed frequency tables (without the magic).
+    fn read_v2<R: Read>(reader: &mut R) -> Result<Self, Error> {
+        let n_raw_bytes = read_u32(reader)?;
+        let mut fields = [0_u64; 3];
+        for field in fields.iter_mut() {
+            let mut bytes = [0_u8; 8];
+            reader.read_exact(&mut bytes)?;
+            *field = u64::from_le_bytes(bytes);
+        }
+        let [v0, v1, v2] = fields;
+        let header_len = get_field(v2, 0, 32) as usize;
+        if !(V2_HEADER_LEN..=V2_MAX_HEADER_LEN).contains(&header_len) {
+            return Err(invalid_data("invalid lzfse block header size"));
+        }
+        let mut freq_bytes = [0_u8; V2_MAX_HEADER_LEN - V2_HEADER_LEN];
+        let freq_bytes = &mut freq_bytes[..header_len - V2_HEADER_LEN];
+        reader.read_exact(freq_bytes)?;
+        let mut freq = [0_u16; FREQ_LEN];
+        // the tables are omitted if all frequencies are zero
+        if !freq_bytes.is_empty() {
+            let mut bytes = freq_bytes.iter();
+            let mut accum = 0_u32;
+            let mut accum_bits = 0;
+            for freq in freq.iter_mut() {
+                while accum_bits + 8 <= 32 {
+                    let Some(byte) = bytes.next() else {
+                        break;
+                    };
+                    accum |= (*byte as u32) << accum_bits;
+                    accum_bits += 8;
+                }
+                let (value, nbits) = decode_freq(accum);
+                if nbits > accum_bits {
+                    return Err(invalid_data("invalid lzfse frequency table"));
+                }
+                *freq = value;
+                accum >>= nbits;
+                accum_bits -= nbits;
+            }
+            if accum_bits >= 8 || bytes.next().is_some() {
+                return Err(invalid_data("invalid lzfse frequency table"));
+            }
+        }
+        Ok(Self {
+            n_raw_bytes,
+            n_literals: get_field(v0, 0, 20) as u32,
+            n_literal_payload_bytes: get_field(v0, 20, 20) as u32,
+            n_matches: get_field(v0, 40, 20) as u32,
+            literal_bits: get_field(v0, 60, 3) as i32 - 7,
+            literal_state: [
+                get_field(v1, 0, 10) as u16,
+                get_field(v1, 10, 10) as u16,
+                get_field(v1, 20, 10) as u16,
+                get_field(v1, 30, 10) as u16,
+            ],
+            n_lmd_payload_bytes: get_field(v1, 40, 20) as u32,
+            lmd_bits: get_field(v1, 60, 3) as i32 - 7,
+            l_state: get_field(v2, 32, 10) as u16,
+            m_state: get_field(v2, 42, 10) as u16,
+            d_state: get_field(v2, 52, 10) as u16,
+            freq,
+        })
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        let (l_freq, rest) = self.freq.split_at(L_SYMBOLS);
+        let (m_freq, rest) = rest.split_at(M_SYMBOLS);
+        let (d_freq, literal_freq) = rest.split_at(D_SYMBOLS);
+        let sum = |freq: &[u16]| freq.iter().map(|f| *f as usize).sum::<usize>();
+        let valid = self.n_literals as usize <= LITERALS_PER_BLOCK
+            && self.n_matches as usize <= MATCHES_PER_BLOCK
+            && self
+                .literal_state
+                .iter()
+                .all(|state| (*state as usize) < LITERAL_STATES)
+            && (self.l_state as usize) < L_STATES
+            && (self.m_state as usize) < M_STATES
+            && (self.d_state as usize) < D_STATES
+            && sum(literal_freq) <= LITERAL_STATES
+            && sum(l_freq) <= L_STATES
+            && sum(m_freq) <= M_STATES
+            && sum(d_freq) <= D_STATES;
+        if !valid {
+            return Err(invalid_data("invalid lzfse block header"));
+        }
+        Ok(())
+    }
+}
+
+/// Decode the frequency encoded with the fixed Huffman code.
+///
+/// Returns the frequency and the number of bits.
+fn decode_freq(bits: u32) -> (u16, u32) {
+    const NBITS: [u8; 32] = [
+        2, 3, 2, 5, 2, 3, 2, 8, 2, 3, 2, 5, 2, 3, 2, 14, 2, 3, 2, 5, 2, 3, 2, 8, 2, 3, 2, 5, 2, 3,
+        2, 14,
+    ];
+    const VALUES: [u8; 32] = [
+        0, 2, 1, 4, 0, 3, 1, 0, 0, 2, 1, 5, 0, 3, 1, 0, 0, 2, 1, 6, 0, 3, 1, 0, 0, 2, 1, 7, 0, 3,
+        1, 0,
+    ];
+    let b = (bits & 31) as usize;
+    match NBITS[b] {
+        8 => (8 + ((bits >> 4) & 0xf) as u16, 8),
+        14 => (24 + ((bits >> 4) & 0x3ff) as u16, 14),
+        n => (VALUES[b] as u16, n as u32),
+    }
+}
+
+fn get_field(value: u64, offset: u32, nbits: u32) -> u64 {
+    (value >> offset) & ((1 << nbits) - 1)
+}
+
+#[derive(Clone, Copy, Default)]
+struct LiteralEntry {
+    k: u8,
+    symbol: u8,
+    delta: u16,
+}
+
+#[derive(Clone, Copy, Default)]
+struct ValueEntry {
+    total_bits: u8,
+    value_bits: u8,
+    delta: u16,
+    base: u32,
+}
+
+/// Build FSE decoding table with `nstates` entries from the normalized frequencies.
+///
+/// `entry` is called with the symbol, the number of state bits and the base of the next state.
+fn fse_table<T: Clone + Default>(
+    freq: &[u16],
+    nstates: usize,
+    entry: impl Fn(usize, u32, u32) -> T,
+) -> Vec<T> {
+    let nstates = nstates as u32;
+    let mut table = Vec::with_capacity(nstates as usize);
+    for (symbol, f) in freq.iter().enumerate() {
+        let f = *f as u32;
+        if f == 0 {
+            continue;
+        }
+        // N <= (F << K) < 2N
+        let k = f.leading_zeros() - nstates.leading_zeros();
+        let j0 = ((2 * nstates) >> k) - f;
+        for j in 0..f {
+            if j < j0 {
+                table.push(entry(symbol, k, ((f + j) << k) - nstates));
+            } else {
+                table.push(entry(symbol, k - 1, (j - j0) << (k - 1)));
+            }
+        }
+    }
+    // the frequencies might not add up to the number of states
+    table.resize(nstates as usize, T::default());
+    table
+}
+
+fn value_table(
+    freq: &[u16],
+    nstates: usize,
+    extra_bits: &[u8],
+    base_value: &[u32],
+) -> Vec<ValueEntry> {
+    fse_table(freq, nstates, |symbol, k, delta| ValueEntry {
+        total_bits: k as u8 + extra_bits[symbol],
+        value_bits: extra_bits[symbol],
+        delta: delta as u16,
+        base: base_value[symbol],
+    })
+}
+
+fn value_decode(table: &[ValueEntry], state: &mut u16, bits: &mut BitReader) -> usize {
+    let entry = table[*state as usize];
+    let state_and_value = bits.pull(entry.total_bits as u32);
+    *state = entry.delta + (state_and_value >> entry.value_bits) as u16;
+    let value = state_and_value & ((1 << entry.value_bits) - 1);
+    entry.base as usize + value as usize
+}
+
+/// Reads the bits from the end of the input towards the start.
+struct BitReader<'a> {
+    input: &'a [u8],
+    // the number of bytes that weren't read yet
+    pos: usize,
+    accum: u64,
+    nbits: u32,
+}
+
+impl<'a> BitReader<'a> {
+    /// `nbits` is the number of the bits (-7–0) in the last byte that are not used.
+    fn new(input: &'a [u8], nbits: i32) -> Result<Self, Error> {
+        let n = if nbits == 0 { 7 } else { 8 };
+        let Some(pos) = input.len().checked_sub(n) else {
+            return Err(invalid_data("truncated lzfse bit stream"));
+        };
+        let mut bytes = [0_u8; 8];
+        bytes[..n].copy_from_slice(&input[pos..]);
+        let accum = u64::from_le_bytes(bytes);
+        let nbits = nbits + 8 * n as i32;
+        if !(56..64).contains(&nbits) || accum >> nbits != 0 {
+            return Err(invalid_data("invalid lzfse bit stream"));
+        }
+        Ok(Self {
+            input,
+            pos,
+            accum,
+            nbits: nbits as u32,
+        })
+    }
+
+    /// Load whole bytes until there are at least 56 bits in the accumulator.
+    fn refill(&mut self) -> Result<(), Error> {
+        let n = ((63 - self.nbits) / 8) as usize;
+        let Some(pos) = self.pos.checked_sub(n) else {
+            return Err(invalid_data("truncated lzfse bit stream"));
+        };
+        let mut bytes = [0_u8; 8];
+        bytes[..n].copy_from_slice(&self.input[pos..self.pos]);
+        self.accum = (self.accum << (8 * n)) | u64::from_le_bytes(bytes);
+        self.nbits += 8 * n as u32;
+        self.pos = pos;
+        Ok(())
+    }
+
+    fn pull(&mut self, n: u32) -> u64 {
+        self.nbits -= n;
+        let value = self.accum >> self.nbits;
+        self.accum &= (1 << self.nbits) - 1;
+        value
+    }
+}
+
+/// Decompress LZVN `input` and append exactly `len` bytes to `output`.
+///
+/// The matches might reference the data that is already in `output`.
+fn lzvn_decompress(mut input: &[u8], output: &mut Vec<u8>, len: usize) -> Result<(), Error> {
+    let max_len = output.len() + len;
+    let mut distance = 0;
+    loop {
+        let [opcode, ref rest @ ..] = *input else {
+            return Err(lzvn_truncated());
+        };
+        let byte = |i: usize| {
+            rest.get(i)
+                .copied()
+                .map(usize::from)
+                .ok_or_else(lzvn_truncated)
+        };
+        let opcode = opcode as usize;
+        // the opcode length, the number of literals, the match length and the distance
+        let (opcode_len, l, m, d) = match opcode {
+            // end of stream
+            0x06 => {
+                // the opcode is followed by seven bytes of padding
+                if input.len() < 8 {
+                    return Err(lzvn_truncated());
+                }
+                break;
+            }
+            // nop
+            0x0e | 0x16 => (1, 0, 0, None),
+            0x1e | 0x26 | 0x2e | 0x36 | 0x3e | 0x70..=0x7f => {
+                return Err(invalid_data("undefined lzvn opcode"));
+            }
+            // medium distance
+            0xa0..=0xbf => {
+                let b = byte(0)? | (byte(1)? << 8);
+                let m = (((opcode & 7) << 2) | (b & 3)) + 3;
+                (3, (opcode >> 3) & 3, m, Some(b >> 2))
+            }
+            // large literal
+            0xe0 => (2, byte(0)? + 16, 0, None),
+            // small literal
+            0xe1..=0xef => (1, opcode & 0xf, 0, None),
+            // large match
+            0xf0 => (2, 0, byte(0)? + 16, None),
+            // small match
+            0xf1..=0xff => (1, 0, opcode & 0xf, None),
+            // previous distance
+            _ if opcode & 7 == 6 => (1, opcode >> 6, ((opcode >> 3) & 7) + 3, None),
+            // large distance
+            _ if opcode & 7 == 7 => {
+                let d = byte(0)? | (byte(1)? << 8);
+                (3, opcode >> 6, ((opcode >> 3) & 7) + 3, Some(d))
+            }
+            // small distance
+            _ => {
+                let d = ((opcode & 7) << 8) | byte(0)?;
+                (2, opcode >> 6, ((opcode >> 3) & 7) + 3, Some(d))
+            }
+        };
+        let Some(literals) = input.get(opcode_len..opcode_len + l) else {
+            return Err(lzvn_truncated());
+        };
+        if output.len() + l + m > max_len {
+            return Err(invalid_data("lzvn output is too large"));
+        }
+        output.extend_from_slice(literals);
+        input = &input[opcode_len + l..];
+        if let Some(d) = d {
+            distance = d;
+        }
+        if m != 0 {
+            copy_match(output, distance, m)?;
+        }
+    }
+    if output.len() != max_len {
+        return Err(invalid_data("lzvn block size mismatch"));
+    }
+    Ok(())
+}
+
+/// Append `len` bytes that start `distance` bytes before the end of `output`.
+fn copy_match(output: &mut Vec<u8>, distance: usize, len: usize) -> Result<(), Error> {
+    if distance == 0 || distance > output.len() {
+        return Err(invalid_data("lzfse match is out of bounds"));
+    }
+    let mut remaining = len;
+    while remaining != 0 {
+        // the match might overlap with itself
+        let from = output.len() - distance;
+        let n = remaining.min(distance);
+        output.extend_from_within(from..from + n);
+        remaining -= n;
+    }
+    Ok(())
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, Error> {
+    let mut bytes = [0_u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn lzvn_truncated() -> Error {
+    invalid_data("truncated lzvn block")
+}
+
+fn invalid_data(message: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+pub(crate) const LZFSE_ENDOFSTREAM_BLOCK_MAGIC: u32 = u32::from_le_bytes(*b"bvx$");
+pub(crate) const LZFSE_UNCOMPRESSED_BLOCK_MAGIC: u32 = u32::from_le_bytes(*b"bvx-");
+pub(crate) const LZFSE_COMPRESSEDV1_BLOCK_MAGIC: u32 = u32::from_le_bytes(*b"bvx1");
+pub(crate) const LZFSE_COMPRESSEDV2_BLOCK_MAGIC: u32 = u32::from_le_bytes(*b"bvx2");
+pub(crate) const LZFSE_COMPRESSEDLZVN_BLOCK_MAGIC: u32 = u32::from_le_bytes(*b"bvxn");
+/// The largest match distance (LZFSE) rounded up to the power of two.
+const MAX_DISTANCE: usize = 256 * 1024;
+const LITERALS_PER_BLOCK: usize = 4 * MATCHES_PER_BLOCK;
+const MATCHES_PER_BLOCK: usize = 10000;
+const L_SYMBOLS: usize = 20;
+const M_SYMBOLS: usize = 20;
+const D_SYMBOLS: usize = 64;
+const LITERAL_SYMBOLS: usize = 256;
+const FREQ_LEN: usize = L_SYMBOLS + M_SYMBOLS + D_SYMBOLS + LITERAL_SYMBOLS;
+const L_STATES: usize = 64;
+const M_STATES: usize = 64;
+const D_STATES: usize = 256;
+const LITERAL_STATES: usize = 1024;
+/// Seven 32-bit fields, four literal states, three L, M, D states,
+/// two bit counts, frequency tables and padding.
+const V1_HEADER_LEN: usize = 7 * 4 + 4 + 4 * 2 + 4 + 3 * 2 + FREQ_LEN * 2 + 2;
+/// The magic, the block size and three packed 64-bit fields.
+const V2_HEADER_LEN: usize = 4 + 4 + 3 * 8;
+/// All frequencies use the longest (14-bit) code.
+const V2_MAX_HEADER_LEN: usize = V2_HEADER_LEN + (FREQ_LEN * 14).div_ceil(8);
+const L_EXTRA_BITS: [u8; L_SYMBOLS] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 3, 5, 8];
+const L_BASE_VALUE: [u32; L_SYMBOLS] = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 20, 28, 60,
+];
+const M_EXTRA_BITS: [u8; M_SYMBOLS] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 5, 8, 11];
+const M_BASE_VALUE: [u32; M_SYMBOLS] = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 24, 56, 312,
+];
+const D_EXTRA_BITS: [u8; D_SYMBOLS] = [
+    0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 6, 6, 6, 6, 7, 7, 7, 7,
+    8, 8, 8, 8, 9, 9, 9, 9, 10, 10, 10, 10, 11, 11, 11, 11, 12, 12, 12, 12, 13, 13, 13, 13, 14, 14,
+    14, 14, 15, 15, 15, 15,
+];
+const D_BASE_VALUE: [u32; D_SYMBOLS] = [
+    0, 1, 2, 3, 4, 6, 8, 10, 12, 16, 20, 24, 28, 36, 44, 52, 60, 76, 92, 108, 124, 156, 188, 220,
+    252, 316, 380, 444, 508, 636, 764, 892, 1020, 1276, 1532, 1788, 2044, 2556, 3068, 3580, 4092,
+    5116, 6140, 7164, 8188, 10236, 12284, 14332, 16380, 20476, 24572, 28668, 32764, 40956, 49148,
+    57340, 65532, 81916, 98300, 114684, 131068, 163836, 196604, 229372,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lzvn_instructions() {
+        let input = [
+            // small literal
+            &[0xe3, b'a', b'b', b'c'][..],
+            // small distance with one literal
+            &[0x40, 3, b'd'],
+            // previous distance with one literal
+            &[0x4e, b'e'],
+            // medium distance
+            &[0xa1, (5 << 2) | 3, 0],
+            // large distance
+            &[0x07, 2, 0],
+            // nop
+            &[0x16],
+            // small match with the previous distance
+            &[0xf2],
+            // large literal
+            &[0xe0, 0],
+            b"0123456789abcdef",
+            // large match with the previous distance
+            &[0xf0, 1],
+            // end of stream
+            &[0x06, 0, 0, 0, 0, 0, 0, 0],
+        ]
+        .concat();
+        let expected = b"abcdbcdecdececdececdececece0123456789abcdefefefefefefefefefe";
+        let mut actual = Vec::new();
+        lzvn_decompress(&input, &mut actual, expected.len()).unwrap();
+        assert_eq!(&expected[..], &actual[..]);
+        // the output is larger than the limit
+        let mut actual = Vec::new();
+        assert!(lzvn_decompress(&input, &mut actual, expected.len() - 1).is_err());
+        // the stream is truncated
+        let mut actual = Vec::new();
+        assert!(lzvn_decompress(&input[..input.len() - 1], &mut actual, expected.len()).is_err());
+    }
+}
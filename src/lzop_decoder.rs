@@ -0,0 +1,450 @@
+use std::io::BufRead;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+
+use crate::adler32;
+
+/// Decoder of the files compressed by `lzop`.
+///
+/// The file consists of the header followed by independently compressed blocks;
+/// each block is compressed with LZO1X or is stored if it's not compressible.
+/// The checksums of the header and of the blocks are verified.
+/// Filters and multipart files are not supported.
+pub(crate) struct LzopDecoder<R> {
+    reader: R,
+    state: State,
+    flags: u32,
+    // compressed block
+    input: Vec<u8>,
+    // decompressed block
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: BufRead> LzopDecoder<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            state: State::Header,
+            flags: 0,
+            input: Vec::new(),
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl<R> LzopDecoder<R> {
+    pub(crate) fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: BufRead> LzopDecoder<R> {
+    fn read_header(&mut self) -> Result<(), Error> {
+        let mut magic = [0_u8; LZOP_MAGIC.len()];
+        self.reader.read_exact(&mut magic)?;
+        if magic != LZOP_MAGIC {
+            return Err(invalid_data("invalid lzop header"));
+        }
+        // the checksum covers everything except the magic and the checksum itself
+        let mut header = Vec::new();
+        let version = u16::from_be_bytes(read_field(&mut self.reader, &mut header)?);
+        if version < LZOP_MIN_VERSION {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "lzop version is not supported",
+            ));
+        }
+        // library version
+        read_field::<_, 2>(&mut self.reader, &mut header)?;
+        if version >= LZOP_VERSION_0940 {
+            // version needed to extract
+            read_field::<_, 2>(&mut self.reader, &mut header)?;
+        }
+        let [method] = read_field(&mut self.reader, &mut header)?;
+        if !matches!(
+            method,
+            LZOP_M_LZO1X_1 | LZOP_M_LZO1X_1_15 | LZOP_M_LZO1X_999
+        ) {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("lzop compression method {method} is not supported"),
+            ));
+        }
+        if version >= LZOP_VERSION_0940 {
+            // compression level
+            read_field::<_, 1>(&mut self.reader, &mut header)?;
+        }
+        let flags = u32::from_be_bytes(read_field(&mut self.reader, &mut header)?);
+        if flags & LZOP_F_H_FILTER != 0 {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "lzop filters are not supported",
+            ));
+        }
+        if flags & LZOP_F_MULTIPART != 0 {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "multipart lzop files are not supported",
+            ));
+        }
+        // mode and the lower 32 bits of the modification time
+        read_field::<_, 8>(&mut self.reader, &mut header)?;
+        if version >= LZOP_VERSION_0940 {
+            // the upper 32 bits of the modification time
+            read_field::<_, 4>(&mut self.reader, &mut header)?;
+        }
+        let [name_len] = read_field(&mut self.reader, &mut header)?;
+        let start = header.len();
+        header.resize(start + name_len as usize, 0);
+        self.reader.read_exact(&mut header[start..])?;
+        let checksum = if flags & LZOP_F_H_CRC32 != 0 {
+            crc32(&header)
+        } else {
+            adler32(&header)
+        };
+        if read_u32(&mut self.reader)? != checksum {
+            return Err(invalid_data("lzop header checksum mismatch"));
+        }
+        if flags & LZOP_F_H_EXTRA_FIELD != 0 {
+            // the extra field and its checksum are skipped
+            let len = read_u32(&mut self.reader)? as u64;
+            let skipped =
+                std::io::copy(&mut (&mut self.reader).take(len + 4), &mut std::io::sink())?;
+            if skipped != len + 4 {
+                return Err(truncated());
+            }
+        }
+        self.flags = flags;
+        Ok(())
+    }
+
+    /// Decode the next block into `buf`.
+    ///
+    /// Returns `false` at the end of the stream.
+    fn read_block(&mut self) -> Result<bool, Error> {
+        let dst_len = read_u32(&mut self.reader)?;
+        if dst_len == 0 {
+            return Ok(false);
+        }
+        if dst_len as usize > LZOP_MAX_BLOCK_SIZE {
+            return Err(invalid_data("lzop block is too large"));
+        }
+        let src_len = read_u32(&mut self.reader)?;
+        if src_len == 0 || src_len > dst_len {
+            return Err(invalid_data("invalid lzop block size"));
+        }
+        let compressed = src_len < dst_len;
+        let mut checksums = [None; 4];
+        for (checksum, flag) in checksums.iter_mut().zip([
+            LZOP_F_ADLER32_D,
+            LZOP_F_CRC32_D,
+            LZOP_F_ADLER32_C,
+            LZOP_F_CRC32_C,
+        ]) {
+            // the checksums of the compressed data are omitted for stored blocks
+            let stored = matches!(flag, LZOP_F_ADLER32_C | LZOP_F_CRC32_C) && !compressed;
+            if self.flags & flag != 0 && !stored {
+                *checksum = Some(read_u32(&mut self.reader)?);
+            }
+        }
+        let [adler32_d, crc32_d, adler32_c, crc32_c] = checksums;
+        self.input.resize(src_len as usize, 0);
+        self.reader.read_exact(&mut self.input)?;
+        check(adler32_c, || adler32(&self.input))?;
+        check(crc32_c, || crc32(&self.input))?;
+        self.buf.clear();
+        self.pos = 0;
+        if compressed {
+            lzo1x_decompress(&self.input, &mut self.buf, dst_len as usize)?;
+            if self.buf.len() != dst_len as usize {
+                return Err(invalid_data("lzop block size mismatch"));
+            }
+        } else {
+            std::mem::swap(&mut self.buf, &mut self.input);
+        }
+        check(adler32_d, || adler32(&self.buf))?;
+        check(crc32_d, || crc32(&self.buf))?;
+        Ok(true)
+    }
+
+    /// Decode the next portion of the data into `buf`.
+    ///
+    /// Returns `false` at the end of the stream.
+    fn step(&mut self) -> Result<bool, Error> {
+        match self.state {
+            State::Header => {
+                self.read_header()?;
+                self.state = State::Blocks;
+                Ok(true)
+            }
+            State::Blocks => {
+                let more = self.read_block()?;
+                if !more {
+                    self.state = State::Done;
+                }
+                Ok(more)
+            }
+            State::Done => Ok(false),
+        }
+    }
+}
+
+impl<R: BufRead> Read for LzopDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.pos == self.buf.len() {
+            if !self.step()? {
+                return Ok(0);
+            }
+        }
+        let n = buf.len().min(self.buf.len() - self.pos);
+        buf[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Header,
+    Blocks,
+    Done,
+}
+
+/// Decompress LZO1X `input` and append at most `max_len` bytes to `output`.
+///
+/// The algorithm is described in the [LZO](https://www.oberhumer.com/opensource/lzo/) sources.
+/// All LZO1X variants (LZO1X-1, LZO1X-999 etc.) produce the same format.
+fn lzo1x_decompress(input: &[u8], output: &mut Vec<u8>, max_len: usize) -> Result<(), Error> {
+    let start = output.len();
+    let max_len = start + max_len;
+    let mut input = LzoInput { bytes: input };
+    // the number of literals that were copied after the last instruction:
+    // 0 if none, 1–3 for the literals encoded in the match, 4 for the literal run
+    let mut state = 0;
+    let mut t = input.byte()? as usize;
+    if t > 17 {
+        // the first literal run
+        let len = t - 17;
+        input.copy_literals(output, len, max_len)?;
+        state = len.min(4);
+        t = input.byte()? as usize;
+    }
+    loop {
+        let (distance, len, next_state) = if t >= 64 {
+            let b = input.byte()? as usize;
+            (1 + ((t >> 2) & 7) + (b << 3), (t >> 5) + 1, t & 3)
+        } else if t >= 32 {
+            let len = input.length(t & 31, 31)? + 2;
+            let (distance, next_state) = input.distance()?;
+            (1 + distance, len, next_state)
+        } else if t >= 16 {
+            let len = input.length(t & 7, 7)? + 2;
+            let (distance, next_state) = input.distance()?;
+            let distance = ((t & 8) << 11) + distance;
+            if distance == 0 {
+                if !input.bytes.is_empty() {
+                    return Err(invalid_data("trailing data after lzo stream"));
+                }
+                return Ok(());
+            }
+            (0x4000 + distance, len, next_state)
+        } else if state == 0 {
+            let len = input.length(t, 15)? + 3;
+            input.copy_literals(output, len, max_len)?;
+            state = 4;
+            t = input.byte()? as usize;
+            continue;
+        } else if state == 4 {
+            // a short match after the literal run
+            let b = input.byte()? as usize;
+            (1 + 0x800 + (t >> 2) + (b << 2), 3, t & 3)
+        } else {
+            // a short match after the literals of the previous match
+            let b = input.byte()? as usize;
+            (1 + (t >> 2) + (b << 2), 2, t & 3)
+        };
+        if distance > output.len() - start {
+            return Err(invalid_data("lzo match is out of bounds"));
+        }
+        if output.len() + len > max_len {
+            return Err(invalid_data("lzo output is too large"));
+        }
+        let mut remaining = len;
+        while remaining != 0 {
+            // the match might overlap with itself
+            let from = output.len() - distance;
+            let n = remaining.min(distance);
+            output.extend_from_within(from..from + n);
+            remaining -= n;
+        }
+        state = next_state;
+        input.copy_literals(output, state, max_len)?;
+        t = input.byte()? as usize;
+    }
+}
+
+struct LzoInput<'a> {
+    bytes: &'a [u8],
+}
+
+impl LzoInput<'_> {
+    fn byte(&mut self) -> Result<u8, Error> {
+        let (byte, rest) = self.bytes.split_first().ok_or_else(lzo_truncated)?;
+        self.bytes = rest;
+        Ok(*byte)
+    }
+
+    /// Decode the length that is either stored in the instruction byte (`value`)
+    /// or is encoded in the following zero bytes and the first non-zero byte.
+    fn length(&mut self, value: usize, max_value: usize) -> Result<usize, Error> {
+        if value != 0 {
+            return Ok(value);
+        }
+        let mut len = max_value;
+        loop {
+            match self.byte()? {
+                0 => len += 255,
+                b => return Ok(len + b as usize),
+            }
+        }
+    }
+
+    /// Decode 14-bit distance and the number of the literals that follow the match.
+    fn distance(&mut self) -> Result<(usize, usize), Error> {
+        let value = u16::from_le_bytes([self.byte()?, self.byte()?]) as usize;
+        Ok((value >> 2, value & 3))
+    }
+
+    fn copy_literals(
+        &mut self,
+        output: &mut Vec<u8>,
+        len: usize,
+        max_len: usize,
+    ) -> Result<(), Error> {
+        if len > self.bytes.len() {
+            return Err(lzo_truncated());
+        }
+        if output.len() + len > max_len {
+            return Err(invalid_data("lzo output is too large"));
+        }
+        let (literals, rest) = self.bytes.split_at(len);
+        output.extend_from_slice(literals);
+        self.bytes = rest;
+        Ok(())
+    }
+}
+
+fn check(expected: Option<u32>, actual: impl FnOnce() -> u32) -> Result<(), Error> {
+    match expected {
+        Some(expected) if expected != actual() => Err(invalid_data("lzop checksum mismatch")),
+        _ => Ok(()),
+    }
+}
+
+/// Read `N` bytes and append them to `header`.
+fn read_field<R: Read, const N: usize>(
+    reader: &mut R,
+    header: &mut Vec<u8>,
+) -> Result<[u8; N], Error> {
+    let mut bytes = [0_u8; N];
+    reader.read_exact(&mut bytes)?;
+    header.extend_from_slice(&bytes);
+    Ok(bytes)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, Error> {
+    let mut bytes = [0_u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn truncated() -> Error {
+    Error::new(ErrorKind::UnexpectedEof, "truncated lzop file")
+}
+
+fn lzo_truncated() -> Error {
+    invalid_data("truncated lzo block")
+}
+
+fn invalid_data(message: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+pub(crate) const LZOP_MAGIC: [u8; 9] = *b"\x89LZO\x00\r\n\x1a\n";
+/// The largest uncompressed block size.
+const LZOP_MAX_BLOCK_SIZE: usize = 64 * 1024 * 1024;
+/// The version of the format that added the version needed to extract, the compression level
+/// and the upper 32 bits of the modification time to the header.
+pub(crate) const LZOP_VERSION_0940: u16 = 0x0940;
+pub(crate) const LZOP_M_LZO1X_1: u8 = 1;
+pub(crate) const LZOP_F_ADLER32_D: u32 = 0x1;
+const LZOP_MIN_VERSION: u16 = 0x0900;
+const LZOP_M_LZO1X_1_15: u8 = 2;
+const LZOP_M_LZO1X_999: u8 = 3;
+const LZOP_F_ADLER32_C: u32 = 0x2;
+const LZOP_F_H_EXTRA_FIELD: u32 = 0x40;
+const LZOP_F_CRC32_D: u32 = 0x100;
+const LZOP_F_CRC32_C: u32 = 0x200;
+const LZOP_F_MULTIPART: u32 = 0x400;
+const LZOP_F_H_FILTER: u32 = 0x800;
+const LZOP_F_H_CRC32: u32 = 0x1000;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lzo1x_instructions() {
+        let input = [
+            // the first literal run
+            &[19, b'a', b'b'][..],
+            // the short match after the literals, followed by one literal
+            &[5, 0, b'c'],
+            // the short match after the literal
+            &[0, 0],
+            // the literal run
+            &[1, b'd', b'e', b'f', b'g'],
+            // M2 match
+            &[72, 1],
+            // M3 match with the length encoded in the following byte
+            &[32, 7, 8, 0],
+            // end of stream
+            &[0x11, 0, 0],
+        ]
+        .concat();
+        let mut expected = b"ababcccdefgaba".to_vec();
+        expected.extend(b"aba".repeat(14).iter().take(40));
+        let mut actual = Vec::new();
+        lzo1x_decompress(&input, &mut actual, expected.len()).unwrap();
+        assert_eq!(expected, actual);
+        // the output is larger than the limit
+        let mut actual = Vec::new();
+        assert!(lzo1x_decompress(&input, &mut actual, expected.len() - 1).is_err());
+    }
+}
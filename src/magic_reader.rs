@@ -10,21 +10,78 @@ macro_rules! define_magic_reader {
 
         pub struct MagicReader<R> {
             reader: R,
-            buf: [u8; MAX_MAGIC_BYTES],
+            buf: Vec<u8>,
             first: usize,
             last: usize,
+            /// Whether the underlying reader is an in-memory slice,
+            /// i.e. whether all the remaining data is available at once.
+            in_memory: bool,
+            /// The number of bytes that are allowed to be read from the underlying reader.
+            limit: u64,
+            /// Whether the limit was set explicitly.
+            limited: bool,
+            #[cfg(feature = "metrics")]
+            compressed_bytes: metrics::Counter,
         }
 
         impl<R> MagicReader<R> {
             pub fn new(reader: R) -> Self {
                 Self {
                     reader,
-                    buf: [0; MAX_MAGIC_BYTES],
+                    // allocated on demand
+                    buf: Vec::new(),
                     first: 0,
                     last: 0,
+                    in_memory: false,
+                    limit: u64::MAX,
+                    limited: false,
+                    #[cfg(feature = "metrics")]
+                    compressed_bytes: crate::compressed_bytes_counter(),
                 }
             }
 
+            /// Create new reader from the in-memory `reader`.
+            pub fn in_memory(reader: R) -> Self {
+                Self {
+                    in_memory: true,
+                    ..Self::new(reader)
+                }
+            }
+
+            /// Whether all the remaining data is available at once.
+            pub fn is_in_memory(&self) -> bool {
+                self.in_memory
+            }
+
+            /// Read at most `limit` bytes from the underlying reader from now on.
+            pub fn set_limit(&mut self, limit: u64) {
+                self.limit = limit;
+                self.limited = true;
+            }
+
+            /// Whether the number of bytes that can be read from the underlying reader is limited.
+            #[allow(unused)]
+            pub fn is_limited(&self) -> bool {
+                self.limited
+            }
+
+            /// Truncate `len` to the number of bytes that are allowed to be read.
+            #[inline]
+            fn max_len(&self, len: usize) -> usize {
+                if self.limit < len as u64 {
+                    self.limit as usize
+                } else {
+                    len
+                }
+            }
+
+            /// Count the bytes that were passed to the decoder.
+            #[inline]
+            fn count(&self, _n: usize) {
+                #[cfg(feature = "metrics")]
+                self.compressed_bytes.increment(_n as u64);
+            }
+
             pub fn get_ref(&self) -> &R {
                 &self.reader
             }
@@ -37,6 +94,39 @@ macro_rules! define_magic_reader {
                 self.reader
             }
 
+            /// Get the buffered bytes.
+            pub fn buffered(&self) -> &[u8] {
+                &self.buf[self.first..self.last]
+            }
+
+            /// Discard the first `n` buffered bytes.
+            pub fn skip_buffered(&mut self, n: usize) {
+                debug_assert!(self.first + n <= self.last);
+                self.first += n;
+                self.compact();
+            }
+
+            /// Put the bytes back so that they are read before the remaining buffered bytes.
+            #[allow(unused)]
+            pub fn unread(&mut self, bytes: &[u8]) {
+                let mut buf = Vec::with_capacity(bytes.len() + self.last - self.first);
+                buf.extend_from_slice(bytes);
+                buf.extend_from_slice(&self.buf[self.first..self.last]);
+                self.first = 0;
+                self.last = buf.len();
+                if buf.len() < MAX_MAGIC_BYTES {
+                    buf.resize(MAX_MAGIC_BYTES, 0);
+                }
+                self.buf = buf;
+            }
+
+            /// Move the buffered bytes to the start of the buffer.
+            fn compact(&mut self) {
+                self.buf.copy_within(self.first..self.last, 0);
+                self.last -= self.first;
+                self.first = 0;
+            }
+
             #[cold]
             fn do_read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
                 let n = buf.len().min(self.last - self.first);
@@ -57,15 +147,82 @@ macro_rules! define_magic_reader {
 
         crate::define_read_magic!($trait);
 
+        #[cfg(feature = "xz")]
+        impl<R: Read> MagicReader<R> {
+            /// Skip the padding (null bytes in groups of four) that follows xz stream.
+            pub fn skip_xz_padding(&mut self) -> Result<(), Error> {
+                loop {
+                    let mut padding = [0_u8; 4];
+                    let mut n = 0;
+                    while n != padding.len() {
+                        match self.read(&mut padding[n..]) {
+                            Ok(0) => break,
+                            Ok(m) => n += m,
+                            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                            Err(e) => return Err(e),
+                        }
+                    }
+                    if n != padding.len() || padding != [0; 4] {
+                        self.unread(&padding[..n]);
+                        return Ok(());
+                    }
+                }
+            }
+        }
+
+        #[cfg(feature = "zstd")]
+        impl<R: Read> MagicReader<R> {
+            /// Skip zstd skippable frames that precede the current position.
+            pub fn skip_zstd_skippable_frames(&mut self) -> Result<(), Error> {
+                use crate::ZSTD_SKIPPABLE_MAGIC;
+                use crate::ZSTD_SKIPPABLE_MASK;
+                loop {
+                    // frame magic and frame size
+                    let mut header = [0_u8; 8];
+                    let mut n = 0;
+                    while n != header.len() {
+                        match self.read(&mut header[n..]) {
+                            Ok(0) => break,
+                            Ok(m) => n += m,
+                            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                            Err(e) => return Err(e),
+                        }
+                    }
+                    let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+                    if n != header.len() || magic & ZSTD_SKIPPABLE_MASK != ZSTD_SKIPPABLE_MAGIC {
+                        self.unread(&header[..n]);
+                        return Ok(());
+                    }
+                    let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+                    let skipped = std::io::copy(&mut self.take(size as u64), &mut std::io::sink())?;
+                    if skipped != size as u64 {
+                        return Err(ErrorKind::UnexpectedEof.into());
+                    }
+                }
+            }
+        }
+
+        impl<R: Read> MagicReader<R> {
+            /// Read from the underlying reader within the limit.
+            #[inline]
+            fn read_limited(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+                let len = self.max_len(buf.len());
+                let n = self.reader.read(&mut buf[..len])?;
+                self.limit -= n as u64;
+                Ok(n)
+            }
+        }
+
         impl<R: Read> Read for MagicReader<R> {
             fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
-                if self.first == self.last {
-                    self.reader.read(buf)
+                let n = if self.first == self.last {
+                    self.read_limited(buf)?
                 } else {
-                    let mut n = self.do_read(buf)?;
-                    n += self.reader.read(&mut buf[n..])?;
-                    Ok(n)
-                }
+                    let n = self.do_read(buf)?;
+                    n + self.read_limited(&mut buf[n..])?
+                };
+                self.count(n);
+                Ok(n)
             }
 
             #[cfg(feature = "nightly")]
@@ -74,16 +231,24 @@ macro_rules! define_magic_reader {
             }
 
             fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> Result<usize, Error> {
-                if self.first == self.last {
-                    self.reader.read_vectored(bufs)
+                let n = if self.first == self.last && self.limit == u64::MAX {
+                    self.reader.read_vectored(bufs)?
+                } else if self.first == self.last {
+                    let buf = bufs
+                        .iter_mut()
+                        .find(|b| !b.is_empty())
+                        .map_or(&mut [][..], |b| &mut **b);
+                    self.read_limited(buf)?
                 } else {
                     // this is the default `read_vectored` implementation from `std` library
                     let buf = bufs
                         .iter_mut()
                         .find(|b| !b.is_empty())
                         .map_or(&mut [][..], |b| &mut **b);
-                    self.do_read(buf)
-                }
+                    self.do_read(buf)?
+                };
+                self.count(n);
+                Ok(n)
             }
 
             fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize, Error> {
@@ -93,7 +258,10 @@ macro_rules! define_magic_reader {
                     n += self.last - self.first;
                     self.first = self.last;
                 }
-                n += self.reader.read_to_end(buf)?;
+                let m = (&mut self.reader).take(self.limit).read_to_end(buf)?;
+                self.limit -= m as u64;
+                n += m;
+                self.count(n);
                 Ok(n)
             }
 
@@ -107,16 +275,26 @@ macro_rules! define_magic_reader {
                     buf.push_str(s);
                     Ok(n)
                 } else {
-                    self.reader.read_to_string(buf)
+                    let n = (&mut self.reader).take(self.limit).read_to_string(buf)?;
+                    self.limit -= n as u64;
+                    self.count(n);
+                    Ok(n)
                 }
             }
 
             fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<(), Error> {
                 if self.first != self.last {
                     let n = self.do_read(buf)?;
+                    self.count(n);
                     buf = &mut buf[n..];
                 }
-                self.reader.read_exact(buf)
+                if (buf.len() as u64) > self.limit {
+                    return Err(ErrorKind::UnexpectedEof.into());
+                }
+                self.reader.read_exact(buf)?;
+                self.limit -= buf.len() as u64;
+                self.count(buf.len());
+                Ok(())
             }
 
             #[cfg(feature = "nightly")]
@@ -124,7 +302,12 @@ macro_rules! define_magic_reader {
                 if self.first != self.last {
                     self.do_read_buf(&mut buf)?;
                 }
-                self.reader.read_buf(buf)
+                let written = buf.written();
+                (&mut self.reader)
+                    .take(self.limit)
+                    .read_buf(buf.reborrow())?;
+                self.limit -= (buf.written() - written) as u64;
+                Ok(())
             }
 
             #[cfg(feature = "nightly")]
@@ -132,7 +315,13 @@ macro_rules! define_magic_reader {
                 if self.first != self.last {
                     self.do_read_buf(&mut buf)?;
                 }
-                self.reader.read_buf_exact(buf)
+                if (buf.capacity() as u64) > self.limit {
+                    return Err(ErrorKind::UnexpectedEof.into());
+                }
+                let written = buf.written();
+                self.reader.read_buf_exact(buf.reborrow())?;
+                self.limit -= (buf.written() - written) as u64;
+                Ok(())
             }
         }
 
@@ -200,55 +389,91 @@ macro_rules! define_read_magic {
     (Read) => {
         impl<R: Read> MagicReader<R> {
             pub fn read_magic(&mut self) -> Result<&[u8], Error> {
-                let n = self.reader.read(&mut self.buf[self.last..])?;
+                if self.buf.len() < MAX_MAGIC_BYTES {
+                    self.buf.resize(MAX_MAGIC_BYTES, 0);
+                }
+                let len = self.max_len(self.buf.len() - self.last);
+                let n = self
+                    .reader
+                    .read(&mut self.buf[self.last..(self.last + len)])?;
+                self.limit -= n as u64;
                 self.last += n;
                 Ok(&self.buf[..self.last])
             }
 
             #[cold]
             pub fn read_magic_slow(&mut self) -> Result<&[u8], Error> {
-                loop {
-                    let n = match self.reader.read(&mut self.buf[self.last..]) {
+                self.read_magic_window(MAX_MAGIC_BYTES)
+            }
+
+            /// Buffer up to `len` bytes from the start of the stream.
+            #[cold]
+            pub fn read_magic_window(&mut self, len: usize) -> Result<&[u8], Error> {
+                self.compact();
+                if self.buf.len() < len {
+                    self.buf.resize(len, 0);
+                }
+                while self.last < len {
+                    let end = self.last + self.max_len(len - self.last);
+                    let n = match self.reader.read(&mut self.buf[self.last..end]) {
                         Ok(n) => n,
                         Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
                         Err(e) => return Err(e),
                     };
                     if n == 0 {
-                        return Ok(&self.buf[..self.last]);
+                        break;
                     }
+                    self.limit -= n as u64;
                     self.last += n;
-                    if self.last == MAX_MAGIC_BYTES {
-                        return Ok(&self.buf[..]);
-                    }
                 }
+                Ok(&self.buf[..self.last])
             }
         }
     };
     (BufRead) => {
         impl<R: std::io::BufRead> MagicReader<R> {
             pub fn read_magic(&mut self) -> Result<&[u8], Error> {
-                self.reader.fill_buf()
+                if self.first != self.last {
+                    return self.read_magic_slow();
+                }
+                let limit = self.limit;
+                let buf = self.reader.fill_buf()?;
+                Ok(&buf[..buf.len().min(limit.try_into().unwrap_or(usize::MAX))])
             }
 
             #[cold]
             pub fn read_magic_slow(&mut self) -> Result<&[u8], Error> {
-                loop {
+                self.read_magic_window(MAX_MAGIC_BYTES)
+            }
+
+            /// Buffer up to `len` bytes from the start of the stream.
+            #[cold]
+            pub fn read_magic_window(&mut self, len: usize) -> Result<&[u8], Error> {
+                self.compact();
+                if self.buf.len() < len {
+                    self.buf.resize(len, 0);
+                }
+                while self.last < len {
                     let buf = match self.reader.fill_buf() {
                         Ok(buf) => buf,
                         Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
                         Err(e) => return Err(e),
                     };
-                    let n = buf.len().min(MAX_MAGIC_BYTES - self.last);
+                    let n = buf.len().min(len - self.last);
+                    let n = if self.limit < n as u64 {
+                        self.limit as usize
+                    } else {
+                        n
+                    };
                     if n == 0 {
-                        return Ok(&self.buf[..self.last]);
+                        break;
                     }
                     self.buf[self.last..(self.last + n)].copy_from_slice(&buf[..n]);
                     self.reader.consume(n);
+                    self.limit -= n as u64;
                     self.last += n;
-                    if self.last == MAX_MAGIC_BYTES {
-                        return Ok(&self.buf[..]);
-                    }
                 }
+                Ok(&self.buf[..self.last])
             }
         }
     };
@@ -263,15 +488,19 @@ macro_rules! impl_buf_read_for_magic_reader {
         impl<R: BufRead> BufRead for MagicReader<R> {
             fn fill_buf(&mut self) -> Result<&[u8], Error> {
                 if self.first == self.last {
-                    self.reader.fill_buf()
+                    let limit = self.limit;
+                    let buf = self.reader.fill_buf()?;
+                    Ok(&buf[..buf.len().min(limit.try_into().unwrap_or(usize::MAX))])
                 } else {
                     Ok(&self.buf[self.first..self.last])
                 }
             }
 
             fn consume(&mut self, n: usize) {
+                self.count(n);
                 if self.first == self.last {
                     self.reader.consume(n);
+                    self.limit -= n as u64;
                 } else {
                     debug_assert!(self.first + n <= self.last);
                     self.first += n;
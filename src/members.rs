@@ -0,0 +1,155 @@
+macro_rules! define_members {
+    ($trait: ident) => {
+        /// An iterator over the members of the concatenated stream.
+        ///
+        /// Gzip members, xz streams, zstd and LZ4 frames are decoded one by one (see
+        /// [per_member](AnyDecoder::per_member)).
+        /// Other formats are decoded as a whole, i.e. each stream is treated as one member.
+        /// Each member is a reader of its decompressed data that exposes the member's metadata.
+        /// The members borrow the decoder, hence the iteration is done via
+        /// [next_member](Members::next_member) rather than [Iterator].
+        /// The unread data of the member is skipped when the next one is requested.
+        ///
+        /// ```rust
+        /// # #[cfg(feature = "flate2")] {
+        /// use deko::prelude::*;
+        ///
+        /// let mut compressed = Vec::new();
+        /// for data in ["hello", "world"] {
+        ///     let mut writer = AnyEncoder::new(Vec::new(), Format::Gz, Compression::Best).unwrap();
+        ///     writer.write_all(data.as_bytes()).unwrap();
+        ///     compressed.extend(writer.finish().unwrap());
+        /// }
+        /// let mut members = deko::bufread::AnyDecoder::new(&compressed[..]).members();
+        /// while let Some(member) = members.next_member() {
+        ///     let mut member = member.unwrap();
+        ///     let mut string = String::new();
+        ///     member.read_to_string(&mut string).unwrap();
+        ///     println!("{}: {} bytes", member.index(), string.len());
+        /// }
+        /// # }
+        /// ```
+        pub struct Members<'a, R: $trait> {
+            decoder: AnyDecoder<'a, R>,
+            index: usize,
+            started: bool,
+            done: bool,
+        }
+
+        impl<'a, R: $trait> AnyDecoder<'a, R> {
+            /// Iterate over the members of the concatenated stream.
+            ///
+            /// See [Members].
+            pub fn members(mut self) -> Members<'a, R> {
+                self.per_member(true);
+                Members {
+                    decoder: self,
+                    index: 0,
+                    started: false,
+                    done: false,
+                }
+            }
+        }
+
+        impl<'a, R: $trait> Members<'a, R> {
+            /// Get the next member.
+            ///
+            /// Returns `None` at the end of the stream; empty stream has no members.
+            /// The iteration stops after the first error.
+            pub fn next_member(&mut self) -> Option<Result<Member<'_, 'a, R>, Error>> {
+                match self.advance() {
+                    Ok(true) => Some(Ok(Member {
+                        decoder: &mut self.decoder,
+                        index: self.index,
+                    })),
+                    Ok(false) => None,
+                    Err(e) => {
+                        self.done = true;
+                        Some(Err(e))
+                    }
+                }
+            }
+
+            /// Return the decoder.
+            pub fn into_inner(self) -> AnyDecoder<'a, R> {
+                self.decoder
+            }
+
+            fn advance(&mut self) -> Result<bool, Error> {
+                if self.done {
+                    return Ok(false);
+                }
+                if self.started {
+                    if !self.decoder.next_member()? {
+                        self.done = true;
+                        return Ok(false);
+                    }
+                    self.index += 1;
+                    return Ok(true);
+                }
+                self.started = true;
+                if let State::Pending(ref mut reader) = self.decoder.state {
+                    if reader.read_magic()?.is_empty() {
+                        self.done = true;
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+        }
+
+        /// A member of the concatenated stream.
+        ///
+        /// Reading from the member returns its decompressed data.
+        /// Created by [Members::next_member].
+        pub struct Member<'m, 'a, R: $trait> {
+            decoder: &'m mut AnyDecoder<'a, R>,
+            index: usize,
+        }
+
+        impl<'m, 'a, R: $trait> Member<'m, 'a, R> {
+            /// Zero-based index of the member in the stream.
+            pub fn index(&self) -> usize {
+                self.index
+            }
+
+            /// Get the compression format of the member.
+            pub fn kind(&mut self) -> Result<Format, Error> {
+                self.decoder.kind()
+            }
+
+            /// Get the decoder of the member.
+            ///
+            /// Use it to access format-specific metadata, e.g. gzip fields.
+            pub fn decoder(&mut self) -> &mut AnyDecoder<'a, R> {
+                self.decoder
+            }
+        }
+
+        impl<R: $trait> Read for Member<'_, '_, R> {
+            fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+                self.decoder.read(buf)
+            }
+        }
+
+        #[cfg(feature = "flate2")]
+        impl<R: $trait> crate::GzDecoderExt for Member<'_, '_, R> {
+            fn gz_header(&mut self) -> Result<Option<&flate2::GzHeader>, Error> {
+                self.decoder.gz_header()
+            }
+        }
+
+        #[cfg(feature = "zstd")]
+        impl<R: $trait> crate::ZstdDecoderExt for Member<'_, '_, R> {
+            fn dict_id(&mut self) -> Result<Option<u32>, Error> {
+                self.decoder.dict_id()
+            }
+
+            fn content_size(&mut self) -> Result<Option<u64>, Error> {
+                self.decoder.content_size()
+            }
+        }
+    };
+}
+
+pub(crate) use define_members;
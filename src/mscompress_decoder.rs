@@ -0,0 +1,586 @@
+use std::io::BufRead;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+
+/// Decoder of the files compressed by Microsoft `COMPRESS.EXE` (`SZDD` and `KWAJ` formats).
+///
+/// `SZDD` files are compressed with LZSS.
+/// `KWAJ` files are either stored, XOR-ed with `0xff`, compressed with LZSS or
+/// compressed with LZ+Huffman; MSZIP compression is not supported.
+pub(crate) struct MsCompressDecoder<R> {
+    reader: R,
+    container: Container,
+    method: Method,
+    // the size from the header
+    expected_size: Option<u64>,
+    size: u64,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: BufRead> MsCompressDecoder<R> {
+    pub(crate) fn szdd(reader: R) -> Self {
+        Self::new(reader, Container::Szdd)
+    }
+
+    pub(crate) fn kwaj(reader: R) -> Self {
+        Self::new(reader, Container::Kwaj)
+    }
+
+    fn new(reader: R, container: Container) -> Self {
+        Self {
+            reader,
+            container,
+            method: Method::Header,
+            expected_size: None,
+            size: 0,
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl<R> MsCompressDecoder<R> {
+    pub(crate) fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: BufRead> MsCompressDecoder<R> {
+    fn read_szdd_header(&mut self) -> Result<(), Error> {
+        let mut header = [0_u8; SZDD_HEADER_LEN];
+        self.reader.read_exact(&mut header)?;
+        if header[..8] != SZDD_MAGIC {
+            return Err(invalid_data("invalid szdd header"));
+        }
+        if header[8] != b'A' {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "unsupported szdd compression mode",
+            ));
+        }
+        self.expected_size =
+            Some(u32::from_le_bytes([header[10], header[11], header[12], header[13]]) as u64);
+        self.method = Method::Lzss(Box::new(Lzss::new()));
+        Ok(())
+    }
+
+    fn read_kwaj_header(&mut self) -> Result<(), Error> {
+        let mut header = [0_u8; KWAJ_HEADER_LEN];
+        self.reader.read_exact(&mut header)?;
+        if header[..8] != KWAJ_MAGIC {
+            return Err(invalid_data("invalid kwaj header"));
+        }
+        let method = u16::from_le_bytes([header[8], header[9]]);
+        let data_offset = u16::from_le_bytes([header[10], header[11]]) as u64;
+        let flags = u16::from_le_bytes([header[12], header[13]]);
+        let mut offset = KWAJ_HEADER_LEN as u64;
+        if flags & KWAJ_HAS_LENGTH != 0 {
+            let mut length = [0_u8; 4];
+            self.reader.read_exact(&mut length)?;
+            self.expected_size = Some(u32::from_le_bytes(length) as u64);
+            offset += 4;
+        }
+        if flags & KWAJ_HAS_UNKNOWN != 0 {
+            offset += skip(&mut self.reader, 2)?;
+        }
+        if flags & KWAJ_HAS_DATA != 0 {
+            let mut len = [0_u8; 2];
+            self.reader.read_exact(&mut len)?;
+            offset += 2 + skip(&mut self.reader, u16::from_le_bytes(len) as u64)?;
+        }
+        // file name and extension
+        for (flag, max_len) in [(KWAJ_HAS_NAME, 9), (KWAJ_HAS_EXTENSION, 4)] {
+            if flags & flag == 0 {
+                continue;
+            }
+            let mut name = Vec::new();
+            (&mut self.reader).take(max_len).read_until(0, &mut name)?;
+            offset += name.len() as u64;
+        }
+        if flags & KWAJ_HAS_TEXT != 0 {
+            let mut len = [0_u8; 2];
+            self.reader.read_exact(&mut len)?;
+            offset += 2 + skip(&mut self.reader, u16::from_le_bytes(len) as u64)?;
+        }
+        if data_offset < offset {
+            return Err(invalid_data("invalid kwaj data offset"));
+        }
+        skip(&mut self.reader, data_offset - offset)?;
+        self.method = match method {
+            KWAJ_STORED => Method::Stored,
+            KWAJ_XOR => Method::Xor,
+            KWAJ_LZSS => Method::Lzss(Box::new(Lzss::new())),
+            KWAJ_LZH => Method::Lzh(Box::new(Lzh::new(&mut self.reader)?)),
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!("kwaj compression method {method} is not supported"),
+                ))
+            }
+        };
+        Ok(())
+    }
+
+    /// Decode the next portion of the data into `buf`.
+    ///
+    /// Returns `false` at the end of the stream.
+    fn step(&mut self) -> Result<bool, Error> {
+        self.buf.clear();
+        self.pos = 0;
+        let more = match self.method {
+            Method::Header => {
+                match self.container {
+                    Container::Szdd => self.read_szdd_header()?,
+                    Container::Kwaj => self.read_kwaj_header()?,
+                }
+                true
+            }
+            Method::Stored | Method::Xor => {
+                let chunk = self.reader.fill_buf()?;
+                self.buf.extend_from_slice(chunk);
+                self.reader.consume(self.buf.len());
+                if let Method::Xor = self.method {
+                    self.buf.iter_mut().for_each(|b| *b ^= 0xff);
+                }
+                !self.buf.is_empty()
+            }
+            Method::Lzss(ref mut lzss) => lzss.step(&mut self.reader, &mut self.buf)?,
+            Method::Lzh(ref mut lzh) => lzh.step(&mut self.reader, &mut self.buf)?,
+            Method::Done => false,
+        };
+        if let Some(expected_size) = self.expected_size {
+            // the padding bits might be decoded as garbage
+            let max = expected_size - self.size;
+            if self.buf.len() as u64 >= max {
+                self.buf.truncate(max as usize);
+                self.method = Method::Done;
+            }
+        }
+        self.size += self.buf.len() as u64;
+        if !more {
+            self.method = Method::Done;
+            match (self.container, self.expected_size) {
+                (Container::Szdd, Some(expected_size)) if expected_size != self.size => {
+                    return Err(Error::new(ErrorKind::UnexpectedEof, "truncated szdd file"));
+                }
+                _ => {}
+            }
+        }
+        Ok(more || !self.buf.is_empty())
+    }
+}
+
+impl<R: BufRead> Read for MsCompressDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.pos == self.buf.len() {
+            if !self.step()? {
+                return Ok(0);
+            }
+        }
+        let n = buf.len().min(self.buf.len() - self.pos);
+        buf[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Container {
+    Szdd,
+    Kwaj,
+}
+
+enum Method {
+    Header,
+    Stored,
+    Xor,
+    Lzss(Box<Lzss>),
+    Lzh(Box<Lzh>),
+    Done,
+}
+
+/// The last 4 KiB of the output initially filled with spaces.
+struct Window {
+    bytes: [u8; MS_WINDOW_SIZE],
+    pos: usize,
+}
+
+impl Window {
+    fn new(pos: usize) -> Self {
+        Self {
+            bytes: [b' '; MS_WINDOW_SIZE],
+            pos,
+        }
+    }
+
+    fn push(&mut self, byte: u8, out: &mut Vec<u8>) {
+        self.bytes[self.pos] = byte;
+        self.pos = (self.pos + 1) % MS_WINDOW_SIZE;
+        out.push(byte);
+    }
+
+    /// Copy `len` bytes starting from window position `from`.
+    fn copy(&mut self, mut from: usize, len: usize, out: &mut Vec<u8>) {
+        for _ in 0..len {
+            self.push(self.bytes[from], out);
+            from = (from + 1) % MS_WINDOW_SIZE;
+        }
+    }
+}
+
+/// LZSS decoder (`EXPAND.EXE` variant).
+struct Lzss {
+    window: Window,
+}
+
+impl Lzss {
+    fn new() -> Self {
+        Self {
+            window: Window::new(MS_LZSS_START),
+        }
+    }
+
+    /// Decode one control byte and the following literals and matches.
+    fn step<R: BufRead>(&mut self, reader: &mut R, out: &mut Vec<u8>) -> Result<bool, Error> {
+        let Some(control) = read_byte(reader)? else {
+            return Ok(false);
+        };
+        for i in 0..8 {
+            if control & (1 << i) != 0 {
+                let Some(byte) = read_byte(reader)? else {
+                    return Ok(false);
+                };
+                self.window.push(byte, out);
+            } else {
+                let (Some(a), Some(b)) = (read_byte(reader)?, read_byte(reader)?) else {
+                    return Ok(false);
+                };
+                let from = a as usize | ((b as usize & 0xf0) << 4);
+                let len = (b as usize & 0x0f) + MS_LZSS_MIN_MATCH;
+                self.window.copy(from, len, out);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// LZ+Huffman decoder (`KWAJ` compression method 3).
+///
+/// The algorithm is described in the [libmspack](https://www.cabextract.org.uk/libmspack/)
+/// sources.
+struct Lzh {
+    window: Window,
+    bits: BitReader,
+    match_len1: Huffman,
+    match_len2: Huffman,
+    literal_len: Huffman,
+    offset: Huffman,
+    literal: Huffman,
+    literal_run: bool,
+}
+
+impl Lzh {
+    fn new<R: BufRead>(reader: &mut R) -> Result<Self, Error> {
+        let mut bits = BitReader::new();
+        // six table types are stored for alignment, only five are used
+        let mut types = [0_u32; 6];
+        for t in types.iter_mut() {
+            *t = bits.bits(reader, 4)?.ok_or_else(truncated)?;
+        }
+        let mut table = |i: usize, num_symbols| -> Result<Huffman, Error> {
+            let lengths = read_lengths(&mut bits, reader, types[i], num_symbols)?;
+            Huffman::new(&lengths)
+        };
+        let match_len1 = table(0, 16)?;
+        let match_len2 = table(1, 16)?;
+        let literal_len = table(2, 32)?;
+        let offset = table(3, 64)?;
+        let literal = table(4, 256)?;
+        Ok(Self {
+            window: Window::new(0),
+            bits,
+            match_len1,
+            match_len2,
+            literal_len,
+            offset,
+            literal,
+            literal_run: false,
+        })
+    }
+
+    /// Decode one match or one run of literals.
+    ///
+    /// The stream ends where the input ends.
+    fn step<R: BufRead>(&mut self, reader: &mut R, out: &mut Vec<u8>) -> Result<bool, Error> {
+        let table = if self.literal_run {
+            &self.match_len2
+        } else {
+            &self.match_len1
+        };
+        let Some(len) = table.decode(&mut self.bits, reader)? else {
+            return Ok(false);
+        };
+        if len != 0 {
+            self.literal_run = false;
+            let Some(high) = self.offset.decode(&mut self.bits, reader)? else {
+                return Ok(false);
+            };
+            let Some(low) = self.bits.bits(reader, 6)? else {
+                return Ok(false);
+            };
+            let offset = ((high as usize) << 6) | low as usize;
+            let from = (self.window.pos + MS_WINDOW_SIZE - offset) % MS_WINDOW_SIZE;
+            self.window.copy(from, len as usize + 2, out);
+        } else {
+            let Some(len) = self.literal_len.decode(&mut self.bits, reader)? else {
+                return Ok(false);
+            };
+            let len = len + 1;
+            self.literal_run = len != 32;
+            for _ in 0..len {
+                let Some(byte) = self.literal.decode(&mut self.bits, reader)? else {
+                    return Ok(false);
+                };
+                self.window.push(byte as u8, out);
+            }
+        }
+        Ok(true)
+    }
+}
+
+fn read_lengths<R: BufRead>(
+    bits: &mut BitReader,
+    reader: &mut R,
+    table_type: u32,
+    num_symbols: usize,
+) -> Result<Vec<u8>, Error> {
+    let mut read =
+        |n| -> Result<u8, Error> { Ok(bits.bits(reader, n)?.ok_or_else(truncated)? as u8) };
+    let mut lengths = vec![0_u8; num_symbols];
+    match table_type {
+        // fixed-length codes
+        0 => lengths.fill(num_symbols.trailing_zeros() as u8),
+        // the length is either the same as the previous one,
+        // one more than the previous one or is stored explicitly
+        1 => {
+            let mut len = read(4)?;
+            lengths[0] = len;
+            for l in lengths[1..].iter_mut() {
+                if read(1)? == 1 {
+                    len = if read(1)? == 0 {
+                        len.wrapping_add(1)
+                    } else {
+                        read(4)?
+                    };
+                }
+                *l = len;
+            }
+        }
+        // the length is the delta from the previous one or is stored explicitly
+        2 => {
+            let mut len = read(4)?;
+            lengths[0] = len;
+            for l in lengths[1..].iter_mut() {
+                len = match read(2)? {
+                    3 => read(4)?,
+                    delta => len.wrapping_add(delta).wrapping_sub(1),
+                };
+                *l = len;
+            }
+        }
+        // explicit lengths
+        3 => {
+            for l in lengths.iter_mut() {
+                *l = read(4)?;
+            }
+        }
+        _ => return Err(invalid_data("invalid kwaj huffman table type")),
+    }
+    Ok(lengths)
+}
+
+/// Canonical Huffman code with the codes read most-significant bit first.
+struct Huffman {
+    // the number of codes of each length
+    counts: [u16; MAX_CODE_LEN + 1],
+    // the symbols ordered by their codes
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, Error> {
+        let mut counts = [0_u16; MAX_CODE_LEN + 1];
+        for len in lengths.iter() {
+            let len = *len as usize;
+            if len > MAX_CODE_LEN {
+                return Err(invalid_data("invalid kwaj huffman code length"));
+            }
+            counts[len] += 1;
+        }
+        counts[0] = 0;
+        let mut left = 1_i32;
+        for count in counts[1..].iter() {
+            left = (left << 1) - *count as i32;
+            if left < 0 {
+                return Err(invalid_data("invalid kwaj huffman table"));
+            }
+        }
+        let mut symbols = Vec::with_capacity(lengths.len());
+        for len in 1..=MAX_CODE_LEN {
+            for (symbol, _) in lengths
+                .iter()
+                .enumerate()
+                .filter(|(_, l)| **l as usize == len)
+            {
+                symbols.push(symbol as u16);
+            }
+        }
+        Ok(Self { counts, symbols })
+    }
+
+    /// Returns `None` at the end of the input.
+    fn decode<R: BufRead>(
+        &self,
+        bits: &mut BitReader,
+        reader: &mut R,
+    ) -> Result<Option<u16>, Error> {
+        let mut code = 0_i32;
+        let mut first = 0_i32;
+        let mut index = 0_i32;
+        for count in self.counts[1..].iter() {
+            let Some(bit) = bits.bits(reader, 1)? else {
+                return Ok(None);
+            };
+            code |= bit as i32;
+            let count = *count as i32;
+            if code - first < count {
+                return Ok(Some(self.symbols[(index + code - first) as usize]));
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid_data("invalid kwaj huffman code"))
+    }
+}
+
+/// Reads the input stream bit by bit, most-significant bit first.
+struct BitReader {
+    buf: u32,
+    len: u32,
+}
+
+impl BitReader {
+    const fn new() -> Self {
+        Self { buf: 0, len: 0 }
+    }
+
+    /// Returns `None` at the end of the input.
+    fn bits<R: BufRead>(&mut self, reader: &mut R, n: u32) -> Result<Option<u32>, Error> {
+        while self.len < n {
+            let Some(byte) = read_byte(reader)? else {
+                return Ok(None);
+            };
+            self.buf = (self.buf << 8) | byte as u32;
+            self.len += 8;
+        }
+        self.len -= n;
+        let value = (self.buf >> self.len) & ((1 << n) - 1);
+        self.buf &= (1 << self.len) - 1;
+        Ok(Some(value))
+    }
+}
+
+fn read_byte<R: BufRead>(reader: &mut R) -> Result<Option<u8>, Error> {
+    let byte = match reader.fill_buf()?.first() {
+        Some(byte) => *byte,
+        None => return Ok(None),
+    };
+    reader.consume(1);
+    Ok(Some(byte))
+}
+
+fn skip<R: Read>(reader: &mut R, n: u64) -> Result<u64, Error> {
+    let skipped = std::io::copy(&mut reader.take(n), &mut std::io::sink())?;
+    if skipped != n {
+        return Err(truncated());
+    }
+    Ok(n)
+}
+
+fn truncated() -> Error {
+    Error::new(ErrorKind::UnexpectedEof, "truncated kwaj file")
+}
+
+fn invalid_data(message: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+pub(crate) const SZDD_MAGIC: [u8; 8] = *b"SZDD\x88\xf0\x27\x33";
+pub(crate) const KWAJ_MAGIC: [u8; 8] = *b"KWAJ\x88\xf0\x27\xd1";
+// magic, compression mode, the last character of the file name, uncompressed size
+pub(crate) const SZDD_HEADER_LEN: usize = 14;
+// magic, compression method, data offset, header flags
+pub(crate) const KWAJ_HEADER_LEN: usize = 14;
+pub(crate) const KWAJ_LZSS: u16 = 2;
+pub(crate) const MS_WINDOW_SIZE: usize = 4096;
+pub(crate) const MS_LZSS_START: usize = MS_WINDOW_SIZE - 16;
+pub(crate) const MS_LZSS_MIN_MATCH: usize = 3;
+pub(crate) const MS_LZSS_MAX_MATCH: usize = MS_LZSS_MIN_MATCH + 15;
+const KWAJ_STORED: u16 = 0;
+const KWAJ_XOR: u16 = 1;
+const KWAJ_LZH: u16 = 3;
+const KWAJ_HAS_LENGTH: u16 = 1 << 0;
+const KWAJ_HAS_UNKNOWN: u16 = 1 << 1;
+const KWAJ_HAS_DATA: u16 = 1 << 2;
+const KWAJ_HAS_NAME: u16 = 1 << 3;
+const KWAJ_HAS_EXTENSION: u16 = 1 << 4;
+const KWAJ_HAS_TEXT: u16 = 1 << 5;
+const MAX_CODE_LEN: usize = 16;
+
+#[cfg(test)]
+mod tests {
+    use arbtest::arbtest;
+
+    use super::*;
+
+    #[test]
+    fn kwaj_stored() {
+        arbtest(|u| {
+            let expected: Vec<u8> = u.arbitrary()?;
+            let method = *u.choose(&[KWAJ_STORED, KWAJ_XOR])?;
+            let mut file = Vec::new();
+            file.extend(KWAJ_MAGIC);
+            file.extend(method.to_le_bytes());
+            file.extend((KWAJ_HEADER_LEN as u16 + 4 + 6 + 2 + 3).to_le_bytes());
+            file.extend((KWAJ_HAS_LENGTH | KWAJ_HAS_NAME | KWAJ_HAS_TEXT).to_le_bytes());
+            file.extend((expected.len() as u32).to_le_bytes());
+            file.extend(b"hello\0");
+            file.extend(3_u16.to_le_bytes());
+            file.extend(b"abc");
+            match method {
+                KWAJ_XOR => file.extend(expected.iter().map(|b| b ^ 0xff)),
+                _ => file.extend(&expected),
+            }
+            let mut actual = Vec::new();
+            MsCompressDecoder::kwaj(&file[..])
+                .read_to_end(&mut actual)
+                .unwrap();
+            assert_eq!(expected, actual);
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,124 @@
+use std::borrow::Cow;
+
+use crate::CustomFormat;
+use crate::DecoderLimits;
+use crate::Format;
+
+/// Decoder options shared by all [`AnyDecoder`](crate::AnyDecoder) variants.
+#[derive(Clone, Debug)]
+pub(crate) struct DecoderOptions {
+    pub(crate) fail_on_unknown_format: bool,
+    pub(crate) memory_limit: Option<u64>,
+    #[cfg(feature = "bzip2")]
+    pub(crate) bzip2_small_mode: bool,
+    pub(crate) per_member: bool,
+    /// Stop at the end of the first member and don't look for the following ones.
+    pub(crate) single_member: bool,
+    #[cfg(feature = "flate2")]
+    pub(crate) require_gz_header_crc: bool,
+    #[cfg(feature = "zstd")]
+    pub(crate) require_zstd_checksum: bool,
+    /// Look for the magic bytes at the offsets up to this value (zero means no scanning).
+    pub(crate) magic_scan_window: usize,
+    /// Skip detection and decode the stream using this format.
+    pub(crate) format: Option<Format>,
+    /// Hard limits of the hardened mode.
+    pub(crate) limits: Option<DecoderLimits>,
+    /// Confirm the detected format by decoding the first bytes of the stream.
+    pub(crate) trial_decode: bool,
+    /// The formats in the order they are tested during detection.
+    pub(crate) detection_order: Option<Vec<Format>>,
+    /// Only these formats are detected, the others are treated as unknown.
+    pub(crate) allowed_formats: Option<Vec<Format>>,
+    /// User-defined formats that are detected before the built-in ones.
+    pub(crate) custom_formats: Vec<CustomFormat>,
+    /// The size of the buffer that wraps non-buffered readers.
+    pub(crate) buffer_size: usize,
+    /// Preset dictionary of zlib streams.
+    #[cfg(feature = "flate2")]
+    pub(crate) zlib_dictionary: Option<Vec<u8>>,
+    /// Decode only the first zstd frame.
+    #[cfg(feature = "zstd")]
+    pub(crate) zstd_single_frame: bool,
+    /// Detect zlib streams by the two-byte header.
+    #[cfg(feature = "flate2")]
+    pub(crate) zlib_heuristic: bool,
+}
+
+impl Default for DecoderOptions {
+    fn default() -> Self {
+        Self {
+            fail_on_unknown_format: false,
+            memory_limit: None,
+            #[cfg(feature = "bzip2")]
+            bzip2_small_mode: false,
+            per_member: false,
+            single_member: false,
+            #[cfg(feature = "flate2")]
+            require_gz_header_crc: false,
+            #[cfg(feature = "zstd")]
+            require_zstd_checksum: false,
+            magic_scan_window: 0,
+            format: None,
+            limits: None,
+            trial_decode: false,
+            detection_order: None,
+            allowed_formats: None,
+            custom_formats: Vec::new(),
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            #[cfg(feature = "flate2")]
+            zlib_dictionary: None,
+            #[cfg(feature = "zstd")]
+            zstd_single_frame: false,
+            #[cfg(feature = "flate2")]
+            zlib_heuristic: true,
+        }
+    }
+}
+
+/// The same as [BufReader](std::io::BufReader) uses.
+const DEFAULT_BUFFER_SIZE: usize = 8 * 1024;
+
+impl DecoderOptions {
+    /// The formats in the order they are tested during detection.
+    pub(crate) fn detection_order(&self) -> Cow<'_, [Format]> {
+        let order = self
+            .detection_order
+            .as_deref()
+            .unwrap_or(Format::DETECTION_ORDER);
+        if order.iter().all(|format| self.is_detected(*format)) {
+            return order.into();
+        }
+        order
+            .iter()
+            .copied()
+            .filter(|format| self.is_detected(*format))
+            .collect()
+    }
+
+    /// Whether the `format` is not excluded from detection.
+    fn is_detected(&self, format: Format) -> bool {
+        #[cfg(feature = "flate2")]
+        if format == Format::Zlib && !self.zlib_heuristic {
+            return false;
+        }
+        match self.allowed_formats {
+            Some(ref allowed) => allowed.contains(&format),
+            None => true,
+        }
+    }
+
+    /// Whether the decoder returns end-of-file at the end of each member.
+    #[cfg(any(feature = "flate2", feature = "lz4", feature = "xz", feature = "zstd"))]
+    pub(crate) fn stop_at_member_end(&self) -> bool {
+        self.per_member || self.single_member
+    }
+
+    /// The memory limit that takes the hard limits into account.
+    pub(crate) fn max_memory(&self) -> Option<u64> {
+        match (self.memory_limit, self.limits) {
+            (Some(limit), Some(limits)) => Some(limit.min(limits.memory)),
+            (limit, limits) => limit.or(limits.map(|limits| limits.memory)),
+        }
+    }
+}
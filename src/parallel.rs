@@ -0,0 +1,83 @@
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Write;
+
+use rayon::prelude::*;
+
+use crate::write::AnyEncoder;
+use crate::write::Compression;
+use crate::Format;
+
+/// Compress the `input` using all available cores.
+///
+/// The input is split into chunks of `chunk_size` bytes that are compressed in parallel
+/// and concatenated into a valid stream:
+/// each chunk becomes a separate gzip member, zstd frame or xz stream.
+/// Other formats don't support concatenation and are compressed on the current thread.
+pub fn compress_parallel(
+    input: &[u8],
+    format: Format,
+    compression: Compression,
+    chunk_size: usize,
+) -> Result<Vec<u8>, Error> {
+    if chunk_size == 0 {
+        return Err(Error::new(ErrorKind::InvalidInput, "zero chunk size"));
+    }
+    if !supports_concatenation(format) || input.len() <= chunk_size {
+        return compress(input, format, compression);
+    }
+    let chunks = input
+        .par_chunks(chunk_size)
+        .map(|chunk| compress(chunk, format, compression))
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(chunks.concat())
+}
+
+fn compress(input: &[u8], format: Format, compression: Compression) -> Result<Vec<u8>, Error> {
+    let mut writer = AnyEncoder::new(Vec::new(), format, compression)?;
+    writer.write_all(input)?;
+    writer.finish()
+}
+
+fn supports_concatenation(format: Format) -> bool {
+    match format {
+        #[cfg(feature = "flate2")]
+        Format::Gz => true,
+        #[cfg(feature = "xz")]
+        Format::Xz => true,
+        #[cfg(feature = "zstd")]
+        Format::Zstd => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::bufread::AnyDecoder;
+
+    #[test]
+    fn test_compress_parallel() {
+        arbtest(|u| {
+            let format: Format = u.arbitrary()?;
+            let expected: Vec<u8> = u.arbitrary()?;
+            let chunk_size = u.int_in_range(1..=1000)?;
+            let compressed =
+                compress_parallel(&expected, format, Compression::Fast, chunk_size).unwrap();
+            // verbatim data might be detected as some other format
+            if format == Format::Verbatim {
+                assert_eq!(expected, compressed);
+                return Ok(());
+            }
+            let mut reader = AnyDecoder::with_format(&compressed[..], format);
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, actual);
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,313 @@
+//! Types that run compression and decompression on a background thread.
+//!
+//! The data is passed between the caller and the background thread via a bounded channel of
+//! buffers. This overlaps compression with the caller's I/O and is useful for single-threaded
+//! formats like xz and bzip2.
+
+use std::io::BufRead;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
+use std::sync::mpsc::sync_channel;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::SyncSender;
+use std::thread::JoinHandle;
+
+use crate::write::Compression;
+use crate::Format;
+
+/// A decoder that decompresses the supplied input stream on a background thread.
+///
+/// The format is detected automatically, see [read::AnyDecoder](crate::read::AnyDecoder).
+/// The background thread exits when the decoder is dropped.
+pub struct AnyDecoder {
+    receiver: Receiver<Result<Vec<u8>, Error>>,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl AnyDecoder {
+    /// Create new decoder from the supplied `reader` and start the background thread.
+    pub fn new<R: Read + Send + 'static>(reader: R) -> Self {
+        let (sender, receiver) = sync_channel(CHANNEL_CAPACITY);
+        std::thread::spawn(move || decode(reader, sender));
+        Self {
+            receiver,
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+fn decode<R: Read>(reader: R, sender: SyncSender<Result<Vec<u8>, Error>>) {
+    let mut decoder = crate::read::AnyDecoder::new(reader);
+    loop {
+        let mut buf = vec![0_u8; BUFFER_SIZE];
+        let result = match decoder.read(&mut buf) {
+            // closing the channel signals end-of-file
+            Ok(0) => return,
+            Ok(n) => {
+                buf.truncate(n);
+                Ok(buf)
+            }
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => Err(e),
+        };
+        let stop = result.is_err();
+        if sender.send(result).is_err() || stop {
+            return;
+        }
+    }
+}
+
+impl Read for AnyDecoder {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let data = self.fill_buf()?;
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for AnyDecoder {
+    fn fill_buf(&mut self) -> Result<&[u8], Error> {
+        if self.pos == self.buf.len() {
+            match self.receiver.recv() {
+                Ok(Ok(buf)) => {
+                    self.buf = buf;
+                    self.pos = 0;
+                }
+                Ok(Err(e)) => return Err(e),
+                // the thread has finished
+                Err(_) => {
+                    self.buf.clear();
+                    self.pos = 0;
+                }
+            }
+        }
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.pos = (self.pos + n).min(self.buf.len());
+    }
+}
+
+/// An encoder that compresses the data on a background thread.
+///
+/// The data is buffered and sent to the background thread in chunks.
+pub struct AnyEncoder<W: Write + Send + 'static> {
+    sender: Option<SyncSender<Message>>,
+    thread: Option<JoinHandle<Result<W, Error>>>,
+    buf: Vec<u8>,
+    format: Format,
+}
+
+impl<W: Write + Send + 'static> AnyEncoder<W> {
+    /// Create new encoder for the supplied `format` and `compression` ratio and start
+    /// the background thread.
+    pub fn new(writer: W, format: Format, compression: Compression) -> Result<Self, Error> {
+        let encoder = crate::write::AnyEncoder::new(writer, format, compression)?;
+        let (sender, receiver) = sync_channel(CHANNEL_CAPACITY);
+        let thread = std::thread::spawn(move || encode(encoder, receiver));
+        Ok(Self {
+            sender: Some(sender),
+            thread: Some(thread),
+            buf: Vec::with_capacity(BUFFER_SIZE),
+            format,
+        })
+    }
+
+    /// Get encoding format.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Finish encoding, wait for the background thread and return the underlying writer.
+    ///
+    /// This method is **not** automatically called on drop.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.send_buf()?;
+        self.sender = None;
+        self.join()
+    }
+
+    fn send_buf(&mut self) -> Result<(), Error> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let buf = std::mem::replace(&mut self.buf, Vec::with_capacity(BUFFER_SIZE));
+        self.send(Message::Data(buf))
+    }
+
+    fn send(&mut self, message: Message) -> Result<(), Error> {
+        let Some(sender) = self.sender.as_ref() else {
+            return Err(Error::new(
+                ErrorKind::BrokenPipe,
+                "encoder thread has finished",
+            ));
+        };
+        if sender.send(message).is_err() {
+            // the thread has failed
+            self.sender = None;
+            return Err(self.join().err().unwrap_or_else(|| {
+                Error::new(ErrorKind::BrokenPipe, "encoder thread has finished")
+            }));
+        }
+        Ok(())
+    }
+
+    fn join(&mut self) -> Result<W, Error> {
+        let Some(thread) = self.thread.take() else {
+            return Err(Error::new(
+                ErrorKind::BrokenPipe,
+                "encoder thread has finished",
+            ));
+        };
+        thread
+            .join()
+            .map_err(|_| Error::other("encoder thread panicked"))?
+    }
+}
+
+fn encode<W: Write>(
+    mut encoder: crate::write::AnyEncoder<W>,
+    receiver: Receiver<Message>,
+) -> Result<W, Error> {
+    while let Ok(message) = receiver.recv() {
+        match message {
+            Message::Data(buf) => encoder.write_all(&buf)?,
+            Message::Flush => encoder.flush()?,
+        }
+    }
+    encoder.finish()
+}
+
+impl<W: Write + Send + 'static> Write for AnyEncoder<W> {
+    fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        let n = data.len().min(BUFFER_SIZE - self.buf.len());
+        self.buf.extend_from_slice(&data[..n]);
+        if self.buf.len() == BUFFER_SIZE {
+            self.send_buf()?;
+        }
+        Ok(n)
+    }
+
+    /// Send the buffered data to the background thread and ask it to flush the encoder.
+    ///
+    /// This method doesn't wait for the flush to complete.
+    fn flush(&mut self) -> Result<(), Error> {
+        self.send_buf()?;
+        self.send(Message::Flush)
+    }
+}
+
+enum Message {
+    Data(Vec<u8>),
+    Flush,
+}
+
+const BUFFER_SIZE: usize = 64 * 1024;
+const CHANNEL_CAPACITY: usize = 4;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use arbitrary::Unstructured;
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::test::test_bufread_all;
+    use crate::test::test_read_trait;
+    use crate::test::test_write_trait;
+
+    #[test]
+    fn test_any_decoder() {
+        test_read_trait(new_any_decoder);
+        test_bufread_all(new_any_decoder);
+    }
+
+    #[test]
+    fn test_any_encoder() {
+        test_write_trait(new_any_encoder, finish_any_encoder);
+    }
+
+    #[test]
+    fn decode_error() {
+        arbtest(|u| {
+            let format: Format = u.arbitrary()?;
+            // zlib decoder doesn't check that the trailer is complete
+            if matches!(format, Format::Verbatim | Format::Zlib) {
+                return Ok(());
+            }
+            // zip decoder ignores the central directory that follows the entry
+            #[cfg(feature = "zip")]
+            if format == Format::Zip {
+                return Ok(());
+            }
+            // KWAJ encoder doesn't store the uncompressed size, and LZSS has no end marker
+            #[cfg(feature = "mscompress")]
+            if format == Format::Kwaj {
+                return Ok(());
+            }
+            // the formats without magic bytes are not detected
+            if !Format::DETECTION_ORDER.contains(&format) {
+                return Ok(());
+            }
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut writer =
+                crate::write::AnyEncoder::new(Vec::new(), format, Compression::Fast).unwrap();
+            writer.write_all(&expected).unwrap();
+            let mut compressed = writer.finish().unwrap();
+            compressed.pop();
+            // e.g. empty LZFSE stream consists of the end-of-stream magic only
+            let magic = format.magic_bytes();
+            if !magic.iter().any(|magic| compressed.starts_with(magic)) {
+                return Ok(());
+            }
+            let mut reader = AnyDecoder::new(std::io::Cursor::new(compressed));
+            let mut actual = Vec::new();
+            assert!(reader.read_to_end(&mut actual).is_err(), "{:?}", format);
+            Ok(())
+        });
+    }
+
+    fn new_any_decoder(vec: VecDeque<u8>, u: &mut Unstructured) -> AnyDecoder {
+        // verbatim data might be detected as some other format
+        let format = *u.choose(Format::DETECTION_ORDER).unwrap();
+        let mut writer =
+            crate::write::AnyEncoder::new(Vec::new(), format, Compression::Fast).unwrap();
+        let bytes = vec.into_iter().collect::<Vec<_>>();
+        writer.write_all(&bytes).unwrap();
+        let compressed = writer.finish().unwrap();
+        AnyDecoder::new(std::io::Cursor::new(compressed))
+    }
+
+    fn finish_any_encoder(
+        writer: AnyEncoder<VecDeque<u8>>,
+        _u: &mut Unstructured<'_>,
+    ) -> arbitrary::Result<Box<dyn Read>> {
+        let format = writer.format();
+        let inner = writer.finish().unwrap();
+        // verbatim data might be detected as some other format
+        if format == Format::Verbatim {
+            return Ok(Box::new(inner));
+        }
+        Ok(Box::new(crate::read::AnyDecoder::with_format(
+            inner, format,
+        )))
+    }
+
+    fn new_any_encoder(
+        writer: VecDeque<u8>,
+        u: &mut Unstructured<'_>,
+    ) -> arbitrary::Result<AnyEncoder<VecDeque<u8>>> {
+        let format: Format = u.arbitrary()?;
+        let encoder = AnyEncoder::new(writer, format, Compression::Fast).unwrap();
+        assert_eq!(format, encoder.format());
+        Ok(encoder)
+    }
+}
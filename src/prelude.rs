@@ -0,0 +1,34 @@
+//! The most commonly used types and traits.
+//!
+//! ```rust
+//! use deko::prelude::*;
+//!
+//! let mut writer = AnyEncoder::new(Vec::new(), Format::Gz, Compression::Best).unwrap();
+//! writer.write_all(b"Hello world").unwrap();
+//! let compressed_data = writer.finish().unwrap();
+//! let mut reader = AnyDecoder::new(&compressed_data[..]);
+//! let mut string = String::new();
+//! reader.read_to_string(&mut string).unwrap();
+//! assert_eq!("Hello world", string);
+//! ```
+
+pub use std::io::BufRead;
+pub use std::io::Read;
+pub use std::io::Write;
+
+pub use crate::typed::StaticFormat;
+pub use crate::write::AnyEncoder;
+pub use crate::write::Compression;
+pub use crate::write::Config;
+pub use crate::write::EncoderOptions;
+pub use crate::write::FormatConfig;
+pub use crate::AnyDecoder;
+pub use crate::Decode;
+pub use crate::Encode;
+pub use crate::Format;
+#[cfg(feature = "flate2")]
+pub use crate::GzDecoderExt;
+#[cfg(feature = "flate2")]
+pub use crate::ZlibDecoderExt;
+#[cfg(feature = "zstd")]
+pub use crate::ZstdDecoderExt;
@@ -1,4 +1,55 @@
 use crate::read::MagicReader;
 
 crate::define_decoder!(Read);
+crate::define_decoder_builder!(Read);
+crate::define_members!(Read);
 crate::define_decoder_tests!();
+
+impl<R: Read> AnyDecoder<'_, R> {
+    /// Set the size of the buffer that wraps the underlying reader.
+    ///
+    /// The reader is wrapped in [BufReader](std::io::BufReader)
+    /// with the specified capacity when the format is detected.
+    /// The magic bytes are read before that and are buffered separately.
+    ///
+    /// By default the buffer size is 8 KiB.
+    pub fn buffer_size(&mut self, bytes: usize) {
+        self.options.buffer_size = bytes;
+    }
+}
+
+impl<R: Read> DecoderBuilder<'_, R> {
+    /// See [buffer_size](AnyDecoder::buffer_size).
+    pub fn buffer_size(mut self, bytes: usize) -> Self {
+        self.decoder.buffer_size(bytes);
+        self
+    }
+}
+
+#[cfg(all(test, feature = "flate2"))]
+mod buffer_size_tests {
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    use super::*;
+
+    #[test]
+    fn too_large() {
+        let mut writer = GzEncoder::new(Vec::new(), Compression::fast());
+        writer.write_all(b"hello").unwrap();
+        let compressed = writer.finish().unwrap();
+        let mut decoder = AnyDecoder::new(&compressed[..]);
+        // the buffer can't be allocated
+        decoder.buffer_size(usize::MAX);
+        let error = decoder.read(&mut [0_u8; 1]).unwrap_err();
+        assert_eq!(std::io::ErrorKind::InvalidInput, error.kind());
+        // the reader is still there
+        assert!(compressed.ends_with(decoder.get_ref()));
+        decoder.buffer_size(4096);
+        let mut decompressed = Vec::new();
+        decoder.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(b"hello", &decompressed[..]);
+    }
+}
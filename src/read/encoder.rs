@@ -0,0 +1,147 @@
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
+
+use crate::write::Compression;
+use crate::write::Config;
+use crate::write::EncoderOptions;
+use crate::Format;
+
+/// An encoder that compresses the data read from the underlying reader.
+///
+/// The data is compressed by [write::AnyEncoder](crate::write::AnyEncoder),
+/// i.e. all the formats and the options are supported.
+/// The underlying reader is read in chunks, and the compressed data is buffered
+/// until it is read from the encoder.
+pub struct AnyEncoder<R: Read> {
+    reader: R,
+    // `None` when the encoding is finished
+    encoder: Option<crate::write::AnyEncoder<Vec<u8>>>,
+    format: Format,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> AnyEncoder<R> {
+    /// Create new encoder for the supplied `format` and `compression` ratio.
+    pub fn new(reader: R, format: Format, compression: Compression) -> Result<Self, Error> {
+        let encoder = crate::write::AnyEncoder::new(Vec::new(), format, compression)?;
+        Ok(Self::from_encoder(reader, encoder))
+    }
+
+    /// Create new encoder for the supplied `format` using the settings from the `config`.
+    pub fn with_config(reader: R, format: Format, config: &Config) -> Result<Self, Error> {
+        Self::with_options(reader, &config.options(format))
+    }
+
+    /// Create new encoder using the supplied `options`.
+    pub fn with_options(reader: R, options: &EncoderOptions) -> Result<Self, Error> {
+        let encoder = crate::write::AnyEncoder::with_options(Vec::new(), options)?;
+        Ok(Self::from_encoder(reader, encoder))
+    }
+
+    fn from_encoder(reader: R, encoder: crate::write::AnyEncoder<Vec<u8>>) -> Self {
+        Self {
+            reader,
+            format: encoder.format(),
+            encoder: Some(encoder),
+            input: vec![0_u8; BUFFER_SIZE],
+            output: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Get encoding format.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Get immutable reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Get mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Get the underlying reader.
+    ///
+    /// The data that was read from the reader but wasn't compressed yet is lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Compress the next chunk of the input.
+    fn fill_output(&mut self) -> Result<(), Error> {
+        while self.pos == self.output.len() {
+            let Some(encoder) = self.encoder.as_mut() else {
+                return Ok(());
+            };
+            self.output.clear();
+            self.pos = 0;
+            match self.reader.read(&mut self.input) {
+                Ok(0) => {
+                    if let Some(encoder) = self.encoder.take() {
+                        self.output = encoder.finish()?;
+                    }
+                }
+                Ok(n) => {
+                    encoder.write_all(&self.input[..n])?;
+                    // reuse the buffer
+                    std::mem::swap(&mut self.output, encoder.get_mut());
+                }
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for AnyEncoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.fill_output()?;
+        let n = buf.len().min(self.output.len() - self.pos);
+        buf[..n].copy_from_slice(&self.output[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+const BUFFER_SIZE: usize = 32 * 1024;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use arbitrary::Unstructured;
+
+    use super::*;
+    use crate::test::test_read_trait;
+    use crate::test::NBytesReader;
+
+    #[test]
+    fn test_any_encoder() {
+        test_read_trait(new_any_encoder);
+    }
+
+    fn new_any_encoder(
+        vec: VecDeque<u8>,
+        u: &mut Unstructured,
+    ) -> crate::read::AnyDecoder<'static, AnyEncoder<NBytesReader<VecDeque<u8>>>> {
+        let format: Format = u.arbitrary().unwrap();
+        let compression = *u
+            .choose(&[Compression::Fast, Compression::Default, Compression::Best])
+            .unwrap();
+        let n = u.int_in_range(1..=100).unwrap();
+        let encoder = AnyEncoder::new(NBytesReader::new(vec, n), format, compression).unwrap();
+        crate::read::AnyDecoder::with_format(encoder, format)
+    }
+}
@@ -1,7 +1,9 @@
 //! Types that wrap [Read](std::io::Read) streams.
 
 mod decoder;
+mod encoder;
 mod magic_reader;
 
 pub use self::decoder::*;
+pub use self::encoder::*;
 pub(crate) use self::magic_reader::*;
@@ -0,0 +1,140 @@
+use std::fs::File;
+use std::io::BufRead;
+use std::io::Error;
+use std::io::Read;
+use std::path::Path;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::sync_channel;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+
+use crate::read_chunk;
+
+/// A reader that reads the underlying stream ahead in a background thread.
+///
+/// Up to `depth` chunks are read while the previous ones are being decompressed,
+/// i.e. disk I/O overlaps with decompression.
+/// This is most useful for the slow formats (xz, bzip2) on fast storage.
+/// Wrap the reader in [AnyDecoder](crate::bufread::AnyDecoder) to decompress the data.
+///
+/// The thread terminates at the end of the stream, after the first error
+/// or when the reader is dropped.
+pub struct ReadaheadReader {
+    receiver: Receiver<Result<Vec<u8>, Error>>,
+    // returns consumed chunks to the thread
+    recycle: Sender<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl ReadaheadReader {
+    /// Open the file and read it ahead in 1 MiB chunks with the depth of four chunks.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::new(File::open(path)?, DEFAULT_CHUNK_SIZE, DEFAULT_DEPTH)
+    }
+
+    /// Start reading `reader` in chunks of `chunk_size` bytes at most `depth` chunks ahead.
+    ///
+    /// Zero `chunk_size` and `depth` are treated as one.
+    pub fn new<R: Read + Send + 'static>(
+        mut reader: R,
+        chunk_size: usize,
+        depth: usize,
+    ) -> Result<Self, Error> {
+        let chunk_size = chunk_size.max(1);
+        let (sender, receiver) = sync_channel(depth.max(1));
+        let (recycle, recycled) = channel::<Vec<u8>>();
+        std::thread::Builder::new()
+            .name("deko-readahead".into())
+            .spawn(move || loop {
+                let mut chunk = recycled.try_recv().unwrap_or_default();
+                chunk.resize(chunk_size, 0);
+                match read_chunk(&mut reader, &mut chunk) {
+                    Ok(n) => {
+                        chunk.truncate(n);
+                        // empty chunk means end-of-file
+                        if sender.send(Ok(chunk)).is_err() || n == 0 {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        let _ = sender.send(Err(e));
+                        break;
+                    }
+                }
+            })?;
+        Ok(Self {
+            receiver,
+            recycle,
+            chunk: Vec::new(),
+            pos: 0,
+            done: false,
+        })
+    }
+}
+
+impl Read for ReadaheadReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let chunk = self.fill_buf()?;
+        let n = buf.len().min(chunk.len());
+        buf[..n].copy_from_slice(&chunk[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for ReadaheadReader {
+    fn fill_buf(&mut self) -> Result<&[u8], Error> {
+        if self.pos == self.chunk.len() && !self.done {
+            let chunk = match self.receiver.recv() {
+                Ok(Ok(chunk)) => chunk,
+                Ok(Err(e)) => {
+                    self.done = true;
+                    return Err(e);
+                }
+                Err(_) => {
+                    self.done = true;
+                    return Err(Error::other("readahead thread terminated"));
+                }
+            };
+            self.done = chunk.is_empty();
+            let old = std::mem::replace(&mut self.chunk, chunk);
+            // the thread might have already terminated
+            let _ = self.recycle.send(old);
+            self.pos = 0;
+        }
+        Ok(&self.chunk[self.pos..])
+    }
+
+    fn consume(&mut self, n: usize) {
+        debug_assert!(self.pos + n <= self.chunk.len());
+        self.pos += n;
+    }
+}
+
+const DEFAULT_CHUNK_SIZE: usize = 1024 * 1024;
+const DEFAULT_DEPTH: usize = 4;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use arbitrary::Unstructured;
+
+    use super::*;
+    use crate::test::test_bufread_all;
+    use crate::test::test_read_trait;
+
+    #[test]
+    fn readahead() {
+        test_read_trait(new_readahead_reader);
+        test_bufread_all(new_readahead_reader);
+    }
+
+    fn new_readahead_reader(vec: VecDeque<u8>, u: &mut Unstructured) -> ReadaheadReader {
+        let chunk_size = u.int_in_range(1..=100).unwrap();
+        let depth = u.int_in_range(1..=4).unwrap();
+        ReadaheadReader::new(vec, chunk_size, depth).unwrap()
+    }
+}
@@ -0,0 +1,157 @@
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
+
+use crate::read::AnyDecoder;
+use crate::write::AnyEncoder;
+use crate::write::Compression;
+use crate::Format;
+
+/// Statistics collected by [recompress].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct RecompressStats {
+    /// Detected format of the input stream.
+    pub source_format: Format,
+    /// The number of bytes read from the input stream.
+    pub input_bytes: u64,
+    /// The number of decompressed bytes.
+    pub decoded_bytes: u64,
+    /// The number of bytes written to the output stream.
+    pub output_bytes: u64,
+}
+
+/// Decompress the data from the `reader` and compress it to the `writer` using the target
+/// `format` and `compression` level.
+///
+/// The source format is detected automatically.
+/// The data is processed in a single streaming pass with bounded memory.
+pub fn recompress<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    format: Format,
+    compression: Compression,
+) -> Result<RecompressStats, Error> {
+    let mut buf = vec![0_u8; BUFFER_SIZE];
+    recompress_with_buffer(reader, writer, format, compression, &mut buf)
+}
+
+/// The same as [recompress] but uses the supplied scratch buffer.
+pub(crate) fn recompress_with_buffer<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    format: Format,
+    compression: Compression,
+    buf: &mut [u8],
+) -> Result<RecompressStats, Error> {
+    let mut decoder = AnyDecoder::new(CountingReader::new(reader));
+    let source_format = decoder.kind()?;
+    let mut encoder = AnyEncoder::new(CountingWriter::new(writer), format, compression)?;
+    let mut decoded_bytes = 0;
+    loop {
+        let n = match decoder.read(buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        encoder.write_all(&buf[..n])?;
+        decoded_bytes += n as u64;
+    }
+    let mut writer = encoder.finish()?;
+    writer.flush()?;
+    Ok(RecompressStats {
+        source_format,
+        input_bytes: decoder.get_ref().count,
+        decoded_bytes,
+        output_bytes: writer.count,
+    })
+}
+
+pub(crate) const BUFFER_SIZE: usize = 64 * 1024;
+
+struct CountingReader<R> {
+    reader: R,
+    count: u64,
+}
+
+impl<R> CountingReader<R> {
+    fn new(reader: R) -> Self {
+        Self { reader, count: 0 }
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let n = self.reader.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+struct CountingWriter<W> {
+    writer: W,
+    count: u64,
+}
+
+impl<W> CountingWriter<W> {
+    fn new(writer: W) -> Self {
+        Self { writer, count: 0 }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        let n = self.writer.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::bufread;
+
+    #[test]
+    fn test_recompress() {
+        arbtest(|u| {
+            // verbatim data might be detected as some other format
+            let source_format = *u.choose(Format::DETECTION_ORDER)?;
+            let target_format: Format = u.arbitrary()?;
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut writer = AnyEncoder::new(Vec::new(), source_format, Compression::Fast).unwrap();
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            let mut output = Vec::new();
+            let stats = recompress(
+                &compressed[..],
+                &mut output,
+                target_format,
+                Compression::Fast,
+            )
+            .unwrap();
+            assert_eq!(compressed.len() as u64, stats.input_bytes);
+            assert_eq!(expected.len() as u64, stats.decoded_bytes);
+            assert_eq!(output.len() as u64, stats.output_bytes);
+            if !expected.is_empty() {
+                assert_eq!(source_format, stats.source_format);
+            }
+            if target_format == Format::Verbatim {
+                assert_eq!(expected, output);
+                return Ok(());
+            }
+            let mut reader = bufread::AnyDecoder::with_format(&output[..], target_format);
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, actual);
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,25 @@
+/// Replace the value behind `dest` with the value returned by `f`.
+///
+/// Allows the decoder state transitions that move the reader out of the old state into
+/// the new one without a placeholder state.
+/// Everything that can fail or call user code has to happen before the transition:
+/// if `f` panics the process is aborted, because `dest` can't be left moved-out.
+pub(crate) fn replace_with<T, F: FnOnce(T) -> T>(dest: &mut T, f: F) {
+    struct AbortOnPanic;
+
+    impl Drop for AbortOnPanic {
+        fn drop(&mut self) {
+            std::process::abort();
+        }
+    }
+
+    let guard = AbortOnPanic;
+    // SAFETY: The value is read exactly once and the destination is overwritten
+    // before it can be observed; if `f` panics the process is aborted, i.e. the value is
+    // never dropped twice.
+    unsafe {
+        let old = std::ptr::read(dest);
+        std::ptr::write(dest, f(old));
+    }
+    std::mem::forget(guard);
+}
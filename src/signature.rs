@@ -0,0 +1,180 @@
+use crate::Format;
+
+/// Magic bytes of the format at the specified offset from the start of the stream.
+pub(crate) struct Signature {
+    pub(crate) format: Format,
+    /// The offset of the magic bytes.
+    pub(crate) offset: usize,
+    /// The stream matches the signature if it contains any of the patterns at the offset.
+    pub(crate) patterns: &'static [&'static [u8]],
+    /// The bits of each pattern byte that are compared.
+    ///
+    /// Empty mask means that all the bits are compared.
+    pub(crate) mask: &'static [u8],
+}
+
+impl Signature {
+    #[allow(unused)]
+    const fn new(format: Format, patterns: &'static [&'static [u8]]) -> Self {
+        Self {
+            format,
+            offset: 0,
+            patterns,
+            mask: &[],
+        }
+    }
+
+    /// Check whether the `magic` bytes from the start of the stream match the signature.
+    ///
+    /// Returns the number of bytes from the start of the stream that matched.
+    pub(crate) fn match_len(&self, magic: &[u8]) -> Option<usize> {
+        let bytes = magic.get(self.offset..)?;
+        self.patterns
+            .iter()
+            .find(|pattern| {
+                bytes.len() >= pattern.len()
+                    && pattern.iter().zip(bytes).enumerate().all(|(i, (a, b))| {
+                        let mask = self.mask.get(i).copied().unwrap_or(0xff);
+                        a & mask == b & mask
+                    })
+            })
+            .map(|pattern| self.offset + pattern.len())
+    }
+
+    /// The maximum number of bytes from the start of the stream that
+    /// [match_len](Self::match_len) compares.
+    pub(crate) const fn end(&self) -> usize {
+        let mut n = 0;
+        let mut i = 0;
+        while i < self.patterns.len() {
+            if self.patterns[i].len() > n {
+                n = self.patterns[i].len();
+            }
+            i += 1;
+        }
+        self.offset + n
+    }
+}
+
+/// Signatures of the enabled formats.
+///
+/// Zlib is not listed here: its header has no fixed bytes and is detected by the checksum.
+/// Brotli and raw DEFLATE have no magic bytes at all.
+pub(crate) const SIGNATURES: &[Signature] = &[
+    // RFC1952
+    #[cfg(feature = "flate2")]
+    Signature::new(Format::Gz, &[&[0x1f, 0x8b, 0x08]]),
+    // https://en.wikipedia.org/wiki/Bzip2
+    #[cfg(feature = "bzip2")]
+    Signature::new(Format::Bz, &[b"BZh"]),
+    // https://tukaani.org/xz/xz-file-format-1.0.4.txt
+    #[cfg(feature = "xz")]
+    Signature::new(Format::Xz, &[&[0xfd, b'7', b'z', b'X', b'Z', 0]]),
+    // RFC8878
+    #[cfg(feature = "zstd")]
+    Signature::new(Format::Zstd, &[&[0x28, 0xb5, 0x2f, 0xfd]]),
+    // RFC8878, skippable frames `50..5f 2a 4d 18`
+    #[cfg(feature = "zstd")]
+    Signature {
+        mask: &[0xf0, 0xff, 0xff, 0xff],
+        ..Signature::new(Format::Zstd, &[&[0x50, 0x2a, 0x4d, 0x18]])
+    },
+    // https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT
+    #[cfg(feature = "zip")]
+    Signature::new(Format::Zip, &[&crate::ZIP_LOCAL_HEADER_MAGIC]),
+    // https://www.cabextract.org.uk/libmspack/doc/szdd_kwaj_format.html
+    #[cfg(feature = "mscompress")]
+    Signature::new(
+        Format::Szdd,
+        &[&[b'S', b'Z', b'D', b'D', 0x88, 0xf0, 0x27, 0x33]],
+    ),
+    #[cfg(feature = "mscompress")]
+    Signature::new(
+        Format::Kwaj,
+        &[&[b'K', b'W', b'A', b'J', 0x88, 0xf0, 0x27, 0xd1]],
+    ),
+    // https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md
+    #[cfg(feature = "lz4")]
+    Signature::new(
+        Format::Lz4,
+        &[&crate::LZ4_FRAME_MAGIC, &crate::LZ4_LEGACY_MAGIC],
+    ),
+    // https://www.lzop.org/, the format is defined by the lzop sources
+    #[cfg(feature = "lzop")]
+    Signature::new(Format::Lzop, &[&crate::LZOP_MAGIC]),
+    // https://github.com/lzfse/lzfse, the format is defined by the reference implementation
+    #[cfg(feature = "lzfse")]
+    Signature::new(
+        Format::Lzfse,
+        &[b"bvx$", b"bvx-", b"bvx1", b"bvx2", b"bvxn"],
+    ),
+];
+
+/// The number of bytes that is enough to match any of the [signatures](SIGNATURES).
+pub(crate) const MAX_SIGNATURE_LEN: usize = max_signature_len();
+
+const fn max_signature_len() -> usize {
+    let mut n = 0;
+    let mut i = 0;
+    while i < SIGNATURES.len() {
+        let end = SIGNATURES[i].end();
+        if end > n {
+            n = end;
+        }
+        i += 1;
+    }
+    n
+}
+
+/// The length of the longest [magic bytes](Format::magic_bytes) of the enabled formats.
+pub(crate) const MAX_MAGIC_BYTES_LEN: usize = max_magic_bytes_len();
+
+const fn max_magic_bytes_len() -> usize {
+    let mut n = 0;
+    let mut i = 0;
+    while i < SIGNATURES.len() {
+        let signature = &SIGNATURES[i];
+        if signature.offset == 0 && signature.mask.is_empty() {
+            let mut j = 0;
+            while j < signature.patterns.len() {
+                let len = signature.patterns[j].len();
+                if len > n {
+                    n = len;
+                }
+                j += 1;
+            }
+        }
+        i += 1;
+    }
+    n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MAX_MAGIC_BYTES;
+
+    #[test]
+    fn signatures() {
+        for signature in SIGNATURES.iter() {
+            assert!(signature.end() <= MAX_MAGIC_BYTES);
+            assert!(signature.mask.is_empty() || signature.patterns.len() == 1);
+            for pattern in signature.patterns.iter() {
+                assert!(signature.mask.is_empty() || signature.mask.len() == pattern.len());
+                let mut magic = vec![0_u8; signature.offset];
+                magic.extend_from_slice(pattern);
+                assert_eq!(Some(magic.len()), signature.match_len(&magic));
+                assert_eq!(None, signature.match_len(&magic[..magic.len() - 1]));
+            }
+        }
+        #[cfg(feature = "zstd")]
+        {
+            let skippable = SIGNATURES
+                .iter()
+                .find(|signature| !signature.mask.is_empty())
+                .unwrap();
+            assert_eq!(Some(4), skippable.match_len(&[0x5a, 0x2a, 0x4d, 0x18, 0]));
+            assert_eq!(None, skippable.match_len(&[0x60, 0x2a, 0x4d, 0x18, 0]));
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::io::BufRead;
+use std::io::Error;
+use std::io::Read;
+use std::io::Write;
+
+use crate::bufread::AnyDecoder;
+use crate::Format;
+
+/// A member of multi-member stream that was written by [split_members].
+#[derive(Clone, PartialEq, Debug)]
+pub struct MemberInfo {
+    /// Zero-based index of the member in the stream.
+    pub index: usize,
+    /// Detected compression format of the member.
+    pub format: Format,
+    /// The offset of the member from the start of the stream.
+    pub offset: u64,
+    /// The size of the compressed member.
+    ///
+    /// The value is zero until the member is decompressed.
+    pub compressed_size: u64,
+    /// The size of the decompressed data.
+    ///
+    /// The value is zero until the member is decompressed.
+    pub uncompressed_size: u64,
+    /// Gzip header of the member (file name, comment, modification time etc.).
+    #[cfg(feature = "flate2")]
+    pub gz_header: Option<flate2::GzHeader>,
+    /// The ID of the dictionary that is needed to decompress zstd frame.
+    #[cfg(feature = "zstd")]
+    pub zstd_dict_id: Option<u32>,
+}
+
+/// Decompress each member of the concatenated stream to a separate writer.
+///
+/// Gzip members, xz streams and zstd frames are decompressed one by one (see
+/// [per_member](AnyDecoder::per_member)).
+/// Other formats are decompressed as a whole, i.e. each stream is treated as one member;
+/// the trailing data of unknown format becomes a verbatim member.
+/// For each member `new_writer` is called with the member metadata
+/// to create the writer for the decompressed data (e.g. a file named after the gzip header);
+/// the writer is flushed and dropped at the end of the member.
+///
+/// Returns the metadata of all members including their sizes.
+/// Empty stream has no members.
+pub fn split_members<R, W, F>(reader: R, mut new_writer: F) -> Result<Vec<MemberInfo>, Error>
+where
+    R: BufRead,
+    W: Write,
+    F: FnMut(&MemberInfo) -> Result<W, Error>,
+{
+    let mut decoder = AnyDecoder::new(CountingReader::new(reader));
+    decoder.per_member(true);
+    let mut members = Vec::new();
+    // the header might be consumed during format detection,
+    // i.e. the member starts where the previous one ends
+    let mut offset = 0;
+    loop {
+        let format = decoder.kind()?;
+        if format == Format::Verbatim
+            && decoder.buffered_len() == 0
+            && decoder.get_mut().fill_buf()?.is_empty()
+        {
+            break;
+        }
+        let mut member = MemberInfo {
+            index: members.len(),
+            format,
+            offset,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            #[cfg(feature = "flate2")]
+            gz_header: crate::GzDecoderExt::gz_header(&mut decoder)?.cloned(),
+            #[cfg(feature = "zstd")]
+            zstd_dict_id: crate::ZstdDecoderExt::dict_id(&mut decoder)?,
+        };
+        let mut writer = new_writer(&member)?;
+        member.uncompressed_size = std::io::copy(&mut decoder, &mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        let end = decoder.get_ref().count - decoder.buffered_len() as u64;
+        member.compressed_size = end - offset;
+        offset = end;
+        members.push(member);
+        if !decoder.next_member()? {
+            break;
+        }
+    }
+    Ok(members)
+}
+
+struct CountingReader<R> {
+    reader: R,
+    count: u64,
+}
+
+impl<R> CountingReader<R> {
+    fn new(reader: R) -> Self {
+        Self { reader, count: 0 }
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let n = self.reader.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for CountingReader<R> {
+    fn fill_buf(&mut self) -> Result<&[u8], Error> {
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.count += n as u64;
+        self.reader.consume(n);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::test::NBytesReader;
+    use crate::write::AnyEncoder;
+    use crate::write::Compression;
+
+    #[test]
+    #[cfg(all(feature = "flate2", feature = "zstd"))]
+    fn split() {
+        arbtest(|u| {
+            let members: Vec<Vec<u8>> = u.arbitrary()?;
+            let mut compressed = Vec::new();
+            let mut offsets = Vec::new();
+            let mut formats = Vec::new();
+            for data in members.iter() {
+                let format = *u.choose(&[Format::Gz, Format::Zstd])?;
+                let mut writer = AnyEncoder::new(Vec::new(), format, Compression::Fast).unwrap();
+                writer.write_all(data).unwrap();
+                offsets.push(compressed.len() as u64);
+                compressed.extend(writer.finish().unwrap());
+                formats.push(format);
+            }
+            offsets.push(compressed.len() as u64);
+            let capacity = u.int_in_range(1..=4096)?;
+            let outputs: Rc<RefCell<Vec<Vec<u8>>>> = Default::default();
+            let actual = split_members(NBytesReader::new(&compressed[..], capacity), |member| {
+                assert_eq!(outputs.borrow().len(), member.index);
+                outputs.borrow_mut().push(Vec::new());
+                Ok(MemberWriter(outputs.clone()))
+            })
+            .unwrap();
+            assert_eq!(members, *outputs.borrow());
+            assert_eq!(members.len(), actual.len());
+            for (i, member) in actual.iter().enumerate() {
+                assert_eq!(formats[i], member.format);
+                assert_eq!(offsets[i], member.offset);
+                assert_eq!(offsets[i + 1] - offsets[i], member.compressed_size);
+                assert_eq!(members[i].len() as u64, member.uncompressed_size);
+            }
+            Ok(())
+        });
+    }
+
+    struct MemberWriter(Rc<RefCell<Vec<Vec<u8>>>>);
+
+    impl Write for MemberWriter {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+            let mut outputs = self.0.borrow_mut();
+            outputs.last_mut().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+}
@@ -20,7 +20,7 @@ where
     arbtest(|u| {
         let expected: VecDeque<u8> = u.arbitrary()?;
         let mut reader = f(expected.clone(), u);
-        let mut actual = Vec::new();
+        let mut actual: Vec<u8> = Vec::new();
         loop {
             let buf = reader.fill_buf().unwrap();
             if buf.is_empty() {
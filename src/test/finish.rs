@@ -19,6 +19,13 @@ impl<W: Write> Finish<W> for flate2::write::ZlibEncoder<W> {
     }
 }
 
+#[cfg(feature = "flate2")]
+impl<W: Write> Finish<W> for flate2::write::DeflateEncoder<W> {
+    fn finish(self) -> Result<W, Error> {
+        Self::finish(self)
+    }
+}
+
 #[cfg(feature = "bzip2")]
 impl<W: Write> Finish<W> for bzip2::write::BzEncoder<W> {
     fn finish(self) -> Result<W, Error> {
@@ -39,3 +46,10 @@ impl<'a, W: Write> Finish<W> for zstd::stream::write::Encoder<'a, W> {
         Self::finish(self)
     }
 }
+
+#[cfg(feature = "brotli")]
+impl<W: Write> Finish<W> for crate::write::BrotliEncoder<W> {
+    fn finish(self) -> Result<W, Error> {
+        Self::finish(self)
+    }
+}
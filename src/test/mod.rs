@@ -1,13 +1,27 @@
 #![allow(missing_docs)]
 
+#[cfg(test)]
 mod bufread;
+#[cfg(test)]
 mod finish;
 mod n_bytes_reader;
+#[cfg(test)]
 mod read;
+#[cfg(test)]
+#[cfg(any(feature = "lzfse", feature = "lzop", feature = "mscompress"))]
+mod reference_tool;
+#[cfg(test)]
 mod write;
 
+#[cfg(test)]
 pub(crate) use self::bufread::*;
+#[cfg(test)]
 pub(crate) use self::finish::*;
 pub(crate) use self::n_bytes_reader::*;
+#[cfg(test)]
 pub(crate) use self::read::*;
+#[cfg(test)]
+#[cfg(any(feature = "lzfse", feature = "lzop", feature = "mscompress"))]
+pub(crate) use self::reference_tool::*;
+#[cfg(test)]
 pub(crate) use self::write::*;
@@ -2,8 +2,6 @@ use std::io::BufRead;
 use std::io::Error;
 use std::io::Read;
 
-use arbtest::arbtest;
-
 // Reads at most `len` bytes.
 pub struct NBytesReader<R: Read> {
     reader: R,
@@ -49,9 +47,10 @@ impl<R: Read> BufRead for NBytesReader<R> {
     }
 }
 
+#[cfg(test)]
 #[test]
 fn test_n_byte_reader() {
-    arbtest(|u| {
+    arbtest::arbtest(|u| {
         let expected: Vec<u8> = u.arbitrary()?;
         let capacity = u.int_in_range(1..=4096)?;
         let mut reader = NBytesReader::new(&expected[..], capacity);
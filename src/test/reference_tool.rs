@@ -0,0 +1,37 @@
+use std::io::Write;
+use std::process::Command;
+use std::process::Stdio;
+
+/// Check whether the reference implementation `program` is installed.
+///
+/// Missing programs are an error if `DEKO_REFERENCE_TOOLS` environment variable is set.
+pub fn has_reference_tool(program: &str) -> bool {
+    let found = std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()));
+    if !found {
+        assert!(
+            std::env::var_os("DEKO_REFERENCE_TOOLS").is_none(),
+            "`{program}` is not installed"
+        );
+        eprintln!("`{program}` is not installed, skipping");
+    }
+    found
+}
+
+/// Pipe `input` through the reference implementation `program` and return its output.
+pub fn run_reference_tool(program: &str, args: &[&str], input: &[u8]) -> Vec<u8> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_vec();
+    // write in a separate thread, otherwise the pipes might fill up
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output().unwrap();
+    writer.join().unwrap().unwrap();
+    assert!(output.status.success(), "`{program}` failed");
+    output.stdout
+}
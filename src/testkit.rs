@@ -0,0 +1,269 @@
+//! Round-trip test harness for the built-in and third-party codecs.
+//!
+//! The harness takes [Unstructured] data as the input,
+//! i.e. it's meant to be run by [arbtest](https://docs.rs/arbtest) or by a fuzzer.
+//! Each round trip encodes arbitrary data via [Write](std::io::Write) methods in arbitrary chunks,
+//! then decodes it twice: from the reader that returns arbitrary small chunks
+//! and from the same reader wrapped in [BufReader] of arbitrary capacity,
+//! via [read](Read::read) and [read_vectored](Read::read_vectored).
+//! Any mismatch or I/O error fails the test with a panic.
+//!
+//! ```rust
+//! use deko::Format;
+//!
+//! for format in Format::enabled() {
+//!     arbtest::arbtest(|u| deko::testkit::roundtrip(u, &format));
+//! }
+//! ```
+
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Error;
+use std::io::IoSlice;
+use std::io::IoSliceMut;
+use std::io::Read;
+
+use arbitrary::Unstructured;
+
+use crate::test::NBytesReader;
+use crate::write::AnyEncoder;
+use crate::write::Compression;
+use crate::Decode;
+use crate::Encode;
+use crate::Format;
+
+/// A pair of [Encode] and [Decode] implementations of the same format.
+///
+/// The trait is implemented for [Format], i.e. for the built-in codecs.
+pub trait Codec {
+    /// The encoder that compresses the data into a vector.
+    type Encoder: Encode<Writer = Vec<u8>>;
+
+    /// The decoder that decompresses the data read from `R`.
+    type Decoder<R: BufRead>: Decode;
+
+    /// Create the encoder that writes the compressed data to `writer`.
+    fn encoder(&self, writer: Vec<u8>) -> Result<Self::Encoder, Error>;
+
+    /// Create the decoder that reads the compressed data from `reader`.
+    fn decoder<R: BufRead>(&self, reader: R) -> Self::Decoder<R>;
+}
+
+/// Encodes via [AnyEncoder] and decodes via [bufread::AnyDecoder](crate::bufread::AnyDecoder).
+///
+/// The format is detected by the decoder unless it's not in
+/// [DETECTION_ORDER](Format::DETECTION_ORDER).
+impl Codec for Format {
+    type Encoder = AnyEncoder<Vec<u8>>;
+    type Decoder<R: BufRead> = crate::bufread::AnyDecoder<'static, R>;
+
+    fn encoder(&self, writer: Vec<u8>) -> Result<Self::Encoder, Error> {
+        AnyEncoder::new(writer, *self, Compression::Default)
+    }
+
+    fn decoder<R: BufRead>(&self, reader: R) -> Self::Decoder<R> {
+        let builder = crate::bufread::AnyDecoder::builder(reader);
+        if Format::DETECTION_ORDER.contains(self) {
+            builder.build()
+        } else {
+            builder.format(*self).build()
+        }
+    }
+}
+
+/// Encode arbitrary data via the `codec` and decode it back.
+///
+/// The decoder should report the same [kind](Decode::kind) as the encoder's
+/// [format](Encode::format).
+pub fn roundtrip<C: Codec>(u: &mut Unstructured<'_>, codec: &C) -> arbitrary::Result<()> {
+    let expected: Vec<u8> = u.arbitrary()?;
+    let encoder = ok(
+        codec.encoder(Vec::new()),
+        std::any::type_name::<C>(),
+        "create encoder",
+    );
+    let format = encoder.format();
+    let compressed = encode(u, encoder, &expected, format)?;
+    // read
+    let reader = NBytesReader::new(&compressed[..], u.int_in_range(1..=4096)?);
+    let mut decoder = codec.decoder(reader);
+    assert_eq!(format, ok(decoder.kind(), format, "detect"));
+    assert_eq!(expected, decode(u, decoder, format)?, "{format:?}: read");
+    // bufread
+    let reader = NBytesReader::new(&compressed[..], u.int_in_range(1..=4096)?);
+    let reader = BufReader::with_capacity(u.int_in_range(1..=4096)?, reader);
+    let mut decoder = codec.decoder(reader);
+    assert_eq!(format, ok(decoder.kind(), format, "detect"));
+    assert_eq!(expected, decode(u, decoder, format)?, "{format:?}: bufread");
+    Ok(())
+}
+
+/// Write `data` in arbitrary chunks using arbitrary [Write](std::io::Write) methods.
+fn encode<E: Encode<Writer = Vec<u8>>>(
+    u: &mut Unstructured<'_>,
+    mut encoder: E,
+    mut data: &[u8],
+    format: Format,
+) -> arbitrary::Result<Vec<u8>> {
+    while !data.is_empty() {
+        let len = u.int_in_range(1..=data.len())?;
+        let (chunk, rest) = data.split_at(len);
+        match u.int_in_range(0..=2)? {
+            0 => {
+                let n = ok(encoder.write(chunk), format, "write");
+                assert_ne!(0, n, "{format:?}: write");
+                data = &data[n..];
+            }
+            1 => {
+                let (a, b) = chunk.split_at(len / 2);
+                let n = ok(
+                    encoder.write_vectored(&[IoSlice::new(a), IoSlice::new(b)]),
+                    format,
+                    "write_vectored",
+                );
+                assert_ne!(0, n, "{format:?}: write_vectored");
+                data = &data[n..];
+            }
+            _ => {
+                ok(encoder.write_all(chunk), format, "write_all");
+                data = rest;
+            }
+        }
+    }
+    Ok(ok(encoder.finish(), format, "finish"))
+}
+
+/// Read the decoder to the end into the buffers of arbitrary size.
+fn decode<R: Read>(
+    u: &mut Unstructured<'_>,
+    mut decoder: R,
+    format: Format,
+) -> arbitrary::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    loop {
+        let len = u.int_in_range(1..=4096)?;
+        let mut buf = vec![0_u8; len];
+        let n = if u.arbitrary()? {
+            let (a, b) = buf.split_at_mut(len / 2);
+            ok(
+                decoder.read_vectored(&mut [IoSliceMut::new(a), IoSliceMut::new(b)]),
+                format,
+                "read_vectored",
+            )
+        } else {
+            ok(decoder.read(&mut buf), format, "read")
+        };
+        if n == 0 {
+            return Ok(data);
+        }
+        data.extend_from_slice(&buf[..n]);
+    }
+}
+
+/// Unwrap the result of the I/O `operation` or fail the test.
+#[allow(clippy::panic)]
+fn ok<T>(result: Result<T, Error>, subject: impl std::fmt::Debug, operation: &str) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => panic!("{subject:?}: {operation} failed: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::CustomDecoder;
+    use crate::CustomFormat;
+
+    #[test]
+    fn builtin() {
+        for format in Format::enabled() {
+            arbtest(|u| roundtrip(u, &format));
+        }
+    }
+
+    #[test]
+    fn custom() {
+        arbtest(|u| roundtrip(u, &StoredCodec));
+    }
+
+    struct StoredCodec;
+
+    impl Codec for StoredCodec {
+        type Encoder = StoredEncoder;
+        type Decoder<R: BufRead> = crate::bufread::AnyDecoder<'static, R>;
+
+        fn encoder(&self, writer: Vec<u8>) -> Result<Self::Encoder, Error> {
+            StoredEncoder::new(writer)
+        }
+
+        fn decoder<R: BufRead>(&self, reader: R) -> Self::Decoder<R> {
+            let format = CustomFormat::new("stored", MAGIC, || Box::new(Stored { header: false }));
+            crate::bufread::AnyDecoder::builder(reader)
+                .custom_format(format)
+                .build()
+        }
+    }
+
+    // the stream is the magic bytes followed by the uncompressed data
+    struct Stored {
+        header: bool,
+    }
+
+    impl CustomDecoder for Stored {
+        fn read(&mut self, input: &mut dyn BufRead, buf: &mut [u8]) -> Result<usize, Error> {
+            if !self.header {
+                input.read_exact(&mut [0_u8; MAGIC.len()])?;
+                self.header = true;
+            }
+            input.read(buf)
+        }
+    }
+
+    struct StoredEncoder {
+        writer: Vec<u8>,
+    }
+
+    impl StoredEncoder {
+        fn new(mut writer: Vec<u8>) -> Result<Self, Error> {
+            writer.extend_from_slice(MAGIC);
+            Ok(Self { writer })
+        }
+    }
+
+    impl Write for StoredEncoder {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+            self.writer.write(buf)
+        }
+
+        fn flush(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    impl Encode for StoredEncoder {
+        type Writer = Vec<u8>;
+
+        fn format(&self) -> Format {
+            Format::Other("stored")
+        }
+
+        fn get_ref(&self) -> &Self::Writer {
+            &self.writer
+        }
+
+        fn get_mut(&mut self) -> &mut Self::Writer {
+            &mut self.writer
+        }
+
+        fn finish(self) -> Result<Self::Writer, Error> {
+            Ok(self.writer)
+        }
+    }
+
+    const MAGIC: &[u8] = b"STO\0";
+}
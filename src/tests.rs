@@ -3,6 +3,7 @@ macro_rules! define_decoder_tests {
         #[cfg(test)]
         mod tests {
             use std::collections::VecDeque;
+            use std::io::ErrorKind;
             use std::io::Write;
 
             use arbitrary::Unstructured;
@@ -71,6 +72,1300 @@ macro_rules! define_decoder_tests {
                 });
             }
 
+            #[cfg(feature = "xz")]
+            #[test]
+            fn xz_memory_limit() {
+                use xz::write::XzEncoder;
+                let compressed = compress(XzEncoder::new(Vec::new(), 9));
+                assert_eq!(
+                    ErrorKind::OutOfMemory,
+                    decompress_with_memory_limit(&compressed, 1024 * 1024)
+                        .unwrap_err()
+                        .kind()
+                );
+                assert_eq!(
+                    DATA.len(),
+                    decompress_with_memory_limit(&compressed, 128 * 1024 * 1024)
+                        .unwrap()
+                        .len()
+                );
+            }
+
+            #[cfg(feature = "zstd")]
+            #[test]
+            fn zstd_memory_limit() {
+                use zstd::stream::write::Encoder;
+                let compressed = compress(Encoder::new(Vec::new(), 19).unwrap());
+                assert_eq!(
+                    ErrorKind::OutOfMemory,
+                    decompress_with_memory_limit(&compressed, 1024)
+                        .unwrap_err()
+                        .kind()
+                );
+                assert_eq!(
+                    DATA.len(),
+                    decompress_with_memory_limit(&compressed, 128 * 1024 * 1024)
+                        .unwrap()
+                        .len()
+                );
+            }
+
+            #[cfg(feature = "bzip2")]
+            #[test]
+            fn bz_memory_limit() {
+                use bzip2::write::BzEncoder;
+                use bzip2::Compression;
+                let compressed = compress(BzEncoder::new(Vec::new(), Compression::new(9)));
+                assert_eq!(
+                    ErrorKind::OutOfMemory,
+                    decompress_with_memory_limit(&compressed, 1024 * 1024)
+                        .unwrap_err()
+                        .kind()
+                );
+                // small mode
+                assert_eq!(
+                    DATA.len(),
+                    decompress_with_memory_limit(&compressed, 3 * 1024 * 1024)
+                        .unwrap()
+                        .len()
+                );
+            }
+
+            #[cfg(feature = "bzip2")]
+            #[test]
+            fn bz_small_mode() {
+                use bzip2::write::BzEncoder;
+                use bzip2::Compression;
+                arbtest(|u| {
+                    let expected: Vec<u8> = u.arbitrary()?;
+                    let compression = Compression::new(u.int_in_range(1..=9)?);
+                    let mut writer = BzEncoder::new(Vec::new(), compression);
+                    writer.write_all(&expected).unwrap();
+                    let compressed = writer.finish().unwrap();
+                    let mut reader = AnyDecoder::new(&compressed[..]);
+                    reader.bzip2_small_mode(true);
+                    let mut actual = Vec::new();
+                    reader.read_to_end(&mut actual).unwrap();
+                    assert_eq!(expected, actual);
+                    Ok(())
+                });
+            }
+
+            #[cfg(feature = "bzip2")]
+            #[test]
+            fn bz_corrupted() {
+                use bzip2::write::BzEncoder;
+                use bzip2::Compression;
+                let mut compressed = compress(BzEncoder::new(Vec::new(), Compression::new(9)));
+                // corrupt the block magic
+                compressed[4] ^= 0xff;
+                let mut reader = AnyDecoder::new(&compressed[..]);
+                let mut actual = Vec::new();
+                assert_eq!(
+                    ErrorKind::InvalidData,
+                    reader.read_to_end(&mut actual).unwrap_err().kind()
+                );
+            }
+
+            #[cfg(feature = "bzip2")]
+            #[test]
+            fn concatenated_bz() {
+                use bzip2::write::BzEncoder;
+                use bzip2::Compression;
+                arbtest(|u| {
+                    // `pbzip2` output, i.e. one stream per block
+                    let blocks: Vec<Vec<u8>> = u.arbitrary()?;
+                    let mut compressed = Vec::new();
+                    for block in blocks.iter() {
+                        let mut writer = BzEncoder::new(Vec::new(), Compression::fast());
+                        writer.write_all(block).unwrap();
+                        compressed.extend(writer.finish().unwrap());
+                    }
+                    let capacity = u.int_in_range(1..=4096)?;
+                    let mut reader = AnyDecoder::new(NBytesReader::new(&compressed[..], capacity));
+                    reader.bzip2_small_mode(u.arbitrary()?);
+                    let mut actual = Vec::new();
+                    reader.read_to_end(&mut actual).unwrap();
+                    assert_eq!(blocks.concat(), actual);
+                    Ok(())
+                });
+            }
+
+            #[test]
+            #[cfg(feature = "flate2")]
+            fn gz_header_fields() {
+                use crate::write::GzEncoder;
+                use crate::write::GzHeader;
+                use flate2::Compression;
+                arbtest(|u| {
+                    let expected: Vec<u8> = u.arbitrary()?;
+                    let header = GzHeader {
+                        extra_flags: Some(u.arbitrary()?),
+                        operating_system: u.arbitrary()?,
+                        ..Default::default()
+                    };
+                    let mut writer =
+                        GzEncoder::with_header(Vec::new(), Compression::fast(), header.clone());
+                    writer.write_all(&expected).unwrap();
+                    let compressed = writer.finish().unwrap();
+                    let capacity = u.int_in_range(1..=4096)?;
+                    let mut reader = AnyDecoder::new(NBytesReader::new(&compressed[..], capacity));
+                    assert_eq!(header.extra_flags, reader.gz_extra_flags().unwrap());
+                    assert_eq!(
+                        Some(header.operating_system),
+                        reader.gz_operating_system().unwrap()
+                    );
+                    let mut actual = Vec::new();
+                    reader.read_to_end(&mut actual).unwrap();
+                    assert_eq!(expected, actual);
+                    Ok(())
+                });
+                let mut reader = AnyDecoder::new(&b"hello world"[..]);
+                assert_eq!(None, reader.gz_extra_flags().unwrap());
+                assert_eq!(None, reader.gz_operating_system().unwrap());
+            }
+
+            #[test]
+            #[cfg(feature = "flate2")]
+            fn gz_header_ext() {
+                use crate::write::GzEncoder;
+                use crate::write::GzHeader;
+                use crate::GzDecoderExt;
+                use flate2::Compression;
+                arbtest(|u| {
+                    let expected: Vec<u8> = u.arbitrary()?;
+                    let header = GzHeader {
+                        filename: u.arbitrary::<Option<String>>()?.map(Into::into),
+                        comment: u.arbitrary::<Option<String>>()?.map(Into::into),
+                        mtime: u.arbitrary()?,
+                        ..Default::default()
+                    };
+                    let mut writer =
+                        GzEncoder::with_header(Vec::new(), Compression::fast(), header.clone());
+                    writer.write_all(&expected).unwrap();
+                    let compressed = writer.finish().unwrap();
+                    let mut reader = AnyDecoder::new(&compressed[..]);
+                    let actual_header = reader.gz_header().unwrap().unwrap();
+                    // zero bytes are removed from the strings
+                    let trim = |s: Option<Vec<u8>>| {
+                        s.map(|s| s.into_iter().filter(|b| *b != 0).collect::<Vec<_>>())
+                    };
+                    assert_eq!(
+                        trim(header.filename),
+                        actual_header.filename().map(Into::into)
+                    );
+                    assert_eq!(
+                        trim(header.comment),
+                        actual_header.comment().map(Into::into)
+                    );
+                    assert_eq!(header.mtime, actual_header.mtime());
+                    let mut actual = Vec::new();
+                    reader.read_to_end(&mut actual).unwrap();
+                    assert_eq!(expected, actual);
+                    Ok(())
+                });
+                let mut reader = AnyDecoder::new(&b"hello world"[..]);
+                assert_eq!(None, reader.gz_header().unwrap());
+            }
+
+            #[test]
+            #[cfg(feature = "flate2")]
+            fn gz_header_crc() {
+                use crate::write::GzEncoder;
+                use crate::write::GzHeader;
+                use flate2::Compression;
+                arbtest(|u| {
+                    let expected: Vec<u8> = u.arbitrary()?;
+                    let header = GzHeader {
+                        filename: u.arbitrary::<Option<String>>()?.map(Into::into),
+                        header_crc: u.arbitrary()?,
+                        ..Default::default()
+                    };
+                    let mut writer =
+                        GzEncoder::with_header(Vec::new(), Compression::fast(), header.clone());
+                    writer.write_all(&expected).unwrap();
+                    let mut compressed = writer.finish().unwrap();
+                    let mut reader = AnyDecoder::new(&compressed[..]);
+                    reader.require_gz_header_crc(true);
+                    let mut actual = Vec::new();
+                    match reader.read_to_end(&mut actual) {
+                        Ok(..) => assert_eq!(expected, actual),
+                        Err(e) => {
+                            assert!(!header.header_crc);
+                            assert_eq!(ErrorKind::InvalidData, e.kind());
+                        }
+                    }
+                    if header.header_crc {
+                        // corrupt the modification time
+                        compressed[4] ^= 1;
+                        let mut reader = AnyDecoder::new(&compressed[..]);
+                        let mut actual = Vec::new();
+                        assert!(reader.read_to_end(&mut actual).is_err());
+                    }
+                    Ok(())
+                });
+            }
+
+            #[test]
+            #[cfg(feature = "flate2")]
+            fn gz_header_crc_first_member() {
+                use crate::write::GzEncoder;
+                use crate::write::GzHeader;
+                use flate2::Compression;
+                let mut compressed = Vec::new();
+                for header_crc in [true, false] {
+                    let header = GzHeader {
+                        header_crc,
+                        ..Default::default()
+                    };
+                    let mut writer =
+                        GzEncoder::with_header(Vec::new(), Compression::fast(), header);
+                    writer.write_all(&DATA).unwrap();
+                    compressed.extend(writer.finish().unwrap());
+                }
+                // only the first member is checked when decoded as one stream
+                let mut reader = AnyDecoder::new(&compressed[..]);
+                reader.require_gz_header_crc(true);
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                assert_eq!(2 * DATA.len(), actual.len());
+                // each member is checked when decoded one by one
+                let mut reader = AnyDecoder::new(&compressed[..]);
+                reader.require_gz_header_crc(true);
+                reader.per_member(true);
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                assert_eq!(DATA.len(), actual.len());
+                assert_eq!(
+                    ErrorKind::InvalidData,
+                    reader.next_member().unwrap_err().kind()
+                );
+            }
+
+            #[test]
+            #[cfg(feature = "flate2")]
+            fn per_member_gz() {
+                use crate::write::GzEncoder;
+                use flate2::Compression;
+                arbtest(|u| {
+                    let members: Vec<Vec<u8>> = u.arbitrary()?;
+                    let compression = Compression::new(u.int_in_range(0..=9)?);
+                    let mut writer = GzEncoder::new(Vec::new(), compression);
+                    for (i, member) in members.iter().enumerate() {
+                        if i != 0 {
+                            writer.start_new_member().unwrap();
+                        }
+                        writer.write_all(member).unwrap();
+                    }
+                    let compressed = writer.finish().unwrap();
+                    let capacity = u.int_in_range(1..=4096)?;
+                    let actual = read_members(NBytesReader::new(&compressed[..], capacity));
+                    assert_eq!(members.len().max(1), actual.len());
+                    assert_eq!(members.concat(), actual.concat());
+                    if !members.is_empty() {
+                        assert_eq!(members, actual);
+                    }
+                    Ok(())
+                });
+            }
+
+            #[test]
+            #[cfg(feature = "flate2")]
+            fn concatenated_gz() {
+                use flate2::write::GzEncoder;
+                use flate2::Compression;
+                arbtest(|u| {
+                    // `gzip a b; cat a.gz b.gz`
+                    let files: Vec<Vec<u8>> = u.arbitrary()?;
+                    let mut compressed = Vec::new();
+                    for file in files.iter() {
+                        let mut writer = GzEncoder::new(Vec::new(), Compression::fast());
+                        writer.write_all(file).unwrap();
+                        compressed.extend(writer.finish().unwrap());
+                    }
+                    let capacity = u.int_in_range(1..=4096)?;
+                    let mut reader = AnyDecoder::new(NBytesReader::new(&compressed[..], capacity));
+                    let mut actual = Vec::new();
+                    reader.read_to_end(&mut actual).unwrap();
+                    assert_eq!(files.concat(), actual);
+                    Ok(())
+                });
+            }
+
+            #[test]
+            #[cfg(feature = "xz")]
+            fn per_member_xz() {
+                use xz::write::XzEncoder;
+                arbtest(|u| {
+                    let members: Vec<Vec<u8>> = u.arbitrary()?;
+                    let mut compressed = Vec::new();
+                    for member in members.iter() {
+                        let mut writer = XzEncoder::new(Vec::new(), 0);
+                        writer.write_all(member).unwrap();
+                        compressed.extend(writer.finish().unwrap());
+                        // stream padding
+                        compressed.extend(vec![0_u8; 4 * u.int_in_range(0..=2)?]);
+                    }
+                    let capacity = u.int_in_range(1..=4096)?;
+                    let mut reader = AnyDecoder::new(NBytesReader::new(&compressed[..], capacity));
+                    let mut actual = Vec::new();
+                    reader.read_to_end(&mut actual).unwrap();
+                    assert_eq!(members.concat(), actual);
+                    let actual = read_members(NBytesReader::new(&compressed[..], capacity));
+                    if !members.is_empty() {
+                        assert_eq!(members, actual);
+                    }
+                    Ok(())
+                });
+            }
+
+            #[test]
+            #[cfg(feature = "zstd")]
+            fn concatenated_zstd() {
+                arbtest(|u| {
+                    let frames: Vec<Vec<u8>> = u.arbitrary()?;
+                    let mut compressed = Vec::new();
+                    for frame in frames.iter() {
+                        if u.arbitrary()? {
+                            let payload: Vec<u8> = u.arbitrary()?;
+                            compressed
+                                .extend((0x184d_2a50_u32 + u.int_in_range(0..=15)?).to_le_bytes());
+                            compressed.extend((payload.len() as u32).to_le_bytes());
+                            compressed.extend(payload);
+                        }
+                        compressed.extend(zstd::encode_all(&frame[..], 1).unwrap());
+                    }
+                    let capacity = u.int_in_range(1..=4096)?;
+                    let mut reader = AnyDecoder::new(NBytesReader::new(&compressed[..], capacity));
+                    let mut actual = Vec::new();
+                    reader.read_to_end(&mut actual).unwrap();
+                    assert_eq!(frames.concat(), actual);
+                    let mut reader = AnyDecoder::new(NBytesReader::new(&compressed[..], capacity));
+                    reader.zstd_single_frame(true);
+                    let mut actual = Vec::new();
+                    reader.read_to_end(&mut actual).unwrap();
+                    assert_eq!(frames.first().cloned().unwrap_or_default(), actual);
+                    Ok(())
+                });
+            }
+
+            #[test]
+            #[cfg(feature = "zstd")]
+            fn per_member_zstd() {
+                use crate::write::ZstdEncoder;
+                arbtest(|u| {
+                    let members: Vec<Vec<u8>> = u.arbitrary()?;
+                    let compression = u.int_in_range(0..=22)?;
+                    let mut writer = ZstdEncoder::new(Vec::new(), compression).unwrap();
+                    for (i, member) in members.iter().enumerate() {
+                        if i != 0 {
+                            writer.start_new_frame().unwrap();
+                        }
+                        writer.write_all(member).unwrap();
+                    }
+                    let compressed = writer.finish().unwrap();
+                    let capacity = u.int_in_range(1..=4096)?;
+                    let actual = read_members(NBytesReader::new(&compressed[..], capacity));
+                    assert_eq!(members.len().max(1), actual.len());
+                    assert_eq!(members.concat(), actual.concat());
+                    if !members.is_empty() {
+                        assert_eq!(members, actual);
+                    }
+                    Ok(())
+                });
+            }
+
+            #[test]
+            #[cfg(feature = "lz4")]
+            fn per_member_lz4() {
+                use lz4_flex::frame::FrameEncoder;
+                arbtest(|u| {
+                    let members: Vec<Vec<u8>> = u.arbitrary()?;
+                    let mut compressed = Vec::new();
+                    for member in members.iter() {
+                        let mut writer = FrameEncoder::new(compressed);
+                        writer.write_all(member).unwrap();
+                        compressed = writer.finish().unwrap();
+                    }
+                    let capacity = u.int_in_range(1..=4096)?;
+                    let mut reader = AnyDecoder::new(NBytesReader::new(&compressed[..], capacity));
+                    let mut actual = Vec::new();
+                    reader.read_to_end(&mut actual).unwrap();
+                    assert_eq!(members.concat(), actual);
+                    let actual = read_members(NBytesReader::new(&compressed[..], capacity));
+                    assert_eq!(members.len().max(1), actual.len());
+                    assert_eq!(members.concat(), actual.concat());
+                    if !members.is_empty() {
+                        assert_eq!(members, actual);
+                    }
+                    Ok(())
+                });
+            }
+
+            #[test]
+            #[cfg(feature = "lz4")]
+            fn per_member_lz4_legacy() {
+                use lz4_flex::frame::FrameEncoder;
+                arbtest(|u| {
+                    let members: Vec<(bool, Vec<u8>)> = u.arbitrary()?;
+                    let mut compressed = Vec::new();
+                    for (legacy, member) in members.iter() {
+                        if *legacy {
+                            compressed.extend_from_slice(&0x184c2102_u32.to_le_bytes());
+                            if !member.is_empty() {
+                                let block = lz4_flex::block::compress(member);
+                                compressed.extend_from_slice(&(block.len() as u32).to_le_bytes());
+                                compressed.extend_from_slice(&block);
+                            }
+                        } else {
+                            let mut writer = FrameEncoder::new(compressed);
+                            writer.write_all(member).unwrap();
+                            compressed = writer.finish().unwrap();
+                        }
+                    }
+                    let members: Vec<Vec<u8>> =
+                        members.into_iter().map(|(_, member)| member).collect();
+                    let capacity = u.int_in_range(1..=4096)?;
+                    let mut reader = AnyDecoder::new(NBytesReader::new(&compressed[..], capacity));
+                    let mut actual = Vec::new();
+                    reader.read_to_end(&mut actual).unwrap();
+                    assert_eq!(members.concat(), actual);
+                    let actual = read_members(NBytesReader::new(&compressed[..], capacity));
+                    assert_eq!(members.len().max(1), actual.len());
+                    assert_eq!(members.concat(), actual.concat());
+                    if !members.is_empty() {
+                        assert_eq!(members, actual);
+                    }
+                    Ok(())
+                });
+            }
+
+            #[test]
+            #[cfg(all(feature = "flate2", feature = "zstd"))]
+            fn members() {
+                use crate::write::AnyEncoder;
+                use crate::write::Compression;
+                use crate::GzDecoderExt;
+                use crate::ZstdDecoderExt;
+                arbtest(|u| {
+                    let expected: Vec<(bool, Vec<u8>)> = u.arbitrary()?;
+                    let mut compressed = Vec::new();
+                    for (gz, data) in expected.iter() {
+                        if *gz {
+                            let mut writer =
+                                AnyEncoder::new(Vec::new(), Format::Gz, Compression::Fast).unwrap();
+                            writer.write_all(data).unwrap();
+                            compressed.extend(writer.finish().unwrap());
+                        } else {
+                            compressed.extend(zstd::bulk::compress(data, 1).unwrap());
+                        }
+                    }
+                    let capacity = u.int_in_range(1..=4096)?;
+                    let mut members =
+                        AnyDecoder::new(NBytesReader::new(&compressed[..], capacity)).members();
+                    let mut actual = Vec::new();
+                    while let Some(member) = members.next_member() {
+                        let mut member = member.unwrap();
+                        assert_eq!(actual.len(), member.index());
+                        let (gz, data) = &expected[member.index()];
+                        if *gz {
+                            assert_eq!(Format::Gz, member.kind().unwrap());
+                            assert!(member.gz_header().unwrap().is_some());
+                        } else {
+                            assert_eq!(Format::Zstd, member.kind().unwrap());
+                            assert_eq!(Some(data.len() as u64), member.content_size().unwrap());
+                        }
+                        // the rest of the member is skipped
+                        let mut buf = vec![0_u8; u.int_in_range(0..=data.len())?];
+                        member.read_exact(&mut buf).unwrap();
+                        actual.push(buf);
+                    }
+                    assert_eq!(expected.len(), actual.len());
+                    for ((_, data), actual) in expected.iter().zip(actual.iter()) {
+                        assert!(data.starts_with(actual));
+                    }
+                    Ok(())
+                });
+            }
+
+            #[test]
+            fn compressed_size() {
+                use crate::write::AnyEncoder;
+                use crate::write::Compression;
+                arbtest(|u| {
+                    // container entries that are stored back to back
+                    let entries: Vec<Vec<u8>> = u.arbitrary()?;
+                    let mut file = Vec::new();
+                    let mut sizes = Vec::new();
+                    for data in entries.iter() {
+                        let format = *u.choose(Format::DETECTION_ORDER)?;
+                        let mut writer =
+                            AnyEncoder::new(Vec::new(), format, Compression::Fast).unwrap();
+                        writer.write_all(data).unwrap();
+                        let compressed = writer.finish().unwrap();
+                        sizes.push(compressed.len() as u64);
+                        file.extend(compressed);
+                    }
+                    let capacity = u.int_in_range(1..=4096)?;
+                    let mut reader = NBytesReader::new(&file[..], capacity);
+                    for (data, size) in entries.iter().zip(sizes.iter()) {
+                        let mut decoder = AnyDecoder::new(&mut reader);
+                        decoder.compressed_size(*size);
+                        decoder.trial_decode(u.arbitrary()?);
+                        let mut actual = Vec::new();
+                        decoder.read_to_end(&mut actual).unwrap();
+                        assert_eq!(data, &actual);
+                    }
+                    let mut rest = Vec::new();
+                    reader.read_to_end(&mut rest).unwrap();
+                    assert!(rest.is_empty());
+                    Ok(())
+                });
+            }
+
+            #[allow(dead_code)]
+            fn read_members(reader: NBytesReader<&[u8]>) -> Vec<Vec<u8>> {
+                let mut reader = AnyDecoder::new(reader);
+                reader.per_member(true);
+                let mut members = Vec::new();
+                loop {
+                    let mut member = Vec::new();
+                    reader.read_to_end(&mut member).unwrap();
+                    members.push(member);
+                    if !reader.next_member().unwrap() {
+                        break;
+                    }
+                }
+                members
+            }
+
+            const DATA: [u8; 64 * 1024] = [b'x'; 64 * 1024];
+
+            #[allow(dead_code)]
+            fn compress<W: Write + Finish<Vec<u8>>>(mut writer: W) -> Vec<u8> {
+                writer.write_all(&DATA).unwrap();
+                writer.finish().unwrap()
+            }
+
+            #[allow(dead_code)]
+            fn decompress_with_memory_limit(
+                compressed: &[u8],
+                limit: u64,
+            ) -> Result<Vec<u8>, std::io::Error> {
+                let mut reader = AnyDecoder::new(compressed);
+                reader.memory_limit(limit);
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual)?;
+                Ok(actual)
+            }
+
+            #[test]
+            fn boxed() {
+                use std::io::BufRead;
+                use std::io::Cursor;
+                arbtest(|u| {
+                    let expected: Vec<u8> = u.arbitrary()?;
+                    let mut boxed: Vec<Box<dyn Read + Send>> = Vec::new();
+                    let mut decoders: Vec<AnyDecoder<'static, Box<dyn BufRead + Send>>> =
+                        Vec::new();
+                    for format in Format::DETECTION_ORDER.iter() {
+                        let mut writer = crate::write::AnyEncoder::new(
+                            Vec::new(),
+                            *format,
+                            crate::write::Compression::Fast,
+                        )
+                        .unwrap();
+                        writer.write_all(&expected).unwrap();
+                        let compressed = writer.finish().unwrap();
+                        boxed.push(AnyDecoder::boxed(Cursor::new(compressed.clone())));
+                        decoders.push(AnyDecoder::new(Box::new(Cursor::new(compressed))));
+                    }
+                    for mut reader in boxed.into_iter() {
+                        let mut actual = Vec::new();
+                        reader.read_to_end(&mut actual).unwrap();
+                        assert_eq!(expected, actual);
+                    }
+                    for mut reader in decoders.into_iter() {
+                        let mut actual = Vec::new();
+                        reader.read_to_end(&mut actual).unwrap();
+                        assert_eq!(expected, actual);
+                    }
+                    Ok(())
+                });
+            }
+
+            #[test]
+            fn boxed_any_decoder() {
+                use std::io::Cursor;
+                let decoders: Vec<BoxedAnyDecoder> = Format::DETECTION_ORDER
+                    .iter()
+                    .map(|format| {
+                        let mut writer = crate::write::AnyEncoder::new(
+                            Vec::new(),
+                            *format,
+                            crate::write::Compression::Fast,
+                        )
+                        .unwrap();
+                        writer.write_all(&DATA).unwrap();
+                        let compressed = writer.finish().unwrap();
+                        AnyDecoder::new_boxed(Box::new(Cursor::new(compressed)))
+                    })
+                    .collect();
+                let threads: Vec<_> = decoders
+                    .into_iter()
+                    .map(|mut reader| {
+                        std::thread::spawn(move || {
+                            let mut actual = Vec::new();
+                            reader.read_to_end(&mut actual).unwrap();
+                            actual
+                        })
+                    })
+                    .collect();
+                for thread in threads.into_iter() {
+                    assert_eq!(DATA, &thread.join().unwrap()[..]);
+                }
+            }
+
+            #[cfg(feature = "zstd")]
+            #[test]
+            fn zstd_dictionary() {
+                use zstd::dict::DecoderDictionary;
+                arbtest(|u| {
+                    let expected: Vec<u8> = u.arbitrary()?;
+                    // raw dictionary
+                    let mut dictionary = b"raw".to_vec();
+                    dictionary.extend(u.arbitrary::<Vec<u8>>()?);
+                    let dictionary = crate::ZstdDictionary::new(dictionary).unwrap();
+                    let mut writer = zstd::stream::Encoder::with_dictionary(
+                        Vec::new(),
+                        3,
+                        dictionary.as_bytes(),
+                    )
+                    .unwrap();
+                    writer.write_all(&expected).unwrap();
+                    let compressed = writer.finish().unwrap();
+                    // the prepared dictionary is borrowed, not copied into the decoder
+                    let prepared = DecoderDictionary::copy(dictionary.as_bytes());
+                    let mut reader = AnyDecoder::new(&compressed[..]);
+                    reader.zstd_dictionary(&prepared);
+                    let mut actual = Vec::new();
+                    reader.read_to_end(&mut actual).unwrap();
+                    assert_eq!(expected, actual);
+                    Ok(())
+                });
+            }
+
+            #[cfg(feature = "flate2")]
+            #[test]
+            fn scan_for_magic() {
+                use flate2::write::GzEncoder;
+                use flate2::Compression;
+                arbtest(|u| {
+                    let expected: Vec<u8> = u.arbitrary()?;
+                    let window = u.int_in_range(1..=100)?;
+                    // the low nibble rules out all the magic bytes including zlib header
+                    let prefix: Vec<u8> = (0..u.int_in_range(1..=window)?)
+                        .map(|_| Ok((u.arbitrary::<u8>()? & 0xf0) | 1))
+                        .collect::<arbitrary::Result<_>>()?;
+                    let mut writer = GzEncoder::new(prefix.clone(), Compression::fast());
+                    writer.write_all(&expected).unwrap();
+                    let compressed = writer.finish().unwrap();
+                    let mut reader = AnyDecoder::new(&compressed[..]);
+                    reader.scan_for_magic(window);
+                    let mut actual = Vec::new();
+                    reader.read_to_end(&mut actual).unwrap();
+                    assert_eq!(expected, actual);
+                    // the magic is outside of the window
+                    let mut reader = AnyDecoder::new(&compressed[..]);
+                    reader.scan_for_magic(prefix.len() - 1);
+                    let mut actual = Vec::new();
+                    reader.read_to_end(&mut actual).unwrap();
+                    assert_eq!(compressed, actual);
+                    Ok(())
+                });
+            }
+
+            #[cfg(feature = "zstd")]
+            #[test]
+            fn zstd_checksum() {
+                use zstd::stream::write::Encoder;
+                arbtest(|u| {
+                    let expected: Vec<u8> = u.arbitrary()?;
+                    let checksum: bool = u.arbitrary()?;
+                    let mut writer = Encoder::new(Vec::new(), 1).unwrap();
+                    writer.include_checksum(checksum).unwrap();
+                    writer.write_all(&expected).unwrap();
+                    let compressed = writer.finish().unwrap();
+                    let mut reader = AnyDecoder::new(&compressed[..]);
+                    assert_eq!(Some(checksum), reader.zstd_checksum().unwrap());
+                    let mut actual = Vec::new();
+                    reader.read_to_end(&mut actual).unwrap();
+                    assert_eq!(expected, actual);
+                    let mut reader = AnyDecoder::new(&compressed[..]);
+                    reader.require_zstd_checksum(true);
+                    let mut actual = Vec::new();
+                    match reader.read_to_end(&mut actual) {
+                        Ok(..) => assert_eq!(expected, actual),
+                        Err(e) => {
+                            assert!(!checksum);
+                            assert_eq!(ErrorKind::InvalidData, e.kind());
+                        }
+                    }
+                    Ok(())
+                });
+                let mut reader = AnyDecoder::new(&b"hello world"[..]);
+                assert_eq!(None, reader.zstd_checksum().unwrap());
+            }
+
+            #[cfg(feature = "zstd")]
+            #[test]
+            fn zstd_checksum_after_skippable_frame() {
+                use zstd::stream::write::Encoder;
+                let mut writer = Encoder::new(Vec::new(), 1).unwrap();
+                writer.include_checksum(true).unwrap();
+                writer.write_all(&DATA).unwrap();
+                let mut compressed = vec![0x50, 0x2a, 0x4d, 0x18, 3, 0, 0, 0, 1, 2, 3];
+                compressed.extend(writer.finish().unwrap());
+                let mut reader = AnyDecoder::new(&compressed[..]);
+                reader.require_zstd_checksum(true);
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                assert_eq!(DATA[..], actual);
+            }
+
+            #[cfg(feature = "zstd")]
+            #[test]
+            fn zstd_frame_header_after_skippable_frame() {
+                use crate::ZstdDecoderExt;
+                use zstd::stream::write::Encoder;
+                let mut writer = Encoder::new(Vec::new(), 1).unwrap();
+                writer.include_checksum(true).unwrap();
+                writer
+                    .set_pledged_src_size(Some(DATA.len() as u64))
+                    .unwrap();
+                writer.write_all(&DATA).unwrap();
+                let mut compressed = vec![0x50, 0x2a, 0x4d, 0x18, 3, 0, 0, 0, 1, 2, 3];
+                compressed.extend(writer.finish().unwrap());
+                let mut reader = AnyDecoder::new(&compressed[..]);
+                assert_eq!(Some(true), reader.zstd_checksum().unwrap());
+                assert_eq!(Some(DATA.len() as u64), reader.content_size().unwrap());
+                assert_eq!(None, reader.dict_id().unwrap());
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                assert_eq!(DATA[..], actual);
+            }
+
+            #[cfg(feature = "zstd")]
+            #[test]
+            fn zstd_dict_id() {
+                use crate::ZstdDecoderExt;
+                use crate::ZstdDictionary;
+                let samples = (0..1000)
+                    .map(|i| format!("{{\"id\": {i}, \"name\": \"sample {}\"}}", i * 7919 % 1000))
+                    .collect::<Vec<_>>();
+                let dictionary =
+                    ZstdDictionary::new(zstd::dict::from_samples(&samples, 1024).unwrap()).unwrap();
+                let mut writer =
+                    zstd::stream::Encoder::with_dictionary(Vec::new(), 3, dictionary.as_bytes())
+                        .unwrap();
+                writer.write_all(samples[0].as_bytes()).unwrap();
+                let compressed = writer.finish().unwrap();
+                let mut reader = AnyDecoder::new(&compressed[..]);
+                assert_eq!(dictionary.id(), reader.dict_id().unwrap());
+                let compressed = zstd::encode_all(samples[0].as_bytes(), 3).unwrap();
+                let mut reader = AnyDecoder::new(&compressed[..]);
+                assert_eq!(None, reader.dict_id().unwrap());
+                let mut reader = AnyDecoder::new(&b"hello world"[..]);
+                assert_eq!(None, reader.dict_id().unwrap());
+            }
+
+            #[cfg(feature = "flate2")]
+            #[test]
+            fn zlib_dictionary() {
+                use crate::ZlibDecoderExt;
+                use flate2::write::ZlibEncoder;
+                use flate2::Compression;
+                let dictionary = b"the quick brown fox jumps over the lazy dog";
+                let expected = b"the lazy dog jumps over the quick brown fox";
+                // compressed by Python's zlib with `zdict=dictionary`
+                let compressed = [
+                    120, 249, 97, 60, 15, 250, 67, 102, 163, 171, 65, 51, 2, 0, 93, 102, 15, 250,
+                ];
+                // the dictionary is supplied after the detection
+                let mut reader = AnyDecoder::new(&compressed[..]);
+                assert_eq!(Some(0x613c0ffa), reader.zlib_dict_id().unwrap());
+                assert_eq!(Format::Zlib, reader.kind().unwrap());
+                reader.zlib_dictionary(dictionary);
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                assert_eq!(&expected[..], &actual[..]);
+                // the dictionary is missing
+                let mut reader = AnyDecoder::new(&compressed[..]);
+                let error = reader.read_to_end(&mut Vec::new()).unwrap_err();
+                assert_eq!(ErrorKind::InvalidInput, error.kind());
+                // the wrong dictionary
+                let mut reader = AnyDecoder::new(&compressed[..]);
+                reader.zlib_dictionary(expected);
+                let error = reader.read_to_end(&mut Vec::new()).unwrap_err();
+                assert_eq!(ErrorKind::InvalidInput, error.kind());
+                let compressed = compress(ZlibEncoder::new(Vec::new(), Compression::fast()));
+                let mut reader = AnyDecoder::new(&compressed[..]);
+                assert_eq!(None, reader.zlib_dict_id().unwrap());
+            }
+
+            #[test]
+            fn hardened() {
+                use crate::DecoderLimits;
+                let limits = DecoderLimits {
+                    memory: 1024 * 1024,
+                    ..Default::default()
+                };
+                #[cfg(feature = "xz")]
+                {
+                    use xz::write::XzEncoder;
+                    let compressed = compress(XzEncoder::new(Vec::new(), 9));
+                    let mut reader = AnyDecoder::new(&compressed[..]);
+                    reader.hardened(limits);
+                    let mut actual = Vec::new();
+                    assert_eq!(
+                        ErrorKind::OutOfMemory,
+                        reader.read_to_end(&mut actual).unwrap_err().kind()
+                    );
+                    // the smallest limit wins
+                    let mut reader = AnyDecoder::new(&compressed[..]);
+                    reader.hardened(limits);
+                    reader.memory_limit(128 * 1024 * 1024);
+                    assert!(reader.read_to_end(&mut actual).is_err());
+                }
+                #[cfg(feature = "flate2")]
+                {
+                    let mut compressed = vec![0_u8; 100];
+                    compressed.extend(compress(flate2::write::GzEncoder::new(
+                        Vec::new(),
+                        flate2::Compression::fast(),
+                    )));
+                    let mut reader = AnyDecoder::new(&compressed[..]);
+                    reader.hardened(limits);
+                    reader.scan_for_magic(1024 * 1024);
+                    let mut actual = Vec::new();
+                    assert_eq!(
+                        ErrorKind::OutOfMemory,
+                        reader.read_to_end(&mut actual).unwrap_err().kind()
+                    );
+                    let mut reader = AnyDecoder::new(&compressed[..]);
+                    reader.hardened(limits);
+                    reader.scan_for_magic(1024);
+                    let mut actual = Vec::new();
+                    reader.read_to_end(&mut actual).unwrap();
+                    assert_eq!(DATA, &actual[..]);
+                }
+                #[cfg(feature = "zstd")]
+                {
+                    let samples = (0..1000).map(|i| format!("sample {i}")).collect::<Vec<_>>();
+                    let dictionary = zstd::dict::from_samples(&samples, 1024).unwrap();
+                    let compressed = zstd::encode_all(samples[0].as_bytes(), 3).unwrap();
+                    let dictionary = zstd::dict::DecoderDictionary::copy(&dictionary);
+                    let mut reader = AnyDecoder::new(&compressed[..]);
+                    reader.hardened(DecoderLimits {
+                        zstd_dictionary: 1,
+                        ..Default::default()
+                    });
+                    reader.zstd_dictionary(&dictionary);
+                    let mut actual = Vec::new();
+                    assert_eq!(
+                        ErrorKind::OutOfMemory,
+                        reader.read_to_end(&mut actual).unwrap_err().kind()
+                    );
+                }
+            }
+
+            #[cfg(feature = "flate2")]
+            #[test]
+            fn trial_decode() {
+                use flate2::write::ZlibEncoder;
+                use flate2::Compression;
+                // valid zlib header
+                let text = b"hb is not zlib";
+                let mut reader = AnyDecoder::new(&text[..]);
+                let mut actual = Vec::new();
+                assert!(reader.read_to_end(&mut actual).is_err());
+                let mut reader = AnyDecoder::new(&text[..]);
+                reader.trial_decode(true);
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                assert_eq!(text, &actual[..]);
+                arbtest(|u| {
+                    let expected: Vec<u8> = u.arbitrary()?;
+                    let window = u.int_in_range(0..=100)?;
+                    // the low nibble rules out all the magic bytes including zlib header
+                    let prefix: Vec<u8> = (0..u.int_in_range(0..=window)?)
+                        .map(|_| Ok((u.arbitrary::<u8>()? & 0xf0) | 1))
+                        .collect::<arbitrary::Result<_>>()?;
+                    let mut writer = ZlibEncoder::new(prefix.clone(), Compression::fast());
+                    writer.write_all(&expected).unwrap();
+                    let compressed = writer.finish().unwrap();
+                    let capacity = u.int_in_range(1..=4096)?;
+                    let mut reader = AnyDecoder::new(NBytesReader::new(&compressed[..], capacity));
+                    reader.trial_decode(true);
+                    reader.scan_for_magic(window);
+                    let mut actual = Vec::new();
+                    reader.read_to_end(&mut actual).unwrap();
+                    assert_eq!(expected, actual);
+                    Ok(())
+                });
+            }
+
+            #[cfg(feature = "flate2")]
+            #[test]
+            fn detection_order() {
+                use flate2::write::GzEncoder;
+                use flate2::write::ZlibEncoder;
+                use flate2::Compression;
+                let zlib = compress(ZlibEncoder::new(Vec::new(), Compression::fast()));
+                let gz = compress(GzEncoder::new(Vec::new(), Compression::fast()));
+                // zlib is never detected
+                let mut reader = AnyDecoder::new(&zlib[..]);
+                reader.detection_order(&[Format::Gz]);
+                assert_eq!(Format::Verbatim, reader.kind().unwrap());
+                let mut reader = AnyDecoder::new(&gz[..]);
+                reader.detection_order(&[Format::Zlib, Format::Gz]);
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                assert_eq!(DATA, &actual[..]);
+                // empty list disables detection
+                let mut reader = AnyDecoder::new(&gz[..]);
+                reader.detection_order(&[]);
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                assert_eq!(gz, actual);
+            }
+
+            #[test]
+            fn custom_format() {
+                use std::io::BufRead;
+
+                // magic bytes followed by the length and the data
+                struct Stored {
+                    remaining: Option<u64>,
+                }
+
+                impl crate::CustomDecoder for Stored {
+                    fn read(
+                        &mut self,
+                        input: &mut dyn BufRead,
+                        buf: &mut [u8],
+                    ) -> Result<usize, std::io::Error> {
+                        let remaining = match self.remaining {
+                            Some(n) => n,
+                            None => {
+                                let mut header = [0_u8; 12];
+                                input.read_exact(&mut header)?;
+                                u64::from_le_bytes(header[4..].try_into().unwrap())
+                            }
+                        };
+                        let n = (buf.len() as u64).min(remaining) as usize;
+                        let n = input.read(&mut buf[..n])?;
+                        self.remaining = Some(remaining - n as u64);
+                        Ok(n)
+                    }
+                }
+
+                arbtest(|u| {
+                    let data: Vec<u8> = u.arbitrary()?;
+                    let mut compressed = b"STO\0".to_vec();
+                    compressed.extend((data.len() as u64).to_le_bytes());
+                    compressed.extend(&data);
+                    // the trailer is not decoded
+                    compressed.extend(u.arbitrary::<Vec<u8>>()?);
+                    let format = crate::CustomFormat::new("stored", b"STO\0", || {
+                        Box::new(Stored { remaining: None })
+                    });
+                    let mut reader = AnyDecoder::builder(NBytesReader::new(
+                        &compressed[..],
+                        u.int_in_range(1..=compressed.len())?,
+                    ))
+                    .custom_format(format)
+                    .build();
+                    assert_eq!(Format::Other("stored"), reader.kind().unwrap());
+                    let mut actual = Vec::new();
+                    reader.read_to_end(&mut actual).unwrap();
+                    assert_eq!(data, actual);
+                    Ok(())
+                });
+                // not registered
+                let mut reader = AnyDecoder::new(&b"STO\0"[..]);
+                assert_eq!(Format::Verbatim, reader.kind().unwrap());
+                let mut reader = AnyDecoder::with_format(&b"STO\0"[..], Format::Other("stored"));
+                assert_eq!(ErrorKind::Unsupported, reader.kind().unwrap_err().kind());
+            }
+
+            #[test]
+            fn archive_error() {
+                let mut tar = vec![0_u8; 1024];
+                tar[..5].copy_from_slice(b"hello");
+                tar[257..265].copy_from_slice(b"ustar\x0000");
+                let mut reader = AnyDecoder::new(NBytesReader::new(&tar[..], 100));
+                reader.fail_on_unknown_format(true);
+                let mut actual = Vec::new();
+                let error = reader.read_to_end(&mut actual).unwrap_err();
+                assert_eq!(ErrorKind::InvalidData, error.kind());
+                assert!(error.to_string().contains("tar archive"));
+                // read verbatim by default
+                let mut reader = AnyDecoder::new(NBytesReader::new(&tar[..], 100));
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                assert_eq!(tar, actual);
+            }
+
+            #[test]
+            fn unknown_format() {
+                let mut reader = AnyDecoder::new(&DATA[..]);
+                assert!(reader.is_unknown_format().unwrap());
+                assert_eq!(Format::Verbatim, reader.kind().unwrap());
+                let mut reader = AnyDecoder::with_format(&DATA[..], Format::Verbatim);
+                assert!(!reader.is_unknown_format().unwrap());
+                let mut reader = AnyDecoder::new(&b""[..]);
+                assert!(!reader.is_unknown_format().unwrap());
+            }
+
+            #[test]
+            fn encrypted_error() {
+                for input in [&b"age-encryption.org/v1\n"[..], b"Salted__12345678"] {
+                    let mut reader = AnyDecoder::new(input);
+                    reader.fail_on_unknown_format(true);
+                    let error = reader.kind().unwrap_err();
+                    assert_eq!(ErrorKind::InvalidData, error.kind());
+                    assert!(error.to_string().contains("encrypted"));
+                }
+            }
+
+            #[cfg(feature = "flate2")]
+            #[test]
+            fn zlib_heuristic() {
+                use flate2::write::GzEncoder;
+                use flate2::write::ZlibEncoder;
+                use flate2::Compression;
+                let zlib = compress(ZlibEncoder::new(Vec::new(), Compression::fast()));
+                let gz = compress(GzEncoder::new(Vec::new(), Compression::fast()));
+                let mut reader = AnyDecoder::builder(&zlib[..]).zlib_heuristic(false).build();
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                assert_eq!(zlib, actual);
+                let mut reader = AnyDecoder::builder(&gz[..]).zlib_heuristic(false).build();
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                assert_eq!(DATA, &actual[..]);
+                let mut reader = AnyDecoder::builder(&zlib[..])
+                    .zlib_heuristic(false)
+                    .format(Format::Zlib)
+                    .build();
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                assert_eq!(DATA, &actual[..]);
+            }
+
+            #[cfg(all(feature = "flate2", feature = "xz", feature = "zstd"))]
+            #[test]
+            fn allowed_formats() {
+                use flate2::write::GzEncoder;
+                use flate2::Compression;
+                let gz = compress(GzEncoder::new(Vec::new(), Compression::fast()));
+                let xz = compress(xz::write::XzEncoder::new(Vec::new(), 1));
+                let allowed = [Format::Zstd, Format::Gz];
+                let mut reader = AnyDecoder::builder(&gz[..])
+                    .allowed_formats(&allowed)
+                    .build();
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                assert_eq!(DATA, &actual[..]);
+                // xz is treated as unknown format
+                let mut reader = AnyDecoder::builder(&xz[..])
+                    .allowed_formats(&allowed)
+                    .build();
+                assert_eq!(Format::Verbatim, reader.kind().unwrap());
+                let mut reader = AnyDecoder::builder(&xz[..])
+                    .allowed_formats(&allowed)
+                    .fail_on_unknown_format(true)
+                    .trial_decode(true)
+                    .build();
+                let mut actual = Vec::new();
+                assert_eq!(
+                    ErrorKind::InvalidData,
+                    reader.read_to_end(&mut actual).unwrap_err().kind()
+                );
+            }
+
+            #[cfg(feature = "brotli")]
+            #[test]
+            fn brotli_detection() {
+                let brotli = compress(crate::write::BrotliEncoder::new(Vec::new(), 1, 22));
+                // brotli is not detected by default
+                let mut reader = AnyDecoder::new(&brotli[..]);
+                assert_eq!(Format::Verbatim, reader.kind().unwrap());
+                let mut reader = AnyDecoder::new(&brotli[..]);
+                reader.detection_order(&[Format::Gz, Format::Brotli]);
+                assert_eq!(Format::Brotli, reader.kind().unwrap());
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                assert_eq!(DATA, &actual[..]);
+                // plain text is not a brotli stream
+                let mut reader = AnyDecoder::new(&DATA[..]);
+                reader.detection_order(&[Format::Brotli]);
+                assert_eq!(Format::Verbatim, reader.kind().unwrap());
+            }
+
+            #[test]
+            fn detection() {
+                use crate::Detection;
+                let mut reader = AnyDecoder::new(&DATA[..]);
+                assert_eq!(None, reader.detection().unwrap());
+                #[cfg(feature = "flate2")]
+                {
+                    use flate2::write::ZlibEncoder;
+                    let zlib = compress(ZlibEncoder::new(Vec::new(), flate2::Compression::fast()));
+                    let mut reader = AnyDecoder::new(&zlib[..]);
+                    assert_eq!(
+                        Some(Detection {
+                            format: Format::Zlib,
+                            magic_len: 2,
+                            heuristic: true
+                        }),
+                        reader.detection().unwrap()
+                    );
+                    let mut reader = AnyDecoder::with_format(&zlib[..], Format::Zlib);
+                    assert_eq!(None, reader.detection().unwrap());
+                }
+                #[cfg(feature = "zstd")]
+                {
+                    use zstd::stream::write::Encoder;
+                    let mut zstd = vec![0x50, 0x2a, 0x4d, 0x18, 3, 0, 0, 0, 1, 2, 3];
+                    zstd.extend(compress(Encoder::new(Vec::new(), 1).unwrap()));
+                    let mut reader = AnyDecoder::new(&zstd[..]);
+                    assert_eq!(
+                        Some(Detection {
+                            format: Format::Zstd,
+                            magic_len: 4,
+                            heuristic: false
+                        }),
+                        reader.detection().unwrap()
+                    );
+                }
+                #[cfg(feature = "brotli")]
+                {
+                    let brotli = compress(crate::write::BrotliEncoder::new(Vec::new(), 1, 22));
+                    let mut reader = AnyDecoder::new(&brotli[..]);
+                    reader.detection_order(&[Format::Brotli]);
+                    assert_eq!(
+                        Some(Detection {
+                            format: Format::Brotli,
+                            magic_len: 0,
+                            heuristic: true
+                        }),
+                        reader.detection().unwrap()
+                    );
+                }
+            }
+
+            #[cfg(feature = "flate2")]
+            #[test]
+            fn deflate_with_format() {
+                use flate2::write::DeflateEncoder;
+                let deflate =
+                    compress(DeflateEncoder::new(Vec::new(), flate2::Compression::fast()));
+                // raw deflate is never detected
+                let mut reader = AnyDecoder::new(&deflate[..]);
+                reader.detection_order(&[Format::Deflate]);
+                assert_eq!(Format::Verbatim, reader.kind().unwrap());
+                let mut reader = AnyDecoder::with_format(&deflate[..], Format::Deflate);
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                assert_eq!(DATA, &actual[..]);
+            }
+
+            #[cfg(feature = "flate2")]
+            #[test]
+            fn builder() {
+                use flate2::write::GzEncoder;
+                let compressed = compress(GzEncoder::new(Vec::new(), flate2::Compression::fast()));
+                let mut reader = AnyDecoder::builder(&compressed[..])
+                    .fail_on_unknown_format(true)
+                    .memory_limit(1024 * 1024)
+                    .per_member(true)
+                    .build();
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                assert_eq!(DATA, &actual[..]);
+                let mut reader = AnyDecoder::builder(&b"hello"[..])
+                    .fail_on_unknown_format(true)
+                    .build();
+                assert!(reader.read_to_end(&mut Vec::new()).is_err());
+                let mut reader = AnyDecoder::builder(&compressed[..])
+                    .format(Format::Verbatim)
+                    .build();
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                assert_eq!(compressed, actual);
+            }
+
+            #[test]
+            fn decode_encode_traits() {
+                use crate::write::AnyEncoder;
+                use crate::write::Compression;
+                use crate::Decode;
+                use crate::Encode;
+
+                fn encode<E: Encode>(mut encoder: E) -> (Format, E::Writer) {
+                    encoder.write_all(&DATA).unwrap();
+                    (encoder.format(), encoder.finish().unwrap())
+                }
+
+                fn decode<D: Decode>(mut decoder: D) -> (Format, Vec<u8>) {
+                    let format = decoder.kind().unwrap();
+                    let mut actual = Vec::new();
+                    decoder.read_to_end(&mut actual).unwrap();
+                    (format, actual)
+                }
+
+                arbtest(|u| {
+                    let format = *u.choose(Format::DETECTION_ORDER)?;
+                    let encoder = AnyEncoder::new(Vec::new(), format, Compression::Fast).unwrap();
+                    let (actual_format, compressed) = encode(encoder);
+                    assert_eq!(format, actual_format);
+                    let (actual_format, actual) = decode(AnyDecoder::new(&compressed[..]));
+                    assert_eq!(format, actual_format);
+                    assert_eq!(DATA, &actual[..]);
+                    Ok(())
+                });
+            }
+
+            #[test]
+            fn from_slice() {
+                use crate::write::AnyEncoder;
+                use crate::write::Compression;
+                arbtest(|u| {
+                    // verbatim data might be detected as some other format
+                    let format = *u.choose(Format::DETECTION_ORDER)?;
+                    let expected: Vec<u8> = u.arbitrary()?;
+                    let mut writer =
+                        AnyEncoder::new(Vec::new(), format, Compression::Fast).unwrap();
+                    writer.write_all(&expected).unwrap();
+                    let compressed = writer.finish().unwrap();
+                    let mut reader = AnyDecoder::from_slice(&compressed[..]);
+                    assert_eq!(format, reader.kind().unwrap());
+                    let mut actual = Vec::new();
+                    reader.read_to_end(&mut actual).unwrap();
+                    assert_eq!(expected, actual);
+                    Ok(())
+                });
+                for input in [&b""[..], b"x", b"\x1f\x8b"] {
+                    let mut reader = AnyDecoder::from_slice(input);
+                    assert_eq!(Format::Verbatim, reader.kind().unwrap());
+                    let mut actual = Vec::new();
+                    reader.read_to_end(&mut actual).unwrap();
+                    assert_eq!(input, actual);
+                }
+            }
+
             #[test]
             fn test_any_decoder() {
                 #[cfg(feature = "flate2")]
@@ -89,7 +1384,7 @@ macro_rules! define_decoder_tests {
             fn new_gz_reader(
                 vec: VecDeque<u8>,
                 u: &mut Unstructured,
-            ) -> AnyDecoder<NBytesReader<VecDeque<u8>>> {
+            ) -> AnyDecoder<'static, NBytesReader<VecDeque<u8>>> {
                 use flate2::write::GzEncoder;
                 use flate2::Compression;
                 let compression = Compression::new(u.int_in_range(0..=9).unwrap());
@@ -105,7 +1400,7 @@ macro_rules! define_decoder_tests {
             fn new_zlib_reader(
                 vec: VecDeque<u8>,
                 u: &mut Unstructured,
-            ) -> AnyDecoder<NBytesReader<VecDeque<u8>>> {
+            ) -> AnyDecoder<'static, NBytesReader<VecDeque<u8>>> {
                 use flate2::write::ZlibEncoder;
                 use flate2::Compression;
                 let compression = Compression::new(u.int_in_range(0..=9).unwrap());
@@ -121,7 +1416,7 @@ macro_rules! define_decoder_tests {
             fn new_bz_reader(
                 vec: VecDeque<u8>,
                 u: &mut Unstructured,
-            ) -> AnyDecoder<NBytesReader<VecDeque<u8>>> {
+            ) -> AnyDecoder<'static, NBytesReader<VecDeque<u8>>> {
                 use bzip2::write::BzEncoder;
                 use bzip2::Compression;
                 let compression = Compression::new(u.int_in_range(1..=9).unwrap());
@@ -137,7 +1432,7 @@ macro_rules! define_decoder_tests {
             fn new_xz_reader(
                 vec: VecDeque<u8>,
                 u: &mut Unstructured,
-            ) -> AnyDecoder<NBytesReader<VecDeque<u8>>> {
+            ) -> AnyDecoder<'static, NBytesReader<VecDeque<u8>>> {
                 use xz::write::XzEncoder;
                 let compression = u.int_in_range(0..=9).unwrap();
                 let mut writer = XzEncoder::new(Vec::new(), compression);
@@ -152,7 +1447,7 @@ macro_rules! define_decoder_tests {
             fn new_zstd_reader(
                 vec: VecDeque<u8>,
                 u: &mut Unstructured,
-            ) -> AnyDecoder<NBytesReader<VecDeque<u8>>> {
+            ) -> AnyDecoder<'static, NBytesReader<VecDeque<u8>>> {
                 use zstd::stream::write::Encoder;
                 let compression = u.int_in_range(0..=22).unwrap();
                 let mut writer = Encoder::new(Vec::new(), compression).unwrap();
@@ -0,0 +1,365 @@
+//! Decoders and encoders for the formats that are known at compile time.
+//!
+//! [Decoder] and [Encoder] are thin wrappers around [AnyDecoder] and
+//! [AnyEncoder] that fix the format via the type parameter.
+//! The decoder doesn't detect the format, and the encoder can't be created for a different one.
+//! All the options of the wrapped types are accessible via [Deref] and [DerefMut].
+//!
+//! ```rust
+//! # #[cfg(all(feature = "flate2", feature = "zstd"))] {
+//! use deko::typed::{Decoder, Encoder, Zstd};
+//! use deko::write::Compression;
+//! use std::io::Read;
+//! use std::io::Write;
+//!
+//! let mut writer = Encoder::<Zstd, _>::new(Vec::new(), Compression::Default).unwrap();
+//! writer.write_all(b"Hello world").unwrap();
+//! let compressed_data = writer.finish().unwrap();
+//! let mut reader = Decoder::<Zstd, _>::new(&compressed_data[..]);
+//! let mut string = String::new();
+//! reader.read_to_string(&mut string).unwrap();
+//! assert_eq!("Hello world", string);
+//! # }
+//! ```
+
+use std::fmt::Arguments;
+use std::io::BufRead;
+use std::io::Error;
+use std::io::IoSlice;
+use std::io::IoSliceMut;
+use std::io::Read;
+use std::io::Write;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::ops::DerefMut;
+
+use crate::bufread::AnyDecoder;
+use crate::write::AnyEncoder;
+use crate::write::Compression;
+use crate::write::Config;
+use crate::write::EncoderOptions;
+use crate::Format;
+
+/// A format that is known at compile time.
+///
+/// This trait is sealed, i.e. it is implemented only for the types in this module.
+pub trait StaticFormat: private::Sealed {
+    /// The corresponding run-time format.
+    const FORMAT: Format;
+}
+
+macro_rules! define_format {
+    ($(#[$attr: meta])* $name: ident, $feature: literal) => {
+        $(#[$attr])*
+        #[cfg(feature = $feature)]
+        #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+        pub struct $name;
+
+        #[cfg(feature = $feature)]
+        impl StaticFormat for $name {
+            const FORMAT: Format = Format::$name;
+        }
+
+        #[cfg(feature = $feature)]
+        impl private::Sealed for $name {}
+    };
+}
+
+define_format!(
+    /// Gzip format.
+    Gz,
+    "flate2"
+);
+define_format!(
+    /// Bzip2 format.
+    Bz,
+    "bzip2"
+);
+define_format!(
+    /// Zlib format.
+    Zlib,
+    "flate2"
+);
+define_format!(
+    /// XZ format.
+    Xz,
+    "xz"
+);
+define_format!(
+    /// Zstd format.
+    Zstd,
+    "zstd"
+);
+define_format!(
+    /// ZIP format.
+    Zip,
+    "zip"
+);
+define_format!(
+    /// SZDD format.
+    Szdd,
+    "mscompress"
+);
+define_format!(
+    /// KWAJ format.
+    Kwaj,
+    "mscompress"
+);
+define_format!(
+    /// LZ4 frame format.
+    Lz4,
+    "lz4"
+);
+define_format!(
+    /// Brotli format.
+    Brotli,
+    "brotli"
+);
+define_format!(
+    /// Lzop format.
+    Lzop,
+    "lzop"
+);
+define_format!(
+    /// Raw DEFLATE format.
+    Deflate,
+    "flate2"
+);
+define_format!(
+    /// LZFSE format.
+    Lzfse,
+    "lzfse"
+);
+
+/// A decoder that decompresses the supplied input stream using format `F`.
+///
+/// The format is not detected; the stream is decoded as if it was detected as `F`.
+pub struct Decoder<'a, F: StaticFormat, R: BufRead> {
+    inner: AnyDecoder<'a, R>,
+    format: PhantomData<F>,
+}
+
+impl<'a, F: StaticFormat, R: BufRead> Decoder<'a, F, R> {
+    /// Create new decoder from the supplied `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            inner: AnyDecoder::with_format(reader, F::FORMAT),
+            format: PhantomData,
+        }
+    }
+
+    /// Get the decoding format.
+    pub const fn format(&self) -> Format {
+        F::FORMAT
+    }
+
+    /// Return the wrapped decoder.
+    pub fn into_any(self) -> AnyDecoder<'a, R> {
+        self.inner
+    }
+
+    /// Return the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+}
+
+impl<'a, F: StaticFormat, R: BufRead> Deref for Decoder<'a, F, R> {
+    type Target = AnyDecoder<'a, R>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<F: StaticFormat, R: BufRead> DerefMut for Decoder<'_, F, R> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl<F: StaticFormat, R: BufRead> Read for Decoder<'_, F, R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.inner.read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> Result<usize, Error> {
+        self.inner.read_vectored(bufs)
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize, Error> {
+        self.inner.read_to_end(buf)
+    }
+
+    fn read_to_string(&mut self, buf: &mut String) -> Result<usize, Error> {
+        self.inner.read_to_string(buf)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        self.inner.read_exact(buf)
+    }
+}
+
+impl<F: StaticFormat, R: BufRead> crate::Decode for Decoder<'_, F, R> {
+    type Reader = R;
+
+    fn kind(&mut self) -> Result<Format, Error> {
+        Ok(F::FORMAT)
+    }
+
+    fn get_ref(&self) -> &R {
+        self.inner.get_ref()
+    }
+
+    fn get_mut(&mut self) -> &mut R {
+        self.inner.get_mut()
+    }
+
+    fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+}
+
+/// An encoder that compresses the data using format `F`.
+pub struct Encoder<F: StaticFormat, W: Write> {
+    inner: AnyEncoder<W>,
+    format: PhantomData<F>,
+}
+
+impl<F: StaticFormat, W: Write> Encoder<F, W> {
+    /// Create new encoder with the supplied `compression` ratio.
+    pub fn new(writer: W, compression: Compression) -> Result<Self, Error> {
+        Ok(Self::from_any(AnyEncoder::new(
+            writer,
+            F::FORMAT,
+            compression,
+        )?))
+    }
+
+    /// Create new encoder using the settings for format `F` from the `config`.
+    pub fn with_config(writer: W, config: &Config) -> Result<Self, Error> {
+        Self::with_options(writer, &config.options(F::FORMAT))
+    }
+
+    /// Create new encoder using the supplied `options`.
+    ///
+    /// The format from the `options` is ignored.
+    pub fn with_options(writer: W, options: &EncoderOptions) -> Result<Self, Error> {
+        let options = EncoderOptions {
+            format: F::FORMAT,
+            ..options.clone()
+        };
+        Ok(Self::from_any(AnyEncoder::with_options(writer, &options)?))
+    }
+
+    fn from_any(inner: AnyEncoder<W>) -> Self {
+        Self {
+            inner,
+            format: PhantomData,
+        }
+    }
+
+    /// Get the encoding format.
+    pub const fn format(&self) -> Format {
+        F::FORMAT
+    }
+
+    /// Return the wrapped encoder.
+    pub fn into_any(self) -> AnyEncoder<W> {
+        self.inner
+    }
+
+    /// Finish encoding and return the underlying writer.
+    ///
+    /// This method is **not** automatically called on drop.
+    pub fn finish(self) -> Result<W, Error> {
+        self.inner.finish()
+    }
+}
+
+impl<F: StaticFormat, W: Write> Deref for Encoder<F, W> {
+    type Target = AnyEncoder<W>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<F: StaticFormat, W: Write> DerefMut for Encoder<F, W> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl<F: StaticFormat, W: Write> Write for Encoder<F, W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.inner.flush()
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<usize, Error> {
+        self.inner.write_vectored(bufs)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Error> {
+        self.inner.write_all(buf)
+    }
+
+    fn write_fmt(&mut self, fmt: Arguments<'_>) -> Result<(), Error> {
+        self.inner.write_fmt(fmt)
+    }
+}
+
+impl<F: StaticFormat, W: Write> crate::Encode for Encoder<F, W> {
+    type Writer = W;
+
+    fn format(&self) -> Format {
+        F::FORMAT
+    }
+
+    fn get_ref(&self) -> &W {
+        self.inner.get_ref()
+    }
+
+    fn get_mut(&mut self) -> &mut W {
+        self.inner.get_mut()
+    }
+
+    fn finish(self) -> Result<W, Error> {
+        self.inner.finish()
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+#[cfg(test)]
+mod tests {
+    use arbtest::arbtest;
+
+    use super::*;
+
+    #[cfg(all(feature = "flate2", feature = "zstd"))]
+    #[test]
+    fn typed() {
+        arbtest(|u| {
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut writer = Encoder::<Gz, _>::new(Vec::new(), Compression::Fast).unwrap();
+            assert_eq!(Format::Gz, writer.format());
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            let mut reader = Decoder::<Gz, _>::new(&compressed[..]);
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, actual);
+            // no detection, i.e. zstd decoder fails on gzip data
+            let mut reader = Decoder::<Zstd, _>::new(&compressed[..]);
+            assert!(reader.read_to_end(&mut Vec::new()).is_err());
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,224 @@
+use std::io::Error;
+use std::io::Write;
+use std::time::Duration;
+use std::time::Instant;
+
+use zstd::stream::raw::CParameter;
+
+use crate::write::ZstdEncoder;
+
+/// Zstd encoder that adjusts compression level depending on how fast the underlying writer
+/// accepts the data (similar to `zstd --adapt`).
+///
+/// The input is split into frames of the fixed size.
+/// At the end of each frame the encoder compares the time spent waiting for the underlying
+/// writer with the total time.
+/// When the writer is the bottleneck (e.g. slow network), the level is increased
+/// to improve the ratio; when the compression is the bottleneck,
+/// the level is decreased to improve the throughput.
+pub struct AdaptiveZstdEncoder<W: Write> {
+    encoder: ZstdEncoder<TimedWriter<W>>,
+    level: i32,
+    min_level: i32,
+    max_level: i32,
+    frame_size: u64,
+    frame_written: u64,
+    frame_start: Instant,
+}
+
+impl<W: Write> AdaptiveZstdEncoder<W> {
+    /// Create new encoder with the specified initial compression `level`.
+    pub fn new(writer: W, level: i32) -> Result<Self, Error> {
+        let writer = TimedWriter {
+            writer,
+            elapsed: Duration::ZERO,
+        };
+        Ok(Self {
+            encoder: ZstdEncoder::new(writer, level)?,
+            level,
+            min_level: DEFAULT_MIN_LEVEL.min(level),
+            max_level: DEFAULT_MAX_LEVEL.max(level),
+            frame_size: DEFAULT_FRAME_SIZE,
+            frame_written: 0,
+            frame_start: Instant::now(),
+        })
+    }
+
+    /// Set the range of compression levels.
+    ///
+    /// The current level is clamped to the range.
+    /// By default the range is 1–19.
+    pub fn set_level_range(&mut self, min_level: i32, max_level: i32) -> Result<(), Error> {
+        self.min_level = min_level.min(max_level);
+        self.max_level = max_level.max(min_level);
+        self.set_level(self.level.clamp(self.min_level, self.max_level))
+    }
+
+    /// Set uncompressed size of each frame.
+    ///
+    /// The level is adjusted only at frame boundaries.
+    /// Smaller frames make the encoder more responsive but decrease compression ratio.
+    /// By default the size is 1 MiB.
+    pub fn set_frame_size(&mut self, bytes: u64) {
+        self.frame_size = bytes.max(1);
+    }
+
+    /// Get current compression level.
+    pub fn level(&self) -> i32 {
+        self.level
+    }
+
+    /// Get immutable reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.encoder.get_ref().writer
+    }
+
+    /// Get mutable reference to the underlying writer.
+    ///
+    /// Writing to the underlying writer might corrupt the output.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.encoder.get_mut().writer
+    }
+
+    /// Finish encoding and return the underlying writer.
+    ///
+    /// This method is **not** automatically called on drop.
+    pub fn finish(self) -> Result<W, Error> {
+        Ok(self.encoder.finish()?.writer)
+    }
+
+    fn set_level(&mut self, level: i32) -> Result<(), Error> {
+        if level != self.level {
+            self.encoder
+                .set_parameter(CParameter::CompressionLevel(level))?;
+            self.level = level;
+        }
+        Ok(())
+    }
+
+    /// Finish the current frame and adjust the level for the next one.
+    fn adapt(&mut self) -> Result<(), Error> {
+        self.encoder.start_new_frame()?;
+        let total = self.frame_start.elapsed();
+        let waiting = std::mem::take(&mut self.encoder.get_mut().elapsed);
+        let level = if waiting > total.mul_f64(SLOW_WRITER) {
+            (self.level + 1).min(self.max_level)
+        } else if waiting < total.mul_f64(FAST_WRITER) {
+            (self.level - 1).max(self.min_level)
+        } else {
+            self.level
+        };
+        #[cfg(feature = "tracing")]
+        tracing::trace!(?waiting, ?total, level, "adapt zstd level");
+        self.set_level(level)?;
+        self.frame_written = 0;
+        self.frame_start = Instant::now();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for AdaptiveZstdEncoder<W> {
+    fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        let remaining = (self.frame_size - self.frame_written).min(data.len() as u64) as usize;
+        let n = self.encoder.write(&data[..remaining])?;
+        self.frame_written += n as u64;
+        if self.frame_written == self.frame_size {
+            self.adapt()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.encoder.flush()
+    }
+}
+
+/// Measures the time spent in the underlying writer.
+struct TimedWriter<W> {
+    writer: W,
+    elapsed: Duration,
+}
+
+impl<W: Write> Write for TimedWriter<W> {
+    fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        let start = Instant::now();
+        let result = self.writer.write(data);
+        self.elapsed += start.elapsed();
+        result
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        let start = Instant::now();
+        let result = self.writer.flush();
+        self.elapsed += start.elapsed();
+        result
+    }
+}
+
+const DEFAULT_MIN_LEVEL: i32 = 1;
+const DEFAULT_MAX_LEVEL: i32 = 19;
+const DEFAULT_FRAME_SIZE: u64 = 1024 * 1024;
+// The writer is the bottleneck when the encoder waits for it more than this fraction of time.
+const SLOW_WRITER: f64 = 0.5;
+// The compression is the bottleneck when the encoder waits for the writer less than this
+// fraction of time.
+const FAST_WRITER: f64 = 0.1;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::bufread::AnyDecoder;
+
+    #[test]
+    fn round_trip() {
+        arbtest(|u| {
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut writer = AdaptiveZstdEncoder::new(Vec::new(), u.int_in_range(1..=19)?).unwrap();
+            writer.set_frame_size(u.int_in_range(1..=100)?);
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            let mut reader = AnyDecoder::new(&compressed[..]);
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, actual);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn slow_writer() {
+        let mut writer = AdaptiveZstdEncoder::new(SlowWriter(Vec::new()), 3).unwrap();
+        writer.set_level_range(1, 5).unwrap();
+        writer.set_frame_size(1024);
+        writer.write_all(&[0_u8; 10 * 1024]).unwrap();
+        assert_eq!(5, writer.level());
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn fast_writer() {
+        let mut writer = AdaptiveZstdEncoder::new(std::io::sink(), 5).unwrap();
+        writer.set_level_range(1, 5).unwrap();
+        writer.set_frame_size(1024);
+        writer.write_all(&[0_u8; 10 * 1024]).unwrap();
+        assert_eq!(1, writer.level());
+        writer.finish().unwrap();
+    }
+
+    struct SlowWriter(Vec<u8>);
+
+    impl Write for SlowWriter {
+        fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+            std::thread::sleep(Duration::from_millis(5));
+            self.0.write(data)
+        }
+
+        fn flush(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::BufReader;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::path::Path;
+
+use crate::bufread::AnyDecoder;
+use crate::write::AnyEncoder;
+use crate::write::Compression;
+use crate::Format;
+
+impl AnyEncoder<File> {
+    /// Open existing compressed file and append a new member to it.
+    ///
+    /// The format is detected from the contents of the file
+    /// or from the extension (`.gz`, `.zst`, `.bz2`) if the file is empty.
+    /// Before appending, the file is checked so that the combined file remains valid:
+    /// - gzip: the file is decompressed (gzip members can't be located otherwise);
+    /// - zstd: the frame and block headers are walked without decompressing the data;
+    /// - bzip2: the file should end with the end-of-stream marker.
+    ///
+    /// The data is then written as a new gzip member, zstd frame or bzip2 stream
+    /// that the decoders handle as a continuation of the file (see [AnyDecoder]).
+    /// Other formats result in an error of kind [Unsupported](ErrorKind::Unsupported).
+    pub fn append<P: AsRef<Path>>(path: P, compression: Compression) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let mut file = OpenOptions::new().read(true).append(true).open(path)?;
+        let format = match file.seek(SeekFrom::End(0))? {
+            0 => format_from_extension(path)?,
+            _ => {
+                file.rewind()?;
+                let format = AnyDecoder::new(BufReader::new(&mut file)).kind()?;
+                file.rewind()?;
+                check_tail(&mut file, format)?;
+                format
+            }
+        };
+        Self::new(file, format, compression)
+    }
+}
+
+#[allow(unused_variables)]
+fn check_tail(file: &mut File, format: Format) -> Result<(), Error> {
+    match format {
+        #[cfg(feature = "flate2")]
+        Format::Gz => {
+            let mut decoder = flate2::bufread::MultiGzDecoder::new(BufReader::new(file));
+            std::io::copy(&mut decoder, &mut std::io::sink())?;
+            Ok(())
+        }
+        #[cfg(feature = "zstd")]
+        Format::Zstd => crate::zstd_check_frames(file),
+        #[cfg(feature = "bzip2")]
+        Format::Bz => crate::bz_check_tail(file),
+        _ => Err(unsupported(format)),
+    }
+}
+
+fn format_from_extension(path: &Path) -> Result<Format, Error> {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    match extension {
+        #[cfg(feature = "flate2")]
+        Some("gz") => Ok(Format::Gz),
+        #[cfg(feature = "zstd")]
+        Some("zst") => Ok(Format::Zstd),
+        #[cfg(feature = "bzip2")]
+        Some("bz2") => Ok(Format::Bz),
+        _ => Err(unsupported(Format::Verbatim)),
+    }
+}
+
+fn unsupported(format: Format) -> Error {
+    Error::new(
+        ErrorKind::Unsupported,
+        format!("can't append to {format:?} file"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use arbtest::arbtest;
+
+    use super::*;
+
+    #[test]
+    fn append() {
+        let path = temp_path("append");
+        arbtest(|u| {
+            let format = *u.choose(&[
+                #[cfg(feature = "flate2")]
+                Format::Gz,
+                #[cfg(feature = "zstd")]
+                Format::Zstd,
+                #[cfg(feature = "bzip2")]
+                Format::Bz,
+            ])?;
+            let chunks: Vec<Vec<u8>> = u.arbitrary()?;
+            let mut writer =
+                AnyEncoder::new(File::create(&path).unwrap(), format, Compression::Fast).unwrap();
+            writer.write_all(b"first").unwrap();
+            writer.finish().unwrap();
+            for chunk in chunks.iter() {
+                let mut writer = AnyEncoder::append(&path, Compression::Fast).unwrap();
+                assert_eq!(format, writer.format());
+                writer.write_all(chunk).unwrap();
+                writer.finish().unwrap();
+            }
+            let mut decoder = AnyDecoder::new(BufReader::new(File::open(&path).unwrap()));
+            let mut actual = Vec::new();
+            decoder.read_to_end(&mut actual).unwrap();
+            let mut expected = b"first".to_vec();
+            expected.extend(chunks.concat());
+            assert_eq!(expected, actual);
+            Ok(())
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn append_to_truncated_file() {
+        let path = temp_path("truncated");
+        for format in [
+            #[cfg(feature = "flate2")]
+            Format::Gz,
+            #[cfg(feature = "zstd")]
+            Format::Zstd,
+            #[cfg(feature = "bzip2")]
+            Format::Bz,
+        ] {
+            let mut writer = AnyEncoder::new(Vec::new(), format, Compression::Fast).unwrap();
+            writer.write_all(b"hello world").unwrap();
+            let mut compressed = writer.finish().unwrap();
+            compressed.pop();
+            std::fs::write(&path, &compressed).unwrap();
+            assert!(AnyEncoder::append(&path, Compression::Fast).is_err());
+        }
+        std::fs::write(&path, b"hello world").unwrap();
+        assert_eq!(
+            ErrorKind::Unsupported,
+            AnyEncoder::append(&path, Compression::Fast)
+                .err()
+                .unwrap()
+                .kind()
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("deko-{}-{}", name, std::process::id()))
+    }
+}
@@ -0,0 +1,189 @@
+use std::io::Error;
+use std::io::Write;
+
+use flate2::Compress;
+use flate2::Compression;
+use flate2::Crc;
+use flate2::FlushCompress;
+use flate2::Status;
+
+/// BGZF (`bgzip`) encoder.
+///
+/// The input is split into blocks of at most 65280 bytes, and each block is written as
+/// a separate gzip member with its compressed size stored in the `BC` extra subfield
+/// ([specification](https://samtools.github.io/hts-specs/SAMv1.pdf)).
+/// The output ends with the empty end-of-file marker block.
+/// Such files can be indexed by `samtools` and `tabix` and accessed randomly
+/// via [BgzfReader](crate::BgzfReader); other gzip decoders decode them as multi-member files.
+pub struct BgzfEncoder<W: Write> {
+    writer: W,
+    compress: Compress,
+    block_size: usize,
+    // uncompressed data of the current block
+    block: Vec<u8>,
+    // compressed data of the current block
+    buf: Vec<u8>,
+    // the offset of the current block in the output
+    block_offset: u64,
+}
+
+impl<W: Write> BgzfEncoder<W> {
+    /// Create new encoder with the default block size of 65280 bytes.
+    pub fn new(writer: W, level: Compression) -> Self {
+        Self::with_block_size(writer, level, MAX_DATA_LEN as u64)
+    }
+
+    /// Create new encoder with the specified uncompressed `block_size`.
+    ///
+    /// The block size is clamped to 1–65280 B range.
+    pub fn with_block_size(writer: W, level: Compression, block_size: u64) -> Self {
+        let block_size = block_size.clamp(1, MAX_DATA_LEN as u64) as usize;
+        Self {
+            writer,
+            compress: Compress::new(level, false),
+            block_size,
+            block: Vec::with_capacity(block_size),
+            buf: Vec::with_capacity(MAX_BLOCK_SIZE),
+            block_offset: 0,
+        }
+    }
+
+    /// The virtual offset of the next byte that is written.
+    ///
+    /// See [BgzfReader::seek_virtual](crate::BgzfReader::seek_virtual).
+    pub fn virtual_offset(&self) -> u64 {
+        (self.block_offset << 16) | self.block.len() as u64
+    }
+
+    /// Write the current block even if it isn't full.
+    pub fn finish_block(&mut self) -> Result<(), Error> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+        self.compress.reset();
+        self.buf.clear();
+        let status = self
+            .compress
+            .compress_vec(&self.block, &mut self.buf, FlushCompress::Finish)
+            .map_err(Error::other)?;
+        if status != Status::StreamEnd || self.buf.len() > MAX_BLOCK_SIZE - BLOCK_OVERHEAD {
+            // incompressible data, store it as is
+            self.buf.clear();
+            Compress::new(Compression::none(), false)
+                .compress_vec(&self.block, &mut self.buf, FlushCompress::Finish)
+                .map_err(Error::other)?;
+        }
+        let block_size = BLOCK_OVERHEAD + self.buf.len();
+        let mut crc = Crc::new();
+        crc.update(&self.block);
+        let mut header = BGZF_HEADER;
+        header[16..18].copy_from_slice(&((block_size - 1) as u16).to_le_bytes());
+        self.writer.write_all(&header)?;
+        self.writer.write_all(&self.buf)?;
+        self.writer.write_all(&crc.sum().to_le_bytes())?;
+        self.writer.write_all(&crc.amount().to_le_bytes())?;
+        self.block_offset += block_size as u64;
+        self.block.clear();
+        Ok(())
+    }
+
+    /// Get immutable reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Get mutable reference to the underlying writer.
+    ///
+    /// Writing to the underlying writer might corrupt the output.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Finish encoding, write the end-of-file marker and return the underlying writer.
+    ///
+    /// This method is **not** automatically called on drop.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.finish_block()?;
+        self.writer.write_all(&BGZF_EOF)?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for BgzfEncoder<W> {
+    fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        let n = data.len().min(self.block_size - self.block.len());
+        self.block.extend_from_slice(&data[..n]);
+        if self.block.len() == self.block_size {
+            self.finish_block()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.finish_block()?;
+        self.writer.flush()
+    }
+}
+
+/// Gzip header with `BC` extra subfield; the block size is set for each block.
+const BGZF_HEADER: [u8; 18] = [
+    0x1f, 0x8b, 0x08, 0x04, 0, 0, 0, 0, 0, 0xff, 6, 0, b'B', b'C', 2, 0, 0, 0,
+];
+/// The empty block that marks the end of the file.
+const BGZF_EOF: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0, 0, 0, 0, 0, 0xff, 6, 0, b'B', b'C', 2, 0, 0x1b, 0, 0x03, 0, 0, 0, 0,
+    0, 0, 0, 0, 0,
+];
+/// The header, CRC32 and ISIZE.
+const BLOCK_OVERHEAD: usize = BGZF_HEADER.len() + 8;
+const MAX_BLOCK_SIZE: usize = 64 * 1024;
+/// The same limit as in `bgzip`.
+const MAX_DATA_LEN: usize = 0xff00;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::io::Read;
+
+    use arbtest::arbtest;
+    use flate2::read::MultiGzDecoder;
+
+    use super::*;
+    use crate::BgzfReader;
+    use crate::GzDecoderExt;
+
+    #[test]
+    fn bgzf() {
+        arbtest(|u| {
+            let chunks: Vec<Vec<u8>> = u.arbitrary()?;
+            let level = Compression::new(u.int_in_range(0..=9)?);
+            let block_size = u.int_in_range(1..=MAX_DATA_LEN as u64)?;
+            let mut writer = BgzfEncoder::with_block_size(Vec::new(), level, block_size);
+            let mut offsets = Vec::new();
+            for chunk in chunks.iter() {
+                offsets.push(writer.virtual_offset());
+                writer.write_all(chunk).unwrap();
+            }
+            let compressed = writer.finish().unwrap();
+            assert!(compressed.ends_with(&BGZF_EOF));
+            let expected = chunks.concat();
+            let mut actual = Vec::new();
+            MultiGzDecoder::new(&compressed[..])
+                .read_to_end(&mut actual)
+                .unwrap();
+            assert_eq!(expected, actual);
+            let mut reader = crate::AnyDecoder::new(&compressed[..]);
+            assert!(reader.is_bgzf().unwrap());
+            let mut reader = BgzfReader::new(Cursor::new(&compressed));
+            let mut start = 0;
+            for (chunk, offset) in chunks.iter().zip(offsets) {
+                reader.seek_virtual(offset).unwrap();
+                let mut actual = vec![0_u8; chunk.len()];
+                reader.read_exact(&mut actual).unwrap();
+                assert_eq!(&expected[start..start + chunk.len()], &actual[..]);
+                start += chunk.len();
+            }
+            Ok(())
+        });
+    }
+}
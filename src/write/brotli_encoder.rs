@@ -0,0 +1,118 @@
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Write;
+
+use brotli::enc::encode::BrotliEncoderOperation;
+use brotli::enc::encode::BrotliEncoderParameter;
+use brotli::enc::encode::BrotliEncoderStateStruct;
+use brotli::enc::interface::PredictionModeContextMap;
+use brotli::enc::interface::StaticCommand;
+use brotli::enc::InputPair;
+use brotli::enc::InputReferenceMut;
+use brotli::enc::StandardAlloc;
+
+/// Brotli encoder.
+///
+/// Unlike `brotli::CompressorWriter`, the encoder reports the errors
+/// that occur while the stream is being finished.
+pub struct BrotliEncoder<W: Write> {
+    writer: W,
+    state: Box<BrotliEncoderStateStruct<StandardAlloc>>,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> BrotliEncoder<W> {
+    /// Create new encoder with the specified `quality` (0–11)
+    /// and the window size `lgwin` (base-2 logarithm, 10–24).
+    pub fn new(writer: W, quality: u32, lgwin: u32) -> Self {
+        let mut state = Box::new(BrotliEncoderStateStruct::new(StandardAlloc::default()));
+        state.set_parameter(
+            BrotliEncoderParameter::BROTLI_PARAM_QUALITY,
+            quality.min(11),
+        );
+        state.set_parameter(
+            BrotliEncoderParameter::BROTLI_PARAM_LGWIN,
+            lgwin.clamp(10, 24),
+        );
+        Self {
+            writer,
+            state,
+            buf: vec![0_u8; BUFFER_SIZE],
+        }
+    }
+
+    /// Get immutable reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Get mutable reference to the underlying writer.
+    ///
+    /// Writing to the underlying writer might corrupt the output.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Finish encoding and return the underlying writer.
+    ///
+    /// This method is **not** automatically called on drop.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.compress(BrotliEncoderOperation::BROTLI_OPERATION_FINISH, &[])?;
+        Ok(self.writer)
+    }
+
+    /// Compress `input` and write the output to the underlying writer.
+    ///
+    /// Returns when all the input is consumed and, unless `op` is
+    /// [BROTLI_OPERATION_PROCESS](BrotliEncoderOperation::BROTLI_OPERATION_PROCESS),
+    /// when all the output is written.
+    fn compress(&mut self, op: BrotliEncoderOperation, input: &[u8]) -> Result<(), Error> {
+        let mut available_in = input.len();
+        let mut input_offset = 0;
+        loop {
+            let mut available_out = self.buf.len();
+            let mut output_offset = 0;
+            let ok = self.state.compress_stream(
+                op,
+                &mut available_in,
+                input,
+                &mut input_offset,
+                &mut available_out,
+                &mut self.buf,
+                &mut output_offset,
+                &mut None,
+                &mut |_: &mut PredictionModeContextMap<InputReferenceMut>,
+                      _: &mut [StaticCommand],
+                      _: InputPair,
+                      _: &mut StandardAlloc| {},
+            );
+            if !ok {
+                return Err(Error::new(ErrorKind::InvalidInput, "brotli encoder failed"));
+            }
+            self.writer.write_all(&self.buf[..output_offset])?;
+            let done = match op {
+                BrotliEncoderOperation::BROTLI_OPERATION_FINISH => self.state.is_finished(),
+                BrotliEncoderOperation::BROTLI_OPERATION_PROCESS => available_in == 0,
+                _ => available_in == 0 && !self.state.has_more_output(),
+            };
+            if done {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl<W: Write> Write for BrotliEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.compress(BrotliEncoderOperation::BROTLI_OPERATION_PROCESS, buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.compress(BrotliEncoderOperation::BROTLI_OPERATION_FLUSH, &[])?;
+        self.writer.flush()
+    }
+}
+
+/// The size of the output buffer.
+const BUFFER_SIZE: usize = 4096;
@@ -0,0 +1,245 @@
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Write;
+use std::path::Path;
+
+use crate::write::AnyEncoder;
+use crate::write::EncoderOptions;
+
+/// The state that is needed to resume compression after a crash.
+///
+/// The output up to [output_offset](Checkpoint::output_offset) is a complete compressed file
+/// that contains the first [input_offset](Checkpoint::input_offset) bytes of the input.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checkpoint {
+    /// The number of uncompressed bytes consumed by the encoder.
+    pub input_offset: u64,
+    /// The number of compressed bytes written to the underlying writer.
+    pub output_offset: u64,
+}
+
+impl Checkpoint {
+    /// Open the output file for resuming the compression.
+    ///
+    /// Everything that was written after the checkpoint is discarded,
+    /// and the file is opened in append mode.
+    pub fn open_output<P: AsRef<Path>>(&self, path: P) -> Result<File, Error> {
+        let file = OpenOptions::new().append(true).open(path)?;
+        if file.metadata()?.len() < self.output_offset {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "the output is shorter than the checkpoint",
+            ));
+        }
+        file.set_len(self.output_offset)?;
+        Ok(file)
+    }
+}
+
+/// An encoder that can emit [checkpoints](Checkpoint) to resume the compression after a crash.
+///
+/// Each checkpoint finishes the current gzip member, xz stream or zstd frame,
+/// and the following data is written as a new one.
+/// Only the formats that support concatenated members can be resumed;
+/// bzip2 and zlib return an error of kind [Unsupported](ErrorKind::Unsupported).
+///
+/// To resume the compression, open the output via [Checkpoint::open_output],
+/// seek the input to [input_offset](Checkpoint::input_offset),
+/// and create new encoder via [CheckpointEncoder::resume].
+pub struct CheckpointEncoder<W: Write> {
+    encoder: Option<AnyEncoder<CountingWriter<W>>>,
+    options: EncoderOptions,
+    input_offset: u64,
+}
+
+impl<W: Write> CheckpointEncoder<W> {
+    /// Create new encoder using the supplied `options`.
+    pub fn new(writer: W, options: &EncoderOptions) -> Result<Self, Error> {
+        Self::resume(writer, options, Default::default())
+    }
+
+    /// Continue the compression from the `checkpoint`.
+    ///
+    /// The `writer` should be positioned at [output_offset](Checkpoint::output_offset).
+    pub fn resume(
+        writer: W,
+        options: &EncoderOptions,
+        checkpoint: Checkpoint,
+    ) -> Result<Self, Error> {
+        match options.format {
+            #[cfg(feature = "bzip2")]
+            crate::Format::Bz => return Err(unsupported()),
+            #[cfg(feature = "flate2")]
+            crate::Format::Zlib | crate::Format::Deflate => return Err(unsupported()),
+            #[cfg(feature = "zip")]
+            crate::Format::Zip => return Err(unsupported()),
+            #[cfg(feature = "mscompress")]
+            crate::Format::Szdd | crate::Format::Kwaj => return Err(unsupported()),
+            #[cfg(feature = "brotli")]
+            crate::Format::Brotli => return Err(unsupported()),
+            #[cfg(feature = "lzop")]
+            crate::Format::Lzop => return Err(unsupported()),
+            #[cfg(feature = "lzfse")]
+            crate::Format::Lzfse => return Err(unsupported()),
+            _ => {}
+        }
+        let writer = CountingWriter {
+            writer,
+            count: checkpoint.output_offset,
+        };
+        Ok(Self {
+            encoder: Some(AnyEncoder::with_options(writer, options)?),
+            options: options.clone(),
+            input_offset: checkpoint.input_offset,
+        })
+    }
+
+    /// Finish the current member, flush the underlying writer and return the checkpoint.
+    ///
+    /// The following data is written as a new member.
+    pub fn checkpoint(&mut self) -> Result<Checkpoint, Error> {
+        let mut writer = self.encoder.take().ok_or_else(finished)?.finish()?;
+        writer.flush()?;
+        let checkpoint = Checkpoint {
+            input_offset: self.input_offset,
+            output_offset: writer.count,
+        };
+        self.encoder = Some(AnyEncoder::with_options(writer, &self.options)?);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(?checkpoint, "checkpoint");
+        Ok(checkpoint)
+    }
+
+    /// Get immutable reference to the underlying writer.
+    pub fn get_ref(&self) -> Option<&W> {
+        self.encoder
+            .as_ref()
+            .map(|encoder| &encoder.get_ref().writer)
+    }
+
+    /// Get mutable reference to the underlying writer.
+    ///
+    /// Writing to the underlying writer might corrupt the output.
+    pub fn get_mut(&mut self) -> Option<&mut W> {
+        self.encoder
+            .as_mut()
+            .map(|encoder| &mut encoder.get_mut().writer)
+    }
+
+    /// Finish encoding and return the underlying writer.
+    ///
+    /// This method is **not** automatically called on drop.
+    pub fn finish(mut self) -> Result<W, Error> {
+        let encoder = self.encoder.take().ok_or_else(finished)?;
+        Ok(encoder.finish()?.writer)
+    }
+
+    fn encoder(&mut self) -> Result<&mut AnyEncoder<CountingWriter<W>>, Error> {
+        self.encoder.as_mut().ok_or_else(finished)
+    }
+}
+
+impl<W: Write> Write for CheckpointEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        let n = self.encoder()?.write(buf)?;
+        self.input_offset += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.encoder()?.flush()
+    }
+}
+
+/// Counts the bytes written to the underlying writer.
+struct CountingWriter<W> {
+    writer: W,
+    count: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        let n = self.writer.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()
+    }
+}
+
+#[allow(unused)]
+fn unsupported() -> Error {
+    Error::new(
+        ErrorKind::Unsupported,
+        "the format doesn't support concatenated members",
+    )
+}
+
+fn finished() -> Error {
+    Error::other("the encoder failed to start new member")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::bufread::AnyDecoder;
+    use crate::Format;
+
+    #[test]
+    fn resume() {
+        arbtest(|u| {
+            let format: Format = u.arbitrary()?;
+            let options = EncoderOptions::new(format);
+            let chunks: Vec<Vec<u8>> = u.arbitrary()?;
+            let crash: usize = u.choose_index(chunks.len() + 1)?;
+            let mut writer = match CheckpointEncoder::new(Vec::new(), &options) {
+                Ok(writer) => writer,
+                Err(e) => {
+                    assert_eq!(ErrorKind::Unsupported, e.kind());
+                    return Ok(());
+                }
+            };
+            let mut checkpoint = Checkpoint::default();
+            for chunk in chunks[..crash].iter() {
+                writer.write_all(chunk).unwrap();
+                checkpoint = writer.checkpoint().unwrap();
+            }
+            // write some data after the last checkpoint and "crash"
+            let garbage: Vec<u8> = u.arbitrary()?;
+            writer.write_all(&garbage).unwrap();
+            writer.flush().unwrap();
+            let mut output = writer.get_ref().unwrap().clone();
+            let input = chunks.concat();
+            assert_eq!(
+                chunks[..crash].iter().map(|c| c.len() as u64).sum::<u64>(),
+                checkpoint.input_offset
+            );
+            // resume
+            output.truncate(checkpoint.output_offset as usize);
+            let mut writer = CheckpointEncoder::resume(output, &options, checkpoint).unwrap();
+            writer
+                .write_all(&input[checkpoint.input_offset as usize..])
+                .unwrap();
+            let compressed = writer.finish().unwrap();
+            // verbatim data might be detected as some other format
+            if format == Format::Verbatim {
+                assert_eq!(input, compressed);
+                return Ok(());
+            }
+            let mut reader = AnyDecoder::with_format(&compressed[..], format);
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(input, actual);
+            Ok(())
+        });
+    }
+}
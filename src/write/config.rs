@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+use std::io::Error;
+use std::io::Write;
+
+use crate::write::AnyEncoder;
+use crate::write::Compression;
+use crate::Format;
+
+/// Default encoder settings for each format.
+///
+/// Use [AnyEncoder::with_config](crate::write::AnyEncoder::with_config) to create an encoder
+/// with these settings.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct Config {
+    /// Compression level for the formats that don't have their own level.
+    pub compression: Compression,
+    /// Per-format settings.
+    pub formats: BTreeMap<Format, FormatConfig>,
+}
+
+impl Config {
+    /// Get compression level for the `format`.
+    pub fn compression(&self, format: Format) -> Compression {
+        self.formats
+            .get(&format)
+            .and_then(|config| config.compression)
+            .unwrap_or(self.compression)
+    }
+
+    /// Get integrity check setting for the `format`.
+    pub fn checksum(&self, format: Format) -> Option<bool> {
+        self.formats.get(&format).and_then(|config| config.checksum)
+    }
+
+    /// Get block size setting for the `format`.
+    pub fn block_size(&self, format: Format) -> Option<u64> {
+        self.formats
+            .get(&format)
+            .and_then(|config| config.block_size)
+    }
+
+    /// Get complete encoder settings for the `format`.
+    pub fn options(&self, format: Format) -> EncoderOptions {
+        EncoderOptions {
+            format,
+            compression: self.compression(format),
+            checksum: self.checksum(format),
+            block_size: self.block_size(format),
+        }
+    }
+}
+
+/// Encoder settings for a particular format.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct FormatConfig {
+    /// Compression level.
+    ///
+    /// Overrides [Config::compression].
+    pub compression: Option<Compression>,
+    /// Include integrity check of the uncompressed data.
+    ///
+    /// Only xz (CRC64), zstd (XXH64) and LZ4 (XXH32) support this setting.
+    /// Gzip, zlib and bzip2 always include the check.
+    /// When not set, the encoder's default is used.
+    pub checksum: Option<bool>,
+    /// Split the output into blocks of this uncompressed size.
+    ///
+    /// Only gzip, xz, zstd and LZ4 support this setting.
+    /// Gzip files are written in BGZF format (see [BgzfEncoder](crate::write::BgzfEncoder)),
+    /// where each block is a separate member of at most 65280 bytes.
+    /// Xz blocks are listed in the index, hence such files can be decompressed in parallel
+    /// and accessed randomly.
+    /// When not set, the whole xz stream is written as one block.
+    /// Zstd files are written in seekable format (see [SeekableZstdEncoder](crate::write::SeekableZstdEncoder)),
+    /// where each block is a separate frame.
+    /// LZ4 block size is rounded up to 64 KiB, 256 KiB, 1 MiB or 4 MiB;
+    /// when not set, it is chosen from the size of the first write.
+    pub block_size: Option<u64>,
+}
+
+/// Complete encoder settings: the format, the compression level and format-specific settings.
+///
+/// Unlike constructor arguments the settings can be stored, logged and reused
+/// to create any number of encoders via [EncoderOptions::new_encoder].
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EncoderOptions {
+    /// Compression format.
+    pub format: Format,
+    /// Compression level.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub compression: Compression,
+    /// Include integrity check of the uncompressed data.
+    ///
+    /// See [FormatConfig::checksum].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub checksum: Option<bool>,
+    /// Uncompressed block size.
+    ///
+    /// See [FormatConfig::block_size].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub block_size: Option<u64>,
+}
+
+impl EncoderOptions {
+    /// Create new options for the `format` with the default settings.
+    pub fn new(format: Format) -> Self {
+        Self {
+            format,
+            compression: Default::default(),
+            checksum: None,
+            block_size: None,
+        }
+    }
+
+    /// Create new encoder that writes to `writer` using these settings.
+    pub fn new_encoder<W: Write>(&self, writer: W) -> Result<AnyEncoder<W>, Error> {
+        AnyEncoder::with_options(writer, self)
+    }
+}
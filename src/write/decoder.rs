@@ -0,0 +1,459 @@
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Write;
+
+#[cfg(feature = "bzip2")]
+use bzip2::write::BzDecoder;
+#[cfg(feature = "flate2")]
+use flate2::write::MultiGzDecoder;
+#[cfg(feature = "xz")]
+use xz::write::XzDecoder;
+
+use crate::detect_format_in;
+use crate::replace_with;
+use crate::Format;
+use crate::MAX_MAGIC_BYTES;
+
+#[cfg(feature = "zstd")]
+type ZstdDecoder<W> = zstd::stream::zio::Writer<W, zstd::stream::raw::Decoder<'static>>;
+
+/// A decoder that detects compression format from the data written to it
+/// and writes the decompressed data to the underlying writer.
+///
+/// The first bytes are buffered until the magic bytes are complete;
+/// if the stream is shorter than that, the format is detected on [finish](AnyDecoder::finish).
+/// Concatenated gzip members, bzip2 and xz streams and zstd frames are decoded as a single stream.
+/// ZIP, SZDD and KWAJ can't be decoded in a streaming fashion, and `lz4_flex` has no
+/// write-side LZ4 decoder; these formats result in an error of kind
+/// [Unsupported](ErrorKind::Unsupported).
+pub struct AnyDecoder<W: Write> {
+    inner: InnerDecoder<W>,
+    fail_on_unknown_format: bool,
+}
+
+impl<W: Write> AnyDecoder<W> {
+    /// Create new decoder that writes the decompressed data to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self {
+            inner: InnerDecoder::Pending {
+                writer,
+                magic: Vec::with_capacity(MAX_MAGIC_BYTES),
+            },
+            fail_on_unknown_format: false,
+        }
+    }
+
+    /// Throw an error when the decoder fails to detect compression format.
+    ///
+    /// By default no error is thrown, and the data is written verbatim.
+    pub fn fail_on_unknown_format(&mut self, value: bool) {
+        self.fail_on_unknown_format = value;
+    }
+
+    /// Get the input stream format.
+    ///
+    /// Returns `None` if the format hasn't been detected yet.
+    pub fn format(&self) -> Option<Format> {
+        match self.inner {
+            InnerDecoder::Pending { .. } => None,
+            InnerDecoder::Verbatim(..) => Some(Format::Verbatim),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::Gz(..) => Some(Format::Gz),
+            #[cfg(feature = "bzip2")]
+            InnerDecoder::Bz(..) => Some(Format::Bz),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::Zlib(..) => Some(Format::Zlib),
+            #[cfg(feature = "xz")]
+            InnerDecoder::Xz(..) => Some(Format::Xz),
+            #[cfg(feature = "zstd")]
+            InnerDecoder::Zstd(..) => Some(Format::Zstd),
+        }
+    }
+
+    /// Get immutable reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        match self.inner {
+            InnerDecoder::Pending { ref writer, .. } => writer,
+            InnerDecoder::Verbatim(ref w) => w,
+            #[cfg(feature = "flate2")]
+            InnerDecoder::Gz(ref w) => w.get_ref(),
+            #[cfg(feature = "bzip2")]
+            InnerDecoder::Bz(ref w) => w.get_ref(),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::Zlib(ref w) => w.get_ref(),
+            #[cfg(feature = "xz")]
+            InnerDecoder::Xz(ref w) => w.get_ref(),
+            #[cfg(feature = "zstd")]
+            InnerDecoder::Zstd(ref w) => w.writer(),
+        }
+    }
+
+    /// Get mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        match self.inner {
+            InnerDecoder::Pending { ref mut writer, .. } => writer,
+            InnerDecoder::Verbatim(ref mut w) => w,
+            #[cfg(feature = "flate2")]
+            InnerDecoder::Gz(ref mut w) => w.get_mut(),
+            #[cfg(feature = "bzip2")]
+            InnerDecoder::Bz(ref mut w) => w.get_mut(),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::Zlib(ref mut w) => w.get_mut(),
+            #[cfg(feature = "xz")]
+            InnerDecoder::Xz(ref mut w) => w.get_mut(),
+            #[cfg(feature = "zstd")]
+            InnerDecoder::Zstd(ref mut w) => w.writer_mut(),
+        }
+    }
+
+    /// Finish decoding and return the underlying writer.
+    ///
+    /// Returns an error if the compressed stream is incomplete.
+    /// This method is **not** automatically called on drop.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.detect()?;
+        match self.inner {
+            InnerDecoder::Pending { writer, .. } => Ok(writer),
+            InnerDecoder::Verbatim(w) => Ok(w),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::Gz(w) => w.finish(),
+            #[cfg(feature = "bzip2")]
+            InnerDecoder::Bz(mut w) => w.finish(),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::Zlib(w) => w.finish(),
+            #[cfg(feature = "xz")]
+            InnerDecoder::Xz(w) => w.finish(),
+            #[cfg(feature = "zstd")]
+            InnerDecoder::Zstd(mut w) => {
+                w.finish()?;
+                Ok(w.into_inner().0)
+            }
+        }
+    }
+
+    /// Detect the format using the buffered magic bytes and write them to the decoder.
+    pub(crate) fn detect(&mut self) -> Result<(), Error> {
+        let InnerDecoder::Pending { ref mut magic, .. } = self.inner else {
+            return Ok(());
+        };
+        let magic = std::mem::take(magic);
+        let format = match detect_format_in(&magic, Format::DETECTION_ORDER) {
+            Some(format) => format,
+            None if self.fail_on_unknown_format => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "unknown compression format",
+                ))
+            }
+            None => Format::Verbatim,
+        };
+        #[cfg(feature = "tracing")]
+        tracing::debug!(?format, "detected format");
+        match format {
+            #[cfg(feature = "zip")]
+            Format::Zip => return Err(unsupported(format)),
+            #[cfg(feature = "mscompress")]
+            Format::Szdd | Format::Kwaj => return Err(unsupported(format)),
+            #[cfg(feature = "lz4")]
+            Format::Lz4 => return Err(unsupported(format)),
+            #[cfg(feature = "lzop")]
+            Format::Lzop => return Err(unsupported(format)),
+            #[cfg(feature = "lzfse")]
+            Format::Lzfse => return Err(unsupported(format)),
+            _ => {}
+        }
+        #[cfg(feature = "zstd")]
+        let zstd_decoder = match format {
+            Format::Zstd => Some(zstd::stream::raw::Decoder::new()?),
+            _ => None,
+        };
+        replace_with(&mut self.inner, |inner| match inner {
+            InnerDecoder::Pending { writer, .. } => InnerDecoder::new(
+                writer,
+                format,
+                #[cfg(feature = "zstd")]
+                zstd_decoder,
+            ),
+            inner => inner,
+        });
+        self.write_all_detected(&magic)
+    }
+
+    fn write_all_detected(&mut self, mut buf: &[u8]) -> Result<(), Error> {
+        while !buf.is_empty() {
+            match self.write_detected(buf) {
+                Ok(0) => {
+                    return Err(Error::new(
+                        ErrorKind::WriteZero,
+                        "trailing data after the end of the compressed stream",
+                    ))
+                }
+                Ok(n) => buf = &buf[n..],
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn write_detected(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        match self.inner {
+            InnerDecoder::Pending { .. } => Ok(0),
+            InnerDecoder::Verbatim(ref mut w) => w.write(buf),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::Gz(ref mut w) => w.write(buf),
+            #[cfg(feature = "bzip2")]
+            InnerDecoder::Bz(ref mut w) => {
+                let n = w.write(buf)?;
+                if n == 0 && !buf.is_empty() {
+                    // the stream has ended, decode the next one
+                    let writer = w.finish()?;
+                    *w = BzDecoder::new(writer);
+                    return w.write(buf);
+                }
+                Ok(n)
+            }
+            #[cfg(feature = "flate2")]
+            InnerDecoder::Zlib(ref mut w) => w.write(buf),
+            #[cfg(feature = "xz")]
+            InnerDecoder::Xz(ref mut w) => w.write(buf),
+            #[cfg(feature = "zstd")]
+            InnerDecoder::Zstd(ref mut w) => w.write(buf),
+        }
+    }
+}
+
+impl<W: Write> Write for AnyDecoder<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        if let InnerDecoder::Pending { ref mut magic, .. } = self.inner {
+            let n = buf.len().min(MAX_MAGIC_BYTES - magic.len());
+            magic.extend_from_slice(&buf[..n]);
+            if magic.len() == MAX_MAGIC_BYTES {
+                self.detect()?;
+            }
+            return Ok(n);
+        }
+        self.write_detected(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        match self.inner {
+            // nothing can be decoded yet
+            InnerDecoder::Pending { ref mut writer, .. } => writer.flush(),
+            InnerDecoder::Verbatim(ref mut w) => w.flush(),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::Gz(ref mut w) => w.flush(),
+            #[cfg(feature = "bzip2")]
+            InnerDecoder::Bz(ref mut w) => w.flush(),
+            #[cfg(feature = "flate2")]
+            InnerDecoder::Zlib(ref mut w) => w.flush(),
+            #[cfg(feature = "xz")]
+            InnerDecoder::Xz(ref mut w) => w.flush(),
+            #[cfg(feature = "zstd")]
+            InnerDecoder::Zstd(ref mut w) => w.flush(),
+        }
+    }
+}
+
+#[allow(unused)]
+fn unsupported(format: Format) -> Error {
+    Error::new(
+        ErrorKind::Unsupported,
+        format!("{format:?} can't be decoded via Write"),
+    )
+}
+
+/// Zlib decoder that reports incomplete streams.
+#[cfg(feature = "flate2")]
+struct ZlibDecoder<W> {
+    writer: W,
+    decompress: flate2::Decompress,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+#[cfg(feature = "flate2")]
+impl<W: Write> ZlibDecoder<W> {
+    fn new(writer: W) -> Self {
+        Self {
+            writer,
+            decompress: flate2::Decompress::new(true),
+            buf: Vec::with_capacity(32 * 1024),
+            done: false,
+        }
+    }
+
+    fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    fn finish(mut self) -> Result<W, Error> {
+        if !self.done {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "incomplete zlib stream",
+            ));
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(feature = "flate2")]
+impl<W: Write> Write for ZlibDecoder<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        let mut consumed = 0;
+        while !self.done {
+            self.buf.clear();
+            let total_in = self.decompress.total_in();
+            let status = self
+                .decompress
+                .decompress_vec(
+                    &buf[consumed..],
+                    &mut self.buf,
+                    flate2::FlushDecompress::None,
+                )
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+            consumed += (self.decompress.total_in() - total_in) as usize;
+            self.writer.write_all(&self.buf)?;
+            self.done = status == flate2::Status::StreamEnd;
+            // the output buffer isn't full, i.e. all the input has been processed
+            if self.buf.len() < self.buf.capacity() {
+                break;
+            }
+        }
+        Ok(consumed)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()
+    }
+}
+
+enum InnerDecoder<W: Write> {
+    /// The format hasn't been detected yet.
+    Pending {
+        writer: W,
+        magic: Vec<u8>,
+    },
+    Verbatim(W),
+    #[cfg(feature = "flate2")]
+    Gz(MultiGzDecoder<W>),
+    #[cfg(feature = "bzip2")]
+    Bz(BzDecoder<W>),
+    #[cfg(feature = "flate2")]
+    Zlib(ZlibDecoder<W>),
+    #[cfg(feature = "xz")]
+    Xz(XzDecoder<W>),
+    #[cfg(feature = "zstd")]
+    Zstd(ZstdDecoder<W>),
+}
+
+impl<W: Write> InnerDecoder<W> {
+    /// Create the decoder for the detected `format`.
+    fn new(
+        writer: W,
+        format: Format,
+        #[cfg(feature = "zstd")] zstd_decoder: Option<zstd::stream::raw::Decoder<'static>>,
+    ) -> Self {
+        #[cfg(feature = "zstd")]
+        if let Some(decoder) = zstd_decoder {
+            return InnerDecoder::Zstd(ZstdDecoder::new(writer, decoder));
+        }
+        match format {
+            #[cfg(feature = "flate2")]
+            Format::Gz => InnerDecoder::Gz(MultiGzDecoder::new(writer)),
+            #[cfg(feature = "bzip2")]
+            Format::Bz => InnerDecoder::Bz(BzDecoder::new(writer)),
+            #[cfg(feature = "flate2")]
+            Format::Zlib => InnerDecoder::Zlib(ZlibDecoder::new(writer)),
+            #[cfg(feature = "xz")]
+            Format::Xz => InnerDecoder::Xz(XzDecoder::new_multi_decoder(writer)),
+            _ => InnerDecoder::Verbatim(writer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::write::AnyEncoder;
+    use crate::write::Compression;
+
+    #[test]
+    fn write_any() {
+        arbtest(|u| {
+            let format = *u.choose(&[
+                Format::Verbatim,
+                #[cfg(feature = "flate2")]
+                Format::Gz,
+                #[cfg(feature = "bzip2")]
+                Format::Bz,
+                #[cfg(feature = "flate2")]
+                Format::Zlib,
+                #[cfg(feature = "xz")]
+                Format::Xz,
+                #[cfg(feature = "zstd")]
+                Format::Zstd,
+            ])?;
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut writer = AnyEncoder::new(Vec::new(), format, Compression::Fast).unwrap();
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            // verbatim data might be detected as some other format
+            if format == Format::Verbatim && detect_format_in(&compressed, Format::ALL).is_some() {
+                return Ok(());
+            }
+            let mut decoder = AnyDecoder::new(Vec::new());
+            let mut remaining = &compressed[..];
+            while !remaining.is_empty() {
+                let n = u.int_in_range(1..=remaining.len())?;
+                decoder.write_all(&remaining[..n]).unwrap();
+                remaining = &remaining[n..];
+            }
+            let actual = decoder.finish().unwrap();
+            assert_eq!(expected, actual);
+            if format != Format::Verbatim {
+                let mut decoder = AnyDecoder::new(Vec::new());
+                let result = decoder
+                    .write_all(&compressed[..compressed.len() - 1])
+                    .and_then(|_| decoder.finish());
+                assert!(result.is_err());
+            }
+            Ok(())
+        });
+    }
+
+    #[cfg(feature = "bzip2")]
+    #[test]
+    fn concatenated_bz() {
+        let mut compressed = Vec::new();
+        for data in [&b"hello"[..], &b" world"[..]] {
+            let mut writer = AnyEncoder::new(Vec::new(), Format::Bz, Compression::Fast).unwrap();
+            writer.write_all(data).unwrap();
+            compressed.extend(writer.finish().unwrap());
+        }
+        let mut decoder = AnyDecoder::new(Vec::new());
+        decoder.write_all(&compressed).unwrap();
+        assert_eq!(Some(Format::Bz), decoder.format());
+        assert_eq!(b"hello world", &decoder.finish().unwrap()[..]);
+    }
+
+    #[test]
+    fn unknown_format() {
+        let mut decoder = AnyDecoder::new(Vec::new());
+        decoder.write_all(b"x").unwrap();
+        assert_eq!(None, decoder.format());
+        assert_eq!(b"x", &decoder.finish().unwrap()[..]);
+        let mut decoder = AnyDecoder::new(Vec::new());
+        decoder.fail_on_unknown_format(true);
+        decoder.write_all(b"x").unwrap();
+        assert_eq!(ErrorKind::InvalidData, decoder.finish().unwrap_err().kind());
+    }
+}
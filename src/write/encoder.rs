@@ -1,28 +1,60 @@
 use std::fmt::Arguments;
 use std::io::Error;
+use std::io::ErrorKind;
 use std::io::IoSlice;
 use std::io::Write;
 
+#[cfg(feature = "flate2")]
+use crate::write::BgzfEncoder;
+#[cfg(feature = "brotli")]
+use crate::write::BrotliEncoder;
+use crate::write::Config;
+use crate::write::EncoderOptions;
+#[cfg(feature = "flate2")]
+use crate::write::GzEncoder;
+#[cfg(feature = "mscompress")]
+use crate::write::KwajEncoder;
+#[cfg(feature = "lzfse")]
+use crate::write::LzfseEncoder;
+#[cfg(feature = "lzop")]
+use crate::write::LzopEncoder;
+#[cfg(feature = "zstd")]
+use crate::write::SeekableZstdEncoder;
+#[cfg(feature = "mscompress")]
+use crate::write::SzddEncoder;
+#[cfg(feature = "xz")]
+use crate::write::XzBlockEncoder;
+#[cfg(feature = "zip")]
+use crate::write::ZipEncoder;
+#[cfg(feature = "zstd")]
+use crate::write::ZstdEncoder;
+use crate::Format;
 #[cfg(feature = "bzip2")]
 use bzip2::write::BzEncoder;
 #[cfg(feature = "flate2")]
-use flate2::write::GzEncoder;
+use flate2::write::DeflateEncoder;
 #[cfg(feature = "flate2")]
 use flate2::write::ZlibEncoder;
+#[cfg(feature = "lz4")]
+use lz4_flex::frame::FrameEncoder;
 #[cfg(feature = "xz")]
 use xz::write::XzEncoder;
-#[cfg(feature = "zstd")]
-use zstd::stream::write::Encoder as ZstdEncoder;
-
-use crate::Format;
 
 /// An encoder that dynamically selects compression format via [Format] and [Compression].
+///
+/// After [flush](Write::flush) the output written so far can be decoded
+/// up to the last byte that was written before the flush, i.e. the flushed output
+/// is a decodable prefix of the complete stream.
+/// The formats that can't guarantee that return an error (see [supports_flush](Self::supports_flush)).
 pub enum AnyEncoder<W: Write> {
     /// Verbatim encoder.
     Verbatim(W),
     /// Gzip encoder.
     #[cfg(feature = "flate2")]
     Gz(GzEncoder<W>),
+    /// BGZF (`bgzip`) encoder.
+    #[cfg(feature = "flate2")]
+    Bgzf(BgzfEncoder<W>),
     /// Bzip2 encoder.
     #[cfg(feature = "bzip2")]
     Bz(BzEncoder<W>),
@@ -32,14 +64,46 @@ pub enum AnyEncoder<W: Write> {
     /// XZ encoder.
     #[cfg(feature = "xz")]
     Xz(XzEncoder<W>),
+    /// Multi-block XZ encoder.
+    #[cfg(feature = "xz")]
+    XzBlocks(XzBlockEncoder<W>),
     /// Zstd encoder.
     #[cfg(feature = "zstd")]
-    Zstd(ZstdEncoder<'static, W>),
+    Zstd(ZstdEncoder<W>),
+    /// Seekable zstd encoder.
+    #[cfg(feature = "zstd")]
+    SeekableZstd(SeekableZstdEncoder<W>),
+    /// ZIP encoder.
+    #[cfg(feature = "zip")]
+    Zip(ZipEncoder<W>),
+    /// SZDD encoder.
+    #[cfg(feature = "mscompress")]
+    Szdd(SzddEncoder<W>),
+    /// KWAJ encoder.
+    #[cfg(feature = "mscompress")]
+    Kwaj(KwajEncoder<W>),
+    /// Lzop encoder.
+    #[cfg(feature = "lzop")]
+    Lzop(LzopEncoder<W>),
+    /// LZFSE encoder.
+    #[cfg(feature = "lzfse")]
+    Lzfse(LzfseEncoder<W>),
+    /// LZ4 frame encoder.
+    #[cfg(feature = "lz4")]
+    Lz4(FrameEncoder<W>),
+    /// Brotli encoder.
+    #[cfg(feature = "brotli")]
+    Brotli(BrotliEncoder<W>),
+    /// Raw DEFLATE encoder.
+    #[cfg(feature = "flate2")]
+    Deflate(DeflateEncoder<W>),
 }
 
 impl<W: Write> AnyEncoder<W> {
     /// Create new encoder for the supplied `format` and `compression` ratio.
     pub fn new(writer: W, format: Format, compression: Compression) -> Result<Self, Error> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(?format, ?compression, "new encoder");
         match format {
             Format::Verbatim => Ok(Self::Verbatim(writer)),
             #[cfg(feature = "flate2")]
@@ -51,10 +115,140 @@ impl<W: Write> AnyEncoder<W> {
                 writer,
                 compression.to_flate2(),
             ))),
+            #[cfg(feature = "flate2")]
+            Format::Deflate => Ok(Self::Deflate(DeflateEncoder::new(
+                writer,
+                compression.to_flate2(),
+            ))),
             #[cfg(feature = "xz")]
             Format::Xz => Ok(Self::Xz(XzEncoder::new(writer, compression.to_xz()))),
             #[cfg(feature = "zstd")]
             Format::Zstd => Ok(Self::Zstd(ZstdEncoder::new(writer, compression.to_zstd())?)),
+            #[cfg(feature = "zip")]
+            Format::Zip => Ok(Self::Zip(ZipEncoder::new(writer, compression.to_flate2()))),
+            #[cfg(feature = "mscompress")]
+            Format::Szdd => Ok(Self::Szdd(SzddEncoder::new(
+                writer,
+                compression.to_mscompress(),
+            ))),
+            #[cfg(feature = "mscompress")]
+            Format::Kwaj => Ok(Self::Kwaj(KwajEncoder::new(
+                writer,
+                compression.to_mscompress(),
+            ))),
+            #[cfg(feature = "lzop")]
+            Format::Lzop => Ok(Self::Lzop(LzopEncoder::new(writer, compression.to_lzop()))),
+            #[cfg(feature = "lzfse")]
+            Format::Lzfse => Ok(Self::Lzfse(LzfseEncoder::new(
+                writer,
+                compression.to_lzfse(),
+            ))),
+            #[cfg(feature = "lz4")]
+            Format::Lz4 => Ok(Self::Lz4(FrameEncoder::with_frame_info(
+                lz4_flex::frame::FrameInfo::new().block_mode(compression.to_lz4()),
+                writer,
+            ))),
+            #[cfg(feature = "brotli")]
+            Format::Brotli => Ok(Self::Brotli(BrotliEncoder::new(
+                writer,
+                compression.to_brotli(),
+                BROTLI_LGWIN,
+            ))),
+            Format::Other(..) => Err(Error::new(
+                ErrorKind::Unsupported,
+                "custom formats can't be encoded",
+            )),
+        }
+    }
+
+    /// Create new encoder for the supplied `format` using the settings from the `config`.
+    pub fn with_config(writer: W, format: Format, config: &Config) -> Result<Self, Error> {
+        Self::with_options(writer, &config.options(format))
+    }
+
+    /// Create new encoder using the supplied `options`.
+    pub fn with_options(writer: W, options: &EncoderOptions) -> Result<Self, Error> {
+        let compression = options.compression;
+        match (options.format, options.checksum) {
+            #[cfg(feature = "flate2")]
+            (Format::Gz, _) if options.block_size.is_some() => {
+                let block_size = options.block_size.unwrap_or_default();
+                Ok(Self::Bgzf(BgzfEncoder::with_block_size(
+                    writer,
+                    compression.to_flate2(),
+                    block_size,
+                )))
+            }
+            #[cfg(feature = "xz")]
+            (Format::Xz, checksum) if checksum.is_some() || options.block_size.is_some() => {
+                use xz::stream::Check;
+                use xz::stream::Stream;
+                let check = match checksum {
+                    Some(false) => Check::None,
+                    _ => Check::Crc64,
+                };
+                let stream = Stream::new_easy_encoder(compression.to_xz(), check)?;
+                match options.block_size {
+                    Some(block_size) => Ok(Self::XzBlocks(XzBlockEncoder::new_stream(
+                        writer, stream, block_size,
+                    ))),
+                    None => Ok(Self::Xz(XzEncoder::new_stream(writer, stream))),
+                }
+            }
+            #[cfg(feature = "zstd")]
+            (Format::Zstd, checksum) if checksum.is_some() || options.block_size.is_some() => {
+                use zstd::stream::raw::CParameter;
+                let level = compression.to_zstd();
+                match options.block_size {
+                    Some(block_size) => {
+                        let mut encoder = SeekableZstdEncoder::new(writer, level, block_size)?;
+                        if let Some(checksum) = checksum {
+                            encoder.set_parameter(CParameter::ChecksumFlag(checksum))?;
+                        }
+                        Ok(Self::SeekableZstd(encoder))
+                    }
+                    None => {
+                        let mut encoder = ZstdEncoder::new(writer, level)?;
+                        if let Some(checksum) = checksum {
+                            encoder.set_parameter(CParameter::ChecksumFlag(checksum))?;
+                        }
+                        Ok(Self::Zstd(encoder))
+                    }
+                }
+            }
+            #[cfg(feature = "lz4")]
+            (Format::Lz4, checksum) => {
+                let mut frame_info = lz4_flex::frame::FrameInfo::new()
+                    .block_mode(compression.to_lz4())
+                    .content_checksum(checksum.unwrap_or(false));
+                if let Some(block_size) = options.block_size {
+                    frame_info = frame_info.block_size(lz4_block_size(block_size));
+                }
+                Ok(Self::Lz4(FrameEncoder::with_frame_info(frame_info, writer)))
+            }
+            (format, _) => Self::new(writer, format, compression),
+        }
+    }
+
+    /// Check whether [flush](Write::flush) makes all the data written so far decodable.
+    ///
+    /// - verbatim, gzip, zlib, ZIP: deflate sync flush;
+    /// - xz: full flush, the current block is finished;
+    /// - zstd, LZ4: the current block is finished;
+    /// - brotli: the current meta-block is finished;
+    /// - lzop, LZFSE: the current block is finished.
+    ///
+    /// Bzip2 keeps the last bits of the block until the next block starts,
+    /// SZDD header stores the uncompressed size, and KWAJ groups the literals and
+    /// the matches by eight; these encoders return an error of kind
+    /// [Unsupported](ErrorKind::Unsupported) on flush.
+    pub fn supports_flush(&self) -> bool {
+        match self {
+            #[cfg(feature = "bzip2")]
+            Self::Bz(..) => false,
+            #[cfg(feature = "mscompress")]
+            Self::Szdd(..) | Self::Kwaj(..) => false,
+            _ => true,
         }
     }
 
@@ -64,14 +258,76 @@ impl<W: Write> AnyEncoder<W> {
             Self::Verbatim(..) => Format::Verbatim,
             #[cfg(feature = "flate2")]
             Self::Gz(..) => Format::Gz,
+            #[cfg(feature = "flate2")]
+            Self::Bgzf(..) => Format::Gz,
             #[cfg(feature = "bzip2")]
             Self::Bz(..) => Format::Bz,
             #[cfg(feature = "flate2")]
             Self::Zlib(..) => Format::Zlib,
+            #[cfg(feature = "flate2")]
+            Self::Deflate(..) => Format::Deflate,
             #[cfg(feature = "xz")]
             Self::Xz(..) => Format::Xz,
+            #[cfg(feature = "xz")]
+            Self::XzBlocks(..) => Format::Xz,
             #[cfg(feature = "zstd")]
             Self::Zstd(..) => Format::Zstd,
+            #[cfg(feature = "zstd")]
+            Self::SeekableZstd(..) => Format::Zstd,
+            #[cfg(feature = "zip")]
+            Self::Zip(..) => Format::Zip,
+            #[cfg(feature = "mscompress")]
+            Self::Szdd(..) => Format::Szdd,
+            #[cfg(feature = "mscompress")]
+            Self::Kwaj(..) => Format::Kwaj,
+            #[cfg(feature = "lzop")]
+            Self::Lzop(..) => Format::Lzop,
+            #[cfg(feature = "lzfse")]
+            Self::Lzfse(..) => Format::Lzfse,
+            #[cfg(feature = "lz4")]
+            Self::Lz4(..) => Format::Lz4,
+            #[cfg(feature = "brotli")]
+            Self::Brotli(..) => Format::Brotli,
+        }
+    }
+
+    /// Finish the current gzip member, BGZF block or zstd frame and start a new one.
+    ///
+    /// Use [GzEncoder::start_new_member_with_header] to change gzip header of the new member.
+    /// Other formats don't support multiple members and return an error of kind
+    /// [Unsupported](ErrorKind::Unsupported).
+    pub fn start_new_member(&mut self) -> Result<(), Error> {
+        match self {
+            #[cfg(feature = "flate2")]
+            Self::Gz(ref mut w) => w.start_new_member(),
+            #[cfg(feature = "flate2")]
+            Self::Bgzf(ref mut w) => w.finish_block(),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(ref mut w) => w.start_new_frame(),
+            _ => Err(Error::new(
+                ErrorKind::Unsupported,
+                "the format doesn't support multiple members",
+            )),
+        }
+    }
+
+    /// Write zstd skippable frame with the supplied `payload`.
+    ///
+    /// See [ZstdEncoder::write_skippable_frame].
+    /// Other formats return an error of kind [Unsupported](ErrorKind::Unsupported).
+    #[allow(unused_variables)]
+    pub fn write_skippable_frame(
+        &mut self,
+        magic_variant: u8,
+        payload: &[u8],
+    ) -> Result<(), Error> {
+        match self {
+            #[cfg(feature = "zstd")]
+            Self::Zstd(ref mut w) => w.write_skippable_frame(magic_variant, payload),
+            _ => Err(Error::new(
+                ErrorKind::Unsupported,
+                "the format doesn't support skippable frames",
+            )),
         }
     }
 
@@ -81,14 +337,36 @@ impl<W: Write> AnyEncoder<W> {
             Self::Verbatim(ref w) => w,
             #[cfg(feature = "flate2")]
             Self::Gz(ref w) => w.get_ref(),
+            #[cfg(feature = "flate2")]
+            Self::Bgzf(ref w) => w.get_ref(),
             #[cfg(feature = "bzip2")]
             Self::Bz(ref w) => w.get_ref(),
             #[cfg(feature = "flate2")]
             Self::Zlib(ref w) => w.get_ref(),
+            #[cfg(feature = "flate2")]
+            Self::Deflate(ref w) => w.get_ref(),
             #[cfg(feature = "xz")]
             Self::Xz(ref w) => w.get_ref(),
+            #[cfg(feature = "xz")]
+            Self::XzBlocks(ref w) => w.get_ref(),
             #[cfg(feature = "zstd")]
             Self::Zstd(ref w) => w.get_ref(),
+            #[cfg(feature = "zstd")]
+            Self::SeekableZstd(ref w) => w.get_ref(),
+            #[cfg(feature = "zip")]
+            Self::Zip(ref w) => w.get_ref(),
+            #[cfg(feature = "mscompress")]
+            Self::Szdd(ref w) => w.get_ref(),
+            #[cfg(feature = "mscompress")]
+            Self::Kwaj(ref w) => w.get_ref(),
+            #[cfg(feature = "lzop")]
+            Self::Lzop(ref w) => w.get_ref(),
+            #[cfg(feature = "lzfse")]
+            Self::Lzfse(ref w) => w.get_ref(),
+            #[cfg(feature = "lz4")]
+            Self::Lz4(ref w) => w.get_ref(),
+            #[cfg(feature = "brotli")]
+            Self::Brotli(ref w) => w.get_ref(),
         }
     }
 
@@ -98,14 +376,36 @@ impl<W: Write> AnyEncoder<W> {
             Self::Verbatim(ref mut w) => w,
             #[cfg(feature = "flate2")]
             Self::Gz(ref mut w) => w.get_mut(),
+            #[cfg(feature = "flate2")]
+            Self::Bgzf(ref mut w) => w.get_mut(),
             #[cfg(feature = "bzip2")]
             Self::Bz(ref mut w) => w.get_mut(),
             #[cfg(feature = "flate2")]
             Self::Zlib(ref mut w) => w.get_mut(),
+            #[cfg(feature = "flate2")]
+            Self::Deflate(ref mut w) => w.get_mut(),
             #[cfg(feature = "xz")]
             Self::Xz(ref mut w) => w.get_mut(),
+            #[cfg(feature = "xz")]
+            Self::XzBlocks(ref mut w) => w.get_mut(),
             #[cfg(feature = "zstd")]
             Self::Zstd(ref mut w) => w.get_mut(),
+            #[cfg(feature = "zstd")]
+            Self::SeekableZstd(ref mut w) => w.get_mut(),
+            #[cfg(feature = "zip")]
+            Self::Zip(ref mut w) => w.get_mut(),
+            #[cfg(feature = "mscompress")]
+            Self::Szdd(ref mut w) => w.get_mut(),
+            #[cfg(feature = "mscompress")]
+            Self::Kwaj(ref mut w) => w.get_mut(),
+            #[cfg(feature = "lzop")]
+            Self::Lzop(ref mut w) => w.get_mut(),
+            #[cfg(feature = "lzfse")]
+            Self::Lzfse(ref mut w) => w.get_mut(),
+            #[cfg(feature = "lz4")]
+            Self::Lz4(ref mut w) => w.get_mut(),
+            #[cfg(feature = "brotli")]
+            Self::Brotli(ref mut w) => w.get_mut(),
         }
     }
 
@@ -113,18 +413,124 @@ impl<W: Write> AnyEncoder<W> {
     ///
     /// This method is **not** automatically called on drop.
     pub fn finish(self) -> Result<W, Error> {
-        match self {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("deko::finish", format = ?self.format()).entered();
+        let result = match self {
             Self::Verbatim(w) => Ok(w),
             #[cfg(feature = "flate2")]
             Self::Gz(w) => w.finish(),
+            #[cfg(feature = "flate2")]
+            Self::Bgzf(w) => w.finish(),
             #[cfg(feature = "bzip2")]
             Self::Bz(w) => w.finish(),
             #[cfg(feature = "flate2")]
             Self::Zlib(w) => w.finish(),
+            #[cfg(feature = "flate2")]
+            Self::Deflate(w) => w.finish(),
             #[cfg(feature = "xz")]
             Self::Xz(w) => w.finish(),
+            #[cfg(feature = "xz")]
+            Self::XzBlocks(w) => w.finish(),
             #[cfg(feature = "zstd")]
             Self::Zstd(w) => w.finish(),
+            #[cfg(feature = "zstd")]
+            Self::SeekableZstd(w) => w.finish(),
+            #[cfg(feature = "zip")]
+            Self::Zip(w) => w.finish(),
+            #[cfg(feature = "mscompress")]
+            Self::Szdd(w) => w.finish(),
+            #[cfg(feature = "mscompress")]
+            Self::Kwaj(w) => w.finish(),
+            #[cfg(feature = "lzop")]
+            Self::Lzop(w) => w.finish(),
+            #[cfg(feature = "lzfse")]
+            Self::Lzfse(w) => w.finish(),
+            #[cfg(feature = "lz4")]
+            Self::Lz4(w) => w.finish().map_err(Error::from),
+            #[cfg(feature = "brotli")]
+            Self::Brotli(w) => w.finish(),
+        };
+        #[cfg(feature = "tracing")]
+        match result {
+            Ok(..) => tracing::debug!("finished encoding"),
+            Err(ref e) => tracing::debug!(error = %e, "failed to finish encoding"),
+        }
+        result
+    }
+}
+
+impl<W: Write> crate::Encode for AnyEncoder<W> {
+    type Writer = W;
+
+    fn format(&self) -> Format {
+        AnyEncoder::format(self)
+    }
+
+    fn get_ref(&self) -> &W {
+        AnyEncoder::get_ref(self)
+    }
+
+    fn get_mut(&mut self) -> &mut W {
+        AnyEncoder::get_mut(self)
+    }
+
+    fn finish(self) -> Result<W, Error> {
+        AnyEncoder::finish(self)
+    }
+}
+
+impl<W: Write + Send + 'static> AnyEncoder<W> {
+    /// Create new encoder for the supplied `format` and `compression` ratio and erase its type.
+    ///
+    /// Boxed encoders for different sinks (files, sockets, in-memory buffers)
+    /// have the same type and can be stored in one collection.
+    /// The encoder is finished when the box is dropped, and the errors are ignored at that point;
+    /// [flush](Write::flush) the writer before dropping it to catch write errors.
+    /// Alternatively, box the sink itself, i.e. use `AnyEncoder<Box<dyn Write + Send>>`,
+    /// and call [finish](AnyEncoder::finish) explicitly.
+    pub fn boxed(
+        writer: W,
+        format: Format,
+        compression: Compression,
+    ) -> Result<Box<dyn Write + Send>, Error> {
+        let encoder = Self::new(writer, format, compression)?;
+        Ok(Box::new(FinishOnDrop(Some(encoder))))
+    }
+}
+
+/// Finishes the encoder on drop.
+struct FinishOnDrop<W: Write>(Option<AnyEncoder<W>>);
+
+impl<W: Write> FinishOnDrop<W> {
+    fn encoder(&mut self) -> Result<&mut AnyEncoder<W>, Error> {
+        self.0
+            .as_mut()
+            .ok_or_else(|| Error::other("the encoder is finished"))
+    }
+}
+
+impl<W: Write> Write for FinishOnDrop<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.encoder()?.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.encoder()?.flush()
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<usize, Error> {
+        self.encoder()?.write_vectored(bufs)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Error> {
+        self.encoder()?.write_all(buf)
+    }
+}
+
+impl<W: Write> Drop for FinishOnDrop<W> {
+    fn drop(&mut self) {
+        if let Some(encoder) = self.0.take() {
+            let _ = encoder.finish();
         }
     }
 }
@@ -135,6 +541,23 @@ impl<W: Write> Write for AnyEncoder<W> {
     }
 
     fn flush(&mut self) -> Result<(), Error> {
+        if !self.supports_flush() {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("{:?} encoder can't flush decodable output", self.format()),
+            ));
+        }
+        #[cfg(feature = "xz")]
+        if let Self::Xz(encoder) = self {
+            // liblzma keeps the output of the full flush until the next call
+            encoder.flush()?;
+        }
+        #[cfg(feature = "lz4")]
+        if let Self::Lz4(encoder) = self {
+            // the block is written but the underlying writer isn't flushed
+            encoder.flush()?;
+            return encoder.get_mut().flush();
+        }
         dispatch_mut!(self, Write::flush)
     }
 
@@ -168,6 +591,7 @@ impl<W: Write> Write for AnyEncoder<W> {
 /// Compression level.
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
 #[cfg_attr(test, derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Compression {
     /// Usually the lowest compression level.
     Fast,
@@ -193,10 +617,27 @@ impl Compression {
             Format::Bz => CompressionLevel::Bz(self.to_bzip2()),
             #[cfg(feature = "flate2")]
             Format::Zlib => CompressionLevel::Zlib(self.to_flate2()),
+            #[cfg(feature = "flate2")]
+            Format::Deflate => CompressionLevel::Deflate(self.to_flate2()),
             #[cfg(feature = "xz")]
             Format::Xz => CompressionLevel::Xz(self.to_xz()),
             #[cfg(feature = "zstd")]
             Format::Zstd => CompressionLevel::Zstd(self.to_zstd()),
+            #[cfg(feature = "zip")]
+            Format::Zip => CompressionLevel::Zip(self.to_flate2()),
+            #[cfg(feature = "mscompress")]
+            Format::Szdd => CompressionLevel::Szdd(self.to_mscompress()),
+            #[cfg(feature = "mscompress")]
+            Format::Kwaj => CompressionLevel::Kwaj(self.to_mscompress()),
+            #[cfg(feature = "lzop")]
+            Format::Lzop => CompressionLevel::Lzop(self.to_lzop()),
+            #[cfg(feature = "lzfse")]
+            Format::Lzfse => CompressionLevel::Lzfse(self.to_lzfse()),
+            #[cfg(feature = "lz4")]
+            Format::Lz4 => CompressionLevel::Lz4(self.to_lz4()),
+            #[cfg(feature = "brotli")]
+            Format::Brotli => CompressionLevel::Brotli(self.to_brotli()),
+            Format::Other(..) => CompressionLevel::None,
         }
     }
 
@@ -239,6 +680,78 @@ impl Compression {
             Self::Level(i) => i as i32,
         }
     }
+
+    #[cfg(feature = "mscompress")]
+    fn to_mscompress(self) -> u32 {
+        match self {
+            Self::Fast => 1,
+            Self::Default => 5,
+            Self::Best => 9,
+            Self::Level(i) => i,
+        }
+    }
+
+    /// The same levels as `lzop` tool uses (1–9).
+    #[cfg(feature = "lzop")]
+    fn to_lzop(self) -> u32 {
+        match self {
+            Self::Fast => 1,
+            Self::Default => 3,
+            Self::Best => 9,
+            Self::Level(i) => i,
+        }
+    }
+
+    /// The same levels as for lzop.
+    #[cfg(feature = "lzfse")]
+    fn to_lzfse(self) -> u32 {
+        match self {
+            Self::Fast => 1,
+            Self::Default => 3,
+            Self::Best => 9,
+            Self::Level(i) => i,
+        }
+    }
+
+    /// Levels 3 and higher (the high compression levels of `lz4` tool) use linked blocks.
+    #[cfg(feature = "lz4")]
+    fn to_lz4(self) -> lz4_flex::frame::BlockMode {
+        use lz4_flex::frame::BlockMode;
+        match self {
+            Self::Fast | Self::Default | Self::Level(0..=2) => BlockMode::Independent,
+            Self::Best | Self::Level(_) => BlockMode::Linked,
+        }
+    }
+
+    /// Brotli quality (0–11).
+    #[cfg(feature = "brotli")]
+    fn to_brotli(self) -> u32 {
+        match self {
+            Self::Fast => 1,
+            Self::Default => 6,
+            Self::Best => 11,
+            Self::Level(i) => i,
+        }
+    }
+}
+
+/// The window size of brotli encoder (base-2 logarithm), the same as `brotli` tool uses.
+#[cfg(feature = "brotli")]
+const BROTLI_LGWIN: u32 = 22;
+
+/// Round the block size up to the nearest size that LZ4 frame format supports.
+#[cfg(feature = "lz4")]
+fn lz4_block_size(block_size: u64) -> lz4_flex::frame::BlockSize {
+    use lz4_flex::frame::BlockSize;
+    if block_size <= 64 * 1024 {
+        BlockSize::Max64KB
+    } else if block_size <= 256 * 1024 {
+        BlockSize::Max256KB
+    } else if block_size <= 1024 * 1024 {
+        BlockSize::Max1MB
+    } else {
+        BlockSize::Max4MB
+    }
 }
 
 /// Specific compression level for each output format.
@@ -261,6 +774,33 @@ pub enum CompressionLevel {
     /// Zstd compression level (1–22, 0 means default compression).
     #[cfg(feature = "zstd")]
     Zstd(i32),
+    /// ZIP compression level.
+    #[cfg(feature = "zip")]
+    Zip(flate2::Compression),
+    /// SZDD compression level (0–9, 0 means no compression).
+    #[cfg(feature = "mscompress")]
+    Szdd(u32),
+    /// KWAJ compression level (0–9, 0 means no compression).
+    #[cfg(feature = "mscompress")]
+    Kwaj(u32),
+    /// LZ4 block mode.
+    ///
+    /// `lz4_flex` has only one compression level;
+    /// linked blocks improve the ratio by referencing the data of the previous blocks.
+    #[cfg(feature = "lz4")]
+    Lz4(lz4_flex::frame::BlockMode),
+    /// Brotli quality (0–11).
+    #[cfg(feature = "brotli")]
+    Brotli(u32),
+    /// Lzop compression level (1–9).
+    #[cfg(feature = "lzop")]
+    Lzop(u32),
+    /// Raw DEFLATE compression level.
+    #[cfg(feature = "flate2")]
+    Deflate(flate2::Compression),
+    /// LZFSE compression level (1–9).
+    #[cfg(feature = "lzfse")]
+    Lzfse(u32),
 }
 
 macro_rules! dispatch_mut {
@@ -269,14 +809,36 @@ macro_rules! dispatch_mut {
             Self::Verbatim(ref mut w) => $method(w, $($args),*),
             #[cfg(feature = "flate2")]
             Self::Gz(ref mut w) => $method(w, $($args),*),
+            #[cfg(feature = "flate2")]
+            Self::Bgzf(ref mut w) => $method(w, $($args),*),
             #[cfg(feature = "bzip2")]
             Self::Bz(ref mut w) => $method(w, $($args),*),
             #[cfg(feature = "flate2")]
             Self::Zlib(ref mut w) => $method(w, $($args),*),
+            #[cfg(feature = "flate2")]
+            Self::Deflate(ref mut w) => $method(w, $($args),*),
             #[cfg(feature = "xz")]
             Self::Xz(ref mut w) => $method(w, $($args),*),
+            #[cfg(feature = "xz")]
+            Self::XzBlocks(ref mut w) => $method(w, $($args),*),
             #[cfg(feature = "zstd")]
             Self::Zstd(ref mut w) => $method(w, $($args),*),
+            #[cfg(feature = "zstd")]
+            Self::SeekableZstd(ref mut w) => $method(w, $($args),*),
+            #[cfg(feature = "zip")]
+            Self::Zip(ref mut w) => $method(w, $($args),*),
+            #[cfg(feature = "mscompress")]
+            Self::Szdd(ref mut w) => $method(w, $($args),*),
+            #[cfg(feature = "mscompress")]
+            Self::Kwaj(ref mut w) => $method(w, $($args),*),
+            #[cfg(feature = "lzop")]
+            Self::Lzop(ref mut w) => $method(w, $($args),*),
+            #[cfg(feature = "lzfse")]
+            Self::Lzfse(ref mut w) => $method(w, $($args),*),
+            #[cfg(feature = "lz4")]
+            Self::Lz4(ref mut w) => $method(w, $($args),*),
+            #[cfg(feature = "brotli")]
+            Self::Brotli(ref mut w) => $method(w, $($args),*),
         }
     }
 }
@@ -290,14 +852,36 @@ macro_rules! dispatch {
             Self::Verbatim(ref w) => $method(w, $($args),*),
             #[cfg(feature = "flate2")]
             Self::Gz(ref w) => $method(w, $($args),*),
+            #[cfg(feature = "flate2")]
+            Self::Bgzf(ref w) => $method(w, $($args),*),
             #[cfg(feature = "bzip2")]
             Self::Bz(ref w) => $method(w, $($args),*),
             #[cfg(feature = "flate2")]
             Self::Zlib(ref w) => $method(w, $($args),*),
+            #[cfg(feature = "flate2")]
+            Self::Deflate(ref w) => $method(w, $($args),*),
             #[cfg(feature = "xz")]
             Self::Xz(ref w) => $method(w, $($args),*),
+            #[cfg(feature = "xz")]
+            Self::XzBlocks(ref w) => $method(w, $($args),*),
             #[cfg(feature = "zstd")]
             Self::Zstd(ref w) => $method(w, $($args),*),
+            #[cfg(feature = "zstd")]
+            Self::SeekableZstd(ref w) => $method(w, $($args),*),
+            #[cfg(feature = "zip")]
+            Self::Zip(ref w) => $method(w, $($args),*),
+            #[cfg(feature = "mscompress")]
+            Self::Szdd(ref w) => $method(w, $($args),*),
+            #[cfg(feature = "mscompress")]
+            Self::Kwaj(ref w) => $method(w, $($args),*),
+            #[cfg(feature = "lzop")]
+            Self::Lzop(ref w) => $method(w, $($args),*),
+            #[cfg(feature = "lzfse")]
+            Self::Lzfse(ref w) => $method(w, $($args),*),
+            #[cfg(feature = "lz4")]
+            Self::Lz4(ref w) => $method(w, $($args),*),
+            #[cfg(feature = "brotli")]
+            Self::Brotli(ref w) => $method(w, $($args),*),
         }
     }
 }
@@ -311,16 +895,261 @@ mod tests {
     use std::io::Read;
 
     use arbitrary::Unstructured;
+    use arbtest::arbtest;
 
     use super::*;
     use crate::bufread::AnyDecoder;
     use crate::test::test_write_trait;
+    use crate::write::FormatConfig;
 
     #[test]
     fn test_any_encoder() {
         test_write_trait(new_any_encoder, new_any_decoder);
     }
 
+    #[test]
+    fn with_config() {
+        arbtest(|u| {
+            let format: Format = u.arbitrary()?;
+            let mut config = Config {
+                compression: arbitrary_compression(format, u)?,
+                ..Default::default()
+            };
+            config.formats.insert(
+                format,
+                FormatConfig {
+                    compression: Some(arbitrary_compression(format, u)?),
+                    checksum: u.arbitrary()?,
+                    block_size: u.arbitrary()?,
+                },
+            );
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut writer = AnyEncoder::with_config(Vec::new(), format, &config).unwrap();
+            assert_eq!(format, writer.format());
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            // verbatim data might be detected as some other format
+            if format == Format::Verbatim {
+                assert_eq!(expected, compressed);
+                return Ok(());
+            }
+            let mut reader = AnyDecoder::with_format(&compressed[..], format);
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, actual);
+            Ok(())
+        });
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn config_serde() {
+        let json =
+            r#"{"compression": "Fast", "formats": {"Zstd": {"compression": {"Level": 19}}}}"#;
+        let config: Config = serde_json::from_str(json).unwrap();
+        assert_eq!(Compression::Fast, config.compression(Format::Verbatim));
+        assert_eq!(Compression::Level(19), config.compression(Format::Zstd));
+        assert_eq!(None, config.checksum(Format::Zstd));
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(config, serde_json::from_str(&json).unwrap());
+    }
+
+    #[test]
+    fn encoder_options() {
+        arbtest(|u| {
+            let format: Format = u.arbitrary()?;
+            let options = EncoderOptions {
+                format,
+                compression: arbitrary_compression(format, u)?,
+                checksum: u.arbitrary()?,
+                block_size: u.arbitrary()?,
+            };
+            let inputs: Vec<Vec<u8>> = u.arbitrary()?;
+            for expected in inputs.iter() {
+                let mut writer = options.new_encoder(Vec::new()).unwrap();
+                assert_eq!(format, writer.format());
+                writer.write_all(expected).unwrap();
+                let compressed = writer.finish().unwrap();
+                // verbatim data might be detected as some other format
+                if format == Format::Verbatim {
+                    assert_eq!(expected, &compressed);
+                    continue;
+                }
+                let mut reader = AnyDecoder::with_format(&compressed[..], format);
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                assert_eq!(expected, &actual);
+            }
+            Ok(())
+        });
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn encoder_options_serde() {
+        let options: EncoderOptions = serde_json::from_str(r#"{"format": "Verbatim"}"#).unwrap();
+        assert_eq!(EncoderOptions::new(Format::Verbatim), options);
+        let config = Config {
+            compression: Compression::Best,
+            ..Default::default()
+        };
+        let options = config.options(Format::Verbatim);
+        let json = serde_json::to_string(&options).unwrap();
+        assert_eq!(options, serde_json::from_str(&json).unwrap());
+    }
+
+    #[test]
+    fn start_new_member() {
+        arbtest(|u| {
+            let format: Format = u.arbitrary()?;
+            let compression: Compression = arbitrary_compression(format, u)?;
+            let members: Vec<Vec<u8>> = u.arbitrary()?;
+            let mut writer = AnyEncoder::new(Vec::new(), format, compression).unwrap();
+            for (i, member) in members.iter().enumerate() {
+                if i != 0 {
+                    if let Err(e) = writer.start_new_member() {
+                        assert_eq!(ErrorKind::Unsupported, e.kind());
+                        return Ok(());
+                    }
+                }
+                writer.write_all(member).unwrap();
+            }
+            let compressed = writer.finish().unwrap();
+            let mut reader = AnyDecoder::with_format(&compressed[..], format);
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(members.concat(), actual);
+            Ok(())
+        });
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn lz4_frame_info() {
+        arbtest(|u| {
+            let options = EncoderOptions {
+                format: Format::Lz4,
+                compression: u.arbitrary()?,
+                checksum: u.arbitrary()?,
+                block_size: u.arbitrary()?,
+            };
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut writer = options.new_encoder(Vec::new()).unwrap();
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            let (flags, block_descriptor) = (compressed[4], compressed[5]);
+            let independent = matches!(
+                options.compression,
+                Compression::Fast | Compression::Default | Compression::Level(0..=2)
+            );
+            assert_eq!(independent, flags & 0b0010_0000 != 0);
+            assert_eq!(options.checksum == Some(true), flags & 0b0000_0100 != 0);
+            if let Some(block_size) = options.block_size {
+                let max_block_size = 1_u64 << (8 + 2 * ((block_descriptor >> 4) & 0b111));
+                assert!(block_size <= max_block_size || max_block_size == 4 * 1024 * 1024);
+                assert!(block_size > max_block_size / 4 || max_block_size == 64 * 1024);
+            }
+            let mut reader = AnyDecoder::new(&compressed[..]);
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, actual);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn boxed() {
+        use std::sync::Arc;
+        use std::sync::Mutex;
+
+        /// Shared in-memory sink.
+        #[derive(Clone, Default)]
+        struct Sink(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for Sink {
+            fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+
+        arbtest(|u| {
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut sinks = Vec::new();
+            let mut boxed: Vec<Box<dyn Write + Send>> = Vec::new();
+            let mut encoders: Vec<AnyEncoder<Box<dyn Write + Send>>> = Vec::new();
+            for format in Format::DETECTION_ORDER.iter() {
+                let sink = Sink::default();
+                boxed.push(AnyEncoder::boxed(sink.clone(), *format, Compression::Fast).unwrap());
+                sinks.push(sink);
+                let sink = Sink::default();
+                encoders.push(
+                    AnyEncoder::new(
+                        Box::new(sink.clone()) as Box<dyn Write + Send>,
+                        *format,
+                        Compression::Fast,
+                    )
+                    .unwrap(),
+                );
+                sinks.push(sink);
+            }
+            for mut writer in boxed.into_iter() {
+                writer.write_all(&expected).unwrap();
+                if let Err(e) = writer.flush() {
+                    assert_eq!(ErrorKind::Unsupported, e.kind());
+                }
+            }
+            for mut writer in encoders.into_iter() {
+                writer.write_all(&expected).unwrap();
+                writer.finish().unwrap();
+            }
+            for sink in sinks.iter() {
+                let compressed = sink.0.lock().unwrap();
+                let mut reader = AnyDecoder::new(&compressed[..]);
+                let mut actual = Vec::new();
+                reader.read_to_end(&mut actual).unwrap();
+                assert_eq!(expected, actual);
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn flush() {
+        arbtest(|u| {
+            // verbatim data might be detected as some other format
+            let format = *u.choose(Format::DETECTION_ORDER)?;
+            let compression: Compression = arbitrary_compression(format, u)?;
+            let chunks: Vec<Vec<u8>> = u.arbitrary()?;
+            let mut writer = AnyEncoder::new(Vec::new(), format, compression).unwrap();
+            let mut expected = Vec::new();
+            for chunk in chunks.iter() {
+                writer.write_all(chunk).unwrap();
+                expected.extend_from_slice(chunk);
+                if let Err(e) = writer.flush() {
+                    assert!(!writer.supports_flush());
+                    assert_eq!(ErrorKind::Unsupported, e.kind());
+                    return Ok(());
+                }
+                // the stream is incomplete, i.e. the decoder fails at the end
+                let mut reader = AnyDecoder::new(&writer.get_ref()[..]);
+                let mut actual = vec![0_u8; expected.len()];
+                reader.read_exact(&mut actual).unwrap();
+                assert_eq!(expected, actual);
+            }
+            let compressed = writer.finish().unwrap();
+            let mut actual = Vec::new();
+            AnyDecoder::new(&compressed[..])
+                .read_to_end(&mut actual)
+                .unwrap();
+            assert_eq!(expected, actual);
+            Ok(())
+        });
+    }
+
     type AnyEncoderVecDeque = AnyEncoder<VecDeque<u8>>;
 
     fn new_any_encoder(
@@ -341,6 +1170,11 @@ mod tests {
         let format = writer.format();
         let inner = writer.finish().unwrap();
         let any: bool = u.arbitrary()?;
+        // brotli isn't detected by default, and raw deflate isn't detected at all
+        #[cfg(feature = "brotli")]
+        let any = any && format != Format::Brotli;
+        #[cfg(feature = "flate2")]
+        let any = any && format != Format::Deflate;
         let decoder: Box<dyn Read> = if any {
             Box::new(AnyDecoder::new(inner))
         } else {
@@ -350,12 +1184,30 @@ mod tests {
                 Format::Gz => Box::new(flate2::read::GzDecoder::new(inner)),
                 #[cfg(feature = "flate2")]
                 Format::Zlib => Box::new(flate2::read::ZlibDecoder::new(inner)),
+                #[cfg(feature = "flate2")]
+                Format::Deflate => Box::new(flate2::read::DeflateDecoder::new(inner)),
                 #[cfg(feature = "bzip2")]
                 Format::Bz => Box::new(bzip2::read::BzDecoder::new(inner)),
                 #[cfg(feature = "xz")]
                 Format::Xz => Box::new(xz::read::XzDecoder::new(inner)),
                 #[cfg(feature = "zstd")]
                 Format::Zstd => Box::new(zstd::stream::read::Decoder::new(inner).unwrap()),
+                #[cfg(feature = "zip")]
+                Format::Zip => Box::new(crate::read::AnyDecoder::new(inner)),
+                #[cfg(feature = "mscompress")]
+                Format::Szdd => Box::new(crate::read::AnyDecoder::new(inner)),
+                #[cfg(feature = "mscompress")]
+                Format::Kwaj => Box::new(crate::read::AnyDecoder::new(inner)),
+                #[cfg(feature = "lzop")]
+                Format::Lzop => Box::new(crate::read::AnyDecoder::new(inner)),
+                #[cfg(feature = "lzfse")]
+                Format::Lzfse => Box::new(crate::read::AnyDecoder::new(inner)),
+                #[cfg(feature = "lz4")]
+                Format::Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(inner)),
+                #[cfg(feature = "brotli")]
+                Format::Brotli => Box::new(crate::read::AnyDecoder::with_format(inner, format)),
+                // custom formats have no built-in encoders
+                Format::Other(..) => Box::new(crate::read::AnyDecoder::new(inner)),
             }
         };
         Ok(decoder)
@@ -372,12 +1224,29 @@ mod tests {
             Format::Gz => compression.clamp(0, 9),
             #[cfg(feature = "flate2")]
             Format::Zlib => compression.clamp(0, 9),
+            #[cfg(feature = "flate2")]
+            Format::Deflate => compression.clamp(0, 9),
             #[cfg(feature = "bzip2")]
             Format::Bz => compression.clamp(1, 9),
             #[cfg(feature = "xz")]
             Format::Xz => compression.clamp(0, 9),
             #[cfg(feature = "zstd")]
             Format::Zstd => compression.clamp(0, 22),
+            #[cfg(feature = "zip")]
+            Format::Zip => compression.clamp(0, 9),
+            #[cfg(feature = "mscompress")]
+            Format::Szdd => compression.clamp(0, 9),
+            #[cfg(feature = "mscompress")]
+            Format::Kwaj => compression.clamp(0, 9),
+            #[cfg(feature = "lzop")]
+            Format::Lzop => compression.clamp(1, 9),
+            #[cfg(feature = "lzfse")]
+            Format::Lzfse => compression.clamp(1, 9),
+            #[cfg(feature = "lz4")]
+            Format::Lz4 => compression,
+            #[cfg(feature = "brotli")]
+            Format::Brotli => compression,
+            Format::Other(..) => compression,
         })
     }
 
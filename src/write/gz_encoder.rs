@@ -0,0 +1,338 @@
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Write;
+
+use flate2::Compress;
+use flate2::Compression;
+use flate2::Crc;
+use flate2::FlushCompress;
+
+/// Gzip encoder that supports writing multiple members to the same output.
+///
+/// Each member is a complete gzip stream with its own header and trailer.
+/// Decoders that support multi-member files (e.g. `gzip -d`, [AnyDecoder](crate::AnyDecoder))
+/// decode all members as one stream.
+pub struct GzEncoder<W: Write> {
+    writer: W,
+    compress: Compress,
+    level: Compression,
+    crc: Crc,
+    header: GzHeader,
+    header_written: bool,
+    member_finished: bool,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> GzEncoder<W> {
+    /// Create new encoder with the default header.
+    pub fn new(writer: W, level: Compression) -> Self {
+        Self::with_header(writer, level, Default::default())
+    }
+
+    /// Create new encoder with the specified `header`.
+    ///
+    /// The header is reused for every member.
+    pub fn with_header(writer: W, level: Compression, header: GzHeader) -> Self {
+        Self {
+            writer,
+            compress: Compress::new(level, false),
+            level,
+            crc: Crc::new(),
+            header,
+            header_written: false,
+            member_finished: false,
+            buf: Vec::with_capacity(BUFFER_SIZE),
+        }
+    }
+
+    /// Get the header of the current member.
+    pub fn header(&self) -> &GzHeader {
+        &self.header
+    }
+
+    /// Finish the current member and start a new one with the same header.
+    pub fn start_new_member(&mut self) -> Result<(), Error> {
+        self.finish_member()?;
+        self.reset();
+        Ok(())
+    }
+
+    /// Finish the current member and start a new one with the new `header`.
+    pub fn start_new_member_with_header(&mut self, header: GzHeader) -> Result<(), Error> {
+        self.finish_member()?;
+        self.reset();
+        self.header = header;
+        Ok(())
+    }
+
+    /// Get immutable reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Get mutable reference to the underlying writer.
+    ///
+    /// Writing to the underlying writer might corrupt the output.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Finish encoding and return the underlying writer.
+    ///
+    /// This method is **not** automatically called on drop.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.finish_member()?;
+        Ok(self.writer)
+    }
+
+    fn finish_member(&mut self) -> Result<(), Error> {
+        if self.member_finished {
+            return Ok(());
+        }
+        self.write_header();
+        loop {
+            self.dump()?;
+            let before = self.compress.total_out();
+            self.compress
+                .compress_vec(&[], &mut self.buf, FlushCompress::Finish)
+                .map_err(Error::other)?;
+            if before == self.compress.total_out() {
+                break;
+            }
+        }
+        self.buf.extend(self.crc.sum().to_le_bytes());
+        self.buf.extend(self.crc.amount().to_le_bytes());
+        self.dump()?;
+        self.member_finished = true;
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.compress.reset();
+        self.crc.reset();
+        self.header_written = false;
+        self.member_finished = false;
+    }
+
+    fn write_header(&mut self) {
+        if self.header_written {
+            return;
+        }
+        self.header.write_to(&mut self.buf, self.level);
+        self.header_written = true;
+    }
+
+    fn dump(&mut self) -> Result<(), Error> {
+        while !self.buf.is_empty() {
+            let n = self.writer.write(&self.buf)?;
+            if n == 0 {
+                return Err(ErrorKind::WriteZero.into());
+            }
+            self.buf.drain(..n);
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for GzEncoder<W> {
+    fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        if self.member_finished {
+            return Err(Error::other("gzip member is finished"));
+        }
+        self.write_header();
+        loop {
+            self.dump()?;
+            let before = self.compress.total_in();
+            self.compress
+                .compress_vec(data, &mut self.buf, FlushCompress::None)
+                .map_err(Error::other)?;
+            let n = (self.compress.total_in() - before) as usize;
+            if n != 0 || data.is_empty() {
+                self.crc.update(&data[..n]);
+                return Ok(n);
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        if !self.member_finished {
+            self.write_header();
+            let mut flush = FlushCompress::Sync;
+            loop {
+                self.dump()?;
+                let before = self.compress.total_out();
+                self.compress
+                    .compress_vec(&[], &mut self.buf, flush)
+                    .map_err(Error::other)?;
+                if before == self.compress.total_out() {
+                    break;
+                }
+                flush = FlushCompress::None;
+            }
+        }
+        self.dump()?;
+        self.writer.flush()
+    }
+}
+
+/// Gzip member header.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct GzHeader {
+    /// Original file name.
+    pub filename: Option<Vec<u8>>,
+    /// File comment.
+    pub comment: Option<Vec<u8>>,
+    /// Extra field.
+    pub extra: Option<Vec<u8>>,
+    /// Modification time in seconds since Unix epoch.
+    pub mtime: u32,
+    /// Compression-effort hint (XFL).
+    ///
+    /// When not set, the value is derived from the compression level:
+    /// 2 for the best compression, 4 for the fastest compression, 0 otherwise.
+    pub extra_flags: Option<u8>,
+    /// Operating system on which the file was compressed (255 means unknown).
+    pub operating_system: u8,
+    /// Include CRC16 of the header (FHCRC).
+    ///
+    /// The field is optional, and most encoders don't write it,
+    /// however, some receivers require it.
+    pub header_crc: bool,
+}
+
+impl GzHeader {
+    fn write_to(&self, buf: &mut Vec<u8>, level: Compression) {
+        let start = buf.len();
+        let mut flags = 0;
+        if self.header_crc {
+            flags |= FHCRC;
+        }
+        if self.extra.is_some() {
+            flags |= FEXTRA;
+        }
+        if self.filename.is_some() {
+            flags |= FNAME;
+        }
+        if self.comment.is_some() {
+            flags |= FCOMMENT;
+        }
+        buf.extend([0x1f, 0x8b, 8, flags]);
+        buf.extend(self.mtime.to_le_bytes());
+        buf.push(self.extra_flags.unwrap_or_else(|| extra_flags(level)));
+        buf.push(self.operating_system);
+        if let Some(extra) = self.extra.as_ref() {
+            let len = extra.len().min(u16::MAX as usize);
+            buf.extend((len as u16).to_le_bytes());
+            buf.extend(&extra[..len]);
+        }
+        if let Some(filename) = self.filename.as_ref() {
+            buf.extend(filename.iter().filter(|b| **b != 0));
+            buf.push(0);
+        }
+        if let Some(comment) = self.comment.as_ref() {
+            buf.extend(comment.iter().filter(|b| **b != 0));
+            buf.push(0);
+        }
+        if self.header_crc {
+            let mut crc = Crc::new();
+            crc.update(&buf[start..]);
+            // the two least significant bytes of CRC32
+            buf.extend((crc.sum() as u16).to_le_bytes());
+        }
+    }
+}
+
+impl Default for GzHeader {
+    fn default() -> Self {
+        Self {
+            filename: None,
+            comment: None,
+            extra: None,
+            mtime: 0,
+            extra_flags: None,
+            operating_system: OS_UNKNOWN,
+            header_crc: false,
+        }
+    }
+}
+
+// The same values as in `flate2`.
+fn extra_flags(level: Compression) -> u8 {
+    if level.level() >= Compression::best().level() {
+        2
+    } else if level.level() <= Compression::fast().level() {
+        4
+    } else {
+        0
+    }
+}
+
+const FHCRC: u8 = 1 << 1;
+const FEXTRA: u8 = 1 << 2;
+const FNAME: u8 = 1 << 3;
+const FCOMMENT: u8 = 1 << 4;
+const OS_UNKNOWN: u8 = 255;
+const BUFFER_SIZE: usize = 32 * 1024;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use arbtest::arbtest;
+    use flate2::read::GzDecoder;
+    use flate2::read::MultiGzDecoder;
+
+    use super::*;
+
+    #[test]
+    fn multiple_members() {
+        arbtest(|u| {
+            let members: Vec<Vec<u8>> = u.arbitrary()?;
+            let level = Compression::new(u.int_in_range(0..=9)?);
+            let mut writer = GzEncoder::new(Vec::new(), level);
+            for (i, member) in members.iter().enumerate() {
+                if i != 0 {
+                    writer.start_new_member().unwrap();
+                }
+                writer.write_all(member).unwrap();
+            }
+            let compressed = writer.finish().unwrap();
+            let mut reader = MultiGzDecoder::new(&compressed[..]);
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(members.concat(), actual);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn header() {
+        arbtest(|u| {
+            let header = GzHeader {
+                filename: u.arbitrary::<Option<String>>()?.map(Into::into),
+                comment: u.arbitrary::<Option<String>>()?.map(Into::into),
+                extra: u.arbitrary()?,
+                mtime: u.arbitrary()?,
+                extra_flags: u.arbitrary()?,
+                operating_system: u.arbitrary()?,
+                header_crc: u.arbitrary()?,
+            };
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut writer =
+                GzEncoder::with_header(Vec::new(), Compression::fast(), header.clone());
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            assert_eq!(header.extra_flags.unwrap_or(4), compressed[8]);
+            let mut reader = GzDecoder::new(&compressed[..]);
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, actual);
+            let actual_header = reader.header().unwrap();
+            assert_eq!(header.mtime, actual_header.mtime());
+            assert_eq!(header.operating_system, actual_header.operating_system());
+            assert_eq!(header.extra.as_deref(), actual_header.extra());
+            assert_eq!(header.header_crc, compressed[3] & FHCRC != 0);
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,315 @@
+use std::io::Error;
+use std::io::Write;
+
+use crate::LZFSE_COMPRESSEDLZVN_BLOCK_MAGIC;
+use crate::LZFSE_ENDOFSTREAM_BLOCK_MAGIC;
+use crate::LZFSE_UNCOMPRESSED_BLOCK_MAGIC;
+
+/// LZFSE encoder.
+///
+/// The input is split into blocks of 256 KiB;
+/// each block is compressed with LZVN independently or is stored if it's not compressible.
+/// LZFSE entropy coding isn't used, however, the output is decodable by any LZFSE decoder
+/// (e.g. `lzfse` tool, Apple Compression library).
+pub struct LzfseEncoder<W: Write> {
+    writer: W,
+    lzvn: LzvnEncoder,
+    // uncompressed block
+    block: Vec<u8>,
+    // compressed block
+    compressed: Vec<u8>,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> LzfseEncoder<W> {
+    /// Create new encoder with the specified compression `level` (1–9).
+    ///
+    /// The level determines how many earlier positions are searched for the longest match.
+    pub fn new(writer: W, level: u32) -> Self {
+        let level = level.clamp(1, 9);
+        Self {
+            writer,
+            lzvn: LzvnEncoder::new(level),
+            block: Vec::with_capacity(BLOCK_SIZE),
+            compressed: Vec::new(),
+            buf: Vec::new(),
+        }
+    }
+
+    /// Get immutable reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Get mutable reference to the underlying writer.
+    ///
+    /// Writing to the underlying writer might corrupt the output.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Finish encoding and return the underlying writer.
+    ///
+    /// This method is **not** automatically called on drop.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.write_block();
+        self.buf.extend(LZFSE_ENDOFSTREAM_BLOCK_MAGIC.to_le_bytes());
+        self.writer.write_all(&self.buf)?;
+        Ok(self.writer)
+    }
+
+    /// Compress the current block and append it to the output buffer.
+    fn write_block(&mut self) {
+        if self.block.is_empty() {
+            return;
+        }
+        self.compressed.clear();
+        self.lzvn.compress(&self.block, &mut self.compressed);
+        if self.compressed.len() < self.block.len() {
+            self.buf
+                .extend(LZFSE_COMPRESSEDLZVN_BLOCK_MAGIC.to_le_bytes());
+            self.buf.extend((self.block.len() as u32).to_le_bytes());
+            self.buf
+                .extend((self.compressed.len() as u32).to_le_bytes());
+            self.buf.extend_from_slice(&self.compressed);
+        } else {
+            self.buf
+                .extend(LZFSE_UNCOMPRESSED_BLOCK_MAGIC.to_le_bytes());
+            self.buf.extend((self.block.len() as u32).to_le_bytes());
+            self.buf.extend_from_slice(&self.block);
+        }
+        self.block.clear();
+    }
+}
+
+impl<W: Write> Write for LzfseEncoder<W> {
+    fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        let mut data = data;
+        let len = data.len();
+        while !data.is_empty() {
+            let n = data.len().min(BLOCK_SIZE - self.block.len());
+            self.block.extend_from_slice(&data[..n]);
+            data = &data[n..];
+            if self.block.len() == BLOCK_SIZE {
+                self.write_block();
+                self.writer.write_all(&self.buf)?;
+                self.buf.clear();
+            }
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        // the blocks are independent, i.e. the current block can be finished at any time
+        self.write_block();
+        self.writer.write_all(&self.buf)?;
+        self.buf.clear();
+        self.writer.flush()
+    }
+}
+
+/// Greedy LZVN encoder with hash chains.
+struct LzvnEncoder {
+    // the last position of each hash
+    head: Vec<usize>,
+    // the previous position with the same hash
+    prev: Vec<usize>,
+    max_chain: usize,
+}
+
+impl LzvnEncoder {
+    fn new(level: u32) -> Self {
+        Self {
+            head: vec![NONE; HASH_SIZE],
+            prev: Vec::new(),
+            max_chain: 1 << (level - 1),
+        }
+    }
+
+    /// Compress the block `input` and append the result to `out`.
+    fn compress(&mut self, input: &[u8], out: &mut Vec<u8>) {
+        self.head.fill(NONE);
+        self.prev.clear();
+        self.prev.resize(input.len(), NONE);
+        let mut pos = 0;
+        let mut literals = 0;
+        while pos + MIN_MATCH <= input.len() {
+            let (distance, len) = self.find_match(input, pos);
+            if len >= MIN_MATCH {
+                write_match(&input[literals..pos], distance, len, out);
+                for i in pos..pos + len {
+                    self.insert(input, i);
+                }
+                pos += len;
+                literals = pos;
+            } else {
+                self.insert(input, pos);
+                pos += 1;
+            }
+        }
+        write_literals(&input[literals..], out);
+        // end-of-stream opcode followed by seven bytes of padding
+        out.extend([0x06, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    fn find_match(&self, input: &[u8], pos: usize) -> (usize, usize) {
+        let mut best = (0, 0);
+        let max_len = input.len() - pos;
+        let mut candidate = self.head[hash(input, pos)];
+        for _ in 0..self.max_chain {
+            if candidate == NONE || pos - candidate > MAX_DISTANCE {
+                break;
+            }
+            let len = (0..max_len)
+                .take_while(|i| input[candidate + i] == input[pos + i])
+                .count();
+            if len > best.1 {
+                best = (pos - candidate, len);
+                if len == max_len {
+                    break;
+                }
+            }
+            candidate = self.prev[candidate];
+        }
+        best
+    }
+
+    fn insert(&mut self, input: &[u8], pos: usize) {
+        if pos + MIN_MATCH > input.len() {
+            return;
+        }
+        let hash = hash(input, pos);
+        self.prev[pos] = self.head[hash];
+        self.head[hash] = pos;
+    }
+}
+
+/// Write the literals using literal-only opcodes.
+fn write_literals(literals: &[u8], out: &mut Vec<u8>) {
+    for chunk in literals.chunks(MAX_LITERAL_LEN) {
+        if chunk.len() < 16 {
+            out.push(0xe0 | chunk.len() as u8);
+        } else {
+            out.push(0xe0);
+            out.push((chunk.len() - 16) as u8);
+        }
+        out.extend_from_slice(chunk);
+    }
+}
+
+/// Write the literals that precede the match and the match itself.
+fn write_match(literals: &[u8], distance: usize, len: usize, out: &mut Vec<u8>) {
+    // up to three literals are stored in the match opcode
+    let literals = if literals.len() <= 3 {
+        literals
+    } else {
+        write_literals(literals, out);
+        &[]
+    };
+    let l = literals.len();
+    // the opcodes with longer matches are reserved for other instructions
+    let max_short_len = match l {
+        0 => 10,
+        1 => 8,
+        _ => 6,
+    };
+    let m = if distance < 0x600 {
+        let m = len.min(max_short_len);
+        out.push(((l << 6) | ((m - 3) << 3) | (distance >> 8)) as u8);
+        out.push(distance as u8);
+        m
+    } else if distance < 0x4000 {
+        let m = len.min(34);
+        out.push((0xa0 | (l << 3) | ((m - 3) >> 2)) as u8);
+        out.extend((((distance << 2) | ((m - 3) & 3)) as u16).to_le_bytes());
+        m
+    } else {
+        let m = len.min(max_short_len);
+        out.push(((l << 6) | ((m - 3) << 3) | 7) as u8);
+        out.extend((distance as u16).to_le_bytes());
+        m
+    };
+    out.extend_from_slice(literals);
+    // the rest of the match reuses the distance
+    let mut remaining = len - m;
+    while remaining != 0 {
+        let n = remaining.min(MAX_MATCH_LEN);
+        if n < 16 {
+            out.push(0xf0 | n as u8);
+        } else {
+            out.push(0xf0);
+            out.push((n - 16) as u8);
+        }
+        remaining -= n;
+    }
+}
+
+fn hash(input: &[u8], pos: usize) -> usize {
+    let a = input[pos] as usize;
+    let b = input[pos + 1] as usize;
+    let c = input[pos + 2] as usize;
+    ((a << 10) ^ (b << 5) ^ c) % HASH_SIZE
+}
+
+const BLOCK_SIZE: usize = 256 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_DISTANCE: usize = 0xffff;
+/// The longest literal run and match that are encoded in one opcode.
+const MAX_LITERAL_LEN: usize = 16 + 255;
+const MAX_MATCH_LEN: usize = 16 + 255;
+const NONE: usize = usize::MAX;
+const HASH_SIZE: usize = 1 << 15;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::test::has_reference_tool;
+    use crate::test::run_reference_tool;
+    use crate::LzfseDecoder;
+
+    #[test]
+    fn lzfse() {
+        arbtest(|u| {
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut writer = LzfseEncoder::new(Vec::new(), u.int_in_range(1..=9)?);
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            let mut actual = Vec::new();
+            LzfseDecoder::new(&compressed[..])
+                .read_to_end(&mut actual)
+                .unwrap();
+            assert_eq!(expected, actual);
+            Ok(())
+        });
+    }
+
+    // the reference encoder produces LZFSE blocks for large inputs and LZVN blocks for small ones
+    #[test]
+    fn lzfse_reference_tool() {
+        if !has_reference_tool("lzfse") {
+            return;
+        }
+        arbtest(|u| {
+            let chunk: Vec<u8> = u.arbitrary()?;
+            let expected = chunk.repeat(u.int_in_range(1..=100)?);
+            let compressed = run_reference_tool("lzfse", &["-encode"], &expected);
+            let mut actual = Vec::new();
+            LzfseDecoder::new(&compressed[..])
+                .read_to_end(&mut actual)
+                .unwrap();
+            assert_eq!(expected, actual);
+            let mut writer = LzfseEncoder::new(Vec::new(), u.int_in_range(1..=9)?);
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            assert_eq!(
+                expected,
+                run_reference_tool("lzfse", &["-decode"], &compressed)
+            );
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,348 @@
+use std::io::Error;
+use std::io::Write;
+
+use crate::adler32;
+use crate::LZOP_F_ADLER32_D;
+use crate::LZOP_MAGIC;
+use crate::LZOP_M_LZO1X_1;
+use crate::LZOP_VERSION_0940;
+
+/// `lzop` encoder.
+///
+/// The input is split into blocks of 256 KiB (the default block size of `lzop` tool);
+/// each block is compressed with LZO1X independently or is stored if it's not compressible.
+/// The header stores neither the file name nor the modification time.
+pub struct LzopEncoder<W: Write> {
+    writer: W,
+    lzo: Lzo1xEncoder,
+    // uncompressed block
+    block: Vec<u8>,
+    // compressed block
+    compressed: Vec<u8>,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> LzopEncoder<W> {
+    /// Create new encoder with the specified compression `level` (1–9).
+    pub fn new(writer: W, level: u32) -> Self {
+        let level = level.clamp(1, 9);
+        let mut buf = Vec::with_capacity(LZOP_HEADER_LEN);
+        buf.extend(LZOP_MAGIC);
+        let mut header = Vec::with_capacity(LZOP_HEADER_LEN);
+        header.extend(LZOP_VERSION.to_be_bytes());
+        header.extend(LZO_VERSION.to_be_bytes());
+        // version needed to extract
+        header.extend(LZOP_VERSION_0940.to_be_bytes());
+        header.push(LZOP_M_LZO1X_1);
+        header.push(level as u8);
+        header.extend(LZOP_F_ADLER32_D.to_be_bytes());
+        // mode
+        header.extend(0o100644_u32.to_be_bytes());
+        // modification time
+        header.extend(0_u64.to_be_bytes());
+        // no file name
+        header.push(0);
+        buf.extend(&header);
+        buf.extend(adler32(&header).to_be_bytes());
+        Self {
+            writer,
+            lzo: Lzo1xEncoder::new(level),
+            block: Vec::with_capacity(BLOCK_SIZE),
+            compressed: Vec::new(),
+            buf,
+        }
+    }
+
+    /// Get immutable reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Get mutable reference to the underlying writer.
+    ///
+    /// Writing to the underlying writer might corrupt the output.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Finish encoding and return the underlying writer.
+    ///
+    /// This method is **not** automatically called on drop.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.write_block();
+        // end-of-stream marker
+        self.buf.extend(0_u32.to_be_bytes());
+        self.writer.write_all(&self.buf)?;
+        Ok(self.writer)
+    }
+
+    /// Compress the current block and append it to the output buffer.
+    fn write_block(&mut self) {
+        if self.block.is_empty() {
+            return;
+        }
+        self.compressed.clear();
+        self.lzo.compress(&self.block, &mut self.compressed);
+        let data = if self.compressed.len() < self.block.len() {
+            &self.compressed
+        } else {
+            &self.block
+        };
+        self.buf.extend((self.block.len() as u32).to_be_bytes());
+        self.buf.extend((data.len() as u32).to_be_bytes());
+        self.buf.extend(adler32(&self.block).to_be_bytes());
+        self.buf.extend_from_slice(data);
+        self.block.clear();
+    }
+}
+
+impl<W: Write> Write for LzopEncoder<W> {
+    fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        let mut data = data;
+        let len = data.len();
+        while !data.is_empty() {
+            let n = data.len().min(BLOCK_SIZE - self.block.len());
+            self.block.extend_from_slice(&data[..n]);
+            data = &data[n..];
+            if self.block.len() == BLOCK_SIZE {
+                self.write_block();
+                self.writer.write_all(&self.buf)?;
+                self.buf.clear();
+            }
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        // the blocks are independent, i.e. the current block can be finished at any time
+        self.write_block();
+        self.writer.write_all(&self.buf)?;
+        self.buf.clear();
+        self.writer.flush()
+    }
+}
+
+/// Greedy LZO1X encoder with hash chains.
+///
+/// Only the long match instructions (M2, M3 and M4) are produced,
+/// i.e. the output is decodable by any LZO1X decompressor.
+struct Lzo1xEncoder {
+    // the last position of each hash
+    head: Vec<usize>,
+    // the previous position with the same hash
+    prev: Vec<usize>,
+    max_chain: usize,
+}
+
+impl Lzo1xEncoder {
+    fn new(level: u32) -> Self {
+        Self {
+            head: vec![NONE; HASH_SIZE],
+            prev: Vec::new(),
+            max_chain: 1 << (level - 1),
+        }
+    }
+
+    /// Compress the block `input` and append the result to `out`.
+    fn compress(&mut self, input: &[u8], out: &mut Vec<u8>) {
+        let start = out.len();
+        self.head.fill(NONE);
+        self.prev.clear();
+        self.prev.resize(input.len(), NONE);
+        let mut pos = 0;
+        let mut literals = 0;
+        while pos + MIN_MATCH <= input.len() {
+            let (distance, len) = self.find_match(input, pos);
+            if len >= MIN_MATCH {
+                write_literals(&input[literals..pos], out, start);
+                write_match(distance, len, out);
+                for i in pos..pos + len {
+                    self.insert(input, i);
+                }
+                pos += len;
+                literals = pos;
+            } else {
+                self.insert(input, pos);
+                pos += 1;
+            }
+        }
+        write_literals(&input[literals..], out, start);
+        // M4 instruction with zero distance
+        out.extend([M4_MARKER | 1, 0, 0]);
+    }
+
+    fn find_match(&self, input: &[u8], pos: usize) -> (usize, usize) {
+        let mut best = (0, 0);
+        let max_len = input.len() - pos;
+        let mut candidate = self.head[hash(input, pos)];
+        for _ in 0..self.max_chain {
+            if candidate == NONE || pos - candidate > M4_MAX_OFFSET {
+                break;
+            }
+            let distance = pos - candidate;
+            let len = (0..max_len)
+                .take_while(|i| input[candidate + i] == input[pos + i])
+                .count();
+            // three-byte matches are encoded in three bytes unless the distance is short
+            if len > best.1 && (len > MIN_MATCH || distance <= M2_MAX_OFFSET) {
+                best = (distance, len);
+                if len == max_len {
+                    break;
+                }
+            }
+            candidate = self.prev[candidate];
+        }
+        best
+    }
+
+    fn insert(&mut self, input: &[u8], pos: usize) {
+        if pos + MIN_MATCH > input.len() {
+            return;
+        }
+        let hash = hash(input, pos);
+        self.prev[pos] = self.head[hash];
+        self.head[hash] = pos;
+    }
+}
+
+/// Write the run of literals that precedes the next match or the end of the stream.
+///
+/// `start` is the position of the first byte of the compressed block in `out`.
+fn write_literals(literals: &[u8], out: &mut Vec<u8>, start: usize) {
+    let len = literals.len();
+    if len == 0 {
+        return;
+    }
+    if out.len() == start && len <= 238 {
+        // the first literal run
+        out.push((17 + len) as u8);
+    } else if len <= 3 {
+        // short runs are stored in the lowest bits of the previous match
+        let i = out.len() - 2;
+        out[i] |= len as u8;
+    } else if len <= 18 {
+        out.push((len - 3) as u8);
+    } else {
+        out.push(0);
+        write_length(len - 18, out);
+    }
+    out.extend_from_slice(literals);
+}
+
+fn write_match(distance: usize, len: usize, out: &mut Vec<u8>) {
+    if len <= M2_MAX_LEN && distance <= M2_MAX_OFFSET {
+        let distance = distance - 1;
+        out.push((((len - 1) << 5) | ((distance & 7) << 2)) as u8);
+        out.push((distance >> 3) as u8);
+        return;
+    }
+    let distance = if distance <= M3_MAX_OFFSET {
+        if len <= M3_MAX_LEN {
+            out.push(M3_MARKER | (len - 2) as u8);
+        } else {
+            out.push(M3_MARKER);
+            write_length(len - M3_MAX_LEN, out);
+        }
+        distance - 1
+    } else {
+        let distance = distance - 0x4000;
+        let high = ((distance >> 11) & 8) as u8;
+        if len <= M4_MAX_LEN {
+            out.push(M4_MARKER | high | (len - 2) as u8);
+        } else {
+            out.push(M4_MARKER | high);
+            write_length(len - M4_MAX_LEN, out);
+        }
+        distance
+    };
+    out.push((distance << 2) as u8);
+    out.push((distance >> 6) as u8);
+}
+
+/// Write the length that doesn't fit into the instruction byte as a sequence of zero bytes
+/// (each one adds 255) followed by the non-zero remainder.
+fn write_length(mut len: usize, out: &mut Vec<u8>) {
+    while len > 255 {
+        len -= 255;
+        out.push(0);
+    }
+    out.push(len as u8);
+}
+
+fn hash(input: &[u8], pos: usize) -> usize {
+    let a = input[pos] as usize;
+    let b = input[pos + 1] as usize;
+    let c = input[pos + 2] as usize;
+    ((a << 10) ^ (b << 5) ^ c) % HASH_SIZE
+}
+
+/// The version of `lzop` tool that the header claims.
+const LZOP_VERSION: u16 = 0x1030;
+/// The version of LZO library that the header claims.
+const LZO_VERSION: u16 = 0x2080;
+/// Magic, version, library version, version needed to extract, method, level, flags, mode,
+/// modification time, file name length and header checksum.
+const LZOP_HEADER_LEN: usize = 9 + 2 + 2 + 2 + 1 + 1 + 4 + 4 + 8 + 1 + 4;
+const BLOCK_SIZE: usize = 256 * 1024;
+const MIN_MATCH: usize = 3;
+const M2_MAX_LEN: usize = 8;
+const M3_MAX_LEN: usize = 33;
+const M4_MAX_LEN: usize = 9;
+const M2_MAX_OFFSET: usize = 0x0800;
+const M3_MAX_OFFSET: usize = 0x4000;
+const M4_MAX_OFFSET: usize = 0xbfff;
+const M3_MARKER: u8 = 32;
+const M4_MARKER: u8 = 16;
+const NONE: usize = usize::MAX;
+const HASH_SIZE: usize = 1 << 15;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::test::has_reference_tool;
+    use crate::test::run_reference_tool;
+    use crate::LzopDecoder;
+
+    #[test]
+    fn lzop() {
+        arbtest(|u| {
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut writer = LzopEncoder::new(Vec::new(), u.int_in_range(1..=9)?);
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            let mut actual = Vec::new();
+            LzopDecoder::new(&compressed[..])
+                .read_to_end(&mut actual)
+                .unwrap();
+            assert_eq!(expected, actual);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn lzop_reference_tool() {
+        if !has_reference_tool("lzop") {
+            return;
+        }
+        arbtest(|u| {
+            let chunk: Vec<u8> = u.arbitrary()?;
+            let expected = chunk.repeat(u.int_in_range(1..=100)?);
+            let level = format!("-{}", u.int_in_range(1..=9)?);
+            let compressed = run_reference_tool("lzop", &["-c", &level], &expected);
+            let mut actual = Vec::new();
+            LzopDecoder::new(&compressed[..])
+                .read_to_end(&mut actual)
+                .unwrap();
+            assert_eq!(expected, actual);
+            let mut writer = LzopEncoder::new(Vec::new(), u.int_in_range(1..=9)?);
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            assert_eq!(expected, run_reference_tool("lzop", &["-dc"], &compressed));
+            Ok(())
+        });
+    }
+}
@@ -1,5 +1,56 @@
 //! Types that wrap [Write](std::io::Write) streams.
 
+#[cfg(feature = "zstd")]
+mod adaptive_zstd_encoder;
+mod append;
+#[cfg(feature = "flate2")]
+mod bgzf_encoder;
+#[cfg(feature = "brotli")]
+mod brotli_encoder;
+mod checkpoint;
+mod config;
+mod decoder;
 mod encoder;
+#[cfg(feature = "flate2")]
+mod gz_encoder;
+#[cfg(feature = "lzfse")]
+mod lzfse_encoder;
+#[cfg(feature = "lzop")]
+mod lzop_encoder;
+#[cfg(feature = "mscompress")]
+mod mscompress_encoder;
+#[cfg(feature = "zstd")]
+mod seekable_zstd_encoder;
+#[cfg(feature = "xz")]
+mod xz_block_encoder;
+#[cfg(feature = "zip")]
+mod zip_encoder;
+#[cfg(feature = "zstd")]
+mod zstd_encoder;
 
+#[cfg(feature = "zstd")]
+pub use self::adaptive_zstd_encoder::*;
+#[cfg(feature = "flate2")]
+pub use self::bgzf_encoder::*;
+#[cfg(feature = "brotli")]
+pub use self::brotli_encoder::*;
+pub use self::checkpoint::*;
+pub use self::config::*;
+pub use self::decoder::*;
 pub use self::encoder::*;
+#[cfg(feature = "flate2")]
+pub use self::gz_encoder::*;
+#[cfg(feature = "lzfse")]
+pub use self::lzfse_encoder::*;
+#[cfg(feature = "lzop")]
+pub use self::lzop_encoder::*;
+#[cfg(feature = "mscompress")]
+pub use self::mscompress_encoder::*;
+#[cfg(feature = "zstd")]
+pub use self::seekable_zstd_encoder::*;
+#[cfg(feature = "xz")]
+pub use self::xz_block_encoder::*;
+#[cfg(feature = "zip")]
+pub use self::zip_encoder::*;
+#[cfg(feature = "zstd")]
+pub use self::zstd_encoder::*;
@@ -0,0 +1,366 @@
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Write;
+
+use crate::KWAJ_HEADER_LEN;
+use crate::KWAJ_LZSS;
+use crate::KWAJ_MAGIC;
+use crate::MS_LZSS_MAX_MATCH;
+use crate::MS_LZSS_MIN_MATCH;
+use crate::MS_LZSS_START;
+use crate::MS_WINDOW_SIZE;
+use crate::SZDD_MAGIC;
+
+/// `SZDD` encoder (the format of Microsoft `COMPRESS.EXE`).
+///
+/// The header stores the uncompressed size,
+/// hence the compressed data is kept in memory until [finish](Self::finish) is called.
+/// The input can't be larger than 4 GiB.
+pub struct SzddEncoder<W: Write> {
+    writer: W,
+    lzss: LzssEncoder,
+    buf: Vec<u8>,
+    size: u64,
+}
+
+impl<W: Write> SzddEncoder<W> {
+    /// Create new encoder with the specified compression `level` (0–9).
+    ///
+    /// Level 0 means no compression.
+    pub fn new(writer: W, level: u32) -> Self {
+        Self {
+            writer,
+            lzss: LzssEncoder::new(level),
+            buf: Vec::new(),
+            size: 0,
+        }
+    }
+
+    /// Get immutable reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Get mutable reference to the underlying writer.
+    ///
+    /// Writing to the underlying writer might corrupt the output.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Finish encoding and return the underlying writer.
+    ///
+    /// This method is **not** automatically called on drop.
+    pub fn finish(mut self) -> Result<W, Error> {
+        let Ok(size) = u32::try_from(self.size) else {
+            return Err(Error::new(
+                ErrorKind::FileTooLarge,
+                "szdd files larger than 4 GiB are not supported",
+            ));
+        };
+        self.lzss.finish(&mut self.buf);
+        self.writer.write_all(&SZDD_MAGIC)?;
+        // compression mode
+        self.writer.write_all(b"A")?;
+        // the last character of the file name is unknown
+        self.writer.write_all(&[0])?;
+        self.writer.write_all(&size.to_le_bytes())?;
+        self.writer.write_all(&self.buf)?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for SzddEncoder<W> {
+    fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        self.lzss.compress(data, &mut self.buf);
+        self.size += data.len() as u64;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        // nothing can be written before the header
+        Ok(())
+    }
+}
+
+/// `KWAJ` encoder that uses LZSS compression method.
+///
+/// The optional headers (the uncompressed size, the file name etc.) are not written,
+/// i.e. the output is written sequentially.
+pub struct KwajEncoder<W: Write> {
+    writer: W,
+    lzss: LzssEncoder,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> KwajEncoder<W> {
+    /// Create new encoder with the specified compression `level` (0–9).
+    ///
+    /// Level 0 means no compression.
+    pub fn new(writer: W, level: u32) -> Self {
+        let mut buf = Vec::with_capacity(BUFFER_SIZE);
+        buf.extend(KWAJ_MAGIC);
+        buf.extend(KWAJ_LZSS.to_le_bytes());
+        // data offset
+        buf.extend((KWAJ_HEADER_LEN as u16).to_le_bytes());
+        // no optional headers
+        buf.extend(0_u16.to_le_bytes());
+        Self {
+            writer,
+            lzss: LzssEncoder::new(level),
+            buf,
+        }
+    }
+
+    /// Get immutable reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Get mutable reference to the underlying writer.
+    ///
+    /// Writing to the underlying writer might corrupt the output.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Finish encoding and return the underlying writer.
+    ///
+    /// This method is **not** automatically called on drop.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.lzss.finish(&mut self.buf);
+        self.writer.write_all(&self.buf)?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for KwajEncoder<W> {
+    fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        self.lzss.compress(data, &mut self.buf);
+        if self.buf.len() >= BUFFER_SIZE {
+            self.writer.write_all(&self.buf)?;
+            self.buf.clear();
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        // the last few bytes are kept until more data arrives or the encoder is finished
+        self.writer.write_all(&self.buf)?;
+        self.buf.clear();
+        self.writer.flush()
+    }
+}
+
+/// Greedy LZSS encoder (`COMPRESS.EXE` variant) with hash chains.
+struct LzssEncoder {
+    // the window followed by the data that is not encoded yet
+    data: Vec<u8>,
+    // the position of the first byte of `data` in the input
+    base: usize,
+    // the position of the next byte to encode
+    pos: usize,
+    // the last position of each hash
+    head: Vec<usize>,
+    // the previous position with the same hash
+    prev: Vec<usize>,
+    max_chain: usize,
+    // the control byte followed by up to eight literals and matches
+    group: Vec<u8>,
+    group_len: u32,
+}
+
+impl LzssEncoder {
+    fn new(level: u32) -> Self {
+        Self {
+            data: Vec::new(),
+            base: 0,
+            pos: 0,
+            head: vec![NONE; HASH_SIZE],
+            prev: vec![NONE; MS_WINDOW_SIZE],
+            max_chain: match level.min(9) {
+                0 => 0,
+                level => 1 << (level - 1),
+            },
+            group: Vec::with_capacity(1 + 2 * 8),
+            group_len: 0,
+        }
+    }
+
+    fn compress(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        self.data.extend_from_slice(data);
+        while self.pos + MS_LZSS_MAX_MATCH <= self.end() {
+            self.encode_next(out);
+        }
+        // keep only the window
+        let n = self.pos.saturating_sub(self.base + MS_WINDOW_SIZE);
+        if n >= MS_WINDOW_SIZE {
+            self.data.drain(..n);
+            self.base += n;
+        }
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) {
+        while self.pos < self.end() {
+            self.encode_next(out);
+        }
+        if self.group_len != 0 {
+            out.extend_from_slice(&self.group);
+            self.group.clear();
+            self.group_len = 0;
+        }
+    }
+
+    fn end(&self) -> usize {
+        self.base + self.data.len()
+    }
+
+    fn byte(&self, pos: usize) -> u8 {
+        self.data[pos - self.base]
+    }
+
+    fn encode_next(&mut self, out: &mut Vec<u8>) {
+        if self.group_len == 0 {
+            self.group.push(0);
+        }
+        let max_len = MS_LZSS_MAX_MATCH.min(self.end() - self.pos);
+        let (match_pos, match_len) = self.find_match(max_len);
+        let len = if match_len >= MS_LZSS_MIN_MATCH {
+            let window_pos = (match_pos + MS_LZSS_START) % MS_WINDOW_SIZE;
+            self.group.push(window_pos as u8);
+            self.group
+                .push((((window_pos >> 4) & 0xf0) | (match_len - MS_LZSS_MIN_MATCH)) as u8);
+            match_len
+        } else {
+            self.group[0] |= 1 << self.group_len;
+            self.group.push(self.byte(self.pos));
+            1
+        };
+        for _ in 0..len {
+            self.insert();
+            self.pos += 1;
+        }
+        self.group_len += 1;
+        if self.group_len == 8 {
+            out.extend_from_slice(&self.group);
+            self.group.clear();
+            self.group_len = 0;
+        }
+    }
+
+    fn find_match(&self, max_len: usize) -> (usize, usize) {
+        let mut best = (0, 0);
+        if max_len < MS_LZSS_MIN_MATCH {
+            return best;
+        }
+        let mut candidate = self.head[self.hash(self.pos)];
+        for _ in 0..self.max_chain {
+            if candidate == NONE || self.pos - candidate > MS_WINDOW_SIZE - 1 {
+                break;
+            }
+            let len = (0..max_len)
+                .take_while(|i| self.byte(candidate + i) == self.byte(self.pos + i))
+                .count();
+            if len > best.1 {
+                best = (candidate, len);
+                if len == max_len {
+                    break;
+                }
+            }
+            let next = self.prev[candidate % MS_WINDOW_SIZE];
+            // the slot was reused by a newer position
+            if next == NONE || next >= candidate {
+                break;
+            }
+            candidate = next;
+        }
+        best
+    }
+
+    fn insert(&mut self) {
+        if self.pos + MS_LZSS_MIN_MATCH > self.end() {
+            return;
+        }
+        let hash = self.hash(self.pos);
+        self.prev[self.pos % MS_WINDOW_SIZE] = self.head[hash];
+        self.head[hash] = self.pos;
+    }
+
+    fn hash(&self, pos: usize) -> usize {
+        let a = self.byte(pos) as usize;
+        let b = self.byte(pos + 1) as usize;
+        let c = self.byte(pos + 2) as usize;
+        ((a << 10) ^ (b << 5) ^ c) % HASH_SIZE
+    }
+}
+
+const NONE: usize = usize::MAX;
+const HASH_SIZE: usize = 1 << 15;
+const BUFFER_SIZE: usize = 32 * 1024;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::test::has_reference_tool;
+    use crate::test::run_reference_tool;
+    use crate::MsCompressDecoder;
+
+    #[test]
+    fn szdd() {
+        arbtest(|u| {
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut writer = SzddEncoder::new(Vec::new(), u.int_in_range(0..=9)?);
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            let mut actual = Vec::new();
+            MsCompressDecoder::szdd(&compressed[..])
+                .read_to_end(&mut actual)
+                .unwrap();
+            assert_eq!(expected, actual);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn kwaj() {
+        arbtest(|u| {
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut writer = KwajEncoder::new(Vec::new(), u.int_in_range(0..=9)?);
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            let mut actual = Vec::new();
+            MsCompressDecoder::kwaj(&compressed[..])
+                .read_to_end(&mut actual)
+                .unwrap();
+            assert_eq!(expected, actual);
+            Ok(())
+        });
+    }
+
+    // `mscompress` and `msexpand` from `mscompress` package, there is no such tool for KWAJ
+    #[test]
+    fn szdd_reference_tool() {
+        if !has_reference_tool("mscompress") || !has_reference_tool("msexpand") {
+            return;
+        }
+        arbtest(|u| {
+            let chunk: Vec<u8> = u.arbitrary()?;
+            let expected = chunk.repeat(u.int_in_range(1..=100)?);
+            let compressed = run_reference_tool("mscompress", &[], &expected);
+            let mut actual = Vec::new();
+            MsCompressDecoder::szdd(&compressed[..])
+                .read_to_end(&mut actual)
+                .unwrap();
+            assert_eq!(expected, actual);
+            let mut writer = SzddEncoder::new(Vec::new(), u.int_in_range(0..=9)?);
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            assert_eq!(expected, run_reference_tool("msexpand", &[], &compressed));
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,212 @@
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Write;
+
+use zstd::stream::raw::CParameter;
+
+use crate::write::ZstdEncoder;
+use crate::ZSTD_SEEKABLE_MAGIC;
+use crate::ZSTD_SEEK_TABLE_MAGIC;
+
+/// Zstd encoder that produces seekable files.
+///
+/// The input is split into independent frames of fixed uncompressed size,
+/// and their sizes are stored in the seek table at the end of the file
+/// ([format](https://github.com/facebook/zstd/blob/dev/contrib/seekable_format/zstd_seekable_compression_format.md)).
+/// Such files can be accessed randomly via [SeekableZstdDecoder](crate::SeekableZstdDecoder)
+/// or the reference `zstd` seekable library; other decoders skip the seek table.
+/// The seek table doesn't include the checksums of the frames.
+pub struct SeekableZstdEncoder<W: Write> {
+    encoder: ZstdEncoder<CountingWriter<W>>,
+    frame_size: u64,
+    // uncompressed bytes written to the current frame
+    frame_len: u64,
+    // the offset of the current frame in the output
+    frame_offset: u64,
+    // seek table entries
+    entries: Vec<u8>,
+    num_frames: u32,
+}
+
+impl<W: Write> SeekableZstdEncoder<W> {
+    /// Create new encoder with the specified compression `level`
+    /// and uncompressed `frame_size`.
+    ///
+    /// The frame size is clamped to 1 B–1 GiB range.
+    pub fn new(writer: W, level: i32, frame_size: u64) -> Result<Self, Error> {
+        let writer = CountingWriter { writer, count: 0 };
+        Ok(Self {
+            encoder: ZstdEncoder::new(writer, level)?,
+            frame_size: frame_size.clamp(1, MAX_FRAME_SIZE),
+            frame_len: 0,
+            frame_offset: 0,
+            entries: Vec::new(),
+            num_frames: 0,
+        })
+    }
+
+    /// Set compression parameter.
+    ///
+    /// The parameter is applied to the current frame and all subsequent frames.
+    pub fn set_parameter(&mut self, parameter: CParameter) -> Result<(), Error> {
+        self.encoder.set_parameter(parameter)
+    }
+
+    /// Get immutable reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.encoder.get_ref().writer
+    }
+
+    /// Get mutable reference to the underlying writer.
+    ///
+    /// Writing to the underlying writer might corrupt the output.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.encoder.get_mut().writer
+    }
+
+    /// Finish encoding, write the seek table and return the underlying writer.
+    ///
+    /// This method is **not** automatically called on drop.
+    pub fn finish(mut self) -> Result<W, Error> {
+        let mut writer = self.encoder.finish()?;
+        add_entry(
+            &mut self.entries,
+            &mut self.num_frames,
+            writer.count - self.frame_offset,
+            self.frame_len,
+        )?;
+        let mut payload = self.entries;
+        payload.extend(self.num_frames.to_le_bytes());
+        // no checksums
+        payload.push(0);
+        payload.extend(ZSTD_SEEKABLE_MAGIC.to_le_bytes());
+        writer.write_all(&ZSTD_SEEK_TABLE_MAGIC.to_le_bytes())?;
+        writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+        writer.write_all(&payload)?;
+        Ok(writer.writer)
+    }
+
+    /// Finish the current frame and record it in the seek table.
+    fn start_new_frame(&mut self) -> Result<(), Error> {
+        self.encoder.start_new_frame()?;
+        let offset = self.encoder.get_ref().count;
+        add_entry(
+            &mut self.entries,
+            &mut self.num_frames,
+            offset - self.frame_offset,
+            self.frame_len,
+        )?;
+        self.frame_offset = offset;
+        self.frame_len = 0;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for SeekableZstdEncoder<W> {
+    fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        // the frame is finished lazily to not produce empty frame at the end
+        if self.frame_len == self.frame_size && !data.is_empty() {
+            self.start_new_frame()?;
+        }
+        let max = data.len().min((self.frame_size - self.frame_len) as usize);
+        let n = self.encoder.write(&data[..max])?;
+        self.frame_len += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.encoder.flush()
+    }
+}
+
+fn add_entry(
+    entries: &mut Vec<u8>,
+    num_frames: &mut u32,
+    compressed_size: u64,
+    uncompressed_size: u64,
+) -> Result<(), Error> {
+    let Ok(compressed_size) = u32::try_from(compressed_size) else {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "zstd frame is too large for the seek table",
+        ));
+    };
+    if *num_frames == MAX_FRAMES {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "too many zstd frames for the seek table",
+        ));
+    }
+    entries.extend(compressed_size.to_le_bytes());
+    entries.extend((uncompressed_size as u32).to_le_bytes());
+    *num_frames += 1;
+    Ok(())
+}
+
+/// Counts the bytes written to the underlying writer.
+struct CountingWriter<W> {
+    writer: W,
+    count: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        let n = self.writer.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()
+    }
+}
+
+/// The limits of the reference implementation.
+const MAX_FRAME_SIZE: u64 = 1024 * 1024 * 1024;
+const MAX_FRAMES: u32 = 0x0800_0000;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::io::Read;
+    use std::io::Seek;
+    use std::io::SeekFrom;
+
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::bufread::AnyDecoder;
+    use crate::zstd_seek_table;
+    use crate::SeekableZstdDecoder;
+
+    #[test]
+    fn seekable() {
+        arbtest(|u| {
+            let expected: Vec<u8> = u.arbitrary()?;
+            let frame_size = u.int_in_range(1..=100)?;
+            let mut writer = SeekableZstdEncoder::new(Vec::new(), 1, frame_size).unwrap();
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            let frames = zstd_seek_table(Cursor::new(&compressed)).unwrap().unwrap();
+            assert_eq!(
+                expected.len().div_ceil(frame_size as usize).max(1),
+                frames.len()
+            );
+            let mut actual = Vec::new();
+            AnyDecoder::new(&compressed[..])
+                .read_to_end(&mut actual)
+                .unwrap();
+            assert_eq!(expected, actual);
+            let mut reader = SeekableZstdDecoder::new(Cursor::new(&compressed)).unwrap();
+            if expected.is_empty() {
+                return Ok(());
+            }
+            let offset = u.int_in_range(0..=expected.len() - 1)?;
+            let mut actual = Vec::new();
+            reader.seek(SeekFrom::Start(offset as u64)).unwrap();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(&expected[offset..], &actual[..]);
+            Ok(())
+        });
+    }
+}
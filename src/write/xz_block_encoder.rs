@@ -0,0 +1,125 @@
+use std::io::Error;
+use std::io::Write;
+
+use xz::stream::Check;
+use xz::stream::Stream;
+use xz::write::XzEncoder;
+
+/// XZ encoder that splits the output into blocks of fixed uncompressed size.
+///
+/// The sizes of the blocks are stored in the stream index,
+/// i.e. the blocks can be located via [xz_streams](crate::xz_streams)
+/// and decompressed independently (see [xz_decode_block](crate::xz_decode_block)).
+/// Each [flush](Write::flush) ends the current block as well.
+pub struct XzBlockEncoder<W: Write> {
+    inner: XzEncoder<W>,
+    block_size: u64,
+    // uncompressed bytes written to the current block
+    block_len: u64,
+}
+
+impl<W: Write> XzBlockEncoder<W> {
+    /// Create new encoder with the specified compression `level` (0–9) and CRC64 check.
+    ///
+    /// Zero `block_size` is treated as one.
+    pub fn new(writer: W, level: u32, block_size: u64) -> Result<Self, Error> {
+        let stream = Stream::new_easy_encoder(level, Check::Crc64)?;
+        Ok(Self::new_stream(writer, stream, block_size))
+    }
+
+    /// Create new encoder from the supplied encoder `stream`.
+    ///
+    /// Zero `block_size` is treated as one.
+    pub fn new_stream(writer: W, stream: Stream, block_size: u64) -> Self {
+        Self {
+            inner: XzEncoder::new_stream(writer, stream),
+            block_size: block_size.max(1),
+            block_len: 0,
+        }
+    }
+
+    /// Get immutable reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        self.inner.get_ref()
+    }
+
+    /// Get mutable reference to the underlying writer.
+    ///
+    /// Writing to the underlying writer might corrupt the output.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.get_mut()
+    }
+
+    /// Finish encoding and return the underlying writer.
+    ///
+    /// This method is **not** automatically called on drop.
+    pub fn finish(self) -> Result<W, Error> {
+        self.inner.finish()
+    }
+}
+
+impl<W: Write> Write for XzBlockEncoder<W> {
+    fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        let max = data.len().min(
+            (self.block_size - self.block_len)
+                .try_into()
+                .unwrap_or(usize::MAX),
+        );
+        let n = self.inner.write(&data[..max])?;
+        self.block_len += n as u64;
+        if self.block_len == self.block_size {
+            // full flush ends the block
+            self.flush()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        // liblzma keeps the output of the full flush until the next call
+        self.inner.flush()?;
+        self.inner.flush()?;
+        self.block_len = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::io::Read;
+
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::bufread::AnyDecoder;
+    use crate::xz_decode_block;
+    use crate::xz_streams;
+
+    #[test]
+    fn blocks() {
+        arbtest(|u| {
+            let expected: Vec<u8> = u.arbitrary()?;
+            let block_size = u.int_in_range(1..=100)?;
+            let mut writer = XzBlockEncoder::new(Vec::new(), 0, block_size).unwrap();
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            let streams = xz_streams(Cursor::new(&compressed[..])).unwrap();
+            assert_eq!(1, streams.len());
+            let blocks = &streams[0].blocks;
+            assert_eq!(expected.len().div_ceil(block_size as usize), blocks.len());
+            let mut reader = Cursor::new(&compressed[..]);
+            let mut actual = Vec::new();
+            for block in blocks.iter() {
+                assert!(block.uncompressed_size <= block_size);
+                actual.extend(xz_decode_block(&mut reader, block).unwrap());
+            }
+            assert_eq!(expected, actual);
+            let mut actual = Vec::new();
+            AnyDecoder::new(&compressed[..])
+                .read_to_end(&mut actual)
+                .unwrap();
+            assert_eq!(expected, actual);
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,238 @@
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Write;
+
+use flate2::Compress;
+use flate2::Compression;
+use flate2::Crc;
+use flate2::FlushCompress;
+
+use crate::ZIP_DATA_DESCRIPTOR_MAGIC;
+use crate::ZIP_FLAG_DATA_DESCRIPTOR;
+use crate::ZIP_LOCAL_HEADER_LEN;
+use crate::ZIP_LOCAL_HEADER_MAGIC;
+use crate::ZIP_METHOD_DEFLATE;
+
+/// ZIP encoder that writes a single _deflate_-compressed entry.
+///
+/// The sizes and the checksum are not known in advance and are written in the data descriptor
+/// that follows the entry, i.e. the output is written sequentially.
+/// The entry can't be larger than 4 GiB (ZIP64 is not supported).
+pub struct ZipEncoder<W: Write> {
+    writer: W,
+    compress: Compress,
+    crc: Crc,
+    name: Vec<u8>,
+    header_written: bool,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> ZipEncoder<W> {
+    /// Create new encoder with entry name `-` (the same as `zip` uses for standard input).
+    pub fn new(writer: W, level: Compression) -> Self {
+        Self::with_name(writer, level, b"-".to_vec())
+    }
+
+    /// Create new encoder with the specified entry `name`.
+    pub fn with_name(writer: W, level: Compression, mut name: Vec<u8>) -> Self {
+        name.truncate(u16::MAX as usize);
+        Self {
+            writer,
+            compress: Compress::new(level, false),
+            crc: Crc::new(),
+            name,
+            header_written: false,
+            buf: Vec::with_capacity(BUFFER_SIZE),
+        }
+    }
+
+    /// Get immutable reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Get mutable reference to the underlying writer.
+    ///
+    /// Writing to the underlying writer might corrupt the output.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Finish encoding and return the underlying writer.
+    ///
+    /// This method is **not** automatically called on drop.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.write_header();
+        loop {
+            self.dump()?;
+            let before = self.compress.total_out();
+            self.compress
+                .compress_vec(&[], &mut self.buf, FlushCompress::Finish)
+                .map_err(Error::other)?;
+            if before == self.compress.total_out() {
+                break;
+            }
+        }
+        let (Ok(compressed_size), Ok(uncompressed_size)) = (
+            u32::try_from(self.compress.total_out()),
+            u32::try_from(self.compress.total_in()),
+        ) else {
+            return Err(Error::new(
+                ErrorKind::FileTooLarge,
+                "zip entries larger than 4 GiB are not supported",
+            ));
+        };
+        let crc32 = self.crc.sum();
+        self.buf.extend(ZIP_DATA_DESCRIPTOR_MAGIC);
+        self.buf.extend(crc32.to_le_bytes());
+        self.buf.extend(compressed_size.to_le_bytes());
+        self.buf.extend(uncompressed_size.to_le_bytes());
+        let central_directory_offset = (ZIP_LOCAL_HEADER_LEN + self.name.len()) as u64
+            + compressed_size as u64
+            + DATA_DESCRIPTOR_LEN as u64;
+        let central_directory_len = CENTRAL_HEADER_LEN + self.name.len();
+        let Ok(central_directory_offset) = u32::try_from(central_directory_offset) else {
+            return Err(Error::new(
+                ErrorKind::FileTooLarge,
+                "zip files larger than 4 GiB are not supported",
+            ));
+        };
+        // central directory
+        self.buf.extend(CENTRAL_HEADER_MAGIC);
+        self.buf.extend(VERSION.to_le_bytes());
+        self.buf.extend(VERSION.to_le_bytes());
+        self.buf.extend(ZIP_FLAG_DATA_DESCRIPTOR.to_le_bytes());
+        self.buf.extend(ZIP_METHOD_DEFLATE.to_le_bytes());
+        self.buf.extend(DOS_TIME.to_le_bytes());
+        self.buf.extend(DOS_DATE.to_le_bytes());
+        self.buf.extend(crc32.to_le_bytes());
+        self.buf.extend(compressed_size.to_le_bytes());
+        self.buf.extend(uncompressed_size.to_le_bytes());
+        self.buf.extend((self.name.len() as u16).to_le_bytes());
+        // extra field length, comment length, disk number, internal and external attributes,
+        // local header offset
+        self.buf.extend([0_u8; 16]);
+        self.buf.extend(&self.name);
+        // end of central directory
+        self.buf.extend(END_OF_CENTRAL_DIRECTORY_MAGIC);
+        // disk numbers
+        self.buf.extend([0_u8; 4]);
+        // the number of entries on this disk and in total
+        self.buf.extend(1_u16.to_le_bytes());
+        self.buf.extend(1_u16.to_le_bytes());
+        self.buf
+            .extend((central_directory_len as u32).to_le_bytes());
+        self.buf.extend(central_directory_offset.to_le_bytes());
+        // comment length
+        self.buf.extend([0_u8; 2]);
+        self.dump()?;
+        Ok(self.writer)
+    }
+
+    fn write_header(&mut self) {
+        if self.header_written {
+            return;
+        }
+        self.buf.extend(ZIP_LOCAL_HEADER_MAGIC);
+        self.buf.extend(VERSION.to_le_bytes());
+        self.buf.extend(ZIP_FLAG_DATA_DESCRIPTOR.to_le_bytes());
+        self.buf.extend(ZIP_METHOD_DEFLATE.to_le_bytes());
+        self.buf.extend(DOS_TIME.to_le_bytes());
+        self.buf.extend(DOS_DATE.to_le_bytes());
+        // CRC-32, compressed and uncompressed sizes are written in the data descriptor
+        self.buf.extend([0_u8; 12]);
+        self.buf.extend((self.name.len() as u16).to_le_bytes());
+        // extra field length
+        self.buf.extend([0_u8; 2]);
+        self.buf.extend(&self.name);
+        self.header_written = true;
+    }
+
+    fn dump(&mut self) -> Result<(), Error> {
+        while !self.buf.is_empty() {
+            let n = self.writer.write(&self.buf)?;
+            if n == 0 {
+                return Err(ErrorKind::WriteZero.into());
+            }
+            self.buf.drain(..n);
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for ZipEncoder<W> {
+    fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        self.write_header();
+        loop {
+            self.dump()?;
+            let before = self.compress.total_in();
+            self.compress
+                .compress_vec(data, &mut self.buf, FlushCompress::None)
+                .map_err(Error::other)?;
+            let n = (self.compress.total_in() - before) as usize;
+            if n != 0 || data.is_empty() {
+                self.crc.update(&data[..n]);
+                return Ok(n);
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.write_header();
+        let mut flush = FlushCompress::Sync;
+        loop {
+            self.dump()?;
+            let before = self.compress.total_out();
+            self.compress
+                .compress_vec(&[], &mut self.buf, flush)
+                .map_err(Error::other)?;
+            if before == self.compress.total_out() {
+                break;
+            }
+            flush = FlushCompress::None;
+        }
+        self.writer.flush()
+    }
+}
+
+const CENTRAL_HEADER_MAGIC: [u8; 4] = *b"PK\x01\x02";
+const END_OF_CENTRAL_DIRECTORY_MAGIC: [u8; 4] = *b"PK\x05\x06";
+const CENTRAL_HEADER_LEN: usize = 46;
+const DATA_DESCRIPTOR_LEN: usize = 16;
+// 2.0: deflate compression
+const VERSION: u16 = 20;
+// 1980-01-01 00:00:00
+const DOS_TIME: u16 = 0;
+const DOS_DATE: u16 = (1 << 5) | 1;
+const BUFFER_SIZE: usize = 32 * 1024;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::ZipDecoder;
+
+    #[test]
+    fn single_entry() {
+        arbtest(|u| {
+            let expected: Vec<u8> = u.arbitrary()?;
+            let name: String = u.arbitrary()?;
+            let level = Compression::new(u.int_in_range(0..=9)?);
+            let mut writer = ZipEncoder::with_name(Vec::new(), level, name.into());
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            let mut reader = ZipDecoder::new(&compressed[..]);
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, actual);
+            assert_eq!(
+                END_OF_CENTRAL_DIRECTORY_MAGIC,
+                compressed[compressed.len() - 22..][..4]
+            );
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,229 @@
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Write;
+
+use zstd::stream::raw::CParameter;
+use zstd::stream::raw::Encoder;
+use zstd::stream::raw::InBuffer;
+use zstd::stream::raw::Operation;
+use zstd::stream::raw::OutBuffer;
+
+use crate::ZSTD_SKIPPABLE_MAGIC;
+
+/// Zstd encoder that supports writing multiple frames to the same output.
+///
+/// Decoders read consecutive frames as one stream.
+pub struct ZstdEncoder<W: Write> {
+    writer: W,
+    encoder: Encoder<'static>,
+    frame_finished: bool,
+    // nothing was written to the current frame yet
+    frame_empty: bool,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> ZstdEncoder<W> {
+    /// Create new encoder with the specified compression `level`.
+    pub fn new(writer: W, level: i32) -> Result<Self, Error> {
+        Ok(Self {
+            writer,
+            encoder: Encoder::new(level)?,
+            frame_finished: false,
+            frame_empty: true,
+            buf: Vec::with_capacity(zstd::zstd_safe::CCtx::out_size()),
+        })
+    }
+
+    /// Set compression parameter.
+    ///
+    /// The parameter is applied to the current frame and all subsequent frames.
+    pub fn set_parameter(&mut self, parameter: CParameter) -> Result<(), Error> {
+        self.encoder.set_parameter(parameter)
+    }
+
+    /// Finish the current frame and start a new one.
+    ///
+    /// Compression parameters are preserved.
+    pub fn start_new_frame(&mut self) -> Result<(), Error> {
+        self.finish_frame()?;
+        self.encoder.reinit()?;
+        self.frame_finished = false;
+        self.frame_empty = true;
+        Ok(())
+    }
+
+    /// Write skippable frame with the supplied `payload`.
+    ///
+    /// The current frame is finished and a new one is started afterwards
+    /// unless nothing was written to the current frame yet.
+    /// Decoders skip such frames, i.e. they can be used to store application-specific metadata.
+    /// The frame magic is `0x184d2a50 + magic_variant`, where `magic_variant` is in 0–15 range.
+    pub fn write_skippable_frame(
+        &mut self,
+        magic_variant: u8,
+        payload: &[u8],
+    ) -> Result<(), Error> {
+        if magic_variant > 15 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "zstd skippable frame magic variant is out of range",
+            ));
+        }
+        let Ok(size) = u32::try_from(payload.len()) else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "zstd skippable frame payload is too large",
+            ));
+        };
+        let restart = !self.frame_empty && !self.frame_finished;
+        if restart {
+            self.finish_frame()?;
+        }
+        self.dump()?;
+        self.writer
+            .write_all(&(ZSTD_SKIPPABLE_MAGIC + magic_variant as u32).to_le_bytes())?;
+        self.writer.write_all(&size.to_le_bytes())?;
+        self.writer.write_all(payload)?;
+        if restart {
+            self.encoder.reinit()?;
+            self.frame_finished = false;
+            self.frame_empty = true;
+        }
+        Ok(())
+    }
+
+    /// Get immutable reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Get mutable reference to the underlying writer.
+    ///
+    /// Writing to the underlying writer might corrupt the output.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Finish encoding and return the underlying writer.
+    ///
+    /// This method is **not** automatically called on drop.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.finish_frame()?;
+        Ok(self.writer)
+    }
+
+    fn finish_frame(&mut self) -> Result<(), Error> {
+        if self.frame_finished {
+            return Ok(());
+        }
+        loop {
+            self.dump()?;
+            let mut output = OutBuffer::around(&mut self.buf);
+            let remaining = self.encoder.finish(&mut output, false)?;
+            if remaining == 0 {
+                break;
+            }
+        }
+        self.dump()?;
+        self.frame_finished = true;
+        Ok(())
+    }
+
+    fn dump(&mut self) -> Result<(), Error> {
+        while !self.buf.is_empty() {
+            let n = self.writer.write(&self.buf)?;
+            if n == 0 {
+                return Err(ErrorKind::WriteZero.into());
+            }
+            self.buf.drain(..n);
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for ZstdEncoder<W> {
+    fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        if self.frame_finished {
+            return Err(Error::other("zstd frame is finished"));
+        }
+        let mut input = InBuffer::around(data);
+        loop {
+            self.dump()?;
+            let mut output = OutBuffer::around(&mut self.buf);
+            self.encoder.run(&mut input, &mut output)?;
+            self.frame_empty &= input.pos() == 0;
+            if input.pos() != 0 || data.is_empty() {
+                return Ok(input.pos());
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        if !self.frame_finished {
+            self.frame_empty = false;
+            loop {
+                self.dump()?;
+                let mut output = OutBuffer::around(&mut self.buf);
+                let remaining = self.encoder.flush(&mut output)?;
+                if remaining == 0 {
+                    break;
+                }
+            }
+        }
+        self.dump()?;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use arbtest::arbtest;
+    use zstd::stream::read::Decoder;
+
+    use super::*;
+
+    #[test]
+    fn multiple_frames() {
+        arbtest(|u| {
+            let frames: Vec<Vec<u8>> = u.arbitrary()?;
+            let level = u.int_in_range(0..=22)?;
+            let mut writer = ZstdEncoder::new(Vec::new(), level).unwrap();
+            for (i, frame) in frames.iter().enumerate() {
+                if i != 0 {
+                    writer.start_new_frame().unwrap();
+                }
+                writer.write_all(frame).unwrap();
+            }
+            let compressed = writer.finish().unwrap();
+            let mut reader = Decoder::new(&compressed[..]).unwrap();
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(frames.concat(), actual);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn skippable_frames() {
+        arbtest(|u| {
+            let chunks: Vec<(Vec<u8>, Vec<u8>)> = u.arbitrary()?;
+            let mut writer = ZstdEncoder::new(Vec::new(), 1).unwrap();
+            let mut expected = Vec::new();
+            for (chunk, payload) in chunks.iter() {
+                writer
+                    .write_skippable_frame(u.int_in_range(0..=15)?, payload)
+                    .unwrap();
+                writer.write_all(chunk).unwrap();
+                expected.extend_from_slice(chunk);
+            }
+            let compressed = writer.finish().unwrap();
+            let mut reader = Decoder::new(&compressed[..]).unwrap();
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, actual);
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,398 @@
+use std::io::BufRead;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Take;
+
+use flate2::read::ZlibDecoder;
+
+use crate::bufread::AnyDecoder;
+use crate::Format;
+
+/// Xar archive entry (file, directory, symbolic link etc.) from the table of contents.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct XarEntry {
+    /// Entry identifier.
+    pub id: u64,
+    /// Slash-separated path of the entry relative to the root of the archive.
+    pub path: String,
+    /// Entry type, e.g. `file`, `directory`, `symlink`.
+    pub file_type: String,
+    /// The location of the contents of the entry in the heap.
+    ///
+    /// Only regular files have contents.
+    pub data: Option<XarData>,
+}
+
+/// The location and the encoding of the entry contents.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct XarData {
+    /// The offset of the encoded contents from the start of the file.
+    pub offset: u64,
+    /// The size of the encoded contents.
+    pub length: u64,
+    /// The size of the decoded contents.
+    pub size: u64,
+    /// MIME type of the encoding, e.g. `application/x-bzip2`.
+    ///
+    /// Empty string means no encoding.
+    pub encoding: String,
+}
+
+impl XarData {
+    /// Get the format that corresponds to the encoding.
+    ///
+    /// Returns `None` if the encoding is unknown or the corresponding feature is disabled.
+    pub fn format(&self) -> Option<Format> {
+        match self.encoding.as_str() {
+            "" | "application/octet-stream" => Some(Format::Verbatim),
+            // xar uses zlib despite the name
+            "application/x-gzip" | "application/zlib" => Some(Format::Zlib),
+            #[cfg(feature = "bzip2")]
+            "application/x-bzip2" => Some(Format::Bz),
+            // xar uses xz despite the name
+            #[cfg(feature = "xz")]
+            "application/x-lzma" | "application/x-xz" => Some(Format::Xz),
+            _ => None,
+        }
+    }
+
+    /// Create new decoder of the contents.
+    ///
+    /// The `reader` is the whole xar file. The format is not detected but inferred from the
+    /// [encoding](Self::encoding) via [AnyDecoder::with_format].
+    pub fn decoder<'a, R: BufRead + Seek>(
+        &self,
+        mut reader: R,
+    ) -> Result<AnyDecoder<'a, Take<R>>, Error> {
+        let Some(format) = self.format() else {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("unsupported xar encoding `{}`", self.encoding),
+            ));
+        };
+        reader.seek(SeekFrom::Start(self.offset))?;
+        Ok(AnyDecoder::with_format(reader.take(self.length), format))
+    }
+}
+
+/// List the entries of xar archive.
+///
+/// The entries are read from the zlib-compressed XML table of contents that follows the header.
+/// The checksums are not verified.
+/// Returns an error of kind [InvalidData](ErrorKind::InvalidData)
+/// if the file doesn't start with `xar!` magic.
+pub fn xar_entries<R: Read + Seek>(mut reader: R) -> Result<Vec<XarEntry>, Error> {
+    reader.rewind()?;
+    let mut header = [0_u8; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+    if header[..4] != MAGIC {
+        return Err(invalid_data("invalid xar magic"));
+    }
+    let header_len = u16::from_be_bytes([header[4], header[5]]) as u64;
+    if header_len < HEADER_LEN as u64 {
+        return Err(invalid_data("invalid xar header size"));
+    }
+    let mut toc_compressed_len = [0_u8; 8];
+    toc_compressed_len.copy_from_slice(&header[8..16]);
+    let toc_compressed_len = u64::from_be_bytes(toc_compressed_len);
+    let mut toc_len = [0_u8; 8];
+    toc_len.copy_from_slice(&header[16..24]);
+    let toc_len = u64::from_be_bytes(toc_len);
+    reader.seek(SeekFrom::Start(header_len))?;
+    let mut toc = String::new();
+    ZlibDecoder::new((&mut reader).take(toc_compressed_len))
+        .take(toc_len)
+        .read_to_string(&mut toc)?;
+    let heap_offset = header_len + toc_compressed_len;
+    let mut entries = parse_toc(&toc)?;
+    for data in entries.iter_mut().filter_map(|entry| entry.data.as_mut()) {
+        data.offset += heap_offset;
+    }
+    Ok(entries)
+}
+
+/// Extract the entries from the XML table of contents.
+///
+/// Only the elements that are needed to locate the entries are parsed.
+fn parse_toc(toc: &str) -> Result<Vec<XarEntry>, Error> {
+    let mut entries: Vec<XarEntry> = Vec::new();
+    // the index of the parent entry of each entry
+    let mut parent_indices: Vec<Option<usize>> = Vec::new();
+    // the indices of the enclosing `file` elements
+    let mut files: Vec<usize> = Vec::new();
+    let mut elements: Vec<&str> = Vec::new();
+    let mut text = String::new();
+    let mut rest = toc;
+    while let Some(i) = rest.find('<') {
+        text.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if let Some(tail) = rest.strip_prefix("<![CDATA[") {
+            let (cdata, tail) = split_once(tail, "]]>")?;
+            // escape the text so that entity decoding restores it as is
+            text.push_str(&escape(cdata));
+            rest = tail;
+            continue;
+        }
+        if let Some(tail) = rest.strip_prefix("<!--") {
+            rest = split_once(tail, "-->")?.1;
+            continue;
+        }
+        if let Some(tail) = rest.strip_prefix("<?") {
+            rest = split_once(tail, "?>")?.1;
+            continue;
+        }
+        let (tag, tail) = split_once(&rest[1..], ">")?;
+        rest = tail;
+        if tag.starts_with('!') {
+            // DOCTYPE
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim();
+            if elements.pop() != Some(name) {
+                return Err(invalid_data("mismatched xar toc element"));
+            }
+            let value = unescape(std::mem::take(&mut text).trim())?;
+            let entry = files.last().and_then(|i| entries.get_mut(*i));
+            match (parents(&elements), name, entry) {
+                (["file"], "name", Some(entry)) => entry.path = value,
+                (["file"], "type", Some(entry)) => entry.file_type = value,
+                (["file", "data"], "offset", Some(entry)) => data(entry).offset = parse(&value)?,
+                (["file", "data"], "length", Some(entry)) => data(entry).length = parse(&value)?,
+                (["file", "data"], "size", Some(entry)) => data(entry).size = parse(&value)?,
+                (["file"], "data", Some(entry)) => {
+                    data(entry);
+                }
+                (_, "file", _) => {
+                    files.pop();
+                }
+                _ => {}
+            }
+            continue;
+        }
+        text.clear();
+        let self_closing = tag.ends_with('/');
+        let tag = tag.strip_suffix('/').unwrap_or(tag);
+        let (name, attributes) = tag
+            .split_once(char::is_whitespace)
+            .unwrap_or((tag, Default::default()));
+        if name.is_empty() {
+            return Err(invalid_data("invalid xar toc element"));
+        }
+        match name {
+            "file" => {
+                parent_indices.push(files.last().copied());
+                files.push(entries.len());
+                entries.push(XarEntry {
+                    id: attribute(attributes, "id")
+                        .map(|id| parse(&id))
+                        .transpose()?
+                        .unwrap_or(0),
+                    ..Default::default()
+                });
+            }
+            "encoding" if parents(&elements) == ["file", "data"] => {
+                if let Some(entry) = files.last().and_then(|i| entries.get_mut(*i)) {
+                    data(entry).encoding = attribute(attributes, "style").unwrap_or_default();
+                }
+            }
+            _ => {}
+        }
+        if !self_closing {
+            elements.push(name);
+        }
+    }
+    if !elements.is_empty() {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "truncated xar table of contents",
+        ));
+    }
+    // parents always precede their children
+    for (i, parent) in parent_indices.into_iter().enumerate() {
+        if let Some(parent) = parent {
+            let path = format!("{}/{}", entries[parent].path, entries[i].path);
+            entries[i].path = path;
+        }
+    }
+    Ok(entries)
+}
+
+/// The enclosing elements starting from the innermost `file` element.
+fn parents<'a, 'b>(elements: &'b [&'a str]) -> &'b [&'a str] {
+    match elements.iter().rposition(|name| *name == "file") {
+        Some(i) => &elements[i..],
+        None => &[],
+    }
+}
+
+fn data(entry: &mut XarEntry) -> &mut XarData {
+    entry.data.get_or_insert_with(Default::default)
+}
+
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+    loop {
+        let (key, tail) = rest.split_once('=')?;
+        let tail = tail.trim_start();
+        let quote = tail.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let (value, tail) = tail[1..].split_once(quote)?;
+        if key.trim() == name {
+            return unescape(value).ok();
+        }
+        rest = tail;
+    }
+}
+
+fn unescape(text: &str) -> Result<String, Error> {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find('&') {
+        output.push_str(&rest[..i]);
+        let (entity, tail) = split_once(&rest[i + 1..], ";")?;
+        let ch = match entity {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse()))
+                .and_then(Result::ok)
+                .and_then(char::from_u32)
+                .ok_or_else(|| invalid_data("invalid xml entity in xar toc"))?,
+        };
+        output.push(ch);
+        rest = tail;
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+}
+
+fn split_once<'a>(text: &'a str, delimiter: &str) -> Result<(&'a str, &'a str), Error> {
+    text.split_once(delimiter)
+        .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "truncated xar table of contents"))
+}
+
+fn parse(value: &str) -> Result<u64, Error> {
+    value
+        .parse()
+        .map_err(|_| invalid_data("invalid number in xar toc"))
+}
+
+fn invalid_data(message: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+const MAGIC: [u8; 4] = *b"xar!";
+// magic, header size, version, compressed and uncompressed toc size, checksum algorithm
+const HEADER_LEN: usize = 28;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::io::Write;
+
+    use arbtest::arbtest;
+    use flate2::write::ZlibEncoder;
+
+    use super::*;
+
+    #[test]
+    fn entries() {
+        arbtest(|u| {
+            let stored: Vec<u8> = u.arbitrary()?;
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut writer = ZlibEncoder::new(Vec::new(), flate2::Compression::fast());
+            writer.write_all(&expected).unwrap();
+            let encoded = writer.finish().unwrap();
+            let toc = format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<xar>
+ <toc>
+  <checksum style="none"/>
+  <file id="1">
+   <name>dir &amp; subdir</name>
+   <type>directory</type>
+   <ea id="0"><name>com.apple.quarantine</name><offset>7</offset><length>1</length></ea>
+   <file id="2">
+    <data>
+     <length>{}</length>
+     <offset>0</offset>
+     <size>{}</size>
+     <encoding style="application/octet-stream"/>
+    </data>
+    <name><![CDATA[a&b]]></name>
+    <type>file</type>
+   </file>
+   <!-- comment <file> -->
+   <file id="3">
+    <name>Payload</name>
+    <type>file</type>
+    <data>
+     <offset>{}</offset>
+     <size>{}</size>
+     <length>{}</length>
+     <encoding style='application/x-gzip'></encoding>
+    </data>
+   </file>
+  </file>
+ </toc>
+</xar>"#,
+                stored.len(),
+                stored.len(),
+                stored.len(),
+                expected.len(),
+                encoded.len(),
+            );
+            let mut compressed_toc = ZlibEncoder::new(Vec::new(), flate2::Compression::fast());
+            compressed_toc.write_all(toc.as_bytes()).unwrap();
+            let compressed_toc = compressed_toc.finish().unwrap();
+            let mut file = Vec::new();
+            file.extend(MAGIC);
+            file.extend((HEADER_LEN as u16).to_be_bytes());
+            file.extend(1_u16.to_be_bytes());
+            file.extend((compressed_toc.len() as u64).to_be_bytes());
+            file.extend((toc.len() as u64).to_be_bytes());
+            file.extend(0_u32.to_be_bytes());
+            file.extend(compressed_toc);
+            file.extend(&stored);
+            file.extend(&encoded);
+            let entries = xar_entries(Cursor::new(&file)).unwrap();
+            let paths = entries.iter().map(|e| e.path.as_str()).collect::<Vec<_>>();
+            assert_eq!(
+                vec!["dir & subdir", "dir & subdir/a&b", "dir & subdir/Payload"],
+                paths
+            );
+            assert_eq!(None, entries[0].data);
+            assert_eq!("directory", entries[0].file_type);
+            assert_eq!(3, entries[2].id);
+            for (entry, expected) in entries[1..].iter().zip([&stored, &expected]) {
+                let data = entry.data.as_ref().unwrap();
+                assert_eq!(expected.len() as u64, data.size);
+                let mut actual = Vec::new();
+                data.decoder(Cursor::new(&file))
+                    .unwrap()
+                    .read_to_end(&mut actual)
+                    .unwrap();
+                assert_eq!(expected, &actual);
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn invalid_magic() {
+        let error = xar_entries(Cursor::new(vec![0_u8; HEADER_LEN])).unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, error.kind());
+    }
+}
@@ -0,0 +1,495 @@
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+#[cfg(feature = "rayon")]
+use std::io::Write;
+
+/// Xz integrity check type.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum XzCheck {
+    /// No check.
+    None,
+    /// CRC32.
+    Crc32,
+    /// CRC64.
+    Crc64,
+    /// SHA-256.
+    Sha256,
+    /// Reserved check type.
+    Other(u8),
+}
+
+impl XzCheck {
+    pub(crate) fn from_id(id: u8) -> Self {
+        match id {
+            0x00 => Self::None,
+            0x01 => Self::Crc32,
+            0x04 => Self::Crc64,
+            0x0a => Self::Sha256,
+            other => Self::Other(other),
+        }
+    }
+
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            Self::None => 0x00,
+            Self::Crc32 => 0x01,
+            Self::Crc64 => 0x04,
+            Self::Sha256 => 0x0a,
+            Self::Other(other) => other,
+        }
+    }
+}
+
+/// Xz stream summary that is read from the stream header, footer and index.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct XzStreamInfo {
+    /// Integrity check type from the stream flags.
+    pub check: XzCheck,
+    /// The offset of the stream header from the start of the file.
+    pub offset: u64,
+    /// The size of the stream including the header, the blocks, the index and the footer.
+    pub compressed_size: u64,
+    /// The size of the decompressed data.
+    pub uncompressed_size: u64,
+    /// The size of the index (_backward size_ from the stream footer).
+    pub index_size: u64,
+    /// The size of the stream padding that follows the stream.
+    pub padding: u64,
+    /// The blocks from the index.
+    pub blocks: Vec<XzBlockInfo>,
+}
+
+/// Xz block summary that is read from the stream index.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct XzBlockInfo {
+    /// The offset of the block header from the start of the file.
+    pub compressed_offset: u64,
+    /// The offset of the decompressed data from the start of the decompressed file.
+    pub uncompressed_offset: u64,
+    /// The size of the block without the block padding.
+    pub unpadded_size: u64,
+    /// The size of the decompressed data.
+    pub uncompressed_size: u64,
+    /// Integrity check type from the flags of the stream that contains the block.
+    pub check: XzCheck,
+}
+
+/// Read the headers, the footers and the indices of all xz streams in the file
+/// without decompressing the blocks.
+///
+/// Concatenated streams and stream padding are supported.
+/// The streams are returned in the order they appear in the file.
+pub fn xz_streams<R: Read + Seek>(mut reader: R) -> Result<Vec<XzStreamInfo>, Error> {
+    let mut streams = Vec::new();
+    let mut end = reader.seek(SeekFrom::End(0))?;
+    while end != 0 {
+        let mut padding = 0;
+        loop {
+            if end < 4 {
+                return Err(invalid_data("truncated xz stream"));
+            }
+            let mut word = [0_u8; 4];
+            read_at(&mut reader, end - 4, &mut word)?;
+            if word != [0, 0, 0, 0] {
+                break;
+            }
+            end -= 4;
+            padding += 4;
+        }
+        if end < 2 * HEADER_LEN {
+            return Err(invalid_data("truncated xz stream"));
+        }
+        // stream footer
+        let mut footer = [0_u8; HEADER_LEN as usize];
+        read_at(&mut reader, end - HEADER_LEN, &mut footer)?;
+        if footer[10..12] != FOOTER_MAGIC {
+            return Err(invalid_data("invalid xz stream footer magic"));
+        }
+        check_crc32(&footer[4..10], &footer[0..4])?;
+        let flags = [footer[8], footer[9]];
+        let backward_size = u32::from_le_bytes([footer[4], footer[5], footer[6], footer[7]]);
+        let index_size = (backward_size as u64 + 1) * 4;
+        if end - 2 * HEADER_LEN < index_size {
+            return Err(invalid_data("xz index is too large"));
+        }
+        // index
+        let index_offset = end - HEADER_LEN - index_size;
+        let mut index = vec![0_u8; index_size as usize];
+        read_at(&mut reader, index_offset, &mut index)?;
+        let blocks = parse_index(&index)?;
+        let blocks_size = blocks
+            .iter()
+            .try_fold(0_u64, |acc, (unpadded_size, _)| {
+                acc.checked_add(round_up4(*unpadded_size))
+            })
+            .ok_or_else(|| invalid_data("invalid xz index"))?;
+        if index_offset - HEADER_LEN < blocks_size {
+            return Err(invalid_data("invalid xz index"));
+        }
+        // stream header
+        let offset = index_offset - blocks_size - HEADER_LEN;
+        let mut header = [0_u8; HEADER_LEN as usize];
+        read_at(&mut reader, offset, &mut header)?;
+        if header[0..6] != HEADER_MAGIC {
+            return Err(invalid_data("invalid xz stream header magic"));
+        }
+        check_crc32(&header[6..8], &header[8..12])?;
+        if header[6..8] != flags {
+            return Err(invalid_data("xz stream header and footer flags differ"));
+        }
+        if flags[0] != 0 || flags[1] & 0xf0 != 0 {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "unsupported xz stream flags",
+            ));
+        }
+        let check = XzCheck::from_id(flags[1]);
+        let mut compressed_offset = offset + HEADER_LEN;
+        let blocks = blocks
+            .into_iter()
+            .map(|(unpadded_size, uncompressed_size)| {
+                let block = XzBlockInfo {
+                    compressed_offset,
+                    uncompressed_offset: 0,
+                    unpadded_size,
+                    uncompressed_size,
+                    check,
+                };
+                compressed_offset += round_up4(unpadded_size);
+                block
+            })
+            .collect::<Vec<_>>();
+        streams.push(XzStreamInfo {
+            check,
+            offset,
+            compressed_size: end - offset,
+            uncompressed_size: blocks.iter().map(|b| b.uncompressed_size).sum(),
+            index_size,
+            padding,
+            blocks,
+        });
+        end = offset;
+    }
+    streams.reverse();
+    let mut uncompressed_offset = 0;
+    for block in streams.iter_mut().flat_map(|s| s.blocks.iter_mut()) {
+        block.uncompressed_offset = uncompressed_offset;
+        uncompressed_offset += block.uncompressed_size;
+    }
+    Ok(streams)
+}
+
+/// Decompress one block of xz file.
+///
+/// The block is read from the `reader` at the offset from the index, see [xz_streams].
+pub fn xz_decode_block<R: Read + Seek>(
+    mut reader: R,
+    block: &XzBlockInfo,
+) -> Result<Vec<u8>, Error> {
+    let data = read_block(&mut reader, block)?;
+    decode_block(&data, block)
+}
+
+/// Decompress xz file using all available cores and write the data to `writer`.
+///
+/// The blocks are located via [xz_streams] and are decompressed in parallel.
+/// The decompressed data is written in the original order.
+/// The blocks are processed in batches that fit into `memory_budget` bytes
+/// (compressed and decompressed data, excluding the decoder state);
+/// a block that doesn't fit into the budget is processed alone.
+/// Only files produced by multi-threaded encoder (e.g. `xz -T`) or by
+/// [XzBlockEncoder](crate::write::XzBlockEncoder) have more than one block per stream.
+///
+/// Returns the number of bytes written.
+#[cfg(feature = "rayon")]
+pub fn xz_decompress_parallel<R: Read + Seek, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    memory_budget: u64,
+) -> Result<u64, Error> {
+    use rayon::prelude::*;
+    let streams = xz_streams(&mut reader)?;
+    let blocks = streams
+        .iter()
+        .flat_map(|s| s.blocks.iter())
+        .collect::<Vec<_>>();
+    let mut total = 0;
+    let mut first = 0;
+    while first != blocks.len() {
+        let mut last = first;
+        let mut memory = 0_u64;
+        while last != blocks.len() {
+            let block = blocks[last];
+            let block_memory =
+                round_up4(block.unpadded_size).saturating_add(block.uncompressed_size);
+            if last != first && memory.saturating_add(block_memory) > memory_budget {
+                break;
+            }
+            memory = memory.saturating_add(block_memory);
+            last += 1;
+        }
+        let batch = &blocks[first..last];
+        let inputs = batch
+            .iter()
+            .map(|block| read_block(&mut reader, block))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let outputs = inputs
+            .par_iter()
+            .zip(batch.par_iter())
+            .map(|(data, block)| decode_block(data, block))
+            .collect::<Result<Vec<_>, Error>>()?;
+        for output in outputs.iter() {
+            writer.write_all(output)?;
+            total += output.len() as u64;
+        }
+        first = last;
+    }
+    Ok(total)
+}
+
+fn read_block<R: Read + Seek>(reader: &mut R, block: &XzBlockInfo) -> Result<Vec<u8>, Error> {
+    let len = usize::try_from(round_up4(block.unpadded_size))
+        .map_err(|_| invalid_data("xz block is too large"))?;
+    let mut data = vec![0_u8; len];
+    read_at(reader, block.compressed_offset, &mut data)?;
+    Ok(data)
+}
+
+/// Wrap the block in a separate stream and decompress it.
+fn decode_block(data: &[u8], block: &XzBlockInfo) -> Result<Vec<u8>, Error> {
+    let flags = [0, block.check.id()];
+    let mut stream = Vec::with_capacity(data.len() + 3 * HEADER_LEN as usize);
+    // stream header
+    stream.extend_from_slice(&HEADER_MAGIC);
+    stream.extend_from_slice(&flags);
+    stream.extend_from_slice(&crc32(&flags).to_le_bytes());
+    stream.extend_from_slice(data);
+    // index
+    let index_offset = stream.len();
+    stream.push(0);
+    write_varint(&mut stream, 1);
+    write_varint(&mut stream, block.unpadded_size);
+    write_varint(&mut stream, block.uncompressed_size);
+    stream.resize(
+        index_offset + round_up4((stream.len() - index_offset) as u64) as usize,
+        0,
+    );
+    let crc = crc32(&stream[index_offset..]);
+    stream.extend_from_slice(&crc.to_le_bytes());
+    let backward_size = ((stream.len() - index_offset) / 4 - 1) as u32;
+    // stream footer
+    let mut footer = [0_u8; 6];
+    footer[..4].copy_from_slice(&backward_size.to_le_bytes());
+    footer[4..].copy_from_slice(&flags);
+    stream.extend_from_slice(&crc32(&footer).to_le_bytes());
+    stream.extend_from_slice(&footer);
+    stream.extend_from_slice(&FOOTER_MAGIC);
+    let mut decoder = xz::read::XzDecoder::new(&stream[..]);
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output)?;
+    if output.len() as u64 != block.uncompressed_size {
+        return Err(invalid_data("xz block size mismatch"));
+    }
+    Ok(output)
+}
+
+/// Returns unpadded and uncompressed sizes of the blocks.
+fn parse_index(index: &[u8]) -> Result<Vec<(u64, u64)>, Error> {
+    let Some((data, crc)) = index.split_last_chunk::<4>() else {
+        return Err(invalid_data("truncated xz index"));
+    };
+    check_crc32(data, crc)?;
+    let mut input = data;
+    if read_byte(&mut input)? != 0 {
+        return Err(invalid_data("invalid xz index indicator"));
+    }
+    let num_records = read_varint(&mut input)?;
+    // every record takes at least two bytes
+    if num_records > input.len() as u64 / 2 {
+        return Err(invalid_data("invalid xz index"));
+    }
+    let mut blocks = Vec::with_capacity(num_records as usize);
+    for _ in 0..num_records {
+        let unpadded_size = read_varint(&mut input)?;
+        let uncompressed_size = read_varint(&mut input)?;
+        if unpadded_size == 0 {
+            return Err(invalid_data("invalid xz index"));
+        }
+        blocks.push((unpadded_size, uncompressed_size));
+    }
+    if input.len() >= 4 || input.iter().any(|b| *b != 0) {
+        return Err(invalid_data("invalid xz index padding"));
+    }
+    Ok(blocks)
+}
+
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, buf: &mut [u8]) -> Result<(), Error> {
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(buf)
+}
+
+fn read_byte(input: &mut &[u8]) -> Result<u8, Error> {
+    let Some((byte, rest)) = input.split_first() else {
+        return Err(invalid_data("truncated xz index"));
+    };
+    *input = rest;
+    Ok(*byte)
+}
+
+fn read_varint(input: &mut &[u8]) -> Result<u64, Error> {
+    let mut value = 0_u64;
+    for i in 0..MAX_VARINT_LEN {
+        let byte = read_byte(input)?;
+        value |= ((byte & 0x7f) as u64) << (i * 7);
+        if byte & 0x80 == 0 {
+            if byte == 0 && i != 0 {
+                return Err(invalid_data("invalid xz varint"));
+            }
+            return Ok(value);
+        }
+    }
+    Err(invalid_data("invalid xz varint"))
+}
+
+fn write_varint(output: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        output.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+fn check_crc32(data: &[u8], expected: &[u8]) -> Result<(), Error> {
+    if crc32(data).to_le_bytes() != expected {
+        return Err(invalid_data("xz crc32 mismatch"));
+    }
+    Ok(())
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+const fn round_up4(n: u64) -> u64 {
+    n.saturating_add(3) & !3
+}
+
+fn invalid_data(message: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+const HEADER_LEN: u64 = 12;
+const HEADER_MAGIC: [u8; 6] = [0xfd, b'7', b'z', b'X', b'Z', 0];
+const FOOTER_MAGIC: [u8; 2] = [b'Y', b'Z'];
+const MAX_VARINT_LEN: u32 = 9;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::io::Write;
+
+    use arbtest::arbtest;
+    use xz::stream::Check;
+    use xz::stream::Stream;
+    use xz::write::XzEncoder;
+
+    use super::*;
+
+    #[test]
+    fn streams() {
+        arbtest(|u| {
+            let inputs: Vec<(Vec<u8>, u8)> = u.arbitrary()?;
+            let mut file = Vec::new();
+            for (input, padding) in inputs.iter() {
+                let (check, expected_check) = *u.choose(&[
+                    (Check::None, XzCheck::None),
+                    (Check::Crc32, XzCheck::Crc32),
+                    (Check::Crc64, XzCheck::Crc64),
+                    (Check::Sha256, XzCheck::Sha256),
+                ])?;
+                let stream = Stream::new_easy_encoder(0, check).unwrap();
+                let mut writer = XzEncoder::new_stream(Vec::new(), stream);
+                writer.write_all(input).unwrap();
+                let compressed = writer.finish().unwrap();
+                let offset = file.len() as u64;
+                file.extend(compressed);
+                file.resize(file.len() + 4 * (*padding as usize % 4), 0);
+                let streams = xz_streams(Cursor::new(&file[..])).unwrap();
+                let last = streams.last().unwrap();
+                assert_eq!(expected_check, last.check);
+                assert_eq!(offset, last.offset);
+                assert_eq!(input.len() as u64, last.uncompressed_size);
+                assert_eq!(4 * (*padding as u64 % 4), last.padding);
+            }
+            let streams = xz_streams(Cursor::new(&file[..])).unwrap();
+            assert_eq!(inputs.len(), streams.len());
+            let total: u64 = streams.iter().map(|s| s.uncompressed_size).sum();
+            assert_eq!(
+                inputs.iter().map(|(x, _)| x.len() as u64).sum::<u64>(),
+                total
+            );
+            let mut uncompressed_offset = 0;
+            for block in streams.iter().flat_map(|s| s.blocks.iter()) {
+                assert_eq!(uncompressed_offset, block.uncompressed_offset);
+                // block header size can't be zero
+                assert_ne!(0, file[block.compressed_offset as usize]);
+                uncompressed_offset += block.uncompressed_size;
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn decode_blocks() {
+        arbtest(|u| {
+            let inputs: Vec<Vec<u8>> = u.arbitrary()?;
+            let mut file = Vec::new();
+            for input in inputs.iter() {
+                let check = *u.choose(&[Check::None, Check::Crc32, Check::Crc64, Check::Sha256])?;
+                let stream = Stream::new_easy_encoder(0, check).unwrap();
+                let mut writer = XzEncoder::new_stream(Vec::new(), stream);
+                writer.write_all(input).unwrap();
+                file.extend(writer.finish().unwrap());
+            }
+            let streams = xz_streams(Cursor::new(&file[..])).unwrap();
+            let mut reader = Cursor::new(&file[..]);
+            let mut actual = Vec::new();
+            for block in streams.iter().flat_map(|s| s.blocks.iter()) {
+                actual.extend(xz_decode_block(&mut reader, block).unwrap());
+            }
+            assert_eq!(inputs.concat(), actual);
+            #[cfg(feature = "rayon")]
+            {
+                let memory_budget = u.int_in_range(0..=1024)?;
+                let mut actual = Vec::new();
+                let n = xz_decompress_parallel(Cursor::new(&file[..]), &mut actual, memory_budget)
+                    .unwrap();
+                assert_eq!(inputs.concat(), actual);
+                assert_eq!(actual.len() as u64, n);
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn corrupted_footer() {
+        let mut writer = XzEncoder::new(Vec::new(), 0);
+        writer.write_all(b"hello").unwrap();
+        let mut compressed = writer.finish().unwrap();
+        let n = compressed.len();
+        compressed[n - 4] ^= 1;
+        let error = xz_streams(Cursor::new(&compressed[..])).unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, error.kind());
+    }
+}
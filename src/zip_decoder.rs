@@ -0,0 +1,329 @@
+use std::io::BufRead;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+
+use flate2::bufread::DeflateDecoder;
+use flate2::Crc;
+
+/// Decoder of the first entry of ZIP stream.
+///
+/// Only _stored_ and _deflate_ compression methods are supported.
+/// The following entries and the central directory are ignored,
+/// i.e. the decoder returns end-of-file at the end of the first entry.
+pub(crate) struct ZipDecoder<R> {
+    decoder: DeflateDecoder<R>,
+    state: State,
+    header: LocalHeader,
+    crc: Crc,
+    /// Whether to read the underlying reader to the end after the first entry.
+    skip_rest: bool,
+}
+
+impl<R: BufRead> ZipDecoder<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            decoder: DeflateDecoder::new(reader),
+            state: State::Header,
+            header: Default::default(),
+            crc: Crc::new(),
+            skip_rest: false,
+        }
+    }
+
+    /// Skip the following entries and the central directory instead of leaving them unread.
+    ///
+    /// Use this mode when the underlying reader ends where the ZIP file ends.
+    pub(crate) fn skip_rest(mut self, value: bool) -> Self {
+        self.skip_rest = value;
+        self
+    }
+}
+
+impl<R> ZipDecoder<R> {
+    pub(crate) fn get_ref(&self) -> &R {
+        self.decoder.get_ref()
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        self.decoder.get_mut()
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.decoder.into_inner()
+    }
+}
+
+impl<R: BufRead> ZipDecoder<R> {
+    fn read_header(&mut self) -> Result<(), Error> {
+        let reader = self.decoder.get_mut();
+        let mut buf = [0_u8; ZIP_LOCAL_HEADER_LEN];
+        reader.read_exact(&mut buf)?;
+        if buf[..4] != ZIP_LOCAL_HEADER_MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "invalid zip header"));
+        }
+        let flags = u16_le(&buf[6..8]);
+        let method = u16_le(&buf[8..10]);
+        let mut header = LocalHeader {
+            flags,
+            crc32: u32_le(&buf[14..18]),
+            compressed_size: u32_le(&buf[18..22]) as u64,
+            uncompressed_size: u32_le(&buf[22..26]) as u64,
+            zip64: false,
+        };
+        let name_len = u16_le(&buf[26..28]) as u64;
+        let extra_len = u16_le(&buf[28..30]) as usize;
+        std::io::copy(&mut reader.take(name_len), &mut std::io::sink())?;
+        let mut extra = vec![0_u8; extra_len];
+        reader.read_exact(&mut extra)?;
+        header.parse_zip64_extra(&extra);
+        if flags & ZIP_FLAG_ENCRYPTED != 0 {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "encrypted zip entries are not supported",
+            ));
+        }
+        self.state = match method {
+            ZIP_METHOD_STORED if header.has_data_descriptor() => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    "stored zip entries of unknown size are not supported",
+                ))
+            }
+            ZIP_METHOD_STORED => State::Stored {
+                remaining: header.compressed_size,
+            },
+            ZIP_METHOD_DEFLATE => State::Deflated,
+            other => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!("zip compression method {other} is not supported"),
+                ))
+            }
+        };
+        self.header = header;
+        Ok(())
+    }
+
+    fn finish_entry(&mut self) -> Result<(), Error> {
+        self.state = State::Done;
+        if self.header.has_data_descriptor() {
+            let reader = self.decoder.get_mut();
+            let mut buf = [0_u8; 4];
+            reader.read_exact(&mut buf)?;
+            // the signature is optional
+            if buf == ZIP_DATA_DESCRIPTOR_MAGIC {
+                reader.read_exact(&mut buf)?;
+            }
+            self.header.crc32 = u32::from_le_bytes(buf);
+            if self.header.zip64 {
+                let mut buf = [0_u8; 16];
+                reader.read_exact(&mut buf)?;
+                self.header.uncompressed_size = u64_le(&buf[8..16]);
+            } else {
+                let mut buf = [0_u8; 8];
+                reader.read_exact(&mut buf)?;
+                self.header.uncompressed_size = u32_le(&buf[4..8]) as u64;
+            }
+        }
+        // the amount is stored modulo 2^32
+        if self.header.crc32 != self.crc.sum()
+            || self.header.uncompressed_size as u32 != self.crc.amount()
+        {
+            return Err(Error::new(ErrorKind::InvalidData, "zip checksum mismatch"));
+        }
+        if self.skip_rest {
+            std::io::copy(self.decoder.get_mut(), &mut std::io::sink())?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for ZipDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let n = match self.state {
+                State::Header => {
+                    self.read_header()?;
+                    continue;
+                }
+                State::Stored { remaining } => {
+                    let max = buf.len().min(remaining.try_into().unwrap_or(usize::MAX));
+                    let n = if max == 0 {
+                        0
+                    } else {
+                        let n = self.decoder.get_mut().read(&mut buf[..max])?;
+                        if n == 0 {
+                            return Err(ErrorKind::UnexpectedEof.into());
+                        }
+                        n
+                    };
+                    self.state = State::Stored {
+                        remaining: remaining - n as u64,
+                    };
+                    n
+                }
+                State::Deflated => self.decoder.read(buf)?,
+                State::Done => return Ok(0),
+            };
+            if n == 0 {
+                self.finish_entry()?;
+                return Ok(0);
+            }
+            self.crc.update(&buf[..n]);
+            return Ok(n);
+        }
+    }
+}
+
+enum State {
+    Header,
+    Stored { remaining: u64 },
+    Deflated,
+    Done,
+}
+
+#[derive(Default)]
+struct LocalHeader {
+    flags: u16,
+    crc32: u32,
+    compressed_size: u64,
+    uncompressed_size: u64,
+    zip64: bool,
+}
+
+impl LocalHeader {
+    fn has_data_descriptor(&self) -> bool {
+        self.flags & ZIP_FLAG_DATA_DESCRIPTOR != 0
+    }
+
+    /// Read the sizes from Zip64 extended information extra field.
+    fn parse_zip64_extra(&mut self, mut extra: &[u8]) {
+        while let [a, b, c, d, rest @ ..] = extra {
+            let id = u16::from_le_bytes([*a, *b]);
+            let len = (u16::from_le_bytes([*c, *d]) as usize).min(rest.len());
+            let (data, rest) = rest.split_at(len);
+            if id == ZIP64_EXTRA_ID {
+                self.zip64 = true;
+                let mut fields = data.chunks_exact(8).map(u64_le);
+                if self.uncompressed_size == u32::MAX as u64 {
+                    if let Some(size) = fields.next() {
+                        self.uncompressed_size = size;
+                    }
+                }
+                if self.compressed_size == u32::MAX as u64 {
+                    if let Some(size) = fields.next() {
+                        self.compressed_size = size;
+                    }
+                }
+            }
+            extra = rest;
+        }
+    }
+}
+
+fn u16_le(bytes: &[u8]) -> u16 {
+    let mut buf = [0_u8; 2];
+    buf.copy_from_slice(bytes);
+    u16::from_le_bytes(buf)
+}
+
+fn u32_le(bytes: &[u8]) -> u32 {
+    let mut buf = [0_u8; 4];
+    buf.copy_from_slice(bytes);
+    u32::from_le_bytes(buf)
+}
+
+fn u64_le(bytes: &[u8]) -> u64 {
+    let mut buf = [0_u8; 8];
+    buf.copy_from_slice(bytes);
+    u64::from_le_bytes(buf)
+}
+
+pub(crate) const ZIP_LOCAL_HEADER_MAGIC: [u8; 4] = *b"PK\x03\x04";
+pub(crate) const ZIP_DATA_DESCRIPTOR_MAGIC: [u8; 4] = *b"PK\x07\x08";
+pub(crate) const ZIP_LOCAL_HEADER_LEN: usize = 30;
+pub(crate) const ZIP_FLAG_ENCRYPTED: u16 = 1 << 0;
+pub(crate) const ZIP_FLAG_DATA_DESCRIPTOR: u16 = 1 << 3;
+pub(crate) const ZIP_METHOD_STORED: u16 = 0;
+pub(crate) const ZIP_METHOD_DEFLATE: u16 = 8;
+const ZIP64_EXTRA_ID: u16 = 1;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use arbtest::arbtest;
+
+    use super::*;
+
+    #[test]
+    fn stored() {
+        arbtest(|u| {
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut crc = Crc::new();
+            crc.update(&expected);
+            let mut zip = Vec::new();
+            zip.extend(ZIP_LOCAL_HEADER_MAGIC);
+            zip.extend(10_u16.to_le_bytes());
+            zip.extend(0_u16.to_le_bytes());
+            zip.extend(ZIP_METHOD_STORED.to_le_bytes());
+            zip.extend([0_u8; 4]);
+            zip.extend(crc.sum().to_le_bytes());
+            zip.extend((expected.len() as u32).to_le_bytes());
+            zip.extend((expected.len() as u32).to_le_bytes());
+            zip.extend(1_u16.to_le_bytes());
+            zip.extend(0_u16.to_le_bytes());
+            zip.push(b'x');
+            zip.extend(&expected);
+            zip.write_all(b"PK\x01\x02 central directory").unwrap();
+            let mut actual = Vec::new();
+            ZipDecoder::new(&zip[..]).read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, actual);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn deflated_with_data_descriptor() {
+        use flate2::write::DeflateEncoder;
+        use flate2::Compression;
+        arbtest(|u| {
+            // e.g. `foo.csv.zip` written by a streaming tool
+            let expected: Vec<u8> = u.arbitrary()?;
+            let mut crc = Crc::new();
+            crc.update(&expected);
+            let mut writer = DeflateEncoder::new(Vec::new(), Compression::fast());
+            writer.write_all(&expected).unwrap();
+            let compressed = writer.finish().unwrap();
+            let mut zip = Vec::new();
+            zip.extend(ZIP_LOCAL_HEADER_MAGIC);
+            zip.extend(20_u16.to_le_bytes());
+            zip.extend(ZIP_FLAG_DATA_DESCRIPTOR.to_le_bytes());
+            zip.extend(ZIP_METHOD_DEFLATE.to_le_bytes());
+            zip.extend([0_u8; 4]);
+            // CRC and sizes are in the data descriptor
+            zip.extend([0_u8; 12]);
+            zip.extend(7_u16.to_le_bytes());
+            zip.extend(0_u16.to_le_bytes());
+            zip.extend(b"foo.csv");
+            zip.extend(&compressed);
+            if u.arbitrary()? {
+                zip.extend(ZIP_DATA_DESCRIPTOR_MAGIC);
+            }
+            zip.extend(crc.sum().to_le_bytes());
+            zip.extend((compressed.len() as u32).to_le_bytes());
+            zip.extend((expected.len() as u32).to_le_bytes());
+            zip.write_all(b"PK\x01\x02 central directory").unwrap();
+            let mut actual = Vec::new();
+            crate::bufread::AnyDecoder::new(&zip[..])
+                .read_to_end(&mut actual)
+                .unwrap();
+            assert_eq!(expected, actual);
+            Ok(())
+        });
+    }
+}
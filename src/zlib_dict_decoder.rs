@@ -0,0 +1,176 @@
+use std::io::BufRead;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+
+use flate2::Decompress;
+use flate2::FlushDecompress;
+use flate2::Status;
+
+use crate::adler32;
+use crate::Adler32;
+
+/// Decoder of zlib streams that were compressed with a preset dictionary (FDICT flag).
+///
+/// `flate2` can't set the dictionary with its default Rust backend.
+/// Instead the dictionary is fed to the raw DEFLATE decoder as a stored block
+/// that precedes the stream, and the corresponding output is discarded,
+/// i.e. the matches of the stream reference the dictionary as if it was decoded earlier.
+pub(crate) struct ZlibDictDecoder<R> {
+    reader: R,
+    decompress: Decompress,
+    // Adler-32 of the dictionary as stored in the header
+    dict_id: u32,
+    dictionary: Option<Vec<u8>>,
+    adler: Adler32,
+    state: State,
+}
+
+impl<R: BufRead> ZlibDictDecoder<R> {
+    pub(crate) fn new(reader: R, dict_id: u32, dictionary: Option<Vec<u8>>) -> Self {
+        Self {
+            reader,
+            decompress: Decompress::new(false),
+            dict_id,
+            dictionary,
+            adler: Adler32::new(),
+            state: State::Header,
+        }
+    }
+}
+
+impl<R> ZlibDictDecoder<R> {
+    pub(crate) fn dict_id(&self) -> u32 {
+        self.dict_id
+    }
+
+    /// Use the supplied dictionary unless decoding has already started.
+    pub(crate) fn set_dictionary(&mut self, dictionary: &[u8]) {
+        if matches!(self.state, State::Header) {
+            self.dictionary = Some(dictionary.to_vec());
+        }
+    }
+
+    pub(crate) fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: BufRead> ZlibDictDecoder<R> {
+    /// Skip the header and load the dictionary into the decoder's window.
+    fn read_header(&mut self) -> Result<(), Error> {
+        let Some(dictionary) = self.dictionary.take() else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("zlib preset dictionary {:08x} is missing", self.dict_id),
+            ));
+        };
+        if adler32(&dictionary) != self.dict_id {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "zlib dictionary ID mismatch: expected {:08x}, actual {:08x}",
+                    self.dict_id,
+                    adler32(&dictionary)
+                ),
+            ));
+        }
+        // CMF, FLG and DICTID
+        let mut header = [0_u8; 6];
+        self.reader.read_exact(&mut header)?;
+        // only the last 32 KiB are referenced by the matches
+        let history = &dictionary[dictionary.len().saturating_sub(WINDOW_SIZE)..];
+        let len = history.len() as u16;
+        let mut block = Vec::with_capacity(5 + history.len());
+        // non-final stored block
+        block.push(0);
+        block.extend(len.to_le_bytes());
+        block.extend((!len).to_le_bytes());
+        block.extend(history);
+        let mut output = vec![0_u8; history.len()];
+        self.decompress
+            .decompress(&block, &mut output, FlushDecompress::None)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        if self.decompress.total_in() != block.len() as u64
+            || self.decompress.total_out() != history.len() as u64
+        {
+            return Err(invalid_data("failed to load zlib dictionary"));
+        }
+        Ok(())
+    }
+
+    /// Verify Adler-32 of the decompressed data.
+    fn read_trailer(&mut self) -> Result<(), Error> {
+        let mut trailer = [0_u8; 4];
+        self.reader.read_exact(&mut trailer)?;
+        if u32::from_be_bytes(trailer) != self.adler.sum() {
+            return Err(invalid_data("zlib checksum mismatch"));
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for ZlibDictDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            match self.state {
+                State::Header => {
+                    self.read_header()?;
+                    self.state = State::Data;
+                }
+                State::Data => {
+                    let input = self.reader.fill_buf()?;
+                    let eof = input.is_empty();
+                    let total_in = self.decompress.total_in();
+                    let total_out = self.decompress.total_out();
+                    let status = self
+                        .decompress
+                        .decompress(input, buf, FlushDecompress::None)
+                        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+                    let consumed = (self.decompress.total_in() - total_in) as usize;
+                    let n = (self.decompress.total_out() - total_out) as usize;
+                    self.reader.consume(consumed);
+                    self.adler.update(&buf[..n]);
+                    if status == Status::StreamEnd {
+                        self.read_trailer()?;
+                        self.state = State::Done;
+                    }
+                    if n != 0 {
+                        return Ok(n);
+                    }
+                    if consumed == 0 && status != Status::StreamEnd {
+                        return Err(if eof {
+                            ErrorKind::UnexpectedEof.into()
+                        } else {
+                            invalid_data("corrupt zlib stream")
+                        });
+                    }
+                }
+                State::Done => return Ok(0),
+            }
+        }
+    }
+}
+
+enum State {
+    Header,
+    Data,
+    Done,
+}
+
+fn invalid_data(message: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+const WINDOW_SIZE: usize = 32 * 1024;
@@ -0,0 +1,142 @@
+use std::fs::File;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+
+/// Zstd dictionary.
+///
+/// The dictionary is either in zstd format (produced by the trainer, starts with magic bytes
+/// followed by dictionary ID and entropy tables) or _raw_ (arbitrary content without a header).
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ZstdDictionary {
+    data: Vec<u8>,
+}
+
+impl ZstdDictionary {
+    /// Create new dictionary from the supplied bytes.
+    ///
+    /// Returns an error if the bytes start with zstd dictionary magic but the header is truncated
+    /// or if the bytes are empty.
+    pub fn new(data: Vec<u8>) -> Result<Self, Error> {
+        if data.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidData, "empty zstd dictionary"));
+        }
+        if data.starts_with(&DICT_MAGIC) && data.len() < DICT_HEADER_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "truncated zstd dictionary header",
+            ));
+        }
+        Ok(Self { data })
+    }
+
+    /// Read the dictionary from the supplied `reader`.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        Self::new(data)
+    }
+
+    /// Write the dictionary to the supplied `writer`.
+    ///
+    /// The dictionary is written as is, i.e. zstd dictionaries retain their header.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_all(&self.data)
+    }
+
+    /// Load the dictionary from the file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::read_from(File::open(path)?)
+    }
+
+    /// Save the dictionary to the file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut file = File::create(path)?;
+        self.write_to(&mut file)?;
+        file.sync_all()
+    }
+
+    /// Get dictionary ID.
+    ///
+    /// Returns `None` for raw dictionaries and for zstd dictionaries with zero ID.
+    pub fn id(&self) -> Option<u32> {
+        zstd::zstd_safe::get_dict_id_from_dict(&self.data).map(Into::into)
+    }
+
+    /// Returns `true` if the dictionary doesn't have zstd dictionary header.
+    pub fn is_raw(&self) -> bool {
+        !self.data.starts_with(&DICT_MAGIC)
+    }
+
+    /// Get dictionary bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Return dictionary bytes.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Get ID of the dictionary that is needed to decompress the zstd frame.
+///
+/// Returns `None` if the frame doesn't need a dictionary, if the ID is not stored in the frame
+/// header, or if the data is not a zstd frame.
+pub fn zstd_frame_dictionary_id(frame: &[u8]) -> Option<u32> {
+    zstd::zstd_safe::get_dict_id_from_frame(frame).map(Into::into)
+}
+
+const DICT_MAGIC: [u8; 4] = [0x37, 0xa4, 0x30, 0xec];
+// magic and dictionary ID
+const DICT_HEADER_LEN: usize = 8;
+
+#[cfg(test)]
+mod tests {
+    use arbtest::arbtest;
+
+    use super::*;
+
+    #[test]
+    fn trained_dictionary() {
+        let samples = (0..1000)
+            .map(|i| format!("{{\"id\": {i}, \"name\": \"sample {}\"}}", i * 7919 % 1000))
+            .collect::<Vec<_>>();
+        let data = zstd::dict::from_samples(&samples, 1024).unwrap();
+        let dictionary = ZstdDictionary::new(data).unwrap();
+        assert!(!dictionary.is_raw());
+        let id = dictionary.id().unwrap();
+        let mut file = Vec::new();
+        dictionary.write_to(&mut file).unwrap();
+        let actual = ZstdDictionary::read_from(&file[..]).unwrap();
+        assert_eq!(dictionary, actual);
+        let mut encoder =
+            zstd::stream::Encoder::with_dictionary(Vec::new(), 3, dictionary.as_bytes()).unwrap();
+        encoder.write_all(samples[0].as_bytes()).unwrap();
+        let frame = encoder.finish().unwrap();
+        assert_eq!(Some(id), zstd_frame_dictionary_id(&frame));
+    }
+
+    #[test]
+    fn raw_dictionary() {
+        arbtest(|u| {
+            let data: Vec<u8> = u.arbitrary()?;
+            if data.is_empty() || data.starts_with(&DICT_MAGIC) {
+                return Ok(());
+            }
+            let dictionary = ZstdDictionary::new(data.clone()).unwrap();
+            assert!(dictionary.is_raw());
+            assert_eq!(None, dictionary.id());
+            assert_eq!(data, dictionary.into_bytes());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn invalid_dictionary() {
+        assert!(ZstdDictionary::new(Vec::new()).is_err());
+        assert!(ZstdDictionary::new(DICT_MAGIC.to_vec()).is_err());
+    }
+}
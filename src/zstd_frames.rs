@@ -0,0 +1,237 @@
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+
+/// Zstd frame summary.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ZstdFrameInfo {
+    /// The offset of the frame magic from the start of the file.
+    pub compressed_offset: u64,
+    /// The size of the frame including the header, the blocks and the checksum.
+    pub compressed_size: u64,
+    /// The offset of the decompressed data from the start of the decompressed file.
+    pub uncompressed_offset: u64,
+    /// The size of the decompressed data.
+    pub uncompressed_size: u64,
+}
+
+/// Locate the frames in zstd file.
+///
+/// The frames are located by walking the frame and block headers.
+/// The decompressed size is read from the frame header;
+/// the frames that don't store it are decompressed to find it out.
+/// Skippable frames are skipped.
+pub fn zstd_frames<R: Read + Seek>(mut reader: R) -> Result<Vec<ZstdFrameInfo>, Error> {
+    let end = reader.seek(SeekFrom::End(0))?;
+    let mut frames = Vec::new();
+    let mut offset = 0;
+    let mut uncompressed_offset = 0;
+    while offset != end {
+        let (size, content_size) = match walk_frame(&mut reader, offset, end)? {
+            Frame::Skippable(size) => {
+                offset += size;
+                continue;
+            }
+            Frame::Data { size, content_size } => (size, content_size),
+        };
+        let uncompressed_size = match content_size {
+            Some(n) => n,
+            None => {
+                reader.seek(SeekFrom::Start(offset))?;
+                let decoder = zstd::stream::read::Decoder::new((&mut reader).take(size))?;
+                std::io::copy(&mut decoder.single_frame(), &mut std::io::sink())?
+            }
+        };
+        frames.push(ZstdFrameInfo {
+            compressed_offset: offset,
+            compressed_size: size,
+            uncompressed_offset,
+            uncompressed_size,
+        });
+        offset += size;
+        uncompressed_offset += uncompressed_size;
+    }
+    Ok(frames)
+}
+
+/// Check that zstd file consists of complete frames.
+///
+/// Only the frame and block headers are checked, the data is not decompressed.
+pub(crate) fn zstd_check_frames<R: Read + Seek>(mut reader: R) -> Result<(), Error> {
+    let end = reader.seek(SeekFrom::End(0))?;
+    let mut offset = 0;
+    while offset != end {
+        offset += match walk_frame(&mut reader, offset, end)? {
+            Frame::Skippable(size) => size,
+            Frame::Data { size, .. } => size,
+        };
+    }
+    Ok(())
+}
+
+enum Frame {
+    Skippable(u64),
+    Data {
+        size: u64,
+        content_size: Option<u64>,
+    },
+}
+
+/// Walk the headers of the frame that starts at `offset`.
+fn walk_frame<R: Read + Seek>(reader: &mut R, offset: u64, end: u64) -> Result<Frame, Error> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let magic = read_u32(&mut *reader)?;
+    if magic & ZSTD_SKIPPABLE_MASK == ZSTD_SKIPPABLE_MAGIC {
+        let size = 8 + read_u32(&mut *reader)? as u64;
+        if offset + size > end {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "truncated zstd frame"));
+        }
+        return Ok(Frame::Skippable(size));
+    }
+    if magic != ZSTD_FRAME_MAGIC {
+        return Err(invalid_data("invalid zstd frame magic"));
+    }
+    let descriptor = read_u8(&mut *reader)?;
+    let single_segment = descriptor & (1 << 5) != 0;
+    let has_checksum = descriptor & (1 << 2) != 0;
+    let dict_id_len = [0, 1, 2, 4][(descriptor & 3) as usize];
+    let content_size_len = match descriptor >> 6 {
+        0 if single_segment => 1,
+        0 => 0,
+        1 => 2,
+        2 => 4,
+        _ => 8,
+    };
+    let mut header_len = 5 + dict_id_len + content_size_len;
+    if !single_segment {
+        header_len += 1;
+    }
+    reader.seek(SeekFrom::Current(
+        header_len as i64 - 5 - content_size_len as i64,
+    ))?;
+    let mut content_size = [0_u8; 8];
+    reader.read_exact(&mut content_size[..content_size_len])?;
+    let content_size = match content_size_len {
+        0 => None,
+        2 => Some(u64::from_le_bytes(content_size) + 256),
+        _ => Some(u64::from_le_bytes(content_size)),
+    };
+    // blocks
+    let mut size = header_len as u64;
+    loop {
+        let mut header = [0_u8; 4];
+        reader.read_exact(&mut header[..3])?;
+        let header = u32::from_le_bytes(header);
+        let last = header & 1 != 0;
+        let block_size = match (header >> 1) & 3 {
+            // raw block
+            0 => header >> 3,
+            // RLE block
+            1 => 1,
+            // compressed block
+            2 => header >> 3,
+            _ => return Err(invalid_data("invalid zstd block type")),
+        };
+        reader.seek(SeekFrom::Current(block_size as i64))?;
+        size += 3 + block_size as u64;
+        if last {
+            break;
+        }
+    }
+    if has_checksum {
+        size += 4;
+    }
+    if offset + size > end {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "truncated zstd frame"));
+    }
+    Ok(Frame::Data { size, content_size })
+}
+
+/// Decompress one frame of zstd file.
+pub fn zstd_decode_frame<R: Read + Seek>(
+    mut reader: R,
+    frame: &ZstdFrameInfo,
+) -> Result<Vec<u8>, Error> {
+    reader.seek(SeekFrom::Start(frame.compressed_offset))?;
+    let decoder = zstd::stream::read::Decoder::new(reader.take(frame.compressed_size))?;
+    let mut output = Vec::new();
+    decoder.single_frame().read_to_end(&mut output)?;
+    if output.len() as u64 != frame.uncompressed_size {
+        return Err(invalid_data("zstd frame size mismatch"));
+    }
+    Ok(output)
+}
+
+fn read_u8<R: Read>(mut reader: R) -> Result<u8, Error> {
+    let mut bytes = [0_u8; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u32<R: Read>(mut reader: R) -> Result<u32, Error> {
+    let mut bytes = [0_u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn invalid_data(message: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+pub(crate) const ZSTD_FRAME_MAGIC: u32 = 0xfd2f_b528;
+pub(crate) const ZSTD_SKIPPABLE_MAGIC: u32 = 0x184d_2a50;
+pub(crate) const ZSTD_SKIPPABLE_MASK: u32 = 0xffff_fff0;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::io::Write;
+
+    use arbtest::arbtest;
+
+    use super::*;
+
+    #[test]
+    fn frames() {
+        arbtest(|u| {
+            let inputs: Vec<Vec<u8>> = u.arbitrary()?;
+            let mut file = Vec::new();
+            for input in inputs.iter() {
+                if u.arbitrary()? {
+                    // skippable frame
+                    file.extend_from_slice(&(ZSTD_SKIPPABLE_MAGIC + 3).to_le_bytes());
+                    file.extend_from_slice(&2_u32.to_le_bytes());
+                    file.extend_from_slice(&[1, 2]);
+                }
+                let mut writer = zstd::stream::write::Encoder::new(Vec::new(), 1).unwrap();
+                if u.arbitrary()? {
+                    writer
+                        .set_pledged_src_size(Some(input.len() as u64))
+                        .unwrap();
+                }
+                writer.include_checksum(u.arbitrary()?).unwrap();
+                writer.write_all(input).unwrap();
+                file.extend(writer.finish().unwrap());
+            }
+            let frames = zstd_frames(Cursor::new(&file[..])).unwrap();
+            assert_eq!(inputs.len(), frames.len());
+            let mut reader = Cursor::new(&file[..]);
+            let mut actual = Vec::new();
+            for frame in frames.iter() {
+                assert_eq!(actual.len() as u64, frame.uncompressed_offset);
+                actual.extend(zstd_decode_frame(&mut reader, frame).unwrap());
+            }
+            assert_eq!(inputs.concat(), actual);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn truncated_frame() {
+        let mut compressed = zstd::encode_all(&b"hello world"[..], 1).unwrap();
+        compressed.pop();
+        assert!(zstd_frames(Cursor::new(&compressed[..])).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+
+use crate::Format;
+use crate::Index;
+use crate::IndexEntry;
+use crate::IndexedReader;
+use crate::ZstdFrameInfo;
+use crate::ZSTD_SKIPPABLE_MAGIC;
+
+/// Read the seek table of zstd seekable file.
+///
+/// The seek table is stored in the skippable frame at the end of the file
+/// ([format](https://github.com/facebook/zstd/blob/dev/contrib/seekable_format/zstd_seekable_compression_format.md)).
+/// Returns `None` if the file doesn't end with the seek table.
+/// The checksums of the frames are not verified.
+pub fn zstd_seek_table<R: Read + Seek>(mut reader: R) -> Result<Option<Vec<ZstdFrameInfo>>, Error> {
+    let end = reader.seek(SeekFrom::End(0))?;
+    if end < SEEK_TABLE_FOOTER_LEN + 8 {
+        return Ok(None);
+    }
+    reader.seek(SeekFrom::Start(end - SEEK_TABLE_FOOTER_LEN))?;
+    let mut footer = [0_u8; SEEK_TABLE_FOOTER_LEN as usize];
+    reader.read_exact(&mut footer)?;
+    let num_frames = u32::from_le_bytes([footer[0], footer[1], footer[2], footer[3]]) as u64;
+    let descriptor = footer[4];
+    let magic = u32::from_le_bytes([footer[5], footer[6], footer[7], footer[8]]);
+    if magic != ZSTD_SEEKABLE_MAGIC {
+        return Ok(None);
+    }
+    if descriptor & SEEK_TABLE_RESERVED_BITS != 0 {
+        return Err(invalid_data("invalid zstd seek table descriptor"));
+    }
+    let entry_len = if descriptor & SEEK_TABLE_CHECKSUM_FLAG != 0 {
+        12
+    } else {
+        8
+    };
+    // skippable frame header, entries and footer
+    let table_len = num_frames
+        .checked_mul(entry_len)
+        .and_then(|n| n.checked_add(8 + SEEK_TABLE_FOOTER_LEN))
+        .filter(|n| *n <= end)
+        .ok_or_else(|| invalid_data("invalid zstd seek table size"))?;
+    let table_offset = end - table_len;
+    reader.seek(SeekFrom::Start(table_offset))?;
+    let mut header = [0_u8; 8];
+    reader.read_exact(&mut header)?;
+    let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let frame_size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as u64;
+    if magic != ZSTD_SEEK_TABLE_MAGIC || frame_size + 8 != table_len {
+        return Err(invalid_data("invalid zstd seek table header"));
+    }
+    let mut frames = Vec::with_capacity(num_frames.min(MAX_PREALLOCATED_FRAMES) as usize);
+    let mut compressed_offset = 0_u64;
+    let mut uncompressed_offset = 0_u64;
+    let mut entry = [0_u8; 12];
+    for _ in 0..num_frames {
+        reader.read_exact(&mut entry[..entry_len as usize])?;
+        let compressed_size = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]) as u64;
+        let uncompressed_size = u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]) as u64;
+        frames.push(ZstdFrameInfo {
+            compressed_offset,
+            compressed_size,
+            uncompressed_offset,
+            uncompressed_size,
+        });
+        compressed_offset += compressed_size;
+        uncompressed_offset += uncompressed_size;
+    }
+    // the frames are stored back to back and are followed by the seek table
+    if compressed_offset != table_offset {
+        return Err(invalid_data("zstd seek table doesn't match the file"));
+    }
+    Ok(Some(frames))
+}
+
+/// Decoder of zstd seekable files.
+///
+/// The frames are located via the seek table (see [zstd_seek_table]), and only the frames that
+/// cover the requested range are decompressed.
+/// The frames are decompressed into memory one at a time (see [IndexedReader]).
+pub struct SeekableZstdDecoder<R> {
+    inner: IndexedReader<R>,
+    uncompressed_size: u64,
+}
+
+impl<R: Read + Seek> SeekableZstdDecoder<R> {
+    /// Create new decoder.
+    ///
+    /// Returns an error of kind [InvalidData](ErrorKind::InvalidData)
+    /// if the file doesn't have the seek table.
+    /// The decoder is positioned at the start of the decompressed data.
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let frames = zstd_seek_table(&mut reader)?
+            .ok_or_else(|| invalid_data("zstd seek table not found"))?;
+        let compressed_size = reader.seek(SeekFrom::End(0))?;
+        let uncompressed_size = frames
+            .last()
+            .map(|frame| frame.uncompressed_offset + frame.uncompressed_size)
+            .unwrap_or(0);
+        let index = Index::from_parts(
+            Format::Zstd,
+            frames.into_iter().map(IndexEntry::Zstd).collect(),
+            compressed_size,
+            uncompressed_size,
+        );
+        Ok(Self {
+            inner: IndexedReader::new(reader, index)?,
+            uncompressed_size,
+        })
+    }
+
+    /// The size of the decompressed data.
+    pub fn uncompressed_size(&self) -> u64 {
+        self.uncompressed_size
+    }
+
+    /// Read the data at the supplied offset in the decompressed data.
+    ///
+    /// This is a shorthand for seek followed by read.
+    pub fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<usize, Error> {
+        self.inner.read_at(buf, offset)
+    }
+
+    /// Return the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+}
+
+impl<R: Read + Seek> Read for SeekableZstdDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.inner.read(buf)
+    }
+}
+
+impl<R: Read + Seek> Seek for SeekableZstdDecoder<R> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Error> {
+        self.inner.seek(pos)
+    }
+}
+
+fn invalid_data(message: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// The skippable frame magic number with the lowest bits set to 0xe.
+pub(crate) const ZSTD_SEEK_TABLE_MAGIC: u32 = ZSTD_SKIPPABLE_MAGIC | 0xe;
+pub(crate) const ZSTD_SEEKABLE_MAGIC: u32 = 0x8f92_eab1;
+/// The number of frames, the descriptor and the magic number.
+const SEEK_TABLE_FOOTER_LEN: u64 = 4 + 1 + 4;
+const SEEK_TABLE_CHECKSUM_FLAG: u8 = 1 << 7;
+const SEEK_TABLE_RESERVED_BITS: u8 = 0b0111_1100;
+const MAX_PREALLOCATED_FRAMES: u64 = 4096;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::io::Write;
+
+    use arbtest::arbtest;
+
+    use super::*;
+
+    #[test]
+    fn seekable() {
+        arbtest(|u| {
+            let inputs: Vec<Vec<u8>> = u.arbitrary()?;
+            let checksum: bool = u.arbitrary()?;
+            let mut file = Vec::new();
+            let mut table = Vec::new();
+            for input in inputs.iter() {
+                let mut writer = zstd::stream::write::Encoder::new(Vec::new(), 1).unwrap();
+                writer.write_all(input).unwrap();
+                let frame = writer.finish().unwrap();
+                table.extend((frame.len() as u32).to_le_bytes());
+                table.extend((input.len() as u32).to_le_bytes());
+                if checksum {
+                    table.extend(u.arbitrary::<u32>()?.to_le_bytes());
+                }
+                file.extend(frame);
+            }
+            table.extend((inputs.len() as u32).to_le_bytes());
+            table.push(if checksum {
+                SEEK_TABLE_CHECKSUM_FLAG
+            } else {
+                0
+            });
+            table.extend(ZSTD_SEEKABLE_MAGIC.to_le_bytes());
+            file.extend(ZSTD_SEEK_TABLE_MAGIC.to_le_bytes());
+            file.extend((table.len() as u32).to_le_bytes());
+            file.extend(table);
+            let expected = inputs.concat();
+            // the file is decodable sequentially
+            let mut actual = Vec::new();
+            crate::AnyDecoder::new(&file[..])
+                .read_to_end(&mut actual)
+                .unwrap();
+            assert_eq!(expected, actual);
+            let index = Index::build(Cursor::new(&file), 1024).unwrap();
+            assert_eq!(inputs.len(), index.entries().len());
+            let mut reader = SeekableZstdDecoder::new(Cursor::new(&file)).unwrap();
+            assert_eq!(expected.len() as u64, reader.uncompressed_size());
+            let mut actual = Vec::new();
+            reader.read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, actual);
+            if expected.is_empty() {
+                return Ok(());
+            }
+            for _ in 0..10 {
+                let offset = u.int_in_range(0..=expected.len() - 1)?;
+                let len = u.int_in_range(0..=expected.len() - offset)?;
+                let mut actual = vec![0_u8; len];
+                reader.seek(SeekFrom::Start(offset as u64)).unwrap();
+                reader.read_exact(&mut actual).unwrap();
+                assert_eq!(&expected[offset..offset + len], &actual[..]);
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn no_seek_table() {
+        let compressed = zstd::encode_all(&b"hello world"[..], 1).unwrap();
+        assert_eq!(None, zstd_seek_table(Cursor::new(&compressed)).unwrap());
+        assert!(SeekableZstdDecoder::new(Cursor::new(&compressed)).is_err());
+    }
+}
@@ -0,0 +1,295 @@
+use std::io::BufRead;
+use std::io::Error;
+use std::io::Read;
+
+use crate::ZSTD_FRAME_MAGIC;
+use crate::ZSTD_SKIPPABLE_MAGIC;
+use crate::ZSTD_SKIPPABLE_MASK;
+
+/// Zstd skippable frame.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ZstdSkippableFrame {
+    /// The offset of the frame magic from the start of the file.
+    pub offset: u64,
+    /// The last four bits of the frame magic (0–15).
+    pub magic_variant: u8,
+    /// The user data.
+    pub payload: Vec<u8>,
+}
+
+/// A reader that collects zstd skippable frames as the data passes through it.
+///
+/// The data is not modified, i.e. the reader is supposed to be wrapped in
+/// [AnyDecoder](crate::bufread::AnyDecoder) that decompresses the data and skips these frames.
+/// The frames are available via [take_frames](Self::take_frames)
+/// as soon as the decoder has consumed them;
+/// reach the reader via [get_mut](crate::bufread::AnyDecoder::get_mut).
+///
+/// The payloads are kept in memory until they are taken.
+/// The frames are located by walking the frame and block headers;
+/// collecting stops at the first unknown or malformed header.
+pub struct ZstdSkippableReader<R> {
+    reader: R,
+    walker: Walker,
+}
+
+impl<R> ZstdSkippableReader<R> {
+    /// Create new reader.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            walker: Walker::new(),
+        }
+    }
+
+    /// Take the skippable frames collected so far.
+    pub fn take_frames(&mut self) -> Vec<ZstdSkippableFrame> {
+        std::mem::take(&mut self.walker.frames)
+    }
+
+    /// Get immutable reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Get mutable reference to the underlying reader.
+    ///
+    /// Reading from the underlying reader might break frame collection.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Get the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Read for ZstdSkippableReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let n = self.reader.read(buf)?;
+        self.walker.observe(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for ZstdSkippableReader<R> {
+    fn fill_buf(&mut self) -> Result<&[u8], Error> {
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, n: usize) {
+        // the buffer was already filled by the preceding `fill_buf` call
+        if let Ok(buf) = self.reader.fill_buf() {
+            self.walker.observe(&buf[..n.min(buf.len())]);
+        }
+        self.reader.consume(n);
+    }
+}
+
+/// Walks the frame and block headers.
+struct Walker {
+    state: State,
+    // the header that is being read
+    header: Vec<u8>,
+    // whether the current frame ends with the checksum
+    checksum: bool,
+    // the number of bytes observed so far
+    position: u64,
+    frames: Vec<ZstdSkippableFrame>,
+}
+
+impl Walker {
+    fn new() -> Self {
+        Self {
+            state: State::Header(Header::Magic, 4),
+            header: Vec::with_capacity(MAX_HEADER_LEN),
+            checksum: false,
+            position: 0,
+            frames: Vec::new(),
+        }
+    }
+
+    fn observe(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            let n = match self.state {
+                State::Header(_, len) => {
+                    let n = (len - self.header.len()).min(bytes.len());
+                    self.header.extend_from_slice(&bytes[..n]);
+                    n
+                }
+                State::Payload(len) => {
+                    let n = (len - self.payload_len()).min(bytes.len());
+                    if let Some(frame) = self.frames.last_mut() {
+                        frame.payload.extend_from_slice(&bytes[..n]);
+                    }
+                    n
+                }
+                State::Data(len, last) => {
+                    let n = len.min(bytes.len() as u64);
+                    self.state = State::Data(len - n, last);
+                    n as usize
+                }
+                State::Unknown => bytes.len(),
+            };
+            bytes = &bytes[n..];
+            self.position += n as u64;
+            self.advance();
+        }
+    }
+
+    /// Move to the next state if the current one is complete.
+    fn advance(&mut self) {
+        loop {
+            self.state = match self.state {
+                State::Header(header, len) if self.header.len() == len => {
+                    let state = self.parse_header(header);
+                    self.header.clear();
+                    state
+                }
+                State::Payload(len) if self.payload_len() == len => State::Header(Header::Magic, 4),
+                State::Data(0, true) => State::Header(Header::Magic, 4),
+                State::Data(0, false) => State::Header(Header::Block, 3),
+                _ => return,
+            };
+        }
+    }
+
+    fn parse_header(&mut self, header: Header) -> State {
+        let bytes = &self.header[..];
+        match header {
+            Header::Magic => {
+                let magic = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                if magic & ZSTD_SKIPPABLE_MASK == ZSTD_SKIPPABLE_MAGIC {
+                    State::Header(Header::SkippableSize((magic & 0xf) as u8), 4)
+                } else if magic == ZSTD_FRAME_MAGIC {
+                    State::Header(Header::Descriptor, 1)
+                } else {
+                    State::Unknown
+                }
+            }
+            Header::SkippableSize(magic_variant) => {
+                let size = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                self.frames.push(ZstdSkippableFrame {
+                    offset: self.position - 8,
+                    magic_variant,
+                    payload: Vec::new(),
+                });
+                State::Payload(size as usize)
+            }
+            Header::Descriptor => {
+                let descriptor = bytes[0];
+                let single_segment = descriptor & (1 << 5) != 0;
+                self.checksum = descriptor & (1 << 2) != 0;
+                let dict_id_len = [0, 1, 2, 4][(descriptor & 3) as usize];
+                let content_size_len = match descriptor >> 6 {
+                    0 if single_segment => 1,
+                    0 => 0,
+                    1 => 2,
+                    2 => 4,
+                    _ => 8,
+                };
+                let window_descriptor_len = if single_segment { 0 } else { 1 };
+                match window_descriptor_len + dict_id_len + content_size_len {
+                    0 => State::Header(Header::Block, 3),
+                    len => State::Header(Header::Frame, len),
+                }
+            }
+            Header::Frame => State::Header(Header::Block, 3),
+            Header::Block => {
+                let header = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]);
+                let last = header & 1 != 0;
+                let size = (header >> 3) as u64;
+                let size = match (header >> 1) & 3 {
+                    // raw and compressed blocks
+                    0 | 2 => size,
+                    // RLE block
+                    1 => 1,
+                    _ => return State::Unknown,
+                };
+                let checksum_len = if last && self.checksum { 4 } else { 0 };
+                State::Data(size + checksum_len, last)
+            }
+        }
+    }
+
+    fn payload_len(&self) -> usize {
+        self.frames.last().map_or(0, |frame| frame.payload.len())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    // collecting the header of the specified length
+    Header(Header, usize),
+    // collecting the payload of the last skippable frame
+    Payload(usize),
+    // skipping the block data, the flag marks the last block of the frame
+    Data(u64, bool),
+    // not a zstd stream
+    Unknown,
+}
+
+#[derive(Clone, Copy)]
+enum Header {
+    Magic,
+    SkippableSize(u8),
+    Descriptor,
+    // the rest of the frame header
+    Frame,
+    Block,
+}
+
+// window descriptor, dictionary id and content size
+const MAX_HEADER_LEN: usize = 1 + 4 + 8;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use arbtest::arbtest;
+
+    use super::*;
+    use crate::bufread::AnyDecoder;
+    use crate::write::ZstdEncoder;
+
+    #[test]
+    fn skippable_frames() {
+        // data optionally followed by a skippable frame (magic variant and payload)
+        type Chunk = (Vec<u8>, Option<(u8, Vec<u8>)>);
+        arbtest(|u| {
+            let chunks: Vec<Chunk> = u.arbitrary()?;
+            let mut writer = ZstdEncoder::new(Vec::new(), u.int_in_range(1..=3)?).unwrap();
+            let mut expected_frames = Vec::new();
+            let mut expected = Vec::new();
+            for (chunk, frame) in chunks.iter() {
+                writer.write_all(chunk).unwrap();
+                expected.extend_from_slice(chunk);
+                if let Some((magic_variant, payload)) = frame {
+                    let magic_variant = magic_variant & 0xf;
+                    writer
+                        .write_skippable_frame(magic_variant, payload)
+                        .unwrap();
+                    expected_frames.push((magic_variant, payload.clone()));
+                }
+            }
+            let compressed = writer.finish().unwrap();
+            let mut decoder = AnyDecoder::new(ZstdSkippableReader::new(&compressed[..]));
+            let mut actual = Vec::new();
+            decoder.read_to_end(&mut actual).unwrap();
+            assert_eq!(expected, actual);
+            let frames = decoder.get_mut().take_frames();
+            let actual_frames: Vec<_> = frames
+                .iter()
+                .map(|frame| (frame.magic_variant, frame.payload.clone()))
+                .collect();
+            assert_eq!(expected_frames, actual_frames);
+            for frame in frames.iter() {
+                let offset = frame.offset as usize;
+                let magic = ZSTD_SKIPPABLE_MAGIC + frame.magic_variant as u32;
+                assert_eq!(&magic.to_le_bytes()[..], &compressed[offset..offset + 4]);
+            }
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,205 @@
+use std::io::Error;
+
+use zstd::stream::raw::Decoder;
+use zstd::stream::raw::InBuffer;
+use zstd::stream::raw::Operation;
+use zstd::stream::raw::OutBuffer;
+use zstd::stream::raw::WriteBuf;
+
+/// Zstd decoder that checks the window size of each frame against the memory limit.
+///
+/// The frames that need larger window are rejected with
+/// [memory_limit_exceeded](crate::memory_limit_exceeded) error before the window is allocated.
+pub(crate) struct ZstdWindowLimit<'a> {
+    decoder: Decoder<'a>,
+    max_window_size: Option<u64>,
+    /// The bytes of the current frame header that have been passed to the decoder.
+    header: [u8; ZSTD_MAX_HEADER_LEN],
+    header_len: usize,
+    /// Whether the window size of the current frame has been checked.
+    checked: bool,
+}
+
+impl<'a> ZstdWindowLimit<'a> {
+    pub(crate) fn new(decoder: Decoder<'a>, max_window_size: Option<u64>) -> Self {
+        Self {
+            decoder,
+            max_window_size,
+            header: [0; ZSTD_MAX_HEADER_LEN],
+            header_len: 0,
+            checked: false,
+        }
+    }
+
+    /// Check the window size using the header bytes seen so far followed by the `input`.
+    fn check(&mut self, input: &[u8]) -> Result<(), Error> {
+        let Some(max_window_size) = self.max_window_size else {
+            return Ok(());
+        };
+        if self.checked {
+            return Ok(());
+        }
+        let mut header = self.header;
+        let n = input.len().min(ZSTD_MAX_HEADER_LEN - self.header_len);
+        header[self.header_len..self.header_len + n].copy_from_slice(&input[..n]);
+        let header = &header[..self.header_len + n];
+        match zstd_window_size(header) {
+            Some(size) if size > max_window_size => return Err(crate::memory_limit_exceeded()),
+            Some(..) => self.checked = true,
+            // skippable frames and invalid data are handled by the decoder
+            None if header.len() >= ZSTD_MAGIC.len() && !header.starts_with(&ZSTD_MAGIC) => {
+                self.checked = true
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Remember the header bytes that the decoder consumed.
+    fn consume(&mut self, input: &[u8]) {
+        if self.checked {
+            return;
+        }
+        let n = input.len().min(ZSTD_MAX_HEADER_LEN - self.header_len);
+        self.header[self.header_len..self.header_len + n].copy_from_slice(&input[..n]);
+        self.header_len += n;
+    }
+
+    /// Prepare for the next frame.
+    fn reset(&mut self) {
+        self.header_len = 0;
+        self.checked = false;
+    }
+}
+
+impl Operation for ZstdWindowLimit<'_> {
+    fn run<C: WriteBuf + ?Sized>(
+        &mut self,
+        input: &mut InBuffer<'_>,
+        output: &mut OutBuffer<'_, C>,
+    ) -> Result<usize, Error> {
+        let start = input.pos();
+        self.check(&input.src[start..])?;
+        let hint = self.decoder.run(input, output)?;
+        self.consume(&input.src[start..input.pos()]);
+        if hint == 0 {
+            // the frame has ended
+            self.reset();
+        }
+        Ok(hint)
+    }
+
+    fn flush<C: WriteBuf + ?Sized>(
+        &mut self,
+        output: &mut OutBuffer<'_, C>,
+    ) -> Result<usize, Error> {
+        self.decoder.flush(output)
+    }
+
+    fn reinit(&mut self) -> Result<(), Error> {
+        self.reset();
+        self.decoder.reinit()
+    }
+
+    fn finish<C: WriteBuf + ?Sized>(
+        &mut self,
+        output: &mut OutBuffer<'_, C>,
+        finished_frame: bool,
+    ) -> Result<usize, Error> {
+        self.decoder.finish(output, finished_frame)
+    }
+}
+
+/// Get the window size from zstd frame `header` (RFC8878, section 3.1.1.1).
+///
+/// Returns `None` if the header is incomplete or if it's not a frame header.
+pub(crate) fn zstd_window_size(header: &[u8]) -> Option<u64> {
+    let [0x28, 0xb5, 0x2f, 0xfd, descriptor, rest @ ..] = header else {
+        return None;
+    };
+    if descriptor & ZSTD_SINGLE_SEGMENT == 0 {
+        let window_descriptor = *rest.first()?;
+        let exponent = u32::from(window_descriptor >> 3);
+        let mantissa = u64::from(window_descriptor & 0b111);
+        let base = 1_u64 << (10 + exponent);
+        return Some(base + base / 8 * mantissa);
+    }
+    // the window size equals the content size
+    let dict_id_len = match descriptor & 0b11 {
+        0 => 0,
+        1 => 1,
+        2 => 2,
+        _ => 4,
+    };
+    let content_size = rest.get(dict_id_len..)?;
+    match descriptor >> 6 {
+        0 => content_size.first().map(|size| u64::from(*size)),
+        1 => content_size
+            .get(..2)
+            .and_then(|size| size.try_into().ok())
+            .map(|size| u64::from(u16::from_le_bytes(size)) + 256),
+        2 => content_size
+            .get(..4)
+            .and_then(|size| size.try_into().ok())
+            .map(|size| u64::from(u32::from_le_bytes(size))),
+        _ => content_size
+            .get(..8)
+            .and_then(|size| size.try_into().ok())
+            .map(u64::from_le_bytes),
+    }
+}
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const ZSTD_SINGLE_SEGMENT: u8 = 1 << 5;
+// magic, descriptor, window descriptor, dictionary ID and content size
+const ZSTD_MAX_HEADER_LEN: usize = 4 + 1 + 1 + 4 + 8;
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn window_size() {
+        // 1 MiB window, no content size
+        assert_eq!(
+            Some(1 << 20),
+            zstd_window_size(&[0x28, 0xb5, 0x2f, 0xfd, 0x00, 10 << 3])
+        );
+        // 1 MiB + 3/8 MiB
+        assert_eq!(
+            Some((1 << 20) + (3 << 17)),
+            zstd_window_size(&[0x28, 0xb5, 0x2f, 0xfd, 0x00, (10 << 3) | 3])
+        );
+        // single segment, two-byte content size
+        assert_eq!(
+            Some(256 + 0x0102),
+            zstd_window_size(&[0x28, 0xb5, 0x2f, 0xfd, 0x60, 0x02, 0x01])
+        );
+        assert_eq!(
+            None,
+            zstd_window_size(&[0x28, 0xb5, 0x2f, 0xfd, 0x60, 0x02])
+        );
+        assert_eq!(None, zstd_window_size(&[0x28, 0xb5, 0x2f, 0xfd]));
+        assert_eq!(None, zstd_window_size(&[0x50, 0x2a, 0x4d, 0x18, 0, 0]));
+    }
+
+    #[test]
+    fn large_window_in_second_frame() {
+        let data = vec![0_u8; 4096];
+        let mut compressed = zstd::stream::encode_all(&data[..], 1).unwrap();
+        let mut encoder = zstd::stream::Encoder::new(Vec::new(), 1).unwrap();
+        encoder.window_log(27).unwrap();
+        encoder.include_contentsize(false).unwrap();
+        std::io::Write::write_all(&mut encoder, &data).unwrap();
+        compressed.extend(encoder.finish().unwrap());
+        let mut reader = crate::bufread::AnyDecoder::new(&compressed[..]);
+        reader.memory_limit(1024 * 1024);
+        let mut actual = Vec::new();
+        let error = reader.read_to_end(&mut actual).unwrap_err();
+        assert_eq!(ErrorKind::OutOfMemory, error.kind());
+        assert_eq!(data, actual);
+    }
+}